@@ -4,29 +4,95 @@ mod chat_runtime;
 mod chat_store;
 mod event;
 mod message;
+mod moderation;
+mod render;
+mod resume_token;
+mod self_check;
+mod subscribers;
 mod terminate_if;
+mod token_bucket;
+
+use std::{
+    num::{NonZeroU64, NonZeroUsize},
+    time::Duration,
+};
 
 use crate::persistence::ExecuteSqlAsync;
 
 pub use self::{
-    chat_http::chat_routes,
-    chat_persistence::migrate_chat_persistence,
-    chat_runtime::{Chat, ChatRuntime},
-    chat_store::ChatError,
+    chat_http::{ChatHttpOptions, MessageRate, UuidPolicy, chat_routes},
+    chat_persistence::{migrate_chat_persistence, warm_up_chat_persistence},
+    chat_runtime::{Chat, ChatRuntime, Lagged, OnLag},
+    chat_store::{AddOutcome, ChatError},
     event::{Event, EventId},
     message::{Message, MessageId},
+    moderation::{ContentPolicy, Moderator, WordlistAction, WordlistModerator},
+    resume_token::ResumeSigner,
+    self_check::self_check,
 };
 
 // Integrate chat store with chat runtime. We do it here, because we want the submodules to be
 // independent from each other. Yet, the decision still belongs to the chat module.
 
-use self::chat_store::PersistentChat;
+use self::{chat_runtime::ActorOptions, chat_store::PersistentChat};
+
+/// Controls how the chat records and reads its messages.
+#[derive(Clone, Debug, Default)]
+pub struct ChatStoreOptions {
+    /// Caps the size of the database. Once it is reached, new messages are rejected. `None` does
+    /// not cap the size.
+    pub max_db_bytes: Option<u64>,
+    /// Store the content of new messages compressed.
+    pub compress_content: bool,
+    /// Retain only the most recent events. `None` retains all of them.
+    pub max_events: Option<NonZeroU64>,
+    /// Keep as many of the most recent events in memory. `None` always queries the database.
+    pub recent_events: Option<NonZeroUsize>,
+    /// Treat messages resent with the same content under a fresh id within this window as
+    /// duplicates. `None` only treats messages with the same id as duplicates.
+    pub dedup_window: Option<Duration>,
+    /// Log a warning for database operations taking longer. `None` does not log them.
+    pub slow_query_threshold: Option<Duration>,
+    /// Fail reading events since an event once it takes longer. `None` waits as long as it takes.
+    pub query_timeout: Option<Duration>,
+    /// Reject messages of new senders once as many distinct senders have posted. `None` admits any
+    /// number of senders.
+    pub max_senders: Option<NonZeroUsize>,
+    /// Maximum number of reads from the database running at once. Further reads wait for one of
+    /// them to finish. `None` does not limit reads.
+    pub max_concurrent_reads: Option<NonZeroUsize>,
+    /// Announce the event a duplicate has originally been recorded as once more, so clients which
+    /// rely on the echo of their messages can confirm them. Open event streams carry it as `echo`
+    /// event.
+    pub echo_duplicates: bool,
+    /// Tombstone messages without an expiry of their own once they are older, and tell open event
+    /// streams. Pinned messages do not expire. `None` keeps them until they are deleted otherwise.
+    pub message_ttl: Option<Duration>,
+    /// How often to look for expired messages. `None` looks once a minute.
+    pub expiry_sweep_interval: Option<Duration>,
+}
 
 impl ChatRuntime {
     pub async fn new(
         persistence: impl ExecuteSqlAsync + Send + Sync + 'static,
+        options: ChatStoreOptions,
     ) -> anyhow::Result<Self> {
-        let chat_store = PersistentChat::new(persistence).await?;
-        Ok(Self::with_chat_store(chat_store))
+        let chat_store = PersistentChat::new(persistence)
+            .await?
+            .with_max_bytes(options.max_db_bytes)
+            .with_compression(options.compress_content)
+            .with_max_events(options.max_events)
+            .with_recent_events(options.recent_events)
+            .with_dedup_window(options.dedup_window)
+            .with_slow_query_threshold(options.slow_query_threshold)
+            .with_query_timeout(options.query_timeout)
+            .with_max_senders(options.max_senders);
+        let actor_options = ActorOptions {
+            max_concurrent_reads: options.max_concurrent_reads,
+            echo_duplicates: options.echo_duplicates,
+            message_ttl: options.message_ttl,
+            expiry_sweep_interval: options.expiry_sweep_interval,
+        };
+        Ok(Self::with_actor_options(chat_store, actor_options))
     }
 }
@@ -1,51 +1,289 @@
-use std::convert::Infallible;
+mod admin;
+mod export;
+mod history;
+mod pin;
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    num::{NonZeroU32, NonZeroUsize},
+    pin::pin,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use axum::{
     Json, Router,
-    extract::State,
-    http::StatusCode,
-    response::{Sse, sse::Event as SseEvent},
-    routing::{get, post},
+    extract::{Query, Request, State},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+        header::{ACCEPT, RETRY_AFTER, VARY},
+    },
+    middleware::{self, Next},
+    response::{
+        IntoResponse as _, Response, Sse,
+        sse::{Event as SseEvent, KeepAlive},
+    },
+    routing::{MethodRouter, delete, get, post, put},
 };
 use futures_util::{Stream, StreamExt as _};
 use serde::{Deserialize, Serialize};
-use tokio::sync::watch;
+use tokio::{
+    sync::{
+        Semaphore,
+        broadcast::{self, error::RecvError},
+        watch,
+    },
+    time::{Interval, MissedTickBehavior, interval, interval_at},
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::debug;
 
 use axum::http::request::Parts;
 
 use crate::{
-    chat::terminate_if::terminate_if,
-    http::{AuthenticateRequest, AuthenticatedUser, HttpError, LastEventId},
-    user::UserId,
+    chat::{
+        render::markdown_to_html,
+        resume_token::{ResumeIds, ResumePosition, ResumeSigner},
+        subscribers::Subscribers,
+        terminate_if::terminate_if,
+        token_bucket::TokenBucket,
+    },
+    http::{AuthenticateRequest, AuthenticatedUser, HttpError, JsonBody, LastEventId, Lifecycle},
+    task::spawn_named,
+    user::{User, UserId, Users},
 };
 
-// Additional imports needed for sabatoge mode, which is only available in debug builds
-#[cfg(debug_assertions)]
-use axum::routing::put;
-#[cfg(debug_assertions)]
-use std::{pin::pin, sync::Arc};
+use self::{
+    admin::{delete_sender, set_motd, subscribers},
+    export::export_sender,
+    history::{
+        DEFAULT_POLL_MAX_EVENTS, MAX_HISTORY_WINDOW_EVENTS, history_window, json_history, poll,
+    },
+    pin::{PinNotice, pin_message, pinned},
+};
+
+use super::{
+    AddOutcome, Chat, ChatError, Event, EventId, Lagged, Message, MessageId,
+    chat_store::MAX_CONTENT_BYTES,
+    event::Tombstone,
+    moderation::{ContentPolicy, Moderator, Verdict},
+};
+
+/// Delay we suggest to clients before reconnecting to the events stream, after it has been closed
+/// due to a shutdown. Reconnecting immediately would likely hit a server which is going away or not
+/// yet back up again.
+const SHUTDOWN_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Number of notices buffered for each event stream. Messages are pinned or deleted rarely, so
+/// streams falling this far behind are treated like lagging ones.
+const NOTICE_CAPACITY: usize = 64;
+
+/// Response header of the `add_message` route. Tells clients whether their message has been `new`,
+/// or a `duplicate` of one added before, e.g. by a retry.
+const X_KLATSCH_OUTCOME: HeaderName = HeaderName::from_static("x-klatsch-outcome");
+
+/// Response header of the `add_message` route, if enabled. Carries the time spent on validating
+/// the message and on recording it in the chat.
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+/// Carries the position to resume after the last event of a JSON answer of the events route from,
+/// if resume positions are signed. The event ids within the payload are not signed.
+const X_KLATSCH_RESUME_TOKEN: HeaderName = HeaderName::from_static("x-klatsch-resume-token");
+
+/// `true` if an answer of the poll route has been cut short by its maximum number of events. The
+/// client should poll again right away, rather than waiting for new events.
+const X_KLATSCH_HAS_MORE: HeaderName = HeaderName::from_static("x-klatsch-has-more");
+
+/// Options for the chat API, which are static for the lifetime of the server.
+#[derive(Clone, Default)]
+pub struct ChatHttpOptions {
+    /// Include a `sender_color` seed in each message, so all clients render the same sender in the
+    /// same color without having to agree on an algorithm.
+    pub sender_color: bool,
+    /// Senders whose name starts with this prefix are tagged as bots. `None` does not classify
+    /// senders.
+    pub bot_prefix: Option<Arc<str>>,
+    /// Messages of bots do not count against `message_rate`, e.g. so an integration relaying a
+    /// burst of notifications does not throttle the humans. Has no effect without `bot_prefix`.
+    pub bot_rate_limit_exempt: bool,
+    /// Names of the users allowed to post messages. `None` allows everyone to post. Reading is not
+    /// affected.
+    pub allowed_senders: Option<Arc<HashSet<String>>>,
+    /// Lowercase names users may not post messages under, e.g. "system", so nobody can impersonate
+    /// messages of the operator. Matched case insensitive. `None` reserves no name. Messages the
+    /// server records itself, like the probe of the self check, do not pass through here.
+    pub reserved_senders: Option<Arc<HashSet<String>>>,
+    /// Message of the day. Sent as a `motd` event at the start of every event stream. It is not
+    /// part of the chat history. Admins may change it at runtime.
+    pub motd: Option<Arc<str>>,
+    /// Origins besides our own, whose pages may read the event stream. Empty allows no other
+    /// origin.
+    pub cors_origins: Vec<HeaderValue>,
+    /// Allow pages of `cors_origins` to read the event stream with credentials, i.e. an
+    /// `EventSource` opened `withCredentials`. The session cookie is `SameSite=Strict`, so this
+    /// only helps origins of the same site.
+    pub cors_allow_credentials: bool,
+    /// Answer add_message with a `Server-Timing` header, so clients can tell how much of the
+    /// latency they observe is spent on the server.
+    pub server_timing: bool,
+    /// Emit a `heartbeat` event carrying the id of the last event forwarded on the stream at this
+    /// interval. Unlike keep-alive comments, this lets idle clients confirm they have not missed
+    /// anything. `None` disables heartbeats.
+    pub heartbeat: Option<Duration>,
+    /// Maximum number of add_message requests handled at once. Further requests are answered with
+    /// `503 Service Unavailable`, rather than queueing up in front of the single writer of the
+    /// database. `None` does not limit concurrent writes.
+    pub max_concurrent_writes: Option<NonZeroUsize>,
+    /// Maximum rate of messages added by all senders together. Further messages are answered with
+    /// `429 Too Many Requests`. Protects the single writer of the database from overload, no matter
+    /// how the messages are distributed among senders. `None` does not limit the rate.
+    pub message_rate: Option<MessageRate>,
+    /// Names of the users allowed to use the admin routes. `None` allows nobody to.
+    pub admins: Option<Arc<HashSet<String>>>,
+    /// Decides about every message before it is recorded. Rejected messages are answered with
+    /// `403 Forbidden`. `None` records all messages as they are.
+    pub moderator: Option<Arc<dyn Moderator>>,
+    /// Clients may ask for messages rendered to sanitized HTML with `?render=html`, e.g. simple
+    /// widgets unable to run a markdown renderer themselves. Off by default, since rendering costs
+    /// CPU and bytes on every delivered message.
+    pub render_html: bool,
+    /// Signs the `id` of message events, i.e. the positions clients resume the event stream from,
+    /// binding them to the user they are issued to. Any other `Last-Event-ID`, or `since` of the
+    /// poll route, is answered with `400 Bad Request`. `None` issues plain event ids and accepts any
+    /// of them.
+    pub resume_signer: Option<ResumeSigner>,
+    /// Absolute URL klatsch is reachable at from the outside, e.g. behind a proxy rewriting paths.
+    /// Told to clients, so they can construct absolute URLs of the routes. Without trailing slash.
+    /// `None` leaves clients to use URLs relative to the page.
+    pub public_url: Option<Arc<str>>,
+    /// Maximum number of events answered by a single poll or JSON request of the events route, so
+    /// a client coming back after a long time does not load the entire history at once. `None`
+    /// uses [`DEFAULT_POLL_MAX_EVENTS`].
+    pub poll_max_events: Option<NonZeroUsize>,
+    /// Strip whitespace and zero-width characters from the start and end of new messages, e.g.
+    /// blank lines pasted along with the content. Applied before moderation and duplicate
+    /// detection. Whitespace within the content is preserved.
+    pub trim_content: bool,
+    /// Which message ids clients may choose. The nil UUID is rejected regardless.
+    pub uuid_policy: UuidPolicy,
+    /// Kinds of content new messages must not contain, e.g. URLs. Violating messages are answered
+    /// with `400 Bad Request`, telling the reason. Checked after trimming, before moderation.
+    pub content_policy: ContentPolicy,
+}
 
-use super::{Chat, ChatError, Event, EventId, Message, MessageId};
+/// Which UUIDs clients may use as ids of new messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UuidPolicy {
+    /// Any UUID, but the nil UUID.
+    #[default]
+    Lenient,
+    /// Only UUID v7, so ids are ordered by time and do not collide with the random ids of
+    /// messages originating from the server.
+    Strict,
+}
+
+/// Rate of messages, allowing for bursts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageRate {
+    /// Messages per second in the long run.
+    pub per_second: NonZeroU32,
+    /// Messages which may be added at once, after a quiet period.
+    pub burst: NonZeroU32,
+}
 
-pub fn chat_routes<C, S>(chat: C, sessions: S, shutting_down: watch::Receiver<bool>) -> Router
+pub fn chat_routes<C, U, S>(
+    chat: C,
+    users: U,
+    sessions: S,
+    lifecycle: Lifecycle,
+    options: ChatHttpOptions,
+) -> Router
 where
     C: Chat + Send + Sync + Clone + 'static,
+    U: Users + Send + Sync + Clone + 'static,
     S: AuthenticateRequest + Send + Sync + Clone + 'static,
 {
     #[cfg(debug_assertions)]
     let (sabotage_tx, sabotage_rx) = watch::channel(false);
 
+    // Shared by both add_message routes, so legacy clients can not bypass the limit.
+    let write_permits = options
+        .max_concurrent_writes
+        .map(|permits| Arc::new(Semaphore::new(permits.get())));
+    let limit_writes = |route: MethodRouter<_>| match &write_permits {
+        Some(permits) => route.layer(middleware::from_fn_with_state(
+            permits.clone(),
+            limit_concurrency,
+        )),
+        None => route,
+    };
+    let add_message_route = limit_writes(post(add_message::<C, U, S, NewMessage>));
+    let legacy_add_message_route = limit_writes(post(add_message::<C, U, S, LegacyMessage>));
+
+    let events_route = get(events::<C, U, S>);
+    let events_route = if options.cors_origins.is_empty() {
+        events_route
+    } else {
+        events_route.layer(events_cors(&options))
+    };
+
+    let (notices, _) = broadcast::channel(NOTICE_CAPACITY);
+    spawn_named(
+        "chat announce expired",
+        announce_expired(chat.clone().expired_messages(), notices.clone()),
+    );
+    spawn_named(
+        "chat announce echoes",
+        announce_echoes(chat.clone().echoed_duplicates(), notices.clone()),
+    );
+    let (public_config, _) = watch::channel(options.public_config());
+    let message_budget = options.message_rate.map(|rate| {
+        Arc::new(TokenBucket::new(
+            rate.burst,
+            rate.per_second,
+            Instant::now(),
+        ))
+    });
+
+    let Lifecycle {
+        shutting_down,
+        draining,
+    } = lifecycle;
     let state = ChatState {
         chat,
+        users,
         sessions,
         shutting_down,
+        draining,
+        notices,
+        config: public_config,
+        message_budget,
+        subscribers: Arc::default(),
+        options,
         #[cfg(debug_assertions)]
         sabotaged: sabotage_rx,
     };
 
     let router = Router::new()
-        .route("/api/v0/add_message", post(add_message::<C, S>))
-        .route("/api/v0/events", get(events::<C, S>))
+        .route("/api/v0/add_message", add_message_route)
+        .route("/api/v0/v1/add_message", legacy_add_message_route)
+        .route("/api/v0/events", events_route)
+        .route("/api/v0/poll", get(poll::<C, U, S>))
+        .route("/api/v0/history", get(history_window::<C, U, S>))
+        .route("/api/v0/config", get(config::<C, U, S>))
+        .route("/api/v0/messages/{id}/pin", post(pin_message::<C, U, S>))
+        .route("/api/v0/pinned", get(pinned::<C, U, S>))
+        .route("/api/v0/admin/subscribers", get(subscribers::<C, U, S>))
+        .route("/api/v0/admin/motd", put(set_motd::<C, U, S>))
+        .route(
+            "/api/v0/admin/senders/{sender}/export",
+            get(export_sender::<C, U, S>),
+        )
+        .route(
+            "/api/v0/admin/senders/{sender}",
+            delete(delete_sender::<C, U, S>),
+        )
         .with_state(state);
 
     #[cfg(debug_assertions)]
@@ -56,11 +294,42 @@ where
     router
 }
 
+/// Rejects requests with `503 Service Unavailable` and `Retry-After`, if all `permits` are taken.
+async fn limit_concurrency(
+    State(permits): State<Arc<Semaphore>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Ok(_permit) = permits.try_acquire() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(RETRY_AFTER, "1")],
+            "Too many messages are being added right now. Try again later.",
+        )
+            .into_response();
+    };
+    next.run(request).await
+}
+
+/// Answers cross-origin requests for the event stream. Matching origins are echoed back, rather
+/// than allowing any origin with `*`, since browsers demand this for requests with credentials.
+fn events_cors(options: &ChatHttpOptions) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(options.cors_origins.iter().cloned()))
+        .allow_methods([Method::GET])
+        .allow_headers([HeaderName::from_static("last-event-id")])
+        .expose_headers([X_KLATSCH_RESUME_TOKEN, X_KLATSCH_HAS_MORE])
+        .allow_credentials(options.cors_allow_credentials)
+}
+
 /// Shared state for all chat API routes.
 #[derive(Clone)]
-struct ChatState<C, S> {
+struct ChatState<C, U, S> {
     /// The chat which provides the events we want to stream to our client
     chat: C,
+    /// Used to look up the names of senders, if only some of them are allowed to post, or bots are
+    /// told apart from humans.
+    users: U,
     /// Session store used to authenticate and identify users.
     sessions: S,
     /// We terminate the events stream in case of a shutdown. So the request finishes cleanly for
@@ -68,6 +337,21 @@ struct ChatState<C, S> {
     /// finish on their own (as there could always be a new message), so graceful shutdown would use
     /// the entire grace period if even one client is still connected.
     shutting_down: watch::Receiver<bool>,
+    /// While draining, no new messages are accepted. Event streams are still served, so clients can
+    /// catch up before the server shuts down.
+    draining: watch::Receiver<bool>,
+    /// Tells event streams about changes to messages recorded before, i.e. messages which have
+    /// been pinned, unpinned or deleted.
+    notices: broadcast::Sender<Notice>,
+    /// Configuration relevant to clients. Event streams tell their clients about changes made at
+    /// runtime, e.g. to the message of the day.
+    config: watch::Sender<PublicConfig>,
+    /// Shared by all senders, if the rate of messages is limited.
+    message_budget: Option<Arc<TokenBucket>>,
+    /// Clients currently following the event stream.
+    subscribers: Arc<Subscribers>,
+    /// Static options controlling the shape of the responses.
+    options: ChatHttpOptions,
     /// We insert a sabotage error and close the event stream in case sabotage mode is enabled. This
     /// helps testing the UI in error states, without needing to cause disc i/o errors and messing
     /// with persistence.
@@ -75,7 +359,9 @@ struct ChatState<C, S> {
     sabotaged: watch::Receiver<bool>,
 }
 
-impl<C: Send + Sync, S: AuthenticateRequest + Sync> AuthenticateRequest for ChatState<C, S> {
+impl<C: Send + Sync, U: Send + Sync, S: AuthenticateRequest + Sync> AuthenticateRequest
+    for ChatState<C, U, S>
+{
     fn authenticate_request(
         &self,
         parts: &Parts,
@@ -86,30 +372,271 @@ impl<C: Send + Sync, S: AuthenticateRequest + Sync> AuthenticateRequest for Chat
 
 /// A message as submitted by the client via the add_message endpoint.
 #[derive(Deserialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
 struct NewMessage {
     id: MessageId,
     content: String,
+    /// Seconds after which the message is tombstoned, e.g. for ephemeral messages. Omitted for
+    /// messages which only expire by the chat wide TTL, if any.
+    #[serde(default)]
+    ttl_secs: Option<u64>,
+}
+
+/// A message in the shape sent by clients predating [`NewMessage`]. Accepted by the legacy
+/// add_message route, so old and new clients can coexist while migrating. Timestamps sent along by
+/// these clients are ignored, since events are stamped by the server.
+#[derive(Deserialize)]
+struct LegacyMessage {
+    message_id: MessageId,
+    text: String,
 }
 
-async fn add_message<C, S>(
+impl From<LegacyMessage> for NewMessage {
+    fn from(legacy: LegacyMessage) -> Self {
+        NewMessage {
+            id: legacy.message_id,
+            content: legacy.text,
+            ttl_secs: None,
+        }
+    }
+}
+
+/// Answer of the add_message endpoint. Describes the event the message is recorded as. For
+/// duplicates this is the event it has originally been recorded as, so retries yield the same
+/// answer.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+struct RecordedMessage {
+    event_id: EventId,
+    /// Milliseconds since Unix epoch
+    timestamp_ms: u64,
+}
+
+/// Records a message submitted in the shape `M`, which is either the current [`NewMessage`] or a
+/// legacy one.
+async fn add_message<C, U, S, M>(
     AuthenticatedUser(user_id): AuthenticatedUser,
-    State(state): State<ChatState<C, S>>,
-    Json(msg): Json<NewMessage>,
-) -> Result<(), HttpError>
+    State(state): State<ChatState<C, U, S>>,
+    JsonBody(msg): JsonBody<M>,
+) -> Result<Response, HttpError>
 where
     C: Chat + Clone + Send + Sync,
+    U: Users + Clone + Send + Sync,
     S: AuthenticateRequest + Clone + Send + Sync,
+    M: Into<NewMessage>,
 {
-    let mut chat = state.chat;
-    chat.add_message(Message {
+    let started = Instant::now();
+    let msg = msg.into();
+    if *state.draining.borrow() {
+        return Err(HttpError {
+            status_code: StatusCode::SERVICE_UNAVAILABLE,
+            message: "Server is about to shut down and does not accept new messages".into(),
+        });
+    }
+    ensure_allowed_sender(&state.options, state.users.clone(), user_id).await?;
+    ensure_valid_message_id(state.options.uuid_policy, msg.id)?;
+    let mut message = Message {
         id: msg.id,
         author: user_id,
         content: msg.content,
-    })
-    .await?;
+        expires_at_ms: msg.ttl_secs.map(expires_at_ms),
+    };
+    if state.options.trim_content {
+        message.content = trim_content(message.content);
+    }
+    ensure_compliant_content(state.options.content_policy, &message.content)?;
+    if let Some(moderator) = &state.options.moderator {
+        match moderator.moderate(&message).await {
+            Verdict::Allow => (),
+            Verdict::Reject { reason } => {
+                return Err(HttpError {
+                    status_code: StatusCode::FORBIDDEN,
+                    message: reason.into(),
+                });
+            }
+            Verdict::Transform { content } => message.content = content,
+        }
+    }
+    // Checked last, so rejected messages do not use up the budget of everyone else.
+    if let Some(budget) = &state.message_budget
+        && !is_exempt_bot(&state.options, state.users, user_id).await
+        && let Err(retry_after) = budget.try_take(Instant::now())
+    {
+        return Ok(too_many_messages(retry_after));
+    }
+    // Content and sender are only logged as `content` and `sender` fields, so they are withheld if
+    // logs are redacted.
+    debug!(
+        target: "http",
+        message_id = %message.id,
+        sender = %user_id,
+        content = %message.content,
+        "Adding message"
+    );
+    let validated = Instant::now();
+    let mut chat = state.chat;
+    let outcome = chat.add_message(message).await?;
+    let finished = Instant::now();
+    let (outcome, event) = match outcome {
+        AddOutcome::New(event) => ("new", event),
+        AddOutcome::Duplicate(event) => ("duplicate", event),
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(X_KLATSCH_OUTCOME, HeaderValue::from_static(outcome));
+    if state.options.server_timing {
+        // The chat round trip includes waiting for the writer and recording the message in the
+        // database.
+        let timing = server_timing(&[
+            ("validate", validated - started),
+            ("chat", finished - validated),
+        ]);
+        headers.insert(SERVER_TIMING, timing);
+    }
+    let recorded = RecordedMessage {
+        event_id: event.id,
+        timestamp_ms: event.timestamp_ms,
+    };
+    Ok((headers, Json(recorded)).into_response())
+}
+
+/// `content` without leading and trailing whitespace and zero-width characters. Whitespace and
+/// zero-width characters within, like joiners of emoji sequences, are kept.
+fn trim_content(content: String) -> String {
+    let is_blank = |c: char| {
+        c.is_whitespace()
+            || matches!(
+                c,
+                '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}'
+            )
+    };
+    let trimmed = content.trim_matches(is_blank);
+    if trimmed.len() == content.len() {
+        content
+    } else {
+        trimmed.to_owned()
+    }
+}
+
+/// Answers a message exceeding the rate of messages with `429 Too Many Requests`. `Retry-After`
+/// tells the client how many seconds to wait, rounded up.
+fn too_many_messages(retry_after: Duration) -> Response {
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, seconds.max(1).to_string())],
+        "Too many messages are being added right now. Try again later.",
+    )
+        .into_response()
+}
+
+/// Rejects users not allowed to post messages with `403 Forbidden`, i.e. users missing from the
+/// allowed senders, or posting under a reserved name.
+async fn ensure_allowed_sender(
+    options: &ChatHttpOptions,
+    mut users: impl Users,
+    user_id: UserId,
+) -> Result<(), HttpError> {
+    if options.allowed_senders.is_none() && options.reserved_senders.is_none() {
+        return Ok(());
+    }
+    let User { name } = users.user_by_id(user_id).await?;
+    if let Some(allowed_senders) = &options.allowed_senders
+        && !allowed_senders.contains(&name)
+    {
+        return Err(HttpError {
+            status_code: StatusCode::FORBIDDEN,
+            message: "You are not allowed to post messages in this chat".into(),
+        });
+    }
+    if let Some(reserved_senders) = &options.reserved_senders
+        && reserved_senders.contains(&name.to_lowercase())
+    {
+        return Err(HttpError {
+            status_code: StatusCode::FORBIDDEN,
+            message: format!("The name '{name}' is reserved and may not post messages").into(),
+        });
+    }
     Ok(())
 }
 
+/// `true` if bots are exempt from the message rate, and the sender is one of them. Senders we fail
+/// to look up are treated as humans, so they are still limited.
+async fn is_exempt_bot(options: &ChatHttpOptions, mut users: impl Users, user_id: UserId) -> bool {
+    let Some(bot_prefix) = options.bot_prefix.as_deref() else {
+        return false;
+    };
+    if !options.bot_rate_limit_exempt {
+        return false;
+    }
+    users
+        .user_by_id(user_id)
+        .await
+        .is_ok_and(|User { name }| name.starts_with(bot_prefix))
+}
+
+/// Milliseconds since Unix epoch, at which a message added now expires after `ttl_secs`.
+fn expires_at_ms(ttl_secs: u64) -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    // u64 covers ~584 million years since epoch, so we can afford to downcast from u128.
+    (now.as_millis() as u64).saturating_add(ttl_secs.saturating_mul(1000))
+}
+
+/// Rejects message ids not allowed by `policy` with `400 Bad Request`.
+fn ensure_valid_message_id(policy: UuidPolicy, id: MessageId) -> Result<(), HttpError> {
+    let message = if id.is_nil() {
+        "Message id must not be the nil UUID"
+    } else if policy == UuidPolicy::Strict && !id.is_v7() {
+        "Message id must be a UUID v7"
+    } else {
+        return Ok(());
+    };
+    Err(HttpError {
+        status_code: StatusCode::BAD_REQUEST,
+        message: message.into(),
+    })
+}
+
+/// Rejects content violating `policy` with `400 Bad Request`, telling the reason.
+fn ensure_compliant_content(policy: ContentPolicy, content: &str) -> Result<(), HttpError> {
+    match policy.violation(content) {
+        Some(reason) => Err(HttpError {
+            status_code: StatusCode::BAD_REQUEST,
+            message: reason.into(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// A change to messages recorded before, which open event streams are told about.
+#[derive(Clone)]
+enum Notice {
+    Pin(PinNotice),
+    /// Messages which have been deleted at once, e.g. all messages of a sender.
+    Tombstones(Arc<[Tombstone]>),
+    /// Original events of messages which have been added again.
+    Echoes(Arc<[Event]>),
+}
+
+/// Payload of `tombstone` events, announcing a deleted message.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+struct HttpTombstone {
+    message_id: MessageId,
+    /// Id of the event the message had been recorded as.
+    event_id: EventId,
+}
+
+/// Value of a `Server-Timing` header, e.g. `validate;dur=0.021, chat;dur=1.337`. Durations are
+/// milliseconds.
+fn server_timing(metrics: &[(&str, Duration)]) -> HeaderValue {
+    let value = metrics
+        .iter()
+        .map(|(name, duration)| format!("{name};dur={:.3}", duration.as_secs_f64() * 1000.0))
+        .collect::<Vec<_>>()
+        .join(", ");
+    HeaderValue::try_from(value).expect("Metric names must be valid header values")
+}
+
 impl From<ChatError> for HttpError {
     fn from(err: ChatError) -> Self {
         match err {
@@ -117,6 +644,26 @@ impl From<ChatError> for HttpError {
                 status_code: StatusCode::CONFLICT,
                 message: "A different message with this ID already exists".into(),
             },
+            ChatError::TooLarge => HttpError {
+                status_code: StatusCode::PAYLOAD_TOO_LARGE,
+                message: "Message content is too large".into(),
+            },
+            ChatError::StorageFull => HttpError {
+                status_code: StatusCode::INSUFFICIENT_STORAGE,
+                message: "Server has no storage left for new messages".into(),
+            },
+            ChatError::NotFound => HttpError {
+                status_code: StatusCode::NOT_FOUND,
+                message: "There is no message with this ID".into(),
+            },
+            ChatError::SeatLimitReached => HttpError {
+                status_code: StatusCode::FORBIDDEN,
+                message: "Seat limit reached. No further senders may post to this chat.".into(),
+            },
+            ChatError::ShuttingDown => HttpError {
+                status_code: StatusCode::SERVICE_UNAVAILABLE,
+                message: "Server is about to shut down and does not accept new messages".into(),
+            },
             ChatError::Internal => HttpError {
                 status_code: StatusCode::INTERNAL_SERVER_ERROR,
                 message: "Internal server error".into(),
@@ -125,409 +672,3431 @@ impl From<ChatError> for HttpError {
     }
 }
 
-async fn events<C, S>(
-    AuthenticatedUser(_): AuthenticatedUser,
-    State(state): State<ChatState<C, S>>,
-    last_event_id: LastEventId<EventId>,
-) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>> + Send + 'static>
+/// Query parameters of the events route, besides `last_event_id`.
+#[derive(Deserialize)]
+struct EventsParams {
+    /// Resume with the first event recorded at or after this point in time, in milliseconds since
+    /// Unix epoch. For clients which remember when they have last been online, but not the last
+    /// event id they have seen. Approximate, since timestamps stem from the system clock of the
+    /// server and are not guaranteed to increase with event ids. Ignored if a last event id is
+    /// passed.
+    since_ms: Option<u64>,
+    /// Deliver at most this many messages per second. See [`paced`].
+    max_rate: Option<NonZeroU32>,
+    render: Option<Render>,
+    /// Only deliver messages whose content contains this text, ignoring case. See
+    /// [`EventFilter`].
+    contains: Option<String>,
+    /// Start the event stream with a `meta` event, telling how many historic events are about to
+    /// be replayed. See [`meta_sse_event`].
+    #[serde(default)]
+    meta: bool,
+}
+
+/// Lets only messages pass which match all of the given criteria. E.g. for clients watching for a
+/// keyword, or for views following a few senders. Messages filtered out are neither delivered nor
+/// announced as gap, yet resume positions still refer to real event ids.
+struct EventFilter {
+    /// Lowercase. `None` lets any content pass.
+    text: Option<String>,
+    /// `None` lets any sender pass.
+    senders: Option<HashSet<UserId>>,
+}
+
+impl EventFilter {
+    /// `None` if there is nothing to filter for.
+    fn new(text: Option<&str>, senders: Option<HashSet<UserId>>) -> Option<Self> {
+        let text = text.filter(|text| !text.is_empty());
+        if text.is_none() && senders.is_none() {
+            return None;
+        }
+        Some(EventFilter {
+            text: text.map(str::to_lowercase),
+            senders,
+        })
+    }
+
+    fn matches(&self, event: &Event) -> bool {
+        let text_matches = self
+            .text
+            .as_ref()
+            .is_none_or(|text| event.message.content.to_lowercase().contains(text));
+        let sender_matches = self
+            .senders
+            .as_ref()
+            .is_none_or(|senders| senders.contains(&event.message.author));
+        text_matches && sender_matches
+    }
+}
+
+/// The senders passed to the events route as `sender`. Either repeated, or as a comma separated
+/// list, or both. `None` if no sender is passed. Answers ids which are not UUIDs with `400 Bad
+/// Request`.
+fn sender_params(query: &[(String, String)]) -> Result<Option<HashSet<UserId>>, HttpError> {
+    let mut senders = HashSet::new();
+    for (_, value) in query.iter().filter(|(key, _)| key == "sender") {
+        for sender in value.split(',').filter(|sender| !sender.is_empty()) {
+            let sender = sender.trim().parse().map_err(|_| HttpError {
+                status_code: StatusCode::BAD_REQUEST,
+                message: format!("Sender '{sender}' is not a valid user id").into(),
+            })?;
+            senders.insert(sender);
+        }
+    }
+    Ok((!senders.is_empty()).then_some(senders))
+}
+
+/// Additional representations of the message content a client may ask for, besides the raw
+/// markdown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Render {
+    /// Include the content rendered to sanitized HTML as `content_html`. Ignored unless enabled in
+    /// the options.
+    Html,
+}
+
+async fn events<C, U, S>(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+    headers: HeaderMap,
+    last_event_id: Option<LastEventId<ResumePosition>>,
+    Query(params): Query<EventsParams>,
+    Query(query): Query<Vec<(String, String)>>,
+) -> Response
 where
-    C: Chat + Send + Sync + 'static,
+    C: Chat + Send + Sync + Clone + 'static,
+    U: Users + Send + Sync + 'static,
     S: AuthenticateRequest + Send + Sync + 'static,
 {
-    let last_event_id = last_event_id.0;
+    let resume_ids = ResumeIds::new(state.options.resume_signer.clone(), user_id);
+    let resuming = last_event_id.is_some();
+    let last_event_id = match (last_event_id, params.since_ms) {
+        (Some(LastEventId(position)), _) => match resume_ids.verify(&position) {
+            Some(last_event_id) => last_event_id,
+            None => {
+                return HttpError {
+                    status_code: StatusCode::BAD_REQUEST,
+                    message: "Last-Event-ID has not been issued to this user".into(),
+                }
+                .into_response();
+            }
+        },
+        (None, Some(since_ms)) => match state.chat.last_event_before(since_ms).await {
+            Ok(last_event_id) => last_event_id,
+            Err(_) => {
+                return HttpError {
+                    status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                    message: "Internal server error".into(),
+                }
+                .into_response();
+            }
+        },
+        (None, None) => EventId::before_all(),
+    };
+    let format = MessageFormat::new(&state.options, state.users).with_render(params.render);
+
+    let senders = match sender_params(&query) {
+        Ok(senders) => senders,
+        Err(error) => return error.into_response(),
+    };
+    let filter = EventFilter::new(params.contains.as_deref(), senders);
+
+    if prefers_json(&headers) {
+        let max_events = state
+            .options
+            .poll_max_events
+            .map_or(DEFAULT_POLL_MAX_EVENTS, NonZeroUsize::get);
+        let mut response = json_history(
+            state.chat,
+            last_event_id,
+            max_events,
+            format,
+            &resume_ids,
+            filter,
+            &headers,
+        )
+        .await
+        .into_response();
+        vary_by_accept(&mut response);
+        return response;
+    }
+
+    // The client has seen events beyond the latest one, so the history it has seen has been lost,
+    // e.g. because the server restarted with an in-memory database. Rather than appearing caught
+    // up, it is told to discard its events and receives the history from the beginning.
+    // Should the latest event be unknown, the server is shutting down and the stream ends anyway.
+    let reset = resuming
+        && state
+            .chat
+            .latest_event_id()
+            .await
+            .is_ok_and(|latest| last_event_id > latest);
+    let last_event_id = if reset {
+        EventId::before_all()
+    } else {
+        last_event_id
+    };
+    let reset = reset.then(|| Ok(reset_sse_event()));
+
+    // Counted as part of the stream rather than awaited here, so the client receives the first
+    // events without waiting for the count.
+    let meta = meta_sse_events(params.meta.then(|| state.chat.clone()), last_event_id);
 
     // Convert chat events into SSE events
-    let events = state.chat.events(last_event_id).map(|chat_event| {
-        let sse_event = match chat_event {
-            Ok(event) => event.into(),
-            Err(_) => SseEvent::default()
-                .event("error")
-                .data("Internal server error"),
-        };
-        Ok(sse_event)
-    });
+    // Subscribed before the chat events are read, so no change after reading is missed.
+    let notices = state.notices.subscribe();
+    let config = state.config.subscribe();
+    let motd = config.borrow().motd.clone();
+    let settings = SseSettings {
+        chat: state.chat.clone(),
+        format,
+        resume_ids,
+        filter,
+        heartbeat: state.options.heartbeat,
+    };
+    let events = sse_events(
+        settings,
+        state.chat.events(last_event_id),
+        last_event_id,
+        notices,
+        config,
+    );
+    let events = paced(events, params.max_rate);
+    let events = meta.chain(events);
+    let events = futures_util::stream::iter(reset).chain(events);
+
+    // No id, the message of the day must not advance the `Last-Event-ID` of the client.
+    let motd = motd.map(|motd| Ok(SseEvent::default().event("motd").data(&*motd)));
+    let events = futures_util::stream::iter(motd).chain(events);
+
+    // Sent first, so clients can compute their clock offset before rendering any message. No id
+    // either.
+    let events = futures_util::stream::iter([Ok(server_time_sse_event())]).chain(events);
 
     #[cfg(debug_assertions)]
     let events = maybe_sabotage(state.sabotaged, events);
 
-    let events = terminate_if(events, state.shutting_down);
+    let events = until_shutdown(state.shutting_down, events);
+
+    // Registered for as long as the response body, and with it the stream, is alive.
+    let subscriber = state.subscribers.register(user_id);
+    let events = events.map(move |event| {
+        let _subscriber = &subscriber;
+        event
+    });
 
-    Sse::new(events)
+    // Keep-alives are written even if the chat is quiet. Otherwise we would never notice a client
+    // which vanished, without closing the connection.
+    let mut response = Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response();
+    vary_by_accept(&mut response);
+    response
 }
 
-impl From<Event> for SseEvent {
-    fn from(source: Event) -> Self {
-        // Destructure source event
-        let Event {
-            id: event_id,
-            message:
-                Message {
-                    id: message_id,
-                    author: sender_id,
-                    content,
-                },
-            timestamp_ms,
-        } = source;
-        SseEvent::default()
-            .id(event_id.to_string())
-            .json_data(HttpMessage {
-                id: message_id,
-                sender_id,
-                content,
-                timestamp_ms,
-            })
-            .expect("Deserializing message must not fail")
-    }
+/// The events route answers with an event stream or a JSON array, depending on `Accept`. Tells
+/// caches so, lest they hand the one to clients asking for the other.
+fn vary_by_accept(response: &mut Response) {
+    response
+        .headers_mut()
+        .insert(VARY, HeaderValue::from_static("accept"));
 }
 
-/// A message as represented by the `events` route.
-#[derive(Serialize)]
-pub struct HttpMessage {
-    /// Sender generated unique identifier for the message. It is used to recover from errors
-    /// sending messages. It also a key for the UI to efficiently update data structures then
-    /// rendering messages.
-    pub id: MessageId,
-    /// User id of the author
-    pub sender_id: UserId,
-    /// Text content of the message. I.e. the actual message
-    pub content: String,
-    /// Unix timestamp of that message being received by the server. Milliseconds since epoch.
-    pub timestamp_ms: u64,
+/// Current time of the server in milliseconds since Unix epoch. Clients rendering relative
+/// timestamps like "2m ago" use it to compensate for the skew of their local clock.
+fn server_time_sse_event() -> SseEvent {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis();
+    SseEvent::default()
+        .event("server_time")
+        .data(now_ms.to_string())
 }
 
-#[cfg(debug_assertions)]
-fn maybe_sabotage<S>(
-    sabotaged: watch::Receiver<bool>,
-    events: S,
-) -> impl Stream<Item = Result<SseEvent, Infallible>> + Send + 'static
+/// Emitted before replaying the history from the beginning to a client resuming after an event
+/// which no longer exists. No id, the events following it carry the ids to resume from.
+fn reset_sse_event() -> SseEvent {
+    SseEvent::default()
+        .event("reset")
+        .data("History has been reset. Discard all events received so far.")
+}
+
+/// Yields a single `meta` event counting the events in `chat` since `last_event_id`, or nothing if
+/// `chat` is `None`. Nothing either, if the events can not be counted. The error has been logged
+/// and the events themselves may still be delivered.
+fn meta_sse_events<C>(
+    chat: Option<C>,
+    last_event_id: EventId,
+) -> impl Stream<Item = Result<SseEvent, Infallible>> + Send
 where
-    S: Stream<Item = Result<SseEvent, Infallible>> + Send + 'static,
+    C: Chat + Send + Sync + 'static,
 {
-    let events = terminate_if(events, sabotaged.clone());
-    async_stream::stream! {
-        let mut events = pin!(events);
-        while let Some(event) = futures_util::StreamExt::next(&mut events).await {
-            yield event;
-        }
-        if *sabotaged.borrow() {
-            yield Ok(SseEvent::default().event("error").data("Sabotage"));
-        }
-    }
+    futures_util::stream::iter(chat).filter_map(move |chat| async move {
+        let remaining = chat.count_events_since(last_event_id).await.ok()?;
+        Some(Ok(meta_sse_event(remaining)))
+    })
 }
 
-/// Developer only endpoint. Enables or disables sabotage mode. Helps with testing the UI behavior
-/// in error states.
-#[cfg(debug_assertions)]
-async fn set_sabotage(
-    State(sabotaged): State<Arc<watch::Sender<bool>>>,
-    Json(enabled): Json<bool>,
-) {
-    let _ = sabotaged.send(enabled);
+/// Tells the client how many historic events are about to be replayed, e.g. to render the progress
+/// of catching up. Counted before filtering, and approximate, since messages may be recorded or
+/// deleted while the history is replayed. No id, it must not advance the `Last-Event-ID` of the
+/// client.
+fn meta_sse_event(remaining: u64) -> SseEvent {
+    SseEvent::default()
+        .event("meta")
+        .json_data(Meta { remaining })
+        .expect("Serializing meta must not fail")
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::http::AuthenticateRequest;
-    use axum::http::request::Parts;
+/// Payload of the `meta` event.
+#[derive(Serialize)]
+struct Meta {
+    /// Number of historic events following.
+    remaining: u64,
+}
 
-    use super::{Chat, ChatError, Event, EventId, Message, MessageId, UserId, chat_routes};
-    use std::{
-        mem::take,
-        sync::{Arc, Mutex},
-        time::{Duration, UNIX_EPOCH},
+/// Emitted if the events could not be read. No id, resuming must start after the last event which
+/// has been delivered successfully.
+fn internal_error_sse_event() -> SseEvent {
+    let error = HttpStreamError {
+        error: "internal",
+        message: "Internal server error",
+        retriable: true,
     };
+    SseEvent::default()
+        .event("error")
+        .json_data(error)
+        .expect("Serializing error must not fail")
+}
 
-    use eventsource_stream::Eventsource as _;
-    use futures_util::{Stream, StreamExt as _, stream::pending};
-    use http_body_util::{BodyExt as _, BodyStream};
-    use tokio::{sync::watch, time::timeout};
-
-    use axum::{
-        body::Body,
-        http::{Request, StatusCode},
+/// Emitted before closing the stream of a client which fell behind the live events. No id, the
+/// client resumes after the last event it received.
+fn lagged_sse_event() -> SseEvent {
+    let error = HttpStreamError {
+        error: "lagged",
+        message: "Fell behind the live events",
+        retriable: true,
     };
-    use double_trait::Dummy;
-    use serde_json::json;
-    use tower::ServiceExt; // for `oneshot`
-
-    #[tokio::test]
-    async fn add_message_route_forwards_arguments_to_chat_api() {
-        // Given
-        #[derive(Clone)]
-        struct SessionsStub;
-        impl AuthenticateRequest for SessionsStub {
-            async fn authenticate_request(
-                &self,
-                _parts: &Parts,
-            ) -> Result<UserId, crate::http::HttpError> {
-                Ok(UserId::BOB)
-            }
-        }
-        let spy = ChatSpy::default();
-        let (_, shutting_down) = watch::channel(false);
-        let app = chat_routes(spy.clone(), SessionsStub, shutting_down);
-        let new_message = json!({
-            "id": MessageId::ALPHA,
-            "content": "Hello, Alice!"
-        });
+    SseEvent::default()
+        .event("error")
+        .json_data(error)
+        .expect("Serializing error must not fail")
+}
 
-        // When
-        let _response = app
-            .oneshot(
-                Request::post("/api/v0/add_message")
-                    .header("content-type", "application/json")
-                    .body(Body::from(new_message.to_string()))
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
+/// Payload of `error` events in the event stream.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+struct HttpStreamError {
+    /// Machine readable kind of the error, allowing clients to branch on it.
+    error: &'static str,
+    /// Human readable description. Does not disclose the internal cause.
+    message: &'static str,
+    /// `true` if reconnecting, e.g. with `Last-Event-ID`, may succeed.
+    retriable: bool,
+}
 
-        // Then
-        let expected_msg = Message {
-            id: MessageId::ALPHA,
-            author: UserId::BOB,
-            content: "Hello, Alice!".to_owned(),
-        };
-        assert_eq!(spy.take_add_message_record(), &[expected_msg]);
+/// `true` if the client explicitly asks for `application/json` and not for `text/event-stream`.
+/// Clients which do not state a preference (e.g. `EventSource`) receive an event stream.
+fn prefers_json(headers: &HeaderMap) -> bool {
+    let media_types = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|media_range| media_range.split(';').next().unwrap_or_default().trim());
+    let mut json = false;
+    for media_type in media_types {
+        match media_type {
+            "text/event-stream" => return false,
+            "application/json" => json = true,
+            _ => (),
+        }
     }
+    json
+}
 
-    #[tokio::test]
-    async fn conflict_error_translates_to_409() {
-        // Given a chat that reports any message as a conflict
-        #[derive(Clone)]
-        struct ChatSaboteur;
-        impl Chat for ChatSaboteur {
-            async fn add_message(&mut self, _: Message) -> Result<(), ChatError> {
-                Err(ChatError::Conflict)
-            }
-        }
+/// The configuration of the chat API relevant to clients, so they can adapt to it. Withholds
+/// anything not meant for clients, e.g. the names of the allowed senders.
+#[derive(Clone, Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+struct PublicConfig {
+    /// Messages with larger content are rejected.
+    max_content_bytes: usize,
+    /// Maximum number of events the history route answers with.
+    max_history_events: usize,
+    /// Messages carry a `sender_color`.
+    sender_color: bool,
+    /// Messages carry the `kind` of their sender.
+    sender_kind: bool,
+    /// Only some users are allowed to post messages.
+    posting_restricted: bool,
+    /// Interval of `heartbeat` events in milliseconds, if enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    heartbeat_ms: Option<u64>,
+    /// Messages carry a `content_html` if requested with `?render=html`.
+    render_html: bool,
+    /// Message of the day, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    motd: Option<String>,
+    /// Absolute URL the routes are reachable at, e.g. `{public_url}/api/v0/events`. If absent,
+    /// clients use URLs relative to the page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_url: Option<String>,
+}
 
-        let (_, shutting_down) = watch::channel(false);
-        let app = chat_routes(ChatSaboteur, AuthDummy, shutting_down);
+impl ChatHttpOptions {
+    fn public_config(&self) -> PublicConfig {
+        PublicConfig {
+            max_content_bytes: MAX_CONTENT_BYTES,
+            max_history_events: MAX_HISTORY_WINDOW_EVENTS,
+            sender_color: self.sender_color,
+            sender_kind: self.bot_prefix.is_some(),
+            posting_restricted: self.allowed_senders.is_some(),
+            heartbeat_ms: self.heartbeat.map(|heartbeat| heartbeat.as_millis() as u64),
+            render_html: self.render_html,
+            motd: self.motd.as_deref().map(str::to_owned),
+            public_url: self.public_url.as_deref().map(str::to_owned),
+        }
+    }
+}
+
+/// Answers authenticated users with the [`PublicConfig`]. Also a cheap way for clients to verify
+/// their session.
+async fn config<C, U, S>(
+    AuthenticatedUser(_): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+) -> Json<PublicConfig>
+where
+    S: AuthenticateRequest + Send + Sync,
+{
+    Json(state.config.borrow().clone())
+}
+
+/// Tells open event streams about messages as they expire, with a `tombstone` event for each of
+/// them. Ends with the stream of expired messages, i.e. once the chat has been shut down.
+async fn announce_expired(
+    expired: impl Stream<Item = Vec<Tombstone>>,
+    notices: broadcast::Sender<Notice>,
+) {
+    let mut expired = pin!(expired);
+    while let Some(tombstones) = expired.next().await {
+        // Only fails if no event stream is open, which is fine.
+        let _ = notices.send(Notice::Tombstones(tombstones.into()));
+    }
+}
+
+/// Tells open event streams about duplicates as they are added, with an `echo` event for each of
+/// them. Ends with the stream of echoed duplicates, i.e. once the chat has been shut down.
+async fn announce_echoes(
+    echoes: impl Stream<Item = Vec<Event>>,
+    notices: broadcast::Sender<Notice>,
+) {
+    let mut echoes = pin!(echoes);
+    while let Some(events) = echoes.next().await {
+        // Only fails if no event stream is open, which is fine.
+        let _ = notices.send(Notice::Echoes(events.into()));
+    }
+}
+
+/// Delivers at most `max_rate` events per second, for subscribers on constrained links. Events are
+/// delayed, but never dropped or merged, since every message of the chat matters. In practice this
+/// paces the replay of the history. Live messages arriving faster than the rate are buffered
+/// briefly. Should a subscriber fall too far behind, its live stream ends like for any other slow
+/// receiver and the client resumes using `Last-Event-ID`. `None` delivers events as fast as the
+/// client reads them.
+fn paced<S>(events: S, max_rate: Option<NonZeroU32>) -> impl Stream<Item = S::Item> + Send
+where
+    S: Stream + Send,
+    S::Item: Send,
+{
+    let mut ticks = max_rate.map(|max_rate| {
+        let mut ticks = interval(Duration::from_secs(1) / max_rate.get());
+        // Quiet periods must not allow for a burst afterwards.
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticks
+    });
+    async_stream::stream! {
+        let mut events = pin!(events);
+        while let Some(event) = futures_util::StreamExt::next(&mut events).await {
+            if let Some(ticks) = &mut ticks {
+                ticks.tick().await;
+            }
+            yield event;
+        }
+    }
+}
+
+/// Terminates the event stream once the server is shutting down. In that case a final `shutdown`
+/// event is emitted, suggesting a reconnect delay. This allows clients to distinguish a shutdown
+/// from other reasons the stream might end and back off accordingly.
+fn until_shutdown<S>(
+    shutting_down: watch::Receiver<bool>,
+    events: S,
+) -> impl Stream<Item = Result<SseEvent, Infallible>> + Send + 'static
+where
+    S: Stream<Item = Result<SseEvent, Infallible>> + Send + 'static,
+{
+    let events = terminate_if(events, shutting_down.clone());
+    // No id, the shutdown must not advance the `Last-Event-ID` of the client.
+    let shutdown = after_end(move || {
+        shutting_down.borrow().then(|| {
+            Ok(SseEvent::default()
+                .event("shutdown")
+                .retry(SHUTDOWN_RECONNECT_DELAY)
+                .json_data(ShutdownNotice {
+                    reconnect_delay_ms: SHUTDOWN_RECONNECT_DELAY.as_millis() as u64,
+                })
+                .expect("Serializing shutdown notice must not fail"))
+        })
+    });
+    events.chain(shutdown)
+}
+
+/// Yields the item returned by `last`, if any. `last` is only called once the stream is polled,
+/// i.e. after the streams chained in front of it have ended. Chaining it, rather than wrapping the
+/// events in a generator, keeps the events stream from growing with every layer.
+fn after_end<T: Send>(last: impl FnOnce() -> Option<T> + Send) -> impl Stream<Item = T> + Send {
+    futures_util::stream::once(async move { last() }).filter_map(futures_util::future::ready)
+}
+
+/// Payload of the `shutdown` event. The reconnect delay is also transported via the `retry` field
+/// of the SSE event, which is honored by `EventSource`. We repeat it in the payload for clients
+/// which implement reconnects themselves.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+struct ShutdownNotice {
+    reconnect_delay_ms: u64,
+}
+
+/// How [`sse_events`] renders the events of the chat for a single client.
+struct SseSettings<C, U> {
+    /// Tells which of the skipped event ids have been pruned, so they are announced as `gap`.
+    chat: C,
+    format: MessageFormat<U>,
+    /// Issues the ids of the message events.
+    resume_ids: ResumeIds,
+    /// Only matching messages are forwarded. `None` forwards all of them.
+    filter: Option<EventFilter>,
+    /// Interval of `heartbeat` events. `None` for no heartbeats.
+    heartbeat: Option<Duration>,
+}
+
+/// Converts the events of the chat following `last_event_id` into SSE events. Event ids are
+/// consecutive, so skipped ids mean the events have either been tombstoned, or been deleted because
+/// only the most recent ones are retained. The latter are announced with a `gap` event, so clients
+/// can tell that their view of the chat is incomplete. Tombstoned messages are not missed, so they
+/// are skipped silently. If a `heartbeat` interval is given, `heartbeat` events are interleaved. So
+/// are `pin` and `tombstone` events for every message pinned, unpinned or deleted while the stream
+/// is open, `echo` events for every duplicate added, and `config` events for every change of the
+/// configuration.
+fn sse_events<C, U>(
+    settings: SseSettings<C, U>,
+    chat_events: impl Stream<Item = anyhow::Result<Event>> + Send,
+    last_event_id: EventId,
+    notices: broadcast::Receiver<Notice>,
+    config: watch::Receiver<PublicConfig>,
+) -> impl Stream<Item = Result<SseEvent, Infallible>> + Send
+where
+    C: Chat + Send + Sync,
+    U: Users + Send,
+{
+    let SseSettings {
+        chat,
+        mut format,
+        resume_ids,
+        filter,
+        heartbeat,
+    } = settings;
+    async_stream::stream! {
+        let mut chat_events = pin!(chat_events);
+        let mut notices = Some(notices);
+        let mut config = Some(config);
+        let mut heartbeats = heartbeat.map(|period| {
+            let mut heartbeats = interval_at(tokio::time::Instant::now() + period, period);
+            heartbeats.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            heartbeats
+        });
+        let mut last_forwarded = last_event_id;
+        let mut expected = last_event_id.successor();
+        // Only ever grows, so it is only looked up again, if skipped ids are beyond it.
+        let mut pruned_up_to = EventId::before_all();
+        loop {
+            let chat_event = tokio::select! {
+                chat_event = chat_events.next() => chat_event,
+                () = next_heartbeat(&mut heartbeats) => {
+                    yield Ok(heartbeat_sse_event(last_forwarded));
+                    continue;
+                }
+                config = next_config(&mut config) => {
+                    yield Ok(config_sse_event(&config));
+                    continue;
+                }
+                notice = next_notice(&mut notices) => {
+                    match notice {
+                        Ok(Notice::Pin(notice)) => {
+                            yield Ok(pin_sse_event(notice));
+                            continue;
+                        }
+                        Ok(Notice::Tombstones(tombstones)) => {
+                            for &tombstone in tombstones.iter() {
+                                yield Ok(tombstone_sse_event(tombstone));
+                            }
+                            continue;
+                        }
+                        Ok(Notice::Echoes(echoes)) => {
+                            for event in echoes.iter() {
+                                if filter.as_ref().is_some_and(|filter| !filter.matches(event)) {
+                                    continue;
+                                }
+                                let message = format.http_message(event.clone()).await;
+                                yield Ok(echo_sse_event(message));
+                            }
+                            continue;
+                        }
+                        // The client would not learn about the missed changes, unless it
+                        // reconnects and fetches the history and pinned messages again.
+                        Err(Lagged) => {
+                            yield Ok(lagged_sse_event());
+                            break;
+                        }
+                    }
+                }
+            };
+            let Some(chat_event) = chat_event else {
+                break;
+            };
+            let event = match chat_event {
+                Ok(event) => event,
+                Err(error) if error.is::<Lagged>() => {
+                    yield Ok(lagged_sse_event());
+                    break;
+                }
+                Err(_) => {
+                    yield Ok(internal_error_sse_event());
+                    continue;
+                }
+            };
+            if event.id > expected {
+                let last_missing = EventId(event.id.0 - 1);
+                if pruned_up_to < last_missing {
+                    // If we can not tell, we rather announce a gap too many, than miss one.
+                    pruned_up_to = chat.pruned_up_to().await.unwrap_or(last_missing);
+                }
+                if pruned_up_to >= expected {
+                    yield Ok(gap_sse_event(expected, last_missing.min(pruned_up_to)));
+                }
+            }
+            expected = event.id.successor();
+            if filter.as_ref().is_some_and(|filter| !filter.matches(&event)) {
+                continue;
+            }
+            last_forwarded = event.id;
+            let event_id = event.id;
+            let message = format.http_message(event).await;
+            yield Ok(message_sse_event(resume_ids.issue(event_id), message));
+        }
+    }
+}
+
+/// Completes with the next tick of `heartbeats`. Never, if heartbeats are disabled.
+async fn next_heartbeat(heartbeats: &mut Option<Interval>) {
+    match heartbeats {
+        Some(heartbeats) => {
+            heartbeats.tick().await;
+        }
+        None => futures_util::future::pending().await,
+    }
+}
+
+/// Completes with the next notice. Never, once no further notices can be sent.
+async fn next_notice(notices: &mut Option<broadcast::Receiver<Notice>>) -> Result<Notice, Lagged> {
+    let Some(receiver) = notices else {
+        return futures_util::future::pending().await;
+    };
+    match receiver.recv().await {
+        Ok(notice) => Ok(notice),
+        Err(RecvError::Lagged(_)) => Err(Lagged),
+        Err(RecvError::Closed) => {
+            *notices = None;
+            futures_util::future::pending().await
+        }
+    }
+}
+
+/// Completes with the configuration, once it has changed. Never, once it can no longer change.
+async fn next_config(config: &mut Option<watch::Receiver<PublicConfig>>) -> PublicConfig {
+    let Some(receiver) = config else {
+        return futures_util::future::pending().await;
+    };
+    if receiver.changed().await.is_err() {
+        *config = None;
+        return futures_util::future::pending().await;
+    }
+    receiver.borrow_and_update().clone()
+}
+
+/// Tells the client the configuration has changed, e.g. the message of the day. Carries the
+/// [`PublicConfig`] as answered by the config route. No id, it is not part of the chat history.
+fn config_sse_event(config: &PublicConfig) -> SseEvent {
+    SseEvent::default()
+        .event("config")
+        .json_data(config)
+        .expect("Serializing config must not fail")
+}
+
+/// Tells the client a message has been pinned or unpinned. No id, the event of the message itself
+/// has been forwarded before.
+fn pin_sse_event(notice: PinNotice) -> SseEvent {
+    SseEvent::default()
+        .event("pin")
+        .json_data(notice)
+        .expect("Serializing pin notice must not fail")
+}
+
+/// Tells the client a message has been deleted. No id, the event of the message itself has been
+/// forwarded before, if at all.
+fn tombstone_sse_event(tombstone: Tombstone) -> SseEvent {
+    let Tombstone {
+        event_id,
+        message_id,
+    } = tombstone;
+    SseEvent::default()
+        .event("tombstone")
+        .json_data(HttpTombstone {
+            message_id,
+            event_id,
+        })
+        .expect("Serializing tombstone must not fail")
+}
+
+/// Repeats a message which has been added again, so clients relying on the echo of their messages
+/// confirm the retried one. Carries the [`HttpMessage`] of the original event. No id, the event
+/// may have been forwarded before, or may still be ahead of the client.
+fn echo_sse_event(message: HttpMessage) -> SseEvent {
+    SseEvent::default()
+        .event("echo")
+        .json_data(message)
+        .expect("Serializing echo must not fail")
+}
+
+/// Tells the client the id of the last event forwarded on this stream. No id, only real events may
+/// advance the `Last-Event-ID` of the client.
+fn heartbeat_sse_event(last_event_id: EventId) -> SseEvent {
+    SseEvent::default()
+        .event("heartbeat")
+        .json_data(Heartbeat { last_event_id })
+        .expect("Serializing heartbeat must not fail")
+}
+
+/// Payload of the `heartbeat` event.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+struct Heartbeat {
+    last_event_id: EventId,
+}
+
+/// Announces the events from `first_missing` to `last_missing` (inclusive) as deleted. No id, the
+/// next message advances the `Last-Event-ID` of the client past them anyway.
+fn gap_sse_event(first_missing: EventId, last_missing: EventId) -> SseEvent {
+    SseEvent::default()
+        .event("gap")
+        .json_data(GapNotice {
+            first_missing_id: first_missing,
+            last_missing_id: last_missing,
+        })
+        .expect("Serializing gap notice must not fail")
+}
+
+/// Payload of the `gap` event.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+struct GapNotice {
+    first_missing_id: EventId,
+    last_missing_id: EventId,
+}
+
+/// SSE event carrying an [`HttpMessage`]. `id` is the position to resume after it from.
+fn message_sse_event(id: String, message: HttpMessage) -> SseEvent {
+    SseEvent::default()
+        .id(id)
+        .json_data(message)
+        .expect("Deserializing message must not fail")
+}
+
+/// A stable seed for the color of a sender. Derived from the sender id using 32Bit FNV-1a. We do
+/// not use the hasher of the standard library, since its output is not guaranteed to be stable
+/// between Rust versions, yet clients may cache colors.
+fn sender_color(sender_id: UserId) -> u32 {
+    sender_id
+        .as_bytes()
+        .iter()
+        .fold(0x811c_9dc5, |hash, &byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        })
+}
+
+/// A message as represented by the `events` route. Fields are in snake_case, or in camelCase if
+/// built with the `camel-case-json` feature.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+pub struct HttpMessage {
+    /// Sender generated unique identifier for the message. It is used to recover from errors
+    /// sending messages. It also a key for the UI to efficiently update data structures then
+    /// rendering messages.
+    pub id: MessageId,
+    /// User id of the author
+    pub sender_id: UserId,
+    /// Text content of the message. I.e. the actual message
+    pub content: String,
+    /// Unix timestamp of that message being received by the server. Milliseconds since epoch.
+    pub timestamp_ms: u64,
+    /// Seed for the color the sender is rendered in. Only present if enabled in the configuration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sender_color: Option<u32>,
+    /// Whether the sender is a bot or a human. Only present if a bot prefix is configured and the
+    /// sender could be looked up.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SenderKind>,
+    /// The message has been pinned, e.g. because it is an announcement.
+    pub pinned: bool,
+    /// `content` rendered from markdown to sanitized HTML. Only present if enabled in the
+    /// configuration and requested by the client.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+}
+
+/// Tells bots apart from humans by the prefix of their name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SenderKind {
+    Human,
+    Bot,
+}
+
+/// Shapes chat events into their HTTP representation, according to the options of the chat API.
+struct MessageFormat<U> {
+    with_sender_color: bool,
+    bot_prefix: Option<Arc<str>>,
+    /// Rendering HTML is enabled in the options. See [`Self::with_render`].
+    html_enabled: bool,
+    render_html: bool,
+    users: U,
+    /// Kinds of the senders looked up so far. Replaying the history would otherwise query the name
+    /// of the same few senders over and over again.
+    sender_kinds: HashMap<UserId, SenderKind>,
+}
+
+impl<U> MessageFormat<U>
+where
+    U: Users,
+{
+    fn new(options: &ChatHttpOptions, users: U) -> Self {
+        MessageFormat {
+            with_sender_color: options.sender_color,
+            bot_prefix: options.bot_prefix.clone(),
+            html_enabled: options.render_html,
+            render_html: false,
+            users,
+            sender_kinds: HashMap::new(),
+        }
+    }
+
+    /// Representations of the content requested by the client, in addition to the raw markdown.
+    /// Requests for HTML are ignored, unless enabled in the options.
+    fn with_render(mut self, render: Option<Render>) -> Self {
+        self.render_html = self.html_enabled && render == Some(Render::Html);
+        self
+    }
+
+    async fn http_events(&mut self, events: Vec<Event>) -> Vec<HttpEvent> {
+        let mut http_events = Vec::with_capacity(events.len());
+        for event in events {
+            let event_id = event.id;
+            let message = self.http_message(event).await;
+            http_events.push(HttpEvent { event_id, message });
+        }
+        http_events
+    }
+
+    async fn http_message(&mut self, event: Event) -> HttpMessage {
+        let kind = self.sender_kind(event.message.author).await;
+        let content_html = self
+            .render_html
+            .then(|| markdown_to_html(&event.message.content));
+        HttpMessage {
+            content_html,
+            ..HttpMessage::new(
+                event.message,
+                event.timestamp_ms,
+                self.with_sender_color,
+                kind,
+                event.pinned,
+            )
+        }
+    }
+
+    async fn sender_kind(&mut self, sender_id: UserId) -> Option<SenderKind> {
+        let bot_prefix = self.bot_prefix.as_deref()?;
+        if let Some(&kind) = self.sender_kinds.get(&sender_id) {
+            return Some(kind);
+        }
+        // Messages of senders we fail to look up are still delivered, just not classified.
+        let User { name } = self.users.user_by_id(sender_id).await.ok()?;
+        let kind = if name.starts_with(bot_prefix) {
+            SenderKind::Bot
+        } else {
+            SenderKind::Human
+        };
+        self.sender_kinds.insert(sender_id, kind);
+        Some(kind)
+    }
+}
+
+impl HttpMessage {
+    fn new(
+        message: Message,
+        timestamp_ms: u64,
+        with_sender_color: bool,
+        kind: Option<SenderKind>,
+        pinned: bool,
+    ) -> Self {
+        let Message {
+            id,
+            author: sender_id,
+            content,
+            expires_at_ms: _,
+        } = message;
+        HttpMessage {
+            id,
+            sender_id,
+            content,
+            timestamp_ms,
+            sender_color: with_sender_color.then(|| sender_color(sender_id)),
+            kind,
+            pinned,
+            content_html: None,
+        }
+    }
+}
+
+/// A message as represented by the `events` route, if the client asks for a JSON array instead of
+/// an event stream. Since there is no SSE `id` field, the event id is part of the payload. Clients
+/// can pass it as `Last-Event-ID` in the next request to only receive newer messages.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+pub struct HttpEvent {
+    pub event_id: EventId,
+    #[serde(flatten)]
+    pub message: HttpMessage,
+}
+
+#[cfg(debug_assertions)]
+fn maybe_sabotage<S>(
+    sabotaged: watch::Receiver<bool>,
+    events: S,
+) -> impl Stream<Item = Result<SseEvent, Infallible>> + Send + 'static
+where
+    S: Stream<Item = Result<SseEvent, Infallible>> + Send + 'static,
+{
+    let events = terminate_if(events, sabotaged.clone());
+    let sabotage = after_end(move || {
+        sabotaged
+            .borrow()
+            .then(|| Ok(SseEvent::default().event("error").data("Sabotage")))
+    });
+    events.chain(sabotage)
+}
+
+/// Developer only endpoint. Enables or disables sabotage mode. Helps with testing the UI behavior
+/// in error states.
+#[cfg(debug_assertions)]
+async fn set_sabotage(
+    State(sabotaged): State<Arc<watch::Sender<bool>>>,
+    Json(enabled): Json<bool>,
+) {
+    let _ = sabotaged.send(enabled);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chat::{
+            WordlistAction, WordlistModerator,
+            resume_token::{ResumeIds, ResumeSigner},
+        },
+        http::{AuthenticateRequest, Lifecycle},
+        tracing::CapturedLogs,
+        user::{User, Users, UsersError},
+    };
+    use axum::http::request::Parts;
+
+    use super::{
+        AddOutcome, Chat, ChatError, ChatHttpOptions, ContentPolicy, Event, EventId, HttpEvent,
+        HttpMessage, Lagged, Message, MessageId, MessageRate, Tombstone, UserId, UuidPolicy,
+        chat_routes, sender_color,
+    };
+    use std::{
+        collections::HashSet,
+        mem::take,
+        num::{NonZeroU32, NonZeroUsize},
+        pin::pin,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use eventsource_stream::Eventsource as _;
+    use futures_util::{Stream, StreamExt as _, future::ready, stream::pending};
+    use http_body_util::{BodyExt as _, BodyStream};
+    use tokio::{
+        sync::{Notify, mpsc, watch},
+        time::{Instant, timeout},
+    };
+    use tokio_stream::wrappers::ReceiverStream;
+
+    use axum::{
+        Router,
+        body::Body,
+        http::{HeaderValue, Request, StatusCode},
+    };
+    use double_trait::Dummy;
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot`
+
+    #[tokio::test]
+    async fn add_message_route_forwards_arguments_to_chat_api() {
+        // Given
+        #[derive(Clone)]
+        struct SessionsStub;
+        impl AuthenticateRequest for SessionsStub {
+            async fn authenticate_request(
+                &self,
+                _parts: &Parts,
+            ) -> Result<UserId, crate::http::HttpError> {
+                Ok(UserId::BOB)
+            }
+        }
+        let spy = ChatSpy::default();
+        let app = chat_routes(
+            spy.clone(),
+            Dummy,
+            SessionsStub,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+        let new_message = json!({
+            "id": MessageId::ALPHA,
+            "content": "Hello, Alice!"
+        });
+
+        // When
+        let _response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .header("content-type", "application/json")
+                    .body(Body::from(new_message.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then
+        let expected_msg = Message {
+            id: MessageId::ALPHA,
+            author: UserId::BOB,
+            content: "Hello, Alice!".to_owned(),
+            expires_at_ms: None,
+        };
+        assert_eq!(spy.take_add_message_record(), &[expected_msg]);
+    }
+
+    #[tokio::test]
+    async fn message_with_ttl_is_recorded_with_expiry() {
+        // Given
+        let spy = ChatSpy::default();
+        let app = chat_routes(
+            spy.clone(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+        let new_message = json!({
+            "id": MessageId::ALPHA,
+            "content": "This message will self-destruct",
+            field("ttl_secs"): 60,
+        });
+
+        // When
+        let before = SystemTime::now();
+        let _response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .header("content-type", "application/json")
+                    .body(Body::from(new_message.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let after = SystemTime::now();
+
+        // Then it expires a minute after it has been added
+        let ms = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let messages = spy.take_add_message_record();
+        let expires_at_ms = messages[0].expires_at_ms.unwrap();
+        assert!(expires_at_ms >= ms(before + Duration::from_secs(60)));
+        assert!(expires_at_ms <= ms(after + Duration::from_secs(60)));
+    }
+
+    #[tokio::test]
+    async fn legacy_message_is_recorded_like_current_one() {
+        // Given
+        let spy = ChatSpy::default();
+        let app = chat_routes(
+            spy.clone(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+        let legacy_message = json!({
+            "message_id": MessageId::ALPHA,
+            "text": "Hello",
+            "timestamp": 1_700_000_000
+        });
+
+        // When posting the same message once in the legacy and once in the current shape
+        let legacy_response = app
+            .clone()
+            .oneshot(
+                Request::post("/api/v0/v1/add_message")
+                    .header("content-type", "application/json")
+                    .body(Body::from(legacy_message.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then both are recorded as the same message
+        assert_eq!(StatusCode::OK, legacy_response.status());
+        assert_eq!(StatusCode::OK, response.status());
+        let recorded = spy.take_add_message_record();
+        assert_eq!(2, recorded.len());
+        assert_eq!(recorded[0], recorded[1]);
+    }
+
+    #[tokio::test]
+    async fn allowed_sender_may_post() {
+        // Given a chat which only allows Alice to post
+        let spy = ChatSpy::default();
+        let options = ChatHttpOptions {
+            allowed_senders: Some(Arc::new(HashSet::from(["Alice".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            spy.clone(),
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When Alice posts a message
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the message is forwarded to the chat
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(spy.take_add_message_record().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn disallowed_sender_is_rejected_with_403() {
+        // Given a chat which only allows Bob to post
+        let spy = ChatSpy::default();
+        let options = ChatHttpOptions {
+            allowed_senders: Some(Arc::new(HashSet::from(["Bob".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            spy.clone(),
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When Alice posts a message
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the message is rejected and never reaches the chat
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(spy.take_add_message_record().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reserved_sender_is_rejected_with_403() {
+        // Given a chat reserving the name of Alice, in different case
+        let spy = ChatSpy::default();
+        let options = ChatHttpOptions {
+            reserved_senders: Some(Arc::new(HashSet::from([
+                "system".to_owned(),
+                "alice".to_owned(),
+            ]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            spy.clone(),
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When Alice posts a message
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the message is rejected and never reaches the chat
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(spy.take_add_message_record().is_empty());
+    }
+
+    #[tokio::test]
+    async fn sender_with_unreserved_name_may_post() {
+        // Given a chat reserving only "system"
+        let spy = ChatSpy::default();
+        let options = ChatHttpOptions {
+            reserved_senders: Some(Arc::new(HashSet::from(["system".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            spy.clone(),
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When Alice posts a message
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the message is forwarded to the chat
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(spy.take_add_message_record().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn moderator_rejecting_message_yields_403_with_reason() {
+        // Given a chat API rejecting messages which contain "Hello"
+        let spy = ChatSpy::default();
+        let app = moderated_chat_routes(spy.clone(), WordlistAction::Reject);
+
+        // When a message saying "Hello" is added
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then it is rejected with the reason and not recorded
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            "Message contains a word which is not allowed in this chat",
+            body
+        );
+        assert!(spy.take_add_message_record().is_empty());
+    }
+
+    #[tokio::test]
+    async fn moderator_transforming_message_records_new_content() {
+        // Given a chat API masking "Hello"
+        let spy = ChatSpy::default();
+        let app = moderated_chat_routes(spy.clone(), WordlistAction::Mask);
+
+        // When a message saying "Hello" is added
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the masked content is recorded
+        assert_eq!(response.status(), StatusCode::OK);
+        let recorded = spy.take_add_message_record();
+        assert_eq!("*****", recorded[0].content);
+    }
+
+    #[tokio::test]
+    async fn pasted_content_is_trimmed_if_configured() {
+        // Given a chat API trimming content
+        let spy = ChatSpy::default();
+        let options = ChatHttpOptions {
+            trim_content: true,
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(spy.clone(), Dummy, AuthDummy, Lifecycle::running(), options);
+
+        // When adding a message surrounded by blank lines and zero-width spaces
+        let content = "\n \u{200B}\n  Hello\n\n  world\u{200D}!\t\u{FEFF}\n\n";
+        let response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({ "id": MessageId::ALPHA, "content": content }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then they are stripped from the start and end, but not from within
+        assert_eq!(response.status(), StatusCode::OK);
+        let recorded = spy.take_add_message_record();
+        assert_eq!("Hello\n\n  world\u{200D}!", recorded[0].content);
+    }
+
+    #[tokio::test]
+    async fn messages_violating_content_policy_yield_400_with_reason() {
+        // Given a chat API refusing URLs
+        let spy = ChatSpy::default();
+        let options = ChatHttpOptions {
+            content_policy: ContentPolicy {
+                disallow_urls: true,
+                disallow_mentions: false,
+            },
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(spy.clone(), Dummy, AuthDummy, Lifecycle::running(), options);
+
+        // When adding a message containing a URL
+        let response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({ "id": MessageId::ALPHA, "content": "See https://example.com" })
+                            .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then it is rejected with the reason and not recorded
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            "Message contains a URL, which is not allowed in this chat",
+            body
+        );
+        assert!(spy.take_add_message_record().is_empty());
+    }
+
+    #[tokio::test]
+    async fn moderator_allowing_message_records_it_unchanged() {
+        // Given a chat API masking "Hello"
+        let spy = ChatSpy::default();
+        let app = moderated_chat_routes(spy.clone(), WordlistAction::Mask);
+
+        // When a message without it is added
+        let response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({ "id": MessageId::ALPHA, "content": "Hi" }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then it is recorded as it is
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!("Hi", spy.take_add_message_record()[0].content);
+    }
+
+    #[tokio::test]
+    async fn messages_are_rejected_with_503_while_draining() {
+        // Given a draining server
+        let spy = ChatSpy::default();
+        let (_, draining) = watch::channel(true);
+        let lifecycle = Lifecycle {
+            draining,
+            ..Lifecycle::running()
+        };
+        let app = chat_routes(
+            spy.clone(),
+            Dummy,
+            AuthDummy,
+            lifecycle,
+            ChatHttpOptions::default(),
+        );
+
+        // When posting a message
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the message is rejected and never reaches the chat
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(spy.take_add_message_record().is_empty());
+    }
+
+    #[tokio::test]
+    async fn events_are_streamed_while_draining() {
+        // Given a draining server with one message in its history
+        let (_, draining) = watch::channel(true);
+        let lifecycle = Lifecycle {
+            draining,
+            ..Lifecycle::running()
+        };
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            lifecycle,
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting the events stream
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the message is still streamed
+        let event = body_to_sse(response.into_body())
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("1", event.id);
+    }
+
+    #[tokio::test]
+    async fn message_content_is_logged_at_debug_level() {
+        // Given captured logs without redaction
+        let logs = CapturedLogs::default();
+        let _guard = logs.capture(false);
+        let app = chat_routes(
+            ChatSpy::default(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When posting a message
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then content and sender appear in the logs
+        assert_eq!(response.status(), StatusCode::OK);
+        let logs = logs.text();
+        assert!(logs.contains("Hello"), "{logs}");
+        assert!(logs.contains(&UserId::nil().to_string()), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn redacted_logs_contain_no_message_content() {
+        // Given captured logs with redaction
+        let logs = CapturedLogs::default();
+        let _guard = logs.capture(true);
+        let app = chat_routes(
+            ChatSpy::default(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When posting a message
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then neither content nor sender appear in the logs, yet the message has been logged
+        assert_eq!(response.status(), StatusCode::OK);
+        let logs = logs.text();
+        assert!(logs.contains("Adding message"), "{logs}");
+        assert!(!logs.contains("Hello"), "{logs}");
+        assert!(!logs.contains(&UserId::nil().to_string()), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn any_sender_may_post_without_allowlist() {
+        // Given a chat without an allowlist. Users are not even looked up.
+        let spy = ChatSpy::default();
+        let app = chat_routes(
+            spy.clone(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When posting a message
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the message is forwarded to the chat
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(spy.take_add_message_record().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn add_message_without_content_type_is_rejected_with_415() {
+        // Given
+        let app = chat_routes(
+            Dummy,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When posting a message without content type
+        let response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .body(Body::from(
+                        r#"{"id":"019c0ab6-9d11-75ef-ab02-60f070b1582a","content":"Hello"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            "Expected request with `Content-Type: application/json`",
+            String::from_utf8(body.to_vec()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn add_message_with_wrong_content_type_is_rejected_with_415() {
+        // Given
+        let app = chat_routes(
+            Dummy,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When posting a message as plain text
+        let response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .header("content-type", "text/plain")
+                    .body(Body::from(
+                        r#"{"id":"019c0ab6-9d11-75ef-ab02-60f070b1582a","content":"Hello"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn add_message_with_malformed_json_is_rejected_with_400() {
+        // Given
+        let app = chat_routes(
+            Dummy,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When posting a body which is not valid JSON
+        let response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"id": "#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(
+            body.starts_with("Request body is not valid JSON"),
+            "Unexpected body: {body}"
+        );
+    }
+
+    #[tokio::test]
+    async fn add_message_with_invalid_uuid_names_the_field() {
+        // When posting a message with an id which is not a UUID
+        let (status, body) = post_new_message(json!({"id": "banana", "content": "Hello"})).await;
+
+        // Then the client is told that the id is at fault
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_field");
+        assert_eq!(body["field"], "id");
+        assert!(
+            body["message"].as_str().unwrap().contains("UUID"),
+            "Unexpected body: {body}"
+        );
+    }
+
+    #[tokio::test]
+    async fn message_ids_must_comply_with_uuid_policy() {
+        for (uuid_policy, id, expected) in [
+            (UuidPolicy::Strict, MessageId::ALPHA, StatusCode::OK),
+            (
+                UuidPolicy::Strict,
+                MessageId::random(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (UuidPolicy::Lenient, MessageId::random(), StatusCode::OK),
+            (
+                UuidPolicy::Lenient,
+                MessageId::nil(),
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            // Given a chat with a policy for message ids
+            let spy = ChatSpy::default();
+            let options = ChatHttpOptions {
+                uuid_policy,
+                ..ChatHttpOptions::default()
+            };
+            let app = chat_routes(spy.clone(), Dummy, AuthDummy, Lifecycle::running(), options);
+
+            // When posting a message with the id
+            let request = Request::post("/api/v0/add_message")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({"id": id, "content": "Hello"}).to_string(),
+                ))
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+
+            // Then only compliant ids are forwarded to the chat
+            assert_eq!(expected, response.status(), "{uuid_policy:?} {id}");
+            let forwarded = spy.take_add_message_record().len();
+            assert_eq!(expected == StatusCode::OK, forwarded == 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn add_message_with_missing_field_names_the_field() {
+        // When posting a message without content
+        let (status, body) = post_new_message(json!({"id": MessageId::ALPHA})).await;
+
+        // Then the client is told that the content is missing
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_field");
+        assert_eq!(body["message"], "missing field `content`");
+    }
+
+    #[tokio::test]
+    async fn add_message_with_wrong_typed_field_names_the_field() {
+        // When posting a message with a number as content
+        let (status, body) = post_new_message(json!({"id": MessageId::ALPHA, "content": 42})).await;
+
+        // Then the client is told that the content has the wrong type
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_field");
+        assert_eq!(body["field"], "content");
+        assert_eq!(
+            body["message"],
+            "invalid type: integer `42`, expected a string"
+        );
+    }
+
+    #[tokio::test]
+    async fn new_message_is_reported_as_new() {
+        // Given a chat recording every message as new
+        let app = chat_routes(
+            ChatSpy::default(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When a message is sent
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the client learns it has been new to the chat
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!("new", response.headers()["x-klatsch-outcome"]);
+    }
+
+    #[tokio::test]
+    async fn duplicate_message_is_reported_as_duplicate() {
+        // Given a chat which already knows every message
+        #[derive(Clone)]
+        struct DuplicateStub;
+        impl Chat for DuplicateStub {
+            async fn add_message(&mut self, _: Message) -> Result<AddOutcome, ChatError> {
+                Ok(AddOutcome::Duplicate(HistoryStub::event()))
+            }
+        }
+        let app = chat_routes(
+            DuplicateStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When a message is sent again, e.g. by a retry
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then it is still accepted, but the client learns it has been deduplicated and which event
+        // it has originally been recorded as
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!("duplicate", response.headers()["x-klatsch-outcome"]);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body, api_json(json!({ "event_id": 1, "timestamp_ms": 0 })));
+    }
+
+    #[tokio::test]
+    async fn writes_beyond_max_concurrency_are_rejected() {
+        // Given a chat which takes forever to add a message, and only one concurrent write allowed
+        #[derive(Clone, Default)]
+        struct SlowChat {
+            entered: Arc<Notify>,
+        }
+        impl Chat for SlowChat {
+            async fn add_message(&mut self, _: Message) -> Result<AddOutcome, ChatError> {
+                self.entered.notify_one();
+                std::future::pending().await
+            }
+        }
+        let chat = SlowChat::default();
+        let options = ChatHttpOptions {
+            max_concurrent_writes: NonZeroUsize::new(1),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            chat.clone(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+        let first = tokio::spawn(app.clone().oneshot(add_message_request()));
+        chat.entered.notified().await;
+
+        // When adding another message, while the first one is still in flight
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then it is rejected and the client is told to retry later
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, response.status());
+        assert!(response.headers().contains_key("retry-after"));
+        first.abort();
+    }
+
+    #[tokio::test]
+    async fn message_rate_is_limited_across_senders() {
+        // Given a chat API allowing a burst of two messages for all senders together, and a session
+        // store telling every request apart as another sender
+        #[derive(Clone, Default)]
+        struct RotatingSenders(Arc<AtomicUsize>);
+        impl AuthenticateRequest for RotatingSenders {
+            async fn authenticate_request(
+                &self,
+                _parts: &Parts,
+            ) -> Result<UserId, crate::http::HttpError> {
+                let senders = [UserId::ALICE, UserId::BOB, UserId::nil()];
+                Ok(senders[self.0.fetch_add(1, Ordering::SeqCst) % senders.len()])
+            }
+        }
+        let options = ChatHttpOptions {
+            message_rate: Some(MessageRate {
+                per_second: NonZeroU32::new(1).unwrap(),
+                burst: NonZeroU32::new(2).unwrap(),
+            }),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            ChatSpy::default(),
+            Dummy,
+            RotatingSenders::default(),
+            Lifecycle::running(),
+            options,
+        );
+
+        // When three different senders add a message each
+        let mut statuses = Vec::new();
+        let mut last = None;
+        for _ in 0..3 {
+            let response = app.clone().oneshot(add_message_request()).await.unwrap();
+            statuses.push(response.status());
+            last = Some(response);
+        }
+
+        // Then the third one is throttled, although each sender only added a single message
+        assert_eq!(
+            [
+                StatusCode::OK,
+                StatusCode::OK,
+                StatusCode::TOO_MANY_REQUESTS
+            ],
+            statuses.as_slice()
+        );
+        assert_eq!("1", last.unwrap().headers()["retry-after"]);
+    }
+
+    #[tokio::test]
+    async fn expired_messages_are_announced_with_tombstones() {
+        // Given a chat API with an open event stream, over a chat whose messages expire
+        #[derive(Clone)]
+        struct ExpiringChat(Arc<Mutex<Option<mpsc::Receiver<Vec<Tombstone>>>>>);
+        impl Chat for ExpiringChat {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                pending()
+            }
+
+            fn expired_messages(self) -> impl Stream<Item = Vec<Tombstone>> + Send {
+                ReceiverStream::new(self.0.lock().unwrap().take().unwrap())
+            }
+        }
+        let (expire, expired) = mpsc::channel(1);
+        let app = chat_routes(
+            ExpiringChat(Arc::new(Mutex::new(Some(expired)))),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+        let stream = app
+            .oneshot(Request::get("/api/v0/events").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let mut events = body_to_sse(stream.into_body());
+
+        // When a message expires
+        let tombstone = Tombstone {
+            event_id: EventId(1),
+            message_id: MessageId::ALPHA,
+        };
+        expire.send(vec![tombstone]).await.unwrap();
+
+        // Then the stream is told about it
+        let tombstone = timeout(Duration::from_secs(1), events.next())
+            .await
+            .expect("timed out waiting for tombstone event")
+            .unwrap()
+            .unwrap();
+        assert_eq!("tombstone", tombstone.event);
+        let tombstone: serde_json::Value = serde_json::from_str(&tombstone.data).unwrap();
+        assert_eq!(
+            api_json(json!({ "message_id": MessageId::ALPHA, "event_id": 1 })),
+            tombstone
+        );
+    }
+
+    #[tokio::test]
+    async fn duplicates_are_echoed_to_open_event_streams() {
+        // Given a chat API with an open event stream, over a chat echoing duplicates
+        #[derive(Clone)]
+        struct EchoingChat(Arc<Mutex<Option<mpsc::Receiver<Vec<Event>>>>>);
+        impl Chat for EchoingChat {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                pending()
+            }
+
+            fn echoed_duplicates(self) -> impl Stream<Item = Vec<Event>> + Send {
+                ReceiverStream::new(self.0.lock().unwrap().take().unwrap())
+            }
+        }
+        let (echo, echoes) = mpsc::channel(1);
+        let app = chat_routes(
+            EchoingChat(Arc::new(Mutex::new(Some(echoes)))),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+        let stream = app
+            .oneshot(Request::get("/api/v0/events").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let mut events = body_to_sse(stream.into_body());
+
+        // When a message is added again
+        let message = Message {
+            id: MessageId::ALPHA,
+            author: UserId::ALICE,
+            content: "Hello".to_owned(),
+            expires_at_ms: None,
+        };
+        let original = Event::with_timestamp(EventId(1), message, UNIX_EPOCH);
+        echo.send(vec![original]).await.unwrap();
+
+        // Then the stream repeats the original message, without advancing the Last-Event-ID
+        let echo = timeout(Duration::from_secs(1), events.next())
+            .await
+            .expect("timed out waiting for echo event")
+            .unwrap()
+            .unwrap();
+        assert_eq!("echo", echo.event);
+        assert!(echo.id.is_empty());
+        let echo: serde_json::Value = serde_json::from_str(&echo.data).unwrap();
+        assert_eq!(json!(MessageId::ALPHA), echo["id"]);
+        assert_eq!(json!("Hello"), echo["content"]);
+    }
+
+    #[tokio::test]
+    async fn server_timing_is_reported_if_enabled() {
+        // Given a chat API configured to report server timing
+        let app = chat_routes(
+            ChatSpy::default(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions {
+                server_timing: true,
+                ..ChatHttpOptions::default()
+            },
+        );
+
+        // When a message is sent
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the time spent validating and recording it is reported in milliseconds
+        assert_eq!(response.status(), StatusCode::OK);
+        let timing = response.headers()["server-timing"].to_str().unwrap();
+        let metrics: Vec<_> = timing
+            .split(", ")
+            .map(|metric| {
+                let (name, duration) = metric.split_once(";dur=").unwrap();
+                let duration: f64 = duration.parse().unwrap();
+                assert!(duration >= 0.0);
+                name
+            })
+            .collect();
+        assert_eq!(metrics, ["validate", "chat"]);
+    }
+
+    #[tokio::test]
+    async fn server_timing_is_not_reported_by_default() {
+        // Given a chat API with default options
+        let app = chat_routes(
+            ChatSpy::default(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When a message is sent
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then no timing is disclosed
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key("server-timing"));
+    }
+
+    #[tokio::test]
+    async fn conflict_error_translates_to_409() {
+        // Given a chat that reports any message as a conflict
+        #[derive(Clone)]
+        struct ChatSaboteur;
+        impl Chat for ChatSaboteur {
+            async fn add_message(&mut self, _: Message) -> Result<AddOutcome, ChatError> {
+                Err(ChatError::Conflict)
+            }
+        }
+
+        let app = chat_routes(
+            ChatSaboteur,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When a message is sent
+        let response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({
+                            "id": "019c0a7f-3d8e-7cf8-bea4-3a8614c8da09",
+                            "content": "dummy"
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the response is 409 Conflict
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn too_large_error_translates_to_413() {
+        // Given a chat that reports any message as too large
+        #[derive(Clone)]
+        struct ChatSaboteur;
+        impl Chat for ChatSaboteur {
+            async fn add_message(&mut self, _: Message) -> Result<AddOutcome, ChatError> {
+                Err(ChatError::TooLarge)
+            }
+        }
+
+        let app = chat_routes(
+            ChatSaboteur,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When a message is sent
+        let response = app
+            .oneshot(
+                Request::post("/api/v0/add_message")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({
+                            "id": "019c0a7f-3d8e-7cf8-bea4-3a8614c8da09",
+                            "content": "dummy"
+                        })
+                        .to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the response is 413 Payload Too Large
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn storage_full_error_translates_to_507() {
+        // Given a chat that reports its storage to be full
+        #[derive(Clone)]
+        struct ChatSaboteur;
+        impl Chat for ChatSaboteur {
+            async fn add_message(&mut self, _: Message) -> Result<AddOutcome, ChatError> {
+                Err(ChatError::StorageFull)
+            }
+        }
+        let app = chat_routes(
+            ChatSaboteur,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
 
         // When a message is sent
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the response is 507 Insufficient Storage
+        assert_eq!(response.status(), StatusCode::INSUFFICIENT_STORAGE);
+    }
+
+    #[tokio::test]
+    async fn events_route_forwards_events_from_chat() {
+        // Given
+        #[derive(Clone)]
+        struct ChatStub;
+
+        impl Chat for ChatStub {
+            fn events(
+                self,
+                _last_event_id: EventId,
+            ) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                let messages = vec![
+                    Event::with_timestamp(
+                        EventId(1),
+                        Message {
+                            id: "019c0050-e4d7-7447-9d8f-81cde690f4a1".parse().unwrap(),
+                            author: UserId::ALICE,
+                            content: "One".to_owned(),
+                            expires_at_ms: None,
+                        },
+                        UNIX_EPOCH + Duration::from_millis(1704531600000),
+                    ),
+                    Event::with_timestamp(
+                        EventId(2),
+                        Message {
+                            id: "019c0051-c29d-7968-b953-4adc898b1360".parse().unwrap(),
+                            author: UserId::BOB,
+                            content: "Two".to_owned(),
+                            expires_at_ms: None,
+                        },
+                        UNIX_EPOCH + Duration::from_millis(1704531601000),
+                    ),
+                    Event::with_timestamp(
+                        EventId(3),
+                        Message {
+                            id: "019c0051-e50d-7ea7-8a0e-f7df4176dd93".parse().unwrap(),
+                            author: UserId::ALICE,
+                            content: "Three".to_owned(),
+                            expires_at_ms: None,
+                        },
+                        UNIX_EPOCH + Duration::from_millis(1704531602000),
+                    ),
+                    Event::with_timestamp(
+                        EventId(4),
+                        Message {
+                            id: "019c0052-09b0-73be-a145-3767cb10cdf6".parse().unwrap(),
+                            author: UserId::BOB,
+                            content: "Four".to_owned(),
+                            expires_at_ms: None,
+                        },
+                        UNIX_EPOCH + Duration::from_millis(1704531603000),
+                    ),
+                ];
+                tokio_stream::iter(messages).map(Ok)
+            }
+        }
+        let app = chat_routes(
+            ChatStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then
+        assert_eq!(response.status(), StatusCode::OK);
+        let actual: Vec<_> = body_to_sse(response.into_body())
+            .map(|r| r.unwrap())
+            .map(|event| {
+                (
+                    event.id.parse::<u64>().unwrap(),
+                    event.event,
+                    serde_json::from_str::<serde_json::Value>(&event.data).unwrap(),
+                )
+            })
+            .collect()
+            .await;
+
+        let expected = [
+            (
+                1,
+                "message".to_owned(),
+                json!({
+                    "id": "019c0050-e4d7-7447-9d8f-81cde690f4a1",
+                    "sender_id": UserId::ALICE,
+                    "content": "One",
+                    "timestamp_ms": 1704531600000u64,
+                "pinned": false
+                }),
+            ),
+            (
+                2,
+                "message".to_owned(),
+                json!({
+                    "id": "019c0051-c29d-7968-b953-4adc898b1360",
+                    "sender_id": UserId::BOB,
+                    "content": "Two",
+                    "timestamp_ms": 1704531601000u64,
+                "pinned": false
+                }),
+            ),
+            (
+                3,
+                "message".to_owned(),
+                json!({
+                    "id": "019c0051-e50d-7ea7-8a0e-f7df4176dd93",
+                    "sender_id": UserId::ALICE,
+                    "content": "Three",
+                    "timestamp_ms": 1704531602000u64,
+                "pinned": false
+                }),
+            ),
+            (
+                4,
+                "message".to_owned(),
+                json!({
+                    "id": "019c0052-09b0-73be-a145-3767cb10cdf6",
+                    "sender_id": UserId::BOB,
+                    "content": "Four",
+                    "timestamp_ms": 1704531603000u64,
+                "pinned": false
+                }),
+            ),
+        ]
+        .map(|(id, event, data)| (id, event, api_json(data)));
+        assert_eq!(expected.as_slice(), &actual);
+    }
+
+    #[tokio::test]
+    async fn sender_color_is_included_if_enabled() {
+        // Given a chat with one message from Alice and sender colors enabled
+        #[derive(Clone)]
+        struct ChatStub;
+        impl Chat for ChatStub {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                tokio_stream::iter(vec![Ok(Event::with_timestamp(
+                    EventId(1),
+                    Message {
+                        author: UserId::ALICE,
+                        ..Message::dummy()
+                    },
+                    UNIX_EPOCH,
+                ))])
+            }
+        }
+        let options = ChatHttpOptions {
+            sender_color: true,
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(ChatStub, Dummy, AuthDummy, Lifecycle::running(), options);
+
+        // When requesting events
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the message carries the color seed of Alice
+        let event = body_to_sse(response.into_body())
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+        let data: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+        assert_eq!(data[field("sender_color")], sender_color(UserId::ALICE));
+    }
+
+    #[tokio::test]
+    async fn senders_with_bot_prefix_are_tagged_as_bots() {
+        // Given a chat with a message of Alice, followed by one of a bot
+        #[derive(Clone)]
+        struct ChatStub;
+        impl Chat for ChatStub {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                let events = [(1, UserId::ALICE), (2, UserId::BOB)].map(|(id, author)| {
+                    Ok(Event::with_timestamp(
+                        EventId(id),
+                        Message {
+                            author,
+                            ..Message::dummy()
+                        },
+                        UNIX_EPOCH,
+                    ))
+                });
+                tokio_stream::iter(events)
+            }
+        }
+        #[derive(Clone)]
+        struct UsersStub;
+        impl Users for UsersStub {
+            async fn user_by_id(&mut self, id: UserId) -> Result<User, UsersError> {
+                let name = if id == UserId::BOB {
+                    "bot:weather"
+                } else {
+                    "Alice"
+                };
+                Ok(User {
+                    name: name.to_owned(),
+                })
+            }
+        }
+        let options = ChatHttpOptions {
+            bot_prefix: Some("bot:".into()),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            ChatStub,
+            UsersStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When requesting events
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the message of the bot is tagged as such, while the one of Alice is not
+        let events: Vec<_> = body_to_sse(response.into_body())
+            .take(2)
+            .map(|event| serde_json::from_str::<serde_json::Value>(&event.unwrap().data).unwrap())
+            .collect()
+            .await;
+        assert_eq!(events[0]["kind"], "human");
+        assert_eq!(events[1]["kind"], "bot");
+    }
+
+    #[tokio::test]
+    async fn bot_messages_bypass_rate_limit_if_exempt() {
+        // Given a chat API allowing a single message at once, with bots exempt from the limit, and
+        // a bot as sender
+        #[derive(Clone)]
+        struct BotStub;
+        impl Users for BotStub {
+            async fn user_by_id(&mut self, _id: UserId) -> Result<User, UsersError> {
+                Ok(User {
+                    name: "bot:weather".to_owned(),
+                })
+            }
+        }
+        let options = ChatHttpOptions {
+            bot_prefix: Some("bot:".into()),
+            bot_rate_limit_exempt: true,
+            message_rate: Some(MessageRate {
+                per_second: NonZeroU32::new(1).unwrap(),
+                burst: NonZeroU32::new(1).unwrap(),
+            }),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            ChatSpy::default(),
+            BotStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When the bot adds three messages in a row
+        let mut statuses = Vec::new();
+        for _ in 0..3 {
+            let response = app.clone().oneshot(add_message_request()).await.unwrap();
+            statuses.push(response.status());
+        }
+
+        // Then none of them is throttled
+        assert_eq!([StatusCode::OK; 3], statuses.as_slice());
+    }
+
+    #[tokio::test]
+    async fn bot_messages_are_rate_limited_unless_exempt() {
+        // Given a chat API allowing a single message at once, which tells bots apart, but does not
+        // exempt them from the limit
+        #[derive(Clone)]
+        struct BotStub;
+        impl Users for BotStub {
+            async fn user_by_id(&mut self, _id: UserId) -> Result<User, UsersError> {
+                Ok(User {
+                    name: "bot:weather".to_owned(),
+                })
+            }
+        }
+        let options = ChatHttpOptions {
+            bot_prefix: Some("bot:".into()),
+            message_rate: Some(MessageRate {
+                per_second: NonZeroU32::new(1).unwrap(),
+                burst: NonZeroU32::new(1).unwrap(),
+            }),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            ChatSpy::default(),
+            BotStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When the bot adds two messages in a row
+        app.clone().oneshot(add_message_request()).await.unwrap();
+        let response = app.oneshot(add_message_request()).await.unwrap();
+
+        // Then the second one is throttled
+        assert_eq!(StatusCode::TOO_MANY_REQUESTS, response.status());
+    }
+
+    #[tokio::test]
+    async fn meta_event_tells_number_of_remaining_events_before_they_flow() {
+        // Given a chat with two events after the one the client has seen last
+        #[derive(Clone)]
+        struct ChatStub;
+        impl Chat for ChatStub {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                tokio_stream::iter(vec![
+                    Ok(Event::with_timestamp(
+                        EventId(6),
+                        Message::dummy(),
+                        UNIX_EPOCH,
+                    )),
+                    Ok(Event::with_timestamp(
+                        EventId(7),
+                        Message::dummy(),
+                        UNIX_EPOCH,
+                    )),
+                ])
+            }
+
+            async fn last_event_before(&self, _timestamp_ms: u64) -> anyhow::Result<EventId> {
+                Ok(EventId(5))
+            }
+
+            async fn count_events_since(&self, last_event_id: EventId) -> anyhow::Result<u64> {
+                assert_eq!(EventId(5), last_event_id);
+                Ok(2)
+            }
+        }
+        let app = chat_routes(
+            ChatStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting events with meta since event 5
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events?meta=true&since_ms=5000")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the meta event comes first, without id, telling two events remain
+        let events: Vec<_> = body_to_sse(response.into_body())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        let frames: Vec<_> = events
+            .iter()
+            .map(|event| (event.event.as_str(), event.id.as_str()))
+            .collect();
+        assert_eq!(
+            vec![("meta", ""), ("message", "6"), ("message", "7")],
+            frames
+        );
+        assert_eq!(
+            json!({"remaining": 2}),
+            serde_json::from_str::<serde_json::Value>(&events[0].data).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn motd_is_first_event_and_sent_only_once() {
+        // Given a chat with two historic messages and a message of the day
+        #[derive(Clone)]
+        struct ChatStub;
+        impl Chat for ChatStub {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                tokio_stream::iter(vec![
+                    Ok(Event::with_timestamp(
+                        EventId(1),
+                        Message::dummy(),
+                        UNIX_EPOCH,
+                    )),
+                    Ok(Event::with_timestamp(
+                        EventId(2),
+                        Message::dummy(),
+                        UNIX_EPOCH,
+                    )),
+                ])
+            }
+        }
+        let options = ChatHttpOptions {
+            motd: Some("Welcome!".into()),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(ChatStub, Dummy, AuthDummy, Lifecycle::running(), options);
+
+        // When requesting events
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the motd comes right after the server time, without id, followed by the historic
+        // messages
+        let events: Vec<_> = body_to_all_sse(response.into_body())
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        let frames: Vec<_> = events
+            .iter()
+            .map(|event| (event.event.as_str(), event.id.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("server_time", ""),
+                ("motd", ""),
+                ("message", "1"),
+                ("message", "2")
+            ],
+            frames
+        );
+        assert_eq!("Welcome!", events[1].data);
+    }
+
+    #[tokio::test]
+    async fn allowed_origin_is_echoed_for_credentialed_event_stream() {
+        // Given credentialed CORS for an allowed origin
+        let options = ChatHttpOptions {
+            cors_origins: vec![HeaderValue::from_static("https://dashboard.example")],
+            cors_allow_credentials: true,
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(HistoryStub, Dummy, AuthDummy, Lifecycle::running(), options);
+
+        // When a page of this origin requests the event stream
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Origin", "https://dashboard.example")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the origin is echoed back and credentials are allowed
+        let headers = response.headers();
+        assert_eq!(
+            "https://dashboard.example",
+            headers["access-control-allow-origin"]
+        );
+        assert_eq!("true", headers["access-control-allow-credentials"]);
+    }
+
+    #[tokio::test]
+    async fn other_origins_may_not_read_event_stream() {
+        // Given credentialed CORS for an allowed origin
+        let options = ChatHttpOptions {
+            cors_origins: vec![HeaderValue::from_static("https://dashboard.example")],
+            cors_allow_credentials: true,
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(HistoryStub, Dummy, AuthDummy, Lifecycle::running(), options);
+
+        // When a page of another origin requests the event stream
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Origin", "https://evil.example")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the browser is not allowed to hand the response to the page
+        assert!(
+            !response
+                .headers()
+                .contains_key("access-control-allow-origin")
+        );
+    }
+
+    #[tokio::test]
+    async fn server_time_is_first_event() {
+        // Given a chat with a historic message
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+        let before = SystemTime::now();
+
+        // When requesting events
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the current time of the server comes first, without id, so it does not advance
+        // `Last-Event-ID`
+        let after = SystemTime::now();
+        let event = body_to_all_sse(response.into_body())
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("server_time", event.event);
+        assert!(event.id.is_empty());
+        let server_time = UNIX_EPOCH + Duration::from_millis(event.data.parse().unwrap());
+        // Milliseconds are truncated, so the server time may be slightly before `before`
+        assert!(before - Duration::from_millis(1) <= server_time && server_time <= after);
+    }
+
+    #[test]
+    fn same_sender_always_yields_same_color() {
+        // Pinned to a literal, so a change in the algorithm, which would recolor senders for all
+        // clients, does not go unnoticed.
+        assert_eq!(sender_color(UserId::ALICE), sender_color(UserId::ALICE));
+        assert_eq!(2_720_174_347, sender_color(UserId::ALICE));
+        assert_ne!(sender_color(UserId::ALICE), sender_color(UserId::BOB));
+    }
+
+    #[tokio::test]
+    async fn events_should_return_content_type_event_stream() {
+        // Given
+        let app = chat_routes(
+            Dummy,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("");
+        assert!(
+            content_type.starts_with("text/event-stream"),
+            "Expected SSE content-type, got: {}",
+            content_type
+        );
+    }
+
+    #[tokio::test]
+    async fn events_as_json_array_if_accept_is_application_json() {
+        // Given a chat with one message in its history
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting events as JSON
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Accept", "application/json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the history is returned as a JSON array including the event ids
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "application/json"
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let actual: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let expected = api_json(json!([{
+            "event_id": 1,
+            "id": MessageId::ALPHA,
+            "sender_id": UserId::ALICE,
+            "content": "Hello",
+            "timestamp_ms": 0,
+            "pinned": false
+        }]));
+        assert_eq!(expected, actual);
+    }
+
+    #[tokio::test]
+    async fn event_stream_varies_by_accept() {
+        // Given a chat with one message in its history
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting an event stream on the same URL
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Accept", "text/event-stream")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then caches are told that it depends on `Accept`, too
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("Vary").unwrap(), "accept");
+    }
+
+    #[tokio::test]
+    async fn events_as_event_stream_if_accept_is_text_event_stream() {
+        // Given a chat with one message in its history
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting events as event stream, even though JSON would also be fine
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Accept", "application/json, text/event-stream")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the message is streamed as SSE event
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            "text/event-stream"
+        );
+        let event = body_to_sse(response.into_body())
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("1", event.id);
+    }
+
+    #[tokio::test]
+    async fn rendered_html_is_included_if_enabled_and_requested() {
+        // Given a chat with one message in its history, allowing to render HTML
+        let options = ChatHttpOptions {
+            render_html: true,
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(HistoryStub, Dummy, AuthDummy, Lifecycle::running(), options);
+
+        // When requesting the history rendered to HTML
+        let response = app
+            .oneshot(
+                Request::get("/api/v0/history?render=html")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the message carries its rendered content besides the raw one
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events[0]["content"], "Hello");
+        assert_eq!(events[0][field("content_html")], "<p>Hello</p>\n");
+    }
+
+    #[tokio::test]
+    async fn rendering_html_is_ignored_unless_enabled() {
+        // Given a chat with one message in its history, with default options
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting the history rendered to HTML
         let response = app
             .oneshot(
-                Request::post("/api/v0/add_message")
-                    .header("content-type", "application/json")
-                    .body(Body::from(
-                        json!({
-                            "id": "019c0a7f-3d8e-7cf8-bea4-3a8614c8da09",
-                            "content": "dummy"
-                        })
-                        .to_string(),
-                    ))
+                Request::get("/api/v0/history?render=html")
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        // Then the response is 409 Conflict
-        assert_eq!(response.status(), StatusCode::CONFLICT);
+        // Then the message is delivered without rendered content
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events[0]["content"], "Hello");
+        assert!(events[0].get(field("content_html")).is_none());
+    }
+
+    #[cfg(not(feature = "camel-case-json"))]
+    #[test]
+    fn json_fields_are_snake_case_by_default() {
+        // Given an event
+        let event = http_event();
+
+        // When serializing it
+        let json = serde_json::to_value(event).unwrap();
+
+        // Then multi word fields are in snake_case
+        assert_eq!(json["event_id"], 1);
+        assert_eq!(json["timestamp_ms"], 1000);
+    }
+
+    #[cfg(feature = "camel-case-json")]
+    #[test]
+    fn json_fields_are_camel_case_if_enabled() {
+        // Given an event
+        let event = http_event();
+
+        // When serializing it
+        let json = serde_json::to_value(event).unwrap();
+
+        // Then multi word fields are in camelCase
+        assert_eq!(json["eventId"], 1);
+        assert_eq!(json["timestampMs"], 1000);
     }
 
     #[tokio::test]
-    async fn events_route_forwards_events_from_chat() {
-        // Given
+    async fn config_reflects_configured_options() {
+        // Given a chat with colored senders and heartbeats
+        let options = ChatHttpOptions {
+            sender_color: true,
+            heartbeat: Some(Duration::from_secs(30)),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(Dummy, Dummy, AuthDummy, Lifecycle::running(), options);
+
+        // When a client asks for the configuration
+        let response = app
+            .oneshot(Request::get("/api/v0/config").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then it reflects the options and limits of the server
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let config: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            api_json(json!({
+                "max_content_bytes": 65536,
+                "max_history_events": 1000,
+                "sender_color": true,
+                "sender_kind": false,
+                "posting_restricted": false,
+                "heartbeat_ms": 30000,
+                "render_html": false,
+            })),
+            config
+        );
+    }
+
+    #[tokio::test]
+    async fn config_tells_clients_the_public_url() {
+        // Given a chat reachable at a public URL behind a proxy
+        let options = ChatHttpOptions {
+            public_url: Some("https://example.com/klatsch".into()),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(Dummy, Dummy, AuthDummy, Lifecycle::running(), options);
+
+        // When a client asks for the configuration
+        let response = app
+            .oneshot(Request::get("/api/v0/config").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then it carries the public URL
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let config: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json!("https://example.com/klatsch"),
+            config[field("public_url")]
+        );
+    }
+
+    #[tokio::test]
+    async fn events_stream_forwards_error_as_sse_error_event() {
+        // Given a chat that fails immediately
         #[derive(Clone)]
-        struct ChatStub;
+        struct ChatSaboteur;
+        impl Chat for ChatSaboteur {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                tokio_stream::iter(vec![Err(anyhow::anyhow!("test error"))])
+            }
+        }
+        let app = chat_routes(
+            ChatSaboteur,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
 
-        impl Chat for ChatStub {
-            fn events(
-                self,
-                _last_event_id: EventId,
-            ) -> impl Stream<Item = anyhow::Result<Event>> + Send {
-                let messages = vec![
-                    Event::with_timestamp(
-                        EventId(1),
-                        Message {
-                            id: "019c0050-e4d7-7447-9d8f-81cde690f4a1".parse().unwrap(),
-                            author: UserId::ALICE,
-                            content: "One".to_owned(),
-                        },
-                        UNIX_EPOCH + Duration::from_millis(1704531600000),
-                    ),
-                    Event::with_timestamp(
-                        EventId(2),
-                        Message {
-                            id: "019c0051-c29d-7968-b953-4adc898b1360".parse().unwrap(),
-                            author: UserId::BOB,
-                            content: "Two".to_owned(),
-                        },
-                        UNIX_EPOCH + Duration::from_millis(1704531601000),
-                    ),
-                    Event::with_timestamp(
-                        EventId(3),
+        // When requesting events
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the response contains:
+        // - An "error" event type so the UI can distinguish it from normal events.
+        // - A JSON payload with the kind of error and a generic message, not the internal cause.
+        // - No id field — the client's Last-Event-ID must not advance past the last successful event.
+        let event = body_to_sse(response.into_body())
+            .next()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.event, "error");
+        let data: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+        assert_eq!(
+            data,
+            json!({
+                "error": "internal",
+                "message": "Internal server error",
+                "retriable": true,
+            })
+        );
+        assert!(
+            event.id.is_empty(),
+            "error events must not advance Last-Event-ID"
+        );
+    }
+
+    #[tokio::test]
+    async fn lagging_client_receives_error_event_and_stream_closes() {
+        // Given a chat which yields one event and then reports the client to be lagging
+        #[derive(Clone)]
+        struct LaggingChat;
+        impl Chat for LaggingChat {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                tokio_stream::iter(vec![
+                    Ok(HistoryStub::event()),
+                    Err(anyhow::Error::new(Lagged)),
+                ])
+                .chain(pending())
+            }
+        }
+        let app = chat_routes(
+            LaggingChat,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting events
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the event is followed by a retriable error, after which the stream ends, so the
+        // client reconnects with the id of the event it received.
+        let events: Vec<_> = timeout(
+            Duration::from_secs(5),
+            body_to_sse(response.into_body()).collect::<Vec<_>>(),
+        )
+        .await
+        .expect("Stream must end after lagging")
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].id, "1");
+        assert_eq!(events[1].event, "error");
+        let data: serde_json::Value = serde_json::from_str(&events[1].data).unwrap();
+        assert_eq!(
+            data,
+            json!({
+                "error": "lagged",
+                "message": "Fell behind the live events",
+                "retriable": true,
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn events_are_paced_to_max_rate_without_loss() {
+        // Given a chat with three events in its history
+        #[derive(Clone)]
+        struct ThreeEvents;
+        impl Chat for ThreeEvents {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                let events = (1..=3).map(|id| {
+                    Ok(Event::with_timestamp(
+                        EventId(id),
                         Message {
-                            id: "019c0051-e50d-7ea7-8a0e-f7df4176dd93".parse().unwrap(),
+                            id: MessageId::ALPHA,
                             author: UserId::ALICE,
-                            content: "Three".to_owned(),
-                        },
-                        UNIX_EPOCH + Duration::from_millis(1704531602000),
-                    ),
-                    Event::with_timestamp(
-                        EventId(4),
-                        Message {
-                            id: "019c0052-09b0-73be-a145-3767cb10cdf6".parse().unwrap(),
-                            author: UserId::BOB,
-                            content: "Four".to_owned(),
+                            content: "Hello".to_owned(),
+                            expires_at_ms: None,
                         },
-                        UNIX_EPOCH + Duration::from_millis(1704531603000),
-                    ),
+                        UNIX_EPOCH,
+                    ))
+                });
+                tokio_stream::iter(events)
+            }
+        }
+        let app = chat_routes(
+            ThreeEvents,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting the events with at most two events per second
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events?max_rate=2")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let start = Instant::now();
+        let mut events = pin!(body_to_sse(response.into_body()));
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            let event = events.next().await.unwrap().unwrap();
+            received.push((event.id, start.elapsed()));
+        }
+
+        // Then all events arrive in order, half a second apart
+        let ids: Vec<_> = received.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(["1", "2", "3"], ids[..]);
+        assert!(received[0].1 < Duration::from_millis(500));
+        assert!(received[1].1 >= Duration::from_millis(500));
+        assert!(received[2].1 >= Duration::from_millis(1000));
+    }
+
+    #[tokio::test]
+    async fn events_are_filtered_by_content() {
+        // Given a chat with mixed content, containing a `%` which must not act as a wildcard
+        #[derive(Clone)]
+        struct MixedContent;
+        impl Chat for MixedContent {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                let contents = [
+                    "Hello",
+                    "Release at 100%",
+                    "Bye",
+                    "10 and done",
+                    "100% DONE",
                 ];
-                tokio_stream::iter(messages).map(Ok)
+                let events = contents.into_iter().zip(1..).map(|(content, id)| {
+                    let message = Message {
+                        content: content.to_owned(),
+                        ..Message::dummy()
+                    };
+                    Ok(Event::with_timestamp(EventId(id), message, UNIX_EPOCH))
+                });
+                tokio_stream::iter(events).chain(pending())
+            }
+
+            async fn latest_event_id(&self) -> anyhow::Result<EventId> {
+                Ok(EventId(5))
             }
         }
-        let (_, shutting_down) = watch::channel(false);
-        let app = chat_routes(ChatStub, AuthDummy, shutting_down);
+        let app = chat_routes(
+            MixedContent,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
 
-        // When
+        // When following the events containing "0% d", in another case
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/api/v0/events")
+                    .uri("/api/v0/events?contains=0%25%20d")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
+        let mut events = pin!(body_to_sse(response.into_body()));
+        let event = events.next().await.unwrap().unwrap();
 
-        // Then
-        assert_eq!(response.status(), StatusCode::OK);
-        let actual: Vec<_> = body_to_sse(response.into_body())
-            .map(|r| r.unwrap())
-            .map(|event| {
-                (
-                    event.id.parse::<u64>().unwrap(),
-                    event.event,
-                    serde_json::from_str::<serde_json::Value>(&event.data).unwrap(),
-                )
-            })
-            .collect()
-            .await;
+        // Then only the matching event is delivered, without announcing the others as gap, and
+        // its id is the real event id
+        assert_eq!("message", event.event);
+        assert_eq!("5", event.id);
+        let message: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+        assert_eq!("100% DONE", message["content"]);
+    }
 
-        let expected = [
-            (
-                1,
-                "message".to_owned(),
-                json!({
-                    "id": "019c0050-e4d7-7447-9d8f-81cde690f4a1",
-                    "sender_id": UserId::ALICE,
-                    "content": "One",
-                    "timestamp_ms": 1704531600000u64
-                }),
-            ),
-            (
-                2,
-                "message".to_owned(),
-                json!({
-                    "id": "019c0051-c29d-7968-b953-4adc898b1360",
-                    "sender_id": UserId::BOB,
-                    "content": "Two",
-                    "timestamp_ms": 1704531601000u64
-                }),
-            ),
-            (
-                3,
-                "message".to_owned(),
-                json!({
-                    "id": "019c0051-e50d-7ea7-8a0e-f7df4176dd93",
-                    "sender_id": UserId::ALICE,
-                    "content": "Three",
-                    "timestamp_ms": 1704531602000u64
-                }),
-            ),
-            (
-                4,
-                "message".to_owned(),
-                json!({
-                    "id": "019c0052-09b0-73be-a145-3767cb10cdf6",
-                    "sender_id": UserId::BOB,
-                    "content": "Four",
-                    "timestamp_ms": 1704531603000u64
-                }),
+    #[tokio::test]
+    async fn events_are_filtered_by_senders() {
+        // Given a chat with messages of Alice, Bob and a third sender, taking turns
+        #[derive(Clone)]
+        struct ThreeSenders;
+        impl Chat for ThreeSenders {
+            fn events(
+                self,
+                last_event_id: EventId,
+            ) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                let senders = [UserId::ALICE, UserId::BOB, UserId::nil()];
+                let events = (1..=6)
+                    .filter(move |&id| id > last_event_id.0)
+                    .map(move |id| {
+                        let message = Message {
+                            author: senders[(id as usize - 1) % 3],
+                            ..Message::dummy()
+                        };
+                        Ok(Event::with_timestamp(EventId(id), message, UNIX_EPOCH))
+                    });
+                tokio_stream::iter(events).chain(pending())
+            }
+
+            async fn latest_event_id(&self) -> anyhow::Result<EventId> {
+                Ok(EventId(6))
+            }
+        }
+        let app = chat_routes(
+            ThreeSenders,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+        async fn first_ids(app: Router, uri: String, last_event_id: Option<&str>) -> Vec<String> {
+            let mut request = Request::builder().uri(uri);
+            if let Some(last_event_id) = last_event_id {
+                request = request.header("Last-Event-ID", last_event_id);
+            }
+            let response = app
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let events = body_to_sse(response.into_body());
+            events
+                .take(2)
+                .map(|event| event.unwrap().id)
+                .collect()
+                .await
+        }
+
+        // When following the events of Alice and Bob, passed as repeated parameters, and resuming
+        // after the first one, passing them as a list
+        let first = first_ids(
+            app.clone(),
+            format!(
+                "/api/v0/events?sender={}&sender={}",
+                UserId::ALICE,
+                UserId::BOB
             ),
-        ];
-        assert_eq!(expected.as_slice(), &actual);
+            None,
+        )
+        .await;
+        let resumed = first_ids(
+            app,
+            format!("/api/v0/events?sender={},{}", UserId::ALICE, UserId::BOB),
+            Some("1"),
+        )
+        .await;
+
+        // Then only their messages are delivered, with the real event ids, so resuming works
+        assert_eq!(vec!["1", "2"], first);
+        assert_eq!(vec!["2", "4"], resumed);
+    }
+
+    #[tokio::test]
+    async fn deleted_events_are_announced_as_gap() {
+        // Given a chat which only retained the events from id 5 onwards
+        #[derive(Clone)]
+        struct PrunedHistory;
+        impl Chat for PrunedHistory {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                let events = [5, 6].map(|id| {
+                    Ok(Event::with_timestamp(
+                        EventId(id),
+                        Message::dummy(),
+                        UNIX_EPOCH,
+                    ))
+                });
+                tokio_stream::iter(events)
+            }
+
+            async fn latest_event_id(&self) -> anyhow::Result<EventId> {
+                Ok(EventId(6))
+            }
+
+            async fn pruned_up_to(&self) -> anyhow::Result<EventId> {
+                Ok(EventId(4))
+            }
+        }
+        let app = chat_routes(
+            PrunedHistory,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When a client resumes after event 1
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Last-Event-ID", "1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then it learns about the deleted events, before receiving the retained ones
+        let events: Vec<_> = body_to_sse(response.into_body())
+            .take(3)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!("gap", events[0].event);
+        assert!(events[0].id.is_empty());
+        assert_eq!(
+            api_json(json!({"first_missing_id": 2, "last_missing_id": 4})),
+            serde_json::from_str::<serde_json::Value>(&events[0].data).unwrap()
+        );
+        assert_eq!("5", events[1].id);
+        assert_eq!("6", events[2].id);
     }
 
     #[tokio::test]
-    async fn events_should_return_content_type_event_stream() {
-        // Given
-        let (_, shutting_down) = watch::channel(false);
-        let app = chat_routes(Dummy, AuthDummy, shutting_down);
+    async fn tombstoned_events_are_not_announced_as_gap() {
+        // Given a chat which only retained the events from id 3 onwards, with events 3 and 4
+        // tombstoned
+        #[derive(Clone)]
+        struct TombstonedHistory;
+        impl Chat for TombstonedHistory {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                let events = [5, 6].map(|id| {
+                    Ok(Event::with_timestamp(
+                        EventId(id),
+                        Message::dummy(),
+                        UNIX_EPOCH,
+                    ))
+                });
+                tokio_stream::iter(events)
+            }
 
-        // When
+            async fn latest_event_id(&self) -> anyhow::Result<EventId> {
+                Ok(EventId(6))
+            }
+
+            async fn pruned_up_to(&self) -> anyhow::Result<EventId> {
+                Ok(EventId(2))
+            }
+        }
+        let app = chat_routes(
+            TombstonedHistory,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When a client resumes after event 1
         let response = app
             .oneshot(
                 Request::builder()
                     .uri("/api/v0/events")
+                    .header("Last-Event-ID", "1")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        // Then
-        let content_type = response
-            .headers()
-            .get("content-type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("");
-        assert!(
-            content_type.starts_with("text/event-stream"),
-            "Expected SSE content-type, got: {}",
-            content_type
+        // Then only the pruned event is announced as gap, not the tombstoned ones
+        let events: Vec<_> = body_to_sse(response.into_body())
+            .take(3)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!("gap", events[0].event);
+        assert_eq!(
+            api_json(json!({"first_missing_id": 2, "last_missing_id": 2})),
+            serde_json::from_str::<serde_json::Value>(&events[0].data).unwrap()
         );
+        assert_eq!("5", events[1].id);
+        assert_eq!("6", events[2].id);
     }
 
     #[tokio::test]
-    async fn events_stream_forwards_error_as_sse_error_event() {
-        // Given a chat that fails immediately
+    async fn client_ahead_of_regressed_history_is_told_to_reset() {
+        // Given a chat which lost its history and recorded two events since
         #[derive(Clone)]
-        struct ChatSaboteur;
-        impl Chat for ChatSaboteur {
-            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
-                tokio_stream::iter(vec![Err(anyhow::anyhow!("test error"))])
+        struct RegressedHistory;
+        impl Chat for RegressedHistory {
+            fn events(self, last_event_id: EventId) -> impl Stream<Item = anyhow::Result<Event>> {
+                let events = [1, 2]
+                    .into_iter()
+                    .filter(move |&id| id > last_event_id.0)
+                    .map(|id| {
+                        Ok(Event::with_timestamp(
+                            EventId(id),
+                            Message::dummy(),
+                            UNIX_EPOCH,
+                        ))
+                    });
+                tokio_stream::iter(events)
+            }
+
+            async fn latest_event_id(&self) -> anyhow::Result<EventId> {
+                Ok(EventId(2))
             }
         }
-        let (_, shutting_down) = watch::channel(false);
-        let app = chat_routes(ChatSaboteur, AuthDummy, shutting_down);
+        let app = chat_routes(
+            RegressedHistory,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
 
-        // When requesting events
+        // When a client resumes after event 5, which it received before the history got lost
         let response = app
             .oneshot(
                 Request::builder()
                     .uri("/api/v0/events")
+                    .header("Last-Event-ID", "5")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        // Then the response contains:
-        // - An "error" event type so the UI can distinguish it from normal events.
-        // - A generic error message, not the internal cause.
-        // - No id field — the client's Last-Event-ID must not advance past the last successful event.
-        let event = body_to_sse(response.into_body())
-            .next()
+        // Then it is told to reset, before receiving the history from the beginning
+        let events: Vec<_> = body_to_sse(response.into_body())
+            .take(3)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!("reset", events[0].event);
+        assert!(events[0].id.is_empty());
+        assert_eq!("1", events[1].id);
+        assert_eq!("2", events[2].id);
+    }
+
+    #[tokio::test]
+    async fn heartbeat_carries_id_of_last_forwarded_event() {
+        // Given a chat with two events, which then stays quiet, and heartbeats enabled
+        #[derive(Clone)]
+        struct TwoEventsThenQuiet;
+        impl Chat for TwoEventsThenQuiet {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                let events = [1, 2].map(|id| {
+                    Ok(Event::with_timestamp(
+                        EventId(id),
+                        Message::dummy(),
+                        UNIX_EPOCH,
+                    ))
+                });
+                tokio_stream::iter(events).chain(pending())
+            }
+        }
+        let options = ChatHttpOptions {
+            heartbeat: Some(Duration::from_millis(20)),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            TwoEventsThenQuiet,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When a client subscribes to the events
+        let response = app
+            .oneshot(Request::get("/api/v0/events").body(Body::empty()).unwrap())
             .await
-            .unwrap()
             .unwrap();
-        assert_eq!(event.event, "error");
-        assert_eq!(event.data, "Internal server error");
-        assert!(
-            event.id.is_empty(),
-            "error events must not advance Last-Event-ID"
-        );
+
+        // Then after the messages, heartbeats tell the id of the last one
+        let events: Vec<_> = body_to_sse(response.into_body())
+            .take(4)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        assert_eq!("1", events[0].id);
+        assert_eq!("2", events[1].id);
+        for heartbeat in &events[2..] {
+            assert_eq!("heartbeat", heartbeat.event);
+            assert_eq!(
+                api_json(json!({"last_event_id": 2})),
+                serde_json::from_str::<serde_json::Value>(&heartbeat.data).unwrap()
+            );
+        }
     }
 
     #[tokio::test]
     async fn last_event_id_forwarded_to_chat_runtime_then_fetching_events() {
         // Given
         let spy = ChatSpy::default();
-        let (_, shutting_down) = watch::channel(false);
-        let app = chat_routes(spy.clone(), AuthDummy, shutting_down);
+        let app = chat_routes(
+            spy.clone(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
 
         // When: request with Last-Event-ID = 7
         let _response = app
@@ -545,6 +4114,109 @@ mod tests {
         assert_eq!(spy.take_events_record(), vec![EventId(7)]);
     }
 
+    #[tokio::test]
+    async fn signed_last_event_id_is_resumed_from() {
+        // Given a chat signing resume positions
+        let spy = ChatSpy::default();
+        let signer = ResumeSigner::new(b"secret");
+        let app = signing_chat_routes(spy.clone(), signer.clone());
+
+        // When resuming with a position issued to the same user
+        let position = ResumeIds::new(Some(signer), UserId::nil()).issue(EventId(7));
+        let response = app
+            .oneshot(
+                Request::get("/api/v0/events")
+                    .header("Last-Event-ID", position)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then events are resumed after the signed event id
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(spy.take_events_record(), vec![EventId(7)]);
+    }
+
+    #[tokio::test]
+    async fn tampered_last_event_id_is_rejected() {
+        // Given a chat signing resume positions
+        let spy = ChatSpy::default();
+        let signer = ResumeSigner::new(b"secret");
+        let app = signing_chat_routes(spy.clone(), signer.clone());
+
+        // When resuming with the signature of event 7, but the id of event 3
+        let position = ResumeIds::new(Some(signer), UserId::nil()).issue(EventId(7));
+        let (_, signature) = position.split_once('.').unwrap();
+        let response = app
+            .oneshot(
+                Request::get("/api/v0/events")
+                    .header("Last-Event-ID", format!("3.{signature}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the request is rejected without reading any events
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert!(spy.take_events_record().is_empty());
+    }
+
+    #[tokio::test]
+    async fn since_ms_resumes_after_last_event_before_timestamp() {
+        // Given a chat with one event per second
+        let spy = ChatSpy::default();
+        let app = chat_routes(
+            spy.clone(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting events since 4.5 seconds after epoch
+        let _response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events?since_ms=4500")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then events are resumed after the event recorded at 4 seconds
+        assert_eq!(spy.take_events_record(), vec![EventId(4)]);
+    }
+
+    #[tokio::test]
+    async fn last_event_id_takes_precedence_over_since_ms() {
+        // Given a chat with one event per second
+        let spy = ChatSpy::default();
+        let app = chat_routes(
+            spy.clone(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting events with both a last event id and a timestamp
+        let _response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events?last_event_id=7&since_ms=4500")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the timestamp is ignored
+        assert_eq!(spy.take_events_record(), vec![EventId(7)]);
+    }
+
     #[tokio::test]
     async fn shutdown_terminates_event_stream() {
         // Given a pending chat and an open request to events
@@ -559,8 +4231,19 @@ mod tests {
             }
         }
 
-        let (shutdown_tx, shutdown_rx) = watch::channel(false);
-        let app = chat_routes(PendingChatStub, AuthDummy, shutdown_rx);
+        let (shutdown_tx, shutting_down) = watch::channel(false);
+        let lifecycle = Lifecycle {
+            shutting_down,
+            ..Lifecycle::running()
+        };
+
+        let app = chat_routes(
+            PendingChatStub,
+            Dummy,
+            AuthDummy,
+            lifecycle,
+            ChatHttpOptions::default(),
+        );
 
         let response_body = app
             .oneshot(
@@ -585,12 +4268,69 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn shutdown_event_is_last_frame_of_event_stream() {
+        // Given a client which received one event and waits for more
+        let (shutdown_tx, shutting_down) = watch::channel(false);
+        let lifecycle = Lifecycle {
+            shutting_down,
+            ..Lifecycle::running()
+        };
+        let app = chat_routes(
+            OneEventThenPendingStub,
+            Dummy,
+            AuthDummy,
+            lifecycle,
+            ChatHttpOptions::default(),
+        );
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let mut sse = body_to_sse(response.into_body());
+        let _first_event = sse.next().await;
+
+        // When the shutdown is initiated
+        shutdown_tx.send(true).unwrap();
+
+        // Then the remainder of the stream is exactly one shutdown event
+        let remaining: Vec<_> = timeout(Duration::from_secs(1), sse.collect::<Vec<_>>())
+            .await
+            .expect("SSE stream should terminate after shutdown")
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(1, remaining.len());
+        let shutdown = &remaining[0];
+        assert_eq!("shutdown", shutdown.event);
+        assert_eq!(Duration::from_secs(5), shutdown.retry.unwrap());
+        assert_eq!(
+            api_json(json!({"reconnect_delay_ms": 5000})),
+            serde_json::from_str::<serde_json::Value>(&shutdown.data).unwrap()
+        );
+        // The parser reports the last event id seen, which must still be the one of the message.
+        assert_eq!(
+            "1", shutdown.id,
+            "shutdown events must not advance Last-Event-ID"
+        );
+    }
+
     #[cfg(debug_assertions)]
     #[tokio::test]
     async fn sabotaged_events_stream_receives_error_event() {
         // Given a server
-        let (_, shutting_down) = watch::channel(false);
-        let app = chat_routes(Dummy, AuthDummy, shutting_down);
+        let app = chat_routes(
+            Dummy,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
 
         // When sabotage is enabled and events are requested
         let _ = app
@@ -628,23 +4368,13 @@ mod tests {
     #[tokio::test]
     async fn sabotage_interrupts_open_events_stream() {
         // Given a client receiving events from a server
-        #[derive(Clone)]
-        struct OneEventThenPendingStub;
-        impl Chat for OneEventThenPendingStub {
-            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
-                tokio_stream::iter(vec![Ok(Event::with_timestamp(
-                    EventId(1),
-                    Message {
-                        id: "019c0050-e4d7-7447-9d8f-81cde690f4a1".parse().unwrap(),
-                        ..Message::dummy()
-                    },
-                    UNIX_EPOCH,
-                ))])
-                .chain(pending())
-            }
-        }
-        let (_, shutting_down) = watch::channel(false);
-        let app = chat_routes(OneEventThenPendingStub, AuthDummy, shutting_down);
+        let app = chat_routes(
+            OneEventThenPendingStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
         let response = app
             .clone()
             .oneshot(
@@ -678,7 +4408,49 @@ mod tests {
         assert_eq!("Sabotage", event.data);
     }
 
-    fn body_to_sse(
+    /// Events of an SSE response, except for the leading `server_time` frame, which most tests do
+    /// not care about.
+    /// Name of a field of the JSON payloads of the API, given in snake_case. In camelCase if built
+    /// with the `camel-case-json` feature, like the payloads themselves.
+    pub(super) fn field(snake_case: &str) -> String {
+        if !cfg!(feature = "camel-case-json") {
+            return snake_case.to_owned();
+        }
+        let mut words = snake_case.split('_');
+        let mut camel_case = words.next().unwrap_or_default().to_owned();
+        for word in words {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                camel_case.extend(first.to_uppercase());
+                camel_case.push_str(chars.as_str());
+            }
+        }
+        camel_case
+    }
+
+    /// `payload` with the fields of its objects named by [`field`], so tests can state the expected
+    /// payloads in snake_case, whether or not the `camel-case-json` feature is enabled.
+    pub(super) fn api_json(payload: serde_json::Value) -> serde_json::Value {
+        match payload {
+            serde_json::Value::Object(fields) => fields
+                .into_iter()
+                .map(|(name, value)| (field(&name), api_json(value)))
+                .collect(),
+            serde_json::Value::Array(items) => items.into_iter().map(api_json).collect(),
+            other => other,
+        }
+    }
+
+    pub(super) fn body_to_sse(
+        body: Body,
+    ) -> impl Stream<
+        Item = Result<eventsource_stream::Event, eventsource_stream::EventStreamError<axum::Error>>,
+    > {
+        body_to_all_sse(body)
+            .filter(|result| ready(!matches!(result, Ok(event) if event.event == "server_time")))
+    }
+
+    fn body_to_all_sse(
         body: Body,
     ) -> impl Stream<
         Item = Result<eventsource_stream::Event, eventsource_stream::EventStreamError<axum::Error>>,
@@ -694,8 +4466,59 @@ mod tests {
             .eventsource()
     }
 
+    /// Yields a single event and then waits forever for the next one.
     #[derive(Clone)]
-    struct AuthDummy;
+    pub(super) struct OneEventThenPendingStub;
+
+    impl Chat for OneEventThenPendingStub {
+        fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+            tokio_stream::iter(vec![Ok(Event::with_timestamp(
+                EventId(1),
+                Message {
+                    id: "019c0050-e4d7-7447-9d8f-81cde690f4a1".parse().unwrap(),
+                    ..Message::dummy()
+                },
+                UNIX_EPOCH,
+            ))])
+            .chain(pending())
+        }
+    }
+
+    /// Answers both `events` and `history` with the same single message of Alice.
+    #[derive(Clone)]
+    pub(super) struct HistoryStub;
+
+    impl HistoryStub {
+        fn event() -> Event {
+            Event::with_timestamp(
+                EventId(1),
+                Message {
+                    id: MessageId::ALPHA,
+                    author: UserId::ALICE,
+                    content: "Hello".to_owned(),
+                    expires_at_ms: None,
+                },
+                UNIX_EPOCH,
+            )
+        }
+    }
+
+    impl Chat for HistoryStub {
+        fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+            tokio_stream::iter(vec![Ok(Self::event())])
+        }
+
+        async fn history(self, _: EventId, _: usize) -> anyhow::Result<(Vec<Event>, bool)> {
+            Ok((vec![Self::event()], false))
+        }
+
+        async fn events_in_window(&self, _: u64, _: u64, _: usize) -> anyhow::Result<Vec<Event>> {
+            Ok(vec![Self::event()])
+        }
+    }
+
+    #[derive(Clone)]
+    pub(super) struct AuthDummy;
 
     impl AuthenticateRequest for AuthDummy {
         async fn authenticate_request(
@@ -706,9 +4529,100 @@ mod tests {
         }
     }
 
-    // Spy that records calls to add_message and events for later inspection
+    /// Chat API moderating messages containing "Hello" with `action`.
+    fn moderated_chat_routes(chat: ChatSpy, action: WordlistAction) -> Router {
+        let moderator = WordlistModerator::new(["hello".to_owned()], action);
+        let options = ChatHttpOptions {
+            moderator: Some(Arc::new(moderator)),
+            ..ChatHttpOptions::default()
+        };
+        chat_routes(chat, Dummy, AuthDummy, Lifecycle::running(), options)
+    }
+
+    /// Chat API signing resume positions with `signer`.
+    pub(super) fn signing_chat_routes(chat: ChatSpy, signer: ResumeSigner) -> Router {
+        let options = ChatHttpOptions {
+            resume_signer: Some(signer),
+            ..ChatHttpOptions::default()
+        };
+        chat_routes(chat, Dummy, AuthDummy, Lifecycle::running(), options)
+    }
+
+    pub(super) async fn subscribers_report(app: &Router) -> serde_json::Value {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/api/v0/admin/subscribers")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn add_message_request() -> Request<Body> {
+        let new_message = json!({
+            "id": MessageId::ALPHA,
+            "content": "Hello"
+        });
+        Request::post("/api/v0/add_message")
+            .header("content-type", "application/json")
+            .body(Body::from(new_message.to_string()))
+            .unwrap()
+    }
+
+    fn http_event() -> HttpEvent {
+        let message = Message {
+            id: MessageId::ALPHA,
+            author: UserId::BOB,
+            content: "Hello".to_owned(),
+            expires_at_ms: None,
+        };
+        HttpEvent {
+            event_id: EventId(1),
+            message: HttpMessage::new(message, 1000, false, None, false),
+        }
+    }
+
+    /// Posts `new_message` to the add_message route of a chat which accepts anything. Answers with
+    /// the status and the JSON body of the response.
+    async fn post_new_message(new_message: serde_json::Value) -> (StatusCode, serde_json::Value) {
+        let app = chat_routes(
+            ChatSpy::default(),
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+        let request = Request::post("/api/v0/add_message")
+            .header("content-type", "application/json")
+            .body(Body::from(new_message.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    /// Every user is named Alice
+    #[derive(Clone)]
+    pub(super) struct AliceStub;
+
+    impl Users for AliceStub {
+        async fn user_by_id(&mut self, _: UserId) -> Result<User, UsersError> {
+            Ok(User {
+                name: "Alice".to_owned(),
+            })
+        }
+    }
+
+    // Spy that records calls to add_message and events for later inspection. Pretends one event has
+    // been recorded every second since epoch.
     #[derive(Clone, Default)]
-    struct ChatSpy {
+    pub(super) struct ChatSpy {
         add_message_record: Arc<Mutex<Vec<Message>>>,
         events_record: Arc<Mutex<Vec<EventId>>>,
     }
@@ -722,18 +4636,34 @@ mod tests {
             tokio_stream::iter(Vec::new())
         }
 
-        async fn add_message(&mut self, message: Message) -> Result<(), ChatError> {
-            self.add_message_record.lock().unwrap().push(message);
-            Ok(())
+        async fn last_event_before(&self, timestamp_ms: u64) -> anyhow::Result<EventId> {
+            Ok(EventId(timestamp_ms / 1_000))
+        }
+
+        async fn latest_event_id(&self) -> anyhow::Result<EventId> {
+            // No history has been lost, whichever event clients resume from.
+            Ok(EventId(u64::MAX))
+        }
+
+        async fn add_message(&mut self, message: Message) -> Result<AddOutcome, ChatError> {
+            self.add_message_record
+                .lock()
+                .unwrap()
+                .push(message.clone());
+            Ok(AddOutcome::New(Event::with_timestamp(
+                EventId(1),
+                message,
+                UNIX_EPOCH,
+            )))
         }
     }
 
     impl ChatSpy {
-        fn take_add_message_record(&self) -> Vec<Message> {
+        pub(super) fn take_add_message_record(&self) -> Vec<Message> {
             take(&mut *self.add_message_record.lock().unwrap())
         }
 
-        fn take_events_record(&self) -> Vec<EventId> {
+        pub(super) fn take_events_record(&self) -> Vec<EventId> {
             take(&mut *self.events_record.lock().unwrap())
         }
     }
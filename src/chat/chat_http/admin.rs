@@ -0,0 +1,372 @@
+use std::time::Instant;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::Chat,
+    http::{AuthenticateRequest, AuthenticatedUser, HttpError, JsonBody},
+    user::{User, UserId, Users},
+};
+
+use super::{ChatHttpOptions, ChatState, Notice};
+
+/// Body of the admin route changing the message of the day.
+#[derive(Deserialize)]
+pub(super) struct HttpMotd {
+    /// `None` removes the message of the day.
+    motd: Option<String>,
+}
+
+/// Changes the message of the day. Open event streams are told with a `config` event, new ones
+/// start with the new message of the day. Only available to admins.
+pub(super) async fn set_motd<C, U, S>(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+    JsonBody(HttpMotd { motd }): JsonBody<HttpMotd>,
+) -> Result<StatusCode, HttpError>
+where
+    U: Users + Send + Sync,
+    S: AuthenticateRequest + Send + Sync,
+{
+    ensure_admin(&state.options, state.users, user_id).await?;
+    state.config.send_modify(|config| config.motd = motd);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Answer of the admin route listing the clients following the event stream. A snapshot, clients
+/// may connect or leave any time.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+pub(super) struct SubscribersReport {
+    count: usize,
+    /// Longest connected first.
+    subscribers: Vec<HttpSubscriber>,
+}
+
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+struct HttpSubscriber {
+    /// User id of the authenticated client.
+    sender_id: UserId,
+    /// How long the event stream has been open, in milliseconds.
+    connected_ms: u64,
+}
+
+/// Lists the clients currently following the event stream, for operational insight. Only
+/// available to admins.
+pub(super) async fn subscribers<C, U, S>(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+) -> Result<Json<SubscribersReport>, HttpError>
+where
+    U: Users + Send + Sync,
+    S: AuthenticateRequest + Send + Sync,
+{
+    ensure_admin(&state.options, state.users, user_id).await?;
+    let now = Instant::now();
+    let subscribers: Vec<_> = state
+        .subscribers
+        .snapshot()
+        .into_iter()
+        .map(|subscriber| HttpSubscriber {
+            sender_id: subscriber.user_id,
+            connected_ms: (now - subscriber.connected_at).as_millis() as u64,
+        })
+        .collect();
+    Ok(Json(SubscribersReport {
+        count: subscribers.len(),
+        subscribers,
+    }))
+}
+
+/// Answer of the admin route deleting the messages of a sender.
+#[derive(Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+pub(super) struct DeletedReport {
+    /// Number of messages deleted. Messages deleted before are not counted again.
+    deleted: usize,
+}
+
+/// Deletes all messages of a sender at once, e.g. to serve a request of a data subject to erase
+/// their data. Their content is erased, but their event ids are not reused. Open event streams are
+/// told with a `tombstone` event for each of them. Only available to admins.
+pub(super) async fn delete_sender<C, U, S>(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+    Path(sender): Path<UserId>,
+) -> Result<Json<DeletedReport>, HttpError>
+where
+    C: Chat + Clone + Send + Sync,
+    U: Users + Send + Sync,
+    S: AuthenticateRequest + Send + Sync,
+{
+    ensure_admin(&state.options, state.users, user_id).await?;
+    let mut chat = state.chat;
+    let tombstones = chat.delete_messages_by_sender(sender).await?;
+    let deleted = tombstones.len();
+    if deleted > 0 {
+        // Only fails if no event stream is open, which is fine.
+        let _ = state.notices.send(Notice::Tombstones(tombstones.into()));
+    }
+    Ok(Json(DeletedReport { deleted }))
+}
+
+/// Rejects users which are not admins with `403 Forbidden`.
+pub(super) async fn ensure_admin(
+    options: &ChatHttpOptions,
+    mut users: impl Users,
+    user_id: UserId,
+) -> Result<(), HttpError> {
+    let forbidden = || HttpError {
+        status_code: StatusCode::FORBIDDEN,
+        message: "Only admins may use this route".into(),
+    };
+    let Some(admins) = &options.admins else {
+        return Err(forbidden());
+    };
+    let User { name } = users.user_by_id(user_id).await?;
+    if !admins.contains(&name) {
+        return Err(forbidden());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc, time::Duration};
+
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use double_trait::Dummy;
+    use futures_util::{Stream, StreamExt as _, stream::pending};
+    use http_body_util::BodyExt as _;
+    use serde_json::json;
+    use tokio::time::timeout;
+    use tower::ServiceExt; // for `oneshot`
+
+    use crate::{
+        chat::{Chat, ChatError, Event, EventId, MessageId, event::Tombstone},
+        http::Lifecycle,
+        user::UserId,
+    };
+
+    use super::super::{
+        ChatHttpOptions, chat_routes,
+        tests::{AliceStub, AuthDummy, api_json, body_to_sse, field, subscribers_report},
+    };
+
+    #[tokio::test]
+    async fn admin_route_lists_open_event_streams() {
+        // Given a chat API with Alice as admin and two open event streams
+        #[derive(Clone)]
+        struct QuietChat;
+        impl Chat for QuietChat {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                pending()
+            }
+        }
+        let options = ChatHttpOptions {
+            admins: Some(Arc::new(HashSet::from(["Alice".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            QuietChat,
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+        let events_request = || Request::get("/api/v0/events").body(Body::empty()).unwrap();
+        let first = app.clone().oneshot(events_request()).await.unwrap();
+        let second = app.clone().oneshot(events_request()).await.unwrap();
+
+        // When asking for the subscribers
+        let report = subscribers_report(&app).await;
+
+        // Then both streams are reported, connected for a plausible duration
+        assert_eq!(2, report["count"]);
+        let subscribers = report["subscribers"].as_array().unwrap();
+        assert_eq!(2, subscribers.len());
+        for subscriber in subscribers {
+            assert_eq!(json!(UserId::nil()), subscriber[field("sender_id")]);
+            assert!(subscriber[field("connected_ms")].as_u64().unwrap() < 5_000);
+        }
+        // Closed streams are no longer reported
+        drop(first);
+        assert_eq!(1, subscribers_report(&app).await["count"]);
+        drop(second);
+    }
+
+    #[tokio::test]
+    async fn deleting_messages_of_sender_is_announced_with_tombstones() {
+        // Given a chat API with Alice as admin, a chat with two messages of Bob and an open event
+        // stream
+        #[derive(Clone)]
+        struct BobsMessages;
+        impl Chat for BobsMessages {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                pending()
+            }
+
+            async fn delete_messages_by_sender(
+                &mut self,
+                sender: UserId,
+            ) -> Result<Vec<Tombstone>, ChatError> {
+                assert_eq!(UserId::BOB, sender);
+                Ok(vec![
+                    Tombstone {
+                        event_id: EventId(1),
+                        message_id: MessageId::ALPHA,
+                    },
+                    Tombstone {
+                        event_id: EventId(3),
+                        message_id: MessageId::GAMMA,
+                    },
+                ])
+            }
+        }
+        let options = ChatHttpOptions {
+            admins: Some(Arc::new(HashSet::from(["Alice".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            BobsMessages,
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+        let stream = app
+            .clone()
+            .oneshot(Request::get("/api/v0/events").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let mut events = body_to_sse(stream.into_body());
+
+        // When deleting the messages of Bob
+        let response = app
+            .oneshot(
+                Request::delete(format!("/api/v0/admin/senders/{}", UserId::BOB))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the number of deleted messages is reported and the stream is told about each
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json!({ "deleted": 2 }), report);
+        for (event_id, message_id) in [(1, MessageId::ALPHA), (3, MessageId::GAMMA)] {
+            let tombstone = timeout(Duration::from_secs(1), events.next())
+                .await
+                .expect("timed out waiting for tombstone event")
+                .unwrap()
+                .unwrap();
+            assert_eq!("tombstone", tombstone.event);
+            let tombstone: serde_json::Value = serde_json::from_str(&tombstone.data).unwrap();
+            assert_eq!(
+                api_json(json!({ "message_id": message_id, "event_id": event_id })),
+                tombstone
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn changed_motd_is_announced_to_open_event_streams() {
+        // Given a chat API with Alice as admin and an open event stream
+        #[derive(Clone)]
+        struct QuietChat;
+        impl Chat for QuietChat {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                pending()
+            }
+        }
+        let options = ChatHttpOptions {
+            admins: Some(Arc::new(HashSet::from(["Alice".to_owned()]))),
+            motd: Some("Welcome!".into()),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            QuietChat,
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+        let stream = app
+            .clone()
+            .oneshot(Request::get("/api/v0/events").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let mut events = body_to_sse(stream.into_body());
+        let motd = events.next().await.unwrap().unwrap();
+        assert_eq!("Welcome!", motd.data);
+
+        // When changing the message of the day
+        let response = app
+            .clone()
+            .oneshot(
+                Request::put("/api/v0/admin/motd")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        json!({ "motd": "Maintenance at noon" }).to_string(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the stream is told with a config event, and so is the config route
+        assert_eq!(StatusCode::NO_CONTENT, response.status());
+        let config = timeout(Duration::from_secs(1), events.next())
+            .await
+            .expect("timed out waiting for config event")
+            .unwrap()
+            .unwrap();
+        assert_eq!("config", config.event);
+        assert!(config.id.is_empty());
+        let config: serde_json::Value = serde_json::from_str(&config.data).unwrap();
+        assert_eq!("Maintenance at noon", config["motd"]);
+        let response = app
+            .oneshot(Request::get("/api/v0/config").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let config: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!("Maintenance at noon", config["motd"]);
+    }
+
+    #[tokio::test]
+    async fn admin_route_is_forbidden_without_admins() {
+        // Given a chat API without admins
+        let app = chat_routes(
+            Dummy,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When asking for the subscribers
+        let response = app
+            .oneshot(
+                Request::get("/api/v0/admin/subscribers")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the request is forbidden
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+    }
+}
@@ -0,0 +1,517 @@
+use std::{borrow::Cow, pin::pin};
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{StatusCode, header::CONTENT_TYPE},
+    response::{IntoResponse as _, Response},
+};
+use futures_util::{Stream, StreamExt as _};
+use serde::Deserialize;
+
+use crate::{
+    chat::{Chat, Event, EventId},
+    http::{AuthenticateRequest, AuthenticatedUser, HttpError},
+    user::{UserId, Users},
+};
+
+use super::{ChatState, HttpEvent, MessageFormat, admin::ensure_admin};
+
+/// Query parameters of the export route.
+#[derive(Deserialize)]
+pub(super) struct ExportParams {
+    #[serde(default)]
+    format: ExportFormat,
+}
+
+/// Shape of an export.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ExportFormat {
+    /// A single JSON array of events.
+    Json,
+    /// One JSON object per event and line. Can be processed line by line, no matter how large the
+    /// export is.
+    #[default]
+    Ndjson,
+    /// Comma separated values with a header line, e.g. for spreadsheets. Holds the event id,
+    /// message id, sender id, content and timestamp of each event.
+    Csv,
+}
+
+/// All messages of a sender, ordered by event id. Serves requests of data subjects to access their
+/// data. Formatted as requested with `?format=json|ndjson|csv`, JSON array by default. Only
+/// available to admins.
+pub(super) async fn export_sender<C, U, S>(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+    Path(sender): Path<UserId>,
+    Query(params): Query<ExportParams>,
+) -> Result<Response, HttpError>
+where
+    C: Chat + Send + Sync + 'static,
+    U: Users + Clone + Send + Sync + 'static,
+    S: AuthenticateRequest + Send + Sync,
+{
+    ensure_admin(&state.options, state.users.clone(), user_id).await?;
+    // The first page is read up front, so failing to read the messages at all can still be
+    // answered with a status code.
+    let first_page = state
+        .chat
+        .messages_by_sender(sender, EventId::before_all(), EXPORT_PAGE_SIZE)
+        .await
+        .map_err(|_| HttpError {
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "Internal server error".into(),
+        })?;
+    let pages = export_pages(state.chat, sender, first_page);
+    let format = MessageFormat::new(&state.options, state.users);
+    let content_type = match params.format {
+        ExportFormat::Json => "application/json",
+        ExportFormat::Ndjson => "application/x-ndjson",
+        ExportFormat::Csv => "text/csv; charset=utf-8",
+    };
+    let body = Body::from_stream(export_chunks(pages, format, params.format));
+    Ok(([(CONTENT_TYPE, content_type)], body).into_response())
+}
+
+/// Number of events read at once while exporting the messages of a sender.
+const EXPORT_PAGE_SIZE: usize = 256;
+
+/// Header line of CSV exports.
+const CSV_HEADER: &str = "event_id,message_id,sender,content,timestamp_ms\r\n";
+
+/// Pages of the events of messages sent by `sender`, starting with `first_page`. The next page is
+/// only read once the previous one has been taken, so the whole export is never held in memory.
+/// Ends with the first empty page, or after the first error.
+fn export_pages<C>(
+    chat: C,
+    sender: UserId,
+    first_page: Vec<Event>,
+) -> impl Stream<Item = anyhow::Result<Vec<Event>>> + Send + 'static
+where
+    C: Chat + Send + Sync + 'static,
+{
+    async_stream::stream! {
+        let mut page = first_page;
+        // Pages are not cut short by a page size smaller than requested, since events which can not
+        // be decoded are skipped.
+        while let Some(last) = page.last() {
+            let last_event_id = last.id;
+            yield Ok(page);
+            match chat
+                .messages_by_sender(sender, last_event_id, EXPORT_PAGE_SIZE)
+                .await
+            {
+                Ok(next) => page = next,
+                Err(error) => {
+                    yield Err(error);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// The body of an export. Events are formatted one at a time, as the body is written, rather than
+/// all at once up front. An error reading a page aborts the body, so the client can tell the export
+/// is incomplete.
+fn export_chunks<U>(
+    pages: impl Stream<Item = anyhow::Result<Vec<Event>>> + Send + 'static,
+    mut format: MessageFormat<U>,
+    export: ExportFormat,
+) -> impl Stream<Item = anyhow::Result<String>> + Send + 'static
+where
+    U: Users + Send + Sync + 'static,
+{
+    async_stream::stream! {
+        match export {
+            ExportFormat::Json => yield Ok("[".to_owned()),
+            ExportFormat::Ndjson => (),
+            ExportFormat::Csv => yield Ok(CSV_HEADER.to_owned()),
+        }
+        let mut first = true;
+        let mut pages = pin!(pages);
+        while let Some(page) = pages.next().await {
+            let page = match page {
+                Ok(page) => page,
+                Err(error) => {
+                    yield Err(error);
+                    return;
+                }
+            };
+            for event in page {
+                let event_id = event.id;
+                let message = format.http_message(event).await;
+                let event = HttpEvent { event_id, message };
+                let chunk = match export {
+                    ExportFormat::Json => {
+                        let separator = if first { "" } else { "," };
+                        format!("{separator}{}", export_json(&event))
+                    }
+                    ExportFormat::Ndjson => format!("{}\n", export_json(&event)),
+                    ExportFormat::Csv => csv_row(&event),
+                };
+                first = false;
+                yield Ok(chunk);
+            }
+        }
+        if export == ExportFormat::Json {
+            yield Ok("]".to_owned());
+        }
+    }
+}
+
+fn export_json(event: &HttpEvent) -> String {
+    serde_json::to_string(event).expect("Serializing an event must not fail")
+}
+
+/// A line of a CSV export, terminated with CRLF as in RFC 4180.
+fn csv_row(event: &HttpEvent) -> String {
+    let HttpEvent { event_id, message } = event;
+    format!(
+        "{event_id},{},{},{},{}\r\n",
+        message.id,
+        message.sender_id,
+        csv_field(&message.content),
+        message.timestamp_ms
+    )
+}
+
+/// Quotes `field`, if it contains a comma, a quote or a line break. Quotes within are doubled.
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Arc, time::UNIX_EPOCH};
+
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt as _;
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot`
+
+    use crate::{
+        chat::{Chat, Event, EventId, Message, MessageId},
+        http::Lifecycle,
+        user::UserId,
+    };
+
+    use super::{
+        super::{
+            ChatHttpOptions, chat_routes,
+            tests::{AliceStub, AuthDummy, field},
+        },
+        EXPORT_PAGE_SIZE,
+    };
+
+    #[tokio::test]
+    async fn export_yields_messages_of_sender() {
+        // Given a chat API with Alice as admin and a chat with a message of Bob
+        #[derive(Clone)]
+        struct BobsMessage;
+        impl Chat for BobsMessage {
+            async fn messages_by_sender(
+                &self,
+                sender: UserId,
+                last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<Vec<Event>> {
+                assert_eq!(UserId::BOB, sender);
+                let message = Message {
+                    author: UserId::BOB,
+                    ..Message::dummy()
+                };
+                let event = Event::with_timestamp(EventId(2), message, UNIX_EPOCH);
+                Ok(vec![event]
+                    .into_iter()
+                    .filter(|e| e.id > last_event_id)
+                    .collect())
+            }
+        }
+        let options = ChatHttpOptions {
+            admins: Some(Arc::new(HashSet::from(["Alice".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            BobsMessage,
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When exporting the messages of Bob as JSON array
+        let response = app
+            .oneshot(
+                Request::get(format!(
+                    "/api/v0/admin/senders/{}/export?format=json",
+                    UserId::BOB
+                ))
+                .body(Body::empty())
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then they are answered as JSON array
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(1, events.as_array().unwrap().len());
+        assert_eq!(2, events[0][field("event_id")]);
+        assert_eq!(json!(UserId::BOB), events[0][field("sender_id")]);
+    }
+
+    #[tokio::test]
+    async fn csv_export_quotes_content_with_commas_and_newlines() {
+        // Given a chat API with Alice as admin and a chat with a message of Bob, whose content
+        // contains a comma, a newline and quotes
+        #[derive(Clone)]
+        struct BobsMessage;
+        impl Chat for BobsMessage {
+            async fn messages_by_sender(
+                &self,
+                _sender: UserId,
+                last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<Vec<Event>> {
+                let message = Message {
+                    id: MessageId::ALPHA,
+                    author: UserId::BOB,
+                    content: "Hello, Alice!\nSay \"hi\"".to_owned(),
+                    expires_at_ms: None,
+                };
+                let event = Event::with_timestamp(EventId(2), message, UNIX_EPOCH);
+                Ok(vec![event]
+                    .into_iter()
+                    .filter(|e| e.id > last_event_id)
+                    .collect())
+            }
+        }
+        let options = ChatHttpOptions {
+            admins: Some(Arc::new(HashSet::from(["Alice".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            BobsMessage,
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When exporting the messages of Bob as CSV
+        let response = app
+            .oneshot(
+                Request::get(format!(
+                    "/api/v0/admin/senders/{}/export?format=csv",
+                    UserId::BOB
+                ))
+                .body(Body::empty())
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the content is quoted, with the quotes within doubled
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(
+            "text/csv; charset=utf-8",
+            response.headers()["content-type"]
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let expected = format!(
+            "event_id,message_id,sender,content,timestamp_ms\r\n\
+            2,{},{},\"Hello, Alice!\nSay \"\"hi\"\"\",0\r\n",
+            MessageId::ALPHA,
+            UserId::BOB
+        );
+        assert_eq!(expected, String::from_utf8(body.to_vec()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn ndjson_export_has_one_event_per_line() {
+        // Given a chat API with Alice as admin and a chat with two messages of Bob, both with a
+        // newline in their content
+        #[derive(Clone)]
+        struct BobsMessages;
+        impl Chat for BobsMessages {
+            async fn messages_by_sender(
+                &self,
+                _sender: UserId,
+                last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<Vec<Event>> {
+                let message = |id| Message {
+                    id,
+                    author: UserId::BOB,
+                    content: "Hello\nworld".to_owned(),
+                    expires_at_ms: None,
+                };
+                let events = vec![
+                    Event::with_timestamp(EventId(2), message(MessageId::ALPHA), UNIX_EPOCH),
+                    Event::with_timestamp(EventId(5), message(MessageId::BETA), UNIX_EPOCH),
+                ];
+                Ok(events
+                    .into_iter()
+                    .filter(|e| e.id > last_event_id)
+                    .collect())
+            }
+        }
+        let options = ChatHttpOptions {
+            admins: Some(Arc::new(HashSet::from(["Alice".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            BobsMessages,
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When exporting the messages of Bob as NDJSON
+        let response = app
+            .oneshot(
+                Request::get(format!(
+                    "/api/v0/admin/senders/{}/export?format=ndjson",
+                    UserId::BOB
+                ))
+                .body(Body::empty())
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then every line holds one event
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let event_ids: Vec<_> = body
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()[field("event_id")].clone()
+            })
+            .collect();
+        assert_eq!(vec![json!(2), json!(5)], event_ids);
+    }
+
+    #[tokio::test]
+    async fn export_defaults_to_ndjson() {
+        // Given a chat API with Alice as admin and a chat with a message of Bob
+        #[derive(Clone)]
+        struct BobsMessage;
+        impl Chat for BobsMessage {
+            async fn messages_by_sender(
+                &self,
+                _sender: UserId,
+                last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<Vec<Event>> {
+                let event = Event::with_timestamp(EventId(2), Message::dummy(), UNIX_EPOCH);
+                Ok(vec![event]
+                    .into_iter()
+                    .filter(|e| e.id > last_event_id)
+                    .collect())
+            }
+        }
+        let options = ChatHttpOptions {
+            admins: Some(Arc::new(HashSet::from(["Alice".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            BobsMessage,
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When exporting the messages of Bob without asking for a format
+        let response = app
+            .oneshot(
+                Request::get(format!("/api/v0/admin/senders/{}/export", UserId::BOB))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then they are answered as NDJSON
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!("application/x-ndjson", response.headers()["content-type"]);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.ends_with('\n'));
+        let event: serde_json::Value = serde_json::from_str(body.trim_end()).unwrap();
+        assert_eq!(2, event[field("event_id")]);
+    }
+
+    #[tokio::test]
+    async fn export_pages_through_messages_of_sender() {
+        // Given a chat API with Alice as admin and a chat with more messages of Bob than fit into
+        // a single page
+        #[derive(Clone)]
+        struct ManyMessages;
+        impl Chat for ManyMessages {
+            async fn messages_by_sender(
+                &self,
+                _sender: UserId,
+                last_event_id: EventId,
+                limit: usize,
+            ) -> anyhow::Result<Vec<Event>> {
+                assert_eq!(EXPORT_PAGE_SIZE, limit);
+                let events = (last_event_id.0 + 1..=600)
+                    .take(limit)
+                    .map(|id| Event::with_timestamp(EventId(id), Message::dummy(), UNIX_EPOCH))
+                    .collect();
+                Ok(events)
+            }
+        }
+        let options = ChatHttpOptions {
+            admins: Some(Arc::new(HashSet::from(["Alice".to_owned()]))),
+            ..ChatHttpOptions::default()
+        };
+        let app = chat_routes(
+            ManyMessages,
+            AliceStub,
+            AuthDummy,
+            Lifecycle::running(),
+            options,
+        );
+
+        // When exporting the messages of Bob as JSON array
+        let response = app
+            .oneshot(
+                Request::get(format!(
+                    "/api/v0/admin/senders/{}/export?format=json",
+                    UserId::BOB
+                ))
+                .body(Body::empty())
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then all of them are exported in order, read one page at a time
+        assert_eq!(StatusCode::OK, response.status());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let event_ids: Vec<_> = events
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event[field("event_id")].as_u64().unwrap())
+            .collect();
+        assert_eq!((1..=600).collect::<Vec<_>>(), event_ids);
+    }
+}
@@ -0,0 +1,838 @@
+use std::{num::NonZeroUsize, pin::pin, time::Duration};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+    },
+    response::{IntoResponse as _, Response},
+};
+use futures_util::{FutureExt as _, StreamExt as _};
+use serde::Deserialize;
+use tokio::time::timeout;
+
+use crate::{
+    chat::{
+        Chat, EventId,
+        resume_token::{ResumeIds, ResumePosition},
+        terminate_if::terminate_if,
+    },
+    http::{AuthenticateRequest, AuthenticatedUser, HttpError},
+    user::Users,
+};
+
+use super::{
+    ChatState, EventFilter, HttpEvent, MessageFormat, Render, X_KLATSCH_HAS_MORE,
+    X_KLATSCH_RESUME_TOKEN,
+};
+
+/// How long the poll route waits for new events, if the client does not specify a timeout.
+const DEFAULT_POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Upper bound for the timeout of the poll route. Prevents clients from tying up requests forever.
+const MAX_POLL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Maximum number of events answered by the poll route, unless configured otherwise.
+pub(super) const DEFAULT_POLL_MAX_EVENTS: usize = 1000;
+
+/// Maximum number of events answered by the history route. Also the default, if the client does
+/// not pass a `limit`.
+pub(super) const MAX_HISTORY_WINDOW_EVENTS: usize = 1000;
+
+/// Answers the events route with the events since `last_event_id` as a plain JSON array. Useful for
+/// clients which can not consume an event stream, e.g. scripts or simple HTTP clients. Like the
+/// poll route, it answers at most `max_events` events. If there are more, the answer carries
+/// `X-Klatsch-Has-More: true` and clients resume after the last event, or with the resume token.
+///
+/// The response carries an `ETag`. Clients and caches revalidating with `If-None-Match` receive
+/// `304 Not Modified`, unless newer events exist. It is marked `private`, since resume tokens and
+/// the rendering of messages depend on the session, so shared caches must not serve it to others.
+pub(super) async fn json_history(
+    chat: impl Chat,
+    last_event_id: EventId,
+    max_events: usize,
+    mut format: MessageFormat<impl Users>,
+    resume_ids: &ResumeIds,
+    filter: Option<EventFilter>,
+    headers: &HeaderMap,
+) -> Result<Response, HttpError> {
+    let (events, has_more) =
+        chat.history(last_event_id, max_events)
+            .await
+            .map_err(|_| HttpError {
+                status_code: StatusCode::INTERNAL_SERVER_ERROR,
+                message: "Internal server error".into(),
+            })?;
+    let latest = events.last().map_or(last_event_id, |event| event.id);
+    let pinned = events
+        .iter()
+        .filter(|event| event.pinned)
+        .map(|event| event.id);
+    let etag = history_etag(last_event_id, latest, events.len(), pinned);
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(
+        ETAG,
+        etag.parse().expect("ETag must be a valid header value"),
+    );
+    response_headers.insert(CACHE_CONTROL, HeaderValue::from_static("private"));
+    insert_resume_token(&mut response_headers, resume_ids, latest);
+    if has_more {
+        response_headers.insert(X_KLATSCH_HAS_MORE, HeaderValue::from_static("true"));
+    }
+    if matches_etag(headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+    // Filtered only now, so the resume token covers the events filtered out, too.
+    let events = match filter {
+        Some(filter) => events
+            .into_iter()
+            .filter(|event| filter.matches(event))
+            .collect(),
+        None => events,
+    };
+    let events = format.http_events(events).await;
+    Ok((response_headers, Json(events)).into_response())
+}
+
+/// Tells clients of JSON answers the position to resume after `last_event_id` from, with the
+/// `X-Klatsch-Resume-Token` header. Only if positions are signed, otherwise it is the event id.
+fn insert_resume_token(headers: &mut HeaderMap, resume_ids: &ResumeIds, last_event_id: EventId) {
+    if resume_ids.is_signed() {
+        let token = resume_ids.issue(last_event_id);
+        headers.insert(
+            X_KLATSCH_RESUME_TOKEN,
+            token
+                .parse()
+                .expect("Resume token must be a valid header value"),
+        );
+    }
+}
+
+/// Part of every history `ETag`. Events are immutable, besides being pinned or deleted, so the
+/// range of event ids together with the number of events within it and the pinned ones identifies
+/// a page of history. Deleting messages only ever removes events from a range. Bump this, should
+/// the representation of past events ever change, e.g. because messages can be edited.
+const HISTORY_ETAG_VERSION: u32 = 3;
+
+/// `events` is the number of events within the page. `pinned` are the ids of the pinned events
+/// within it. If there are any, a fingerprint of them is appended, so pinning or unpinning a
+/// message invalidates the pages it is part of.
+fn history_etag(
+    last_event_id: EventId,
+    latest: EventId,
+    events: usize,
+    pinned: impl Iterator<Item = EventId>,
+) -> String {
+    // 32Bit FNV-1a, like the sender color. Stable, since clients and caches keep the tag.
+    let mut pinned = pinned.peekable();
+    if pinned.peek().is_none() {
+        return format!("\"v{HISTORY_ETAG_VERSION}-{last_event_id}-{latest}-{events}\"");
+    }
+    let fingerprint = pinned
+        .flat_map(|event_id| event_id.0.to_le_bytes())
+        .fold(0x811c_9dc5_u32, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        });
+    format!("\"v{HISTORY_ETAG_VERSION}-{last_event_id}-{latest}-{events}-{fingerprint:08x}\"")
+}
+
+/// `true` if any of the tags in `If-None-Match` matches `etag`. Uses weak comparison, as demanded
+/// by RFC 9110 for `If-None-Match`.
+fn matches_etag(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Query parameters of the poll route.
+#[derive(Deserialize)]
+pub(super) struct PollParams {
+    /// Only events following this position are returned. A plain event id, or a signed one if
+    /// resume positions are signed. Defaults to all events.
+    since: Option<ResumePosition>,
+    /// Seconds to wait for at least one new event before returning an empty array.
+    timeout: Option<u64>,
+    render: Option<Render>,
+}
+
+/// Long polling fallback for clients behind proxies which strip or buffer event streams. Answers
+/// with the events since `since` as a JSON array. If there are none yet, it waits up to `timeout`
+/// seconds for at least one to arrive. Clients resume by passing the last event id they have seen,
+/// or if resume positions are signed, the token of the `X-Klatsch-Resume-Token` header. At most
+/// [`ChatHttpOptions::poll_max_events`] events are answered. If there are more, the answer carries
+/// `X-Klatsch-Has-More: true`.
+pub(super) async fn poll<C, U, S>(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+    Query(params): Query<PollParams>,
+) -> Result<(HeaderMap, Json<Vec<HttpEvent>>), HttpError>
+where
+    C: Chat + Send + Sync + Clone + 'static,
+    U: Users + Send + Sync + 'static,
+    S: AuthenticateRequest + Send + Sync + 'static,
+{
+    let resume_ids = ResumeIds::new(state.options.resume_signer.clone(), user_id);
+    let since = match &params.since {
+        Some(position) => resume_ids.verify(position).ok_or_else(|| HttpError {
+            status_code: StatusCode::BAD_REQUEST,
+            message: "`since` has not been issued to this user".into(),
+        })?,
+        None => EventId::before_all(),
+    };
+    let poll_timeout = params
+        .timeout
+        .map_or(DEFAULT_POLL_TIMEOUT, Duration::from_secs)
+        .min(MAX_POLL_TIMEOUT);
+    let max_events = state
+        .options
+        .poll_max_events
+        .map_or(DEFAULT_POLL_MAX_EVENTS, NonZeroUsize::get);
+    let mut format = MessageFormat::new(&state.options, state.users).with_render(params.render);
+    let events = terminate_if(state.chat.clone().events(since), state.shutting_down);
+    let mut events = pin!(events);
+
+    let internal_error = |_| HttpError {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        message: "Internal server error".into(),
+    };
+
+    // Wait for the first event. Timeout and shutdown both result in an empty answer.
+    let mut batch = Vec::new();
+    match timeout(poll_timeout, events.next()).await {
+        Ok(Some(event)) => batch.push(event.map_err(internal_error)?),
+        Ok(None) | Err(_) => {
+            let mut headers = HeaderMap::new();
+            insert_resume_token(&mut headers, &resume_ids, since);
+            return Ok((headers, Json(Vec::new())));
+        }
+    }
+    // Add further events which are available right away, without waiting for more.
+    while batch.len() < max_events
+        && let Some(Some(event)) = events.next().now_or_never()
+    {
+        batch.push(event.map_err(internal_error)?);
+    }
+    let mut headers = HeaderMap::new();
+    let last_event_id = batch.last().expect("Batch must not be empty").id;
+    insert_resume_token(&mut headers, &resume_ids, last_event_id);
+    if batch.len() == max_events {
+        let latest = state.chat.latest_event_id().await;
+        if latest.is_ok_and(|latest| latest > last_event_id) {
+            headers.insert(X_KLATSCH_HAS_MORE, HeaderValue::from_static("true"));
+        }
+    }
+    Ok((headers, Json(format.http_events(batch).await)))
+}
+
+/// Query parameters of the history route. Milliseconds since Unix epoch.
+#[derive(Deserialize)]
+pub(super) struct HistoryWindowParams {
+    /// Start of the window (inclusive). Defaults to the beginning of the chat.
+    #[serde(default)]
+    from_ms: u64,
+    /// End of the window (inclusive). Defaults to the end of the chat.
+    to_ms: Option<u64>,
+    /// Maximum number of events to answer with. Capped to [`MAX_HISTORY_WINDOW_EVENTS`].
+    limit: Option<usize>,
+    render: Option<Render>,
+}
+
+/// Events recorded within a time window as a JSON array, e.g. to review what happened yesterday.
+/// The window filters on the timestamps of the events, but they are ordered by id. Timestamps stem
+/// from the system clock of the server, so they are not guaranteed to increase with the ids.
+pub(super) async fn history_window<C, U, S>(
+    AuthenticatedUser(_): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+    Query(params): Query<HistoryWindowParams>,
+) -> Result<Json<Vec<HttpEvent>>, HttpError>
+where
+    C: Chat + Send + Sync + 'static,
+    U: Users + Send + Sync + 'static,
+    S: AuthenticateRequest + Send + Sync + 'static,
+{
+    let to_ms = params.to_ms.unwrap_or(u64::MAX);
+    if params.from_ms > to_ms {
+        return Err(HttpError {
+            status_code: StatusCode::BAD_REQUEST,
+            message: "'from_ms' must not be after 'to_ms'".into(),
+        });
+    }
+    let limit = params
+        .limit
+        .unwrap_or(MAX_HISTORY_WINDOW_EVENTS)
+        .min(MAX_HISTORY_WINDOW_EVENTS);
+    let events = state
+        .chat
+        .events_in_window(params.from_ms, to_ms, limit)
+        .await
+        .map_err(|_| HttpError {
+            status_code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "Internal server error".into(),
+        })?;
+    let mut format = MessageFormat::new(&state.options, state.users).with_render(params.render);
+    Ok(Json(format.http_events(events).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        num::NonZeroUsize,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use double_trait::Dummy;
+    use futures_util::{
+        Stream, StreamExt as _,
+        stream::{once, pending},
+    };
+    use http_body_util::BodyExt as _;
+    use tokio::{sync::watch, time::timeout};
+    use tower::ServiceExt; // for `oneshot`
+
+    use crate::{
+        chat::{
+            Chat, Event, EventId, Message,
+            resume_token::{ResumeIds, ResumeSigner},
+        },
+        http::Lifecycle,
+        user::UserId,
+    };
+
+    use super::{
+        super::{
+            ChatHttpOptions, chat_routes,
+            tests::{
+                AuthDummy, ChatSpy, HistoryStub, OneEventThenPendingStub, field,
+                signing_chat_routes,
+            },
+        },
+        history_etag,
+    };
+
+    #[test]
+    fn history_etag_changes_once_message_is_pinned() {
+        // Given the tag of a page of history without pinned messages
+        let unpinned = history_etag(EventId(0), EventId(3), 3, [].into_iter());
+
+        // When a message within it is pinned
+        let pinned = history_etag(EventId(0), EventId(3), 3, [EventId(2)].into_iter());
+
+        // Then the tag changes, and differs from the one of other pinned messages
+        assert_eq!("\"v3-0-3-3\"", unpinned);
+        assert_ne!(unpinned, pinned);
+        assert_ne!(
+            pinned,
+            history_etag(EventId(0), EventId(3), 3, [EventId(1)].into_iter())
+        );
+    }
+
+    #[test]
+    fn history_etag_changes_once_message_is_deleted() {
+        // Given the tag of a page of history with three events
+        let complete = history_etag(EventId(0), EventId(3), 3, [].into_iter());
+
+        // When the message of the second one is deleted
+        let deleted = history_etag(EventId(0), EventId(3), 2, [].into_iter());
+
+        // Then the tag changes
+        assert_ne!(complete, deleted);
+    }
+
+    #[tokio::test]
+    async fn history_carries_etag() {
+        // Given a chat with one message in its history
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting events as JSON for the first time
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Accept", "application/json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the history is returned with an ETag derived from the range of event ids
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("ETag").unwrap(), "\"v3-0-1-1\"");
+    }
+
+    #[tokio::test]
+    async fn history_is_private_and_varies_by_accept() {
+        // Given a chat with one message in its history
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting events as JSON
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Accept", "application/json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then shared caches are told not to store it, and that it depends on `Accept`
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("Cache-Control").unwrap(), "private");
+        assert_eq!(response.headers().get("Vary").unwrap(), "accept");
+    }
+
+    #[tokio::test]
+    async fn history_not_modified_if_etag_matches() {
+        // Given a chat with one message in its history
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When revalidating a previous response with its ETag
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Accept", "application/json")
+                    .header("If-None-Match", "\"v3-0-1-1\"")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the client is told its copy is still up to date
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers().get("ETag").unwrap(), "\"v3-0-1-1\"");
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn history_sent_again_if_newer_events_exist() {
+        // Given a chat with one message in its history
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When revalidating a response which has been cached before the message arrived
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/events")
+                    .header("Accept", "application/json")
+                    .header("If-None-Match", "\"v3-0-0-0\"")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the full history is returned
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get("ETag").unwrap(), "\"v3-0-1-1\"");
+    }
+
+    #[tokio::test]
+    async fn history_window_returns_events_as_json_array() {
+        // Given a chat with one message in its history
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting the events of a time window
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/history?from_ms=0&to_ms=1000&limit=10")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the event is returned
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events.as_array().unwrap().len(), 1);
+        assert_eq!(events[0][field("event_id")], 1);
+        assert_eq!(events[0]["content"], "Hello");
+    }
+
+    #[tokio::test]
+    async fn inverted_history_window_is_rejected_with_400() {
+        // Given a chat
+        let app = chat_routes(
+            HistoryStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When requesting a window which ends before it starts
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/history?from_ms=2000&to_ms=1000")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the request is rejected
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn poll_returns_available_events_immediately() {
+        // Given a chat with one message in its history
+        let app = chat_routes(
+            OneEventThenPendingStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When polling with a long timeout
+        let response = timeout(
+            Duration::from_secs(5),
+            app.oneshot(
+                Request::builder()
+                    .uri("/api/v0/poll?since=0&timeout=30")
+                    .body(Body::empty())
+                    .unwrap(),
+            ),
+        )
+        .await
+        .expect("Poll must not wait if events are available")
+        .unwrap();
+
+        // Then the available event is returned
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events.as_array().unwrap().len(), 1);
+        assert_eq!(events[0][field("event_id")], 1);
+    }
+
+    #[tokio::test]
+    async fn poll_below_max_events_answers_all_events() {
+        // Given a chat with three events and a poll route answering at most five at once
+        let app = poll_routes(ThreeEventsStub, 5);
+
+        // When polling for all events
+        let response = app
+            .oneshot(
+                Request::get("/api/v0/poll?since=0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then all three are answered, without telling the client there are more
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key("x-klatsch-has-more"));
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events.as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn poll_beyond_max_events_tells_client_there_are_more() {
+        // Given a chat with three events and a poll route answering at most two at once
+        let app = poll_routes(ThreeEventsStub, 2);
+
+        // When polling for all events
+        let response = app
+            .oneshot(
+                Request::get("/api/v0/poll?since=0")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the first two are answered, telling the client to poll again for the rest
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!("true", response.headers()["x-klatsch-has-more"]);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events.as_array().unwrap().len(), 2);
+        assert_eq!(events[1][field("event_id")], 2);
+    }
+
+    #[tokio::test]
+    async fn history_beyond_max_events_tells_client_there_are_more() {
+        // Given a chat with three events, answering at most two at once
+        let app = poll_routes(ThreeEventsStub, 2);
+
+        // When requesting all events as JSON
+        let response = app
+            .oneshot(
+                Request::get("/api/v0/events")
+                    .header("Accept", "application/json")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the first two are answered, telling the client to request the rest
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!("true", response.headers()["x-klatsch-has-more"]);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events.as_array().unwrap().len(), 2);
+        assert_eq!(events[1][field("event_id")], 2);
+    }
+
+    /// Chat with the events one to three in its history.
+    #[derive(Clone)]
+    struct ThreeEventsStub;
+
+    impl Chat for ThreeEventsStub {
+        fn events(self, since: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+            let events = (since.0 + 1..=3).map(|id| {
+                Ok(Event::with_timestamp(
+                    EventId(id),
+                    Message::dummy(),
+                    UNIX_EPOCH,
+                ))
+            });
+            tokio_stream::iter(events.collect::<Vec<_>>()).chain(pending())
+        }
+
+        async fn history(self, since: EventId, limit: usize) -> anyhow::Result<(Vec<Event>, bool)> {
+            let mut events: Vec<_> = (since.0 + 1..=3)
+                .map(|id| Event::with_timestamp(EventId(id), Message::dummy(), UNIX_EPOCH))
+                .collect();
+            let has_more = events.len() > limit;
+            events.truncate(limit);
+            Ok((events, has_more))
+        }
+
+        async fn latest_event_id(&self) -> anyhow::Result<EventId> {
+            Ok(EventId(3))
+        }
+    }
+
+    fn poll_routes(chat: impl Chat + Send + Sync + Clone + 'static, max_events: usize) -> Router {
+        let options = ChatHttpOptions {
+            poll_max_events: Some(NonZeroUsize::new(max_events).unwrap()),
+            ..ChatHttpOptions::default()
+        };
+        chat_routes(chat, Dummy, AuthDummy, Lifecycle::running(), options)
+    }
+
+    #[tokio::test]
+    async fn poll_waits_for_live_message() {
+        // Given a chat which receives a message shortly after the request
+        #[derive(Clone)]
+        struct LiveMessageStub;
+        impl Chat for LiveMessageStub {
+            fn events(self, since: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                once(async move {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(Event::with_timestamp(
+                        since.successor(),
+                        Message::dummy(),
+                        UNIX_EPOCH,
+                    ))
+                })
+                .chain(pending())
+            }
+        }
+        let app = chat_routes(
+            LiveMessageStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When polling for events since the last one seen by the client
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/poll?since=41&timeout=30")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the live message is returned once it arrives
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let events: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events.as_array().unwrap().len(), 1);
+        assert_eq!(events[0][field("event_id")], 42);
+    }
+
+    #[tokio::test]
+    async fn poll_returns_empty_array_on_timeout() {
+        // Given a chat without any new messages
+        #[derive(Clone)]
+        struct PendingChatStub;
+        impl Chat for PendingChatStub {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                pending()
+            }
+        }
+        let app = chat_routes(
+            PendingChatStub,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When polling with a timeout of one second
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/v0/poll?since=0&timeout=1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then an empty array is returned
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"[]");
+    }
+
+    #[tokio::test]
+    async fn poll_returns_promptly_on_shutdown() {
+        // Given a chat without any new messages
+        #[derive(Clone)]
+        struct PendingChatStub;
+        impl Chat for PendingChatStub {
+            fn events(self, _: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+                pending()
+            }
+        }
+        let (shutdown_trigger, shutting_down) = watch::channel(false);
+        let lifecycle = Lifecycle {
+            shutting_down,
+            ..Lifecycle::running()
+        };
+        let app = chat_routes(
+            PendingChatStub,
+            Dummy,
+            AuthDummy,
+            lifecycle,
+            ChatHttpOptions::default(),
+        );
+        let response = tokio::spawn(
+            app.oneshot(
+                Request::builder()
+                    .uri("/api/v0/poll?since=0&timeout=30")
+                    .body(Body::empty())
+                    .unwrap(),
+            ),
+        );
+
+        // When the server shuts down during the poll
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        shutdown_trigger.send(true).unwrap();
+
+        // Then the poll finishes with an empty array, without waiting for the timeout
+        let response = timeout(Duration::from_secs(5), response)
+            .await
+            .expect("Poll must finish promptly on shutdown")
+            .unwrap()
+            .unwrap();
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"[]");
+    }
+
+    #[tokio::test]
+    async fn signed_poll_position_is_resumed_from() {
+        // Given a chat signing resume positions
+        let spy = ChatSpy::default();
+        let signer = ResumeSigner::new(b"secret");
+        let app = signing_chat_routes(spy.clone(), signer.clone());
+
+        // When polling with a position issued to the same user
+        let resume_ids = ResumeIds::new(Some(signer), UserId::nil());
+        let position = resume_ids.issue(EventId(7));
+        let response = app
+            .oneshot(
+                Request::get(format!("/api/v0/poll?since={position}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then events are read after the signed event id, and the client is told where to resume
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(spy.take_events_record(), vec![EventId(7)]);
+        assert_eq!(position, response.headers()["x-klatsch-resume-token"]);
+    }
+
+    #[tokio::test]
+    async fn unsigned_poll_position_is_rejected_if_positions_are_signed() {
+        // Given a chat signing resume positions
+        let spy = ChatSpy::default();
+        let app = signing_chat_routes(spy.clone(), ResumeSigner::new(b"secret"));
+
+        // When polling with a plain event id
+        let response = app
+            .oneshot(
+                Request::get("/api/v0/poll?since=7")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the request is rejected without reading any events
+        assert_eq!(StatusCode::BAD_REQUEST, response.status());
+        assert!(spy.take_events_record().is_empty());
+    }
+}
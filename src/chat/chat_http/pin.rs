@@ -0,0 +1,129 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chat::{Chat, EventId, MessageId},
+    http::{AuthenticateRequest, AuthenticatedUser, HttpError, JsonBody},
+    user::Users,
+};
+
+use super::{ChatState, HttpEvent, MessageFormat, Notice, ensure_allowed_sender};
+
+/// Body of the pin route.
+#[derive(Deserialize)]
+pub(super) struct PinRequest {
+    /// `false` unpins the message.
+    pinned: bool,
+}
+
+/// Describes a message which has been pinned or unpinned. Answer of the pin route and payload of
+/// `pin` events.
+#[derive(Clone, Serialize)]
+#[cfg_attr(feature = "camel-case-json", serde(rename_all = "camelCase"))]
+pub(super) struct PinNotice {
+    message_id: MessageId,
+    /// Id of the event the message has been recorded as.
+    event_id: EventId,
+    pinned: bool,
+}
+
+/// Pins or unpins a message, e.g. to keep an announcement at hand. Users allowed to post may pin
+/// any message. Open event streams are told with a `pin` event.
+pub(super) async fn pin_message<C, U, S>(
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+    Path(message_id): Path<MessageId>,
+    JsonBody(request): JsonBody<PinRequest>,
+) -> Result<Json<PinNotice>, HttpError>
+where
+    C: Chat + Clone + Send + Sync,
+    U: Users + Clone + Send + Sync,
+    S: AuthenticateRequest + Clone + Send + Sync,
+{
+    ensure_allowed_sender(&state.options, state.users, user_id).await?;
+    let mut chat = state.chat;
+    let event_id = chat.pin_message(message_id, request.pinned).await?;
+    let notice = PinNotice {
+        message_id,
+        event_id,
+        pinned: request.pinned,
+    };
+
+    // Only fails if no event stream is open, which is fine.
+    let _ = state.notices.send(Notice::Pin(notice.clone()));
+    Ok(Json(notice))
+}
+
+/// All currently pinned messages as a JSON array, ordered by event id. Clients fetch these once
+/// and keep them up to date with the `pin` events of the event stream.
+pub(super) async fn pinned<C, U, S>(
+    AuthenticatedUser(_): AuthenticatedUser,
+    State(state): State<ChatState<C, U, S>>,
+) -> Result<Json<Vec<HttpEvent>>, HttpError>
+where
+    C: Chat + Send + Sync + 'static,
+    U: Users + Send + Sync + 'static,
+    S: AuthenticateRequest + Send + Sync + 'static,
+{
+    let events = state.chat.pinned().await.map_err(|_| HttpError {
+        status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        message: "Internal server error".into(),
+    })?;
+    let mut format = MessageFormat::new(&state.options, state.users);
+    Ok(Json(format.http_events(events).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use double_trait::Dummy;
+    use serde_json::json;
+    use tower::ServiceExt; // for `oneshot`
+
+    use crate::{
+        chat::{Chat, ChatError, EventId, MessageId},
+        http::Lifecycle,
+    };
+
+    use super::super::{ChatHttpOptions, chat_routes, tests::AuthDummy};
+
+    #[tokio::test]
+    async fn pinning_unknown_message_is_rejected_with_404() {
+        // Given a chat which does not know any message
+        #[derive(Clone)]
+        struct EmptyChat;
+        impl Chat for EmptyChat {
+            async fn pin_message(&mut self, _: MessageId, _: bool) -> Result<EventId, ChatError> {
+                Err(ChatError::NotFound)
+            }
+        }
+        let app = chat_routes(
+            EmptyChat,
+            Dummy,
+            AuthDummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+        );
+
+        // When pinning a message
+        let response = app
+            .oneshot(
+                Request::post("/api/v0/messages/019c0a7f-3d8e-7cf8-bea4-3a8614c8da09/pin")
+                    .header("content-type", "application/json")
+                    .body(Body::from(json!({ "pinned": true }).to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the response is 404 Not Found
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,70 +1,183 @@
 use super::{
-    event::{Event, EventId},
-    message::Message,
+    event::{Event, EventId, Tombstone},
+    message::{Message, MessageId},
 };
 use crate::{
-    persistence::{ExecuteSqlAsync, ExecuteSqlSync, GetField as _, PersistenceError as _},
+    persistence::{
+        Argument, Arguments, AsArgument, ExecuteSqlAsync, ExecuteSqlSync, GetField as _,
+        PersistenceError as _,
+    },
     user::UserId,
 };
+use anyhow::bail;
+use tracing::error;
 use uuid::Uuid;
 
 pub enum InsertOutcome {
     /// The message has not been previously recorded and has been added to the record.
     New,
-    /// Exactly the same message has been previously recorded. No change to the record
-    Duplicate,
+    /// Exactly the same message has been previously recorded. No change to the record. Holds the
+    /// event it has been recorded as.
+    Duplicate(Event),
     /// A different message with the same id has been previously recorded. No change to the record
     Conflict,
+    /// There is no room left in the storage to record the message. No change to the record
+    StorageFull,
+}
+
+/// How the content of new messages is stored. Events are read independent of it, since the
+/// encoding is recorded alongside each event.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContentEncoding {
+    /// Stored as text, just as it has been sent.
+    #[default]
+    Plain,
+    /// Compressed with zstd. Saves space for long messages, at the cost of CPU time for reading
+    /// and writing them.
+    Zstd,
 }
 
 #[cfg_attr(test, double_trait::dummies)]
 pub trait ChatPersistence {
-    /// All events since the event with the given `last_event_id` (exclusive).
-    fn events_since(
+    /// Up to `limit` events since the event with the given `last_event_id` (exclusive). The flag is
+    /// `true` if there are more events beyond the returned page.
+    fn events_page(
         &self,
         last_event_id: EventId,
+        limit: usize,
+    ) -> impl Future<Output = anyhow::Result<(Vec<Event>, bool)>> + Send;
+
+    /// Up to `limit` events with a timestamp between `from_ms` and `to_ms` (inclusive), ordered by
+    /// id.
+    fn events_in_window(
+        &self,
+        from_ms: u64,
+        to_ms: u64,
+        limit: usize,
     ) -> impl Future<Output = anyhow::Result<Vec<Event>>> + Send;
 
     /// The id of the most recently recorded event, or `None` if no event has been recorded yet.
     fn max_event_id(&self) -> impl Future<Output = anyhow::Result<Option<EventId>>> + Send;
 
-    /// Records `event`, unless a message with the same id has already been recorded.
+    /// The id of the oldest event still retained, including tombstoned ones. `None` if no event is
+    /// retained.
+    fn min_event_id(&self) -> impl Future<Output = anyhow::Result<Option<EventId>>> + Send;
+
+    /// Size of the entire database in bytes, including free pages.
+    fn database_size(&self) -> impl Future<Output = anyhow::Result<u64>> + Send;
+
+    /// The id of the first event with a timestamp at or after `timestamp_ms`, or `None` if all
+    /// events are older.
+    fn first_event_at(
+        &self,
+        timestamp_ms: u64,
+    ) -> impl Future<Output = anyhow::Result<Option<EventId>>> + Send;
+
+    /// Number of events with an id greater than `last_event_id`, not counting tombstoned ones.
+    fn count_events_since(
+        &self,
+        last_event_id: EventId,
+    ) -> impl Future<Output = anyhow::Result<u64>> + Send;
+
+    /// Deletes all events up to and including `last_event_id`.
+    fn delete_events_up_to(
+        &self,
+        last_event_id: EventId,
+    ) -> impl Future<Output = anyhow::Result<()>> + Send;
+
+    /// Records `event`, unless a message with the same id has already been recorded. The content is
+    /// stored using `encoding`.
     fn insert_event(
         &self,
         event: &Event,
+        encoding: ContentEncoding,
     ) -> impl Future<Output = anyhow::Result<InsertOutcome>> + Send;
+
+    /// Pins or unpins the message with `message_id`. Returns the id of the event the message has
+    /// been recorded as, or `None` if there is no such message or it has been tombstoned.
+    fn set_pinned(
+        &self,
+        message_id: MessageId,
+        pinned: bool,
+    ) -> impl Future<Output = anyhow::Result<Option<EventId>>> + Send;
+
+    /// All pinned events, ordered by id.
+    fn pinned_events(&self) -> impl Future<Output = anyhow::Result<Vec<Event>>> + Send;
+
+    /// Up to `limit` events of messages sent by `sender` since the event with the given
+    /// `last_event_id` (exclusive), ordered by id. Tombstoned ones are omitted.
+    fn events_by_sender(
+        &self,
+        sender: UserId,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> impl Future<Output = anyhow::Result<Vec<Event>>> + Send;
+
+    /// Tombstones all messages sent by `sender` within one transaction, i.e. erases their content.
+    /// Their events are no longer read, but their ids are not reused. Returns the tombstones of the
+    /// messages which had not been tombstoned before, ordered by id.
+    fn tombstone_sender(
+        &self,
+        sender: UserId,
+    ) -> impl Future<Output = anyhow::Result<Vec<Tombstone>>> + Send;
+
+    /// Tombstones all messages which expired at or before `now_ms` within one transaction, like
+    /// [`Self::tombstone_sender`]. Messages without an expiry of their own expire, if they have
+    /// been recorded before `sent_before_ms`. Pinned messages are spared. Returns the tombstones of
+    /// the messages which had not been tombstoned before, ordered by id.
+    fn tombstone_expired(
+        &self,
+        now_ms: u64,
+        sent_before_ms: Option<u64>,
+    ) -> impl Future<Output = anyhow::Result<Vec<Tombstone>>> + Send;
+
+    /// The distinct senders of all messages which have not been tombstoned.
+    fn senders(&self) -> impl Future<Output = anyhow::Result<Vec<UserId>>> + Send;
+
+    /// `true` if a message with `message_id` has been recorded, even if it has been tombstoned
+    /// since.
+    fn is_recorded(
+        &self,
+        message_id: MessageId,
+    ) -> impl Future<Output = anyhow::Result<bool>> + Send;
+
+    /// Records `message` following the latest event, reads its content back and deletes it again,
+    /// all within one transaction. Leaves the events as they have been, so no event id is used up.
+    /// Returns the outcome of recording the message and the content read back, if any.
+    fn probe(
+        &self,
+        message: Message,
+    ) -> impl Future<Output = anyhow::Result<(InsertOutcome, Option<String>)>> + Send;
 }
 
 impl<P> ChatPersistence for P
 where
     P: ExecuteSqlAsync + Send + Sync,
 {
-    async fn events_since(&self, last_event_id: EventId) -> anyhow::Result<Vec<Event>> {
-        let query = "SELECT events.id, message_id, events.author_id, content, timestamp_ms \
-            FROM events \
-            WHERE events.id > ?1 ORDER BY events.id";
-
-        let map = |row: &P::Row<'_>| {
-            let event_id = row.get(0);
-            let message_id = row.get(1);
-            let author = row.get(2);
-            let content = row.get(3);
-            let timestamp_ms: i64 = row.get(4);
-            let timestamp_ms: u64 = timestamp_ms.try_into().unwrap();
-            let message = Message {
-                id: message_id,
-                author,
-                content,
-            };
-            let event = Event {
-                id: event_id,
-                message,
-                timestamp_ms,
-            };
-            Ok(event)
-        };
+    async fn events_page(
+        &self,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> anyhow::Result<(Vec<Event>, bool)> {
+        // We fetch one event more than requested, to learn whether there are more events without
+        // an additional query.
+        let fetch_limit = i64::try_from(limit).unwrap_or(i64::MAX).saturating_add(1);
+        let rows = fetch_rows_since(self, last_event_id, fetch_limit).await?;
+        // Counted before decoding, since events which can not be decoded are skipped.
+        let has_more = rows.len() > limit;
+        Ok((decode_events(rows.into_iter().take(limit)), has_more))
+    }
 
-        self.rows_vec(query, last_event_id, map).await
+    async fn events_in_window(
+        &self,
+        from_ms: u64,
+        to_ms: u64,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Event>> {
+        let from_ms = i64::try_from(from_ms).unwrap_or(i64::MAX);
+        let to_ms = i64::try_from(to_ms).unwrap_or(i64::MAX);
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        fetch_events(self, FETCH_EVENTS_IN_WINDOW, (from_ms, to_ms, limit)).await
     }
 
     async fn max_event_id(&self) -> anyhow::Result<Option<EventId>> {
@@ -75,11 +188,420 @@ where
         .await
     }
 
-    async fn insert_event(&self, event: &Event) -> anyhow::Result<InsertOutcome> {
+    async fn min_event_id(&self) -> anyhow::Result<Option<EventId>> {
+        self.row("SELECT MIN(id) FROM events", (), |row| {
+            let maybe_event_id: Option<EventId> = row.get(0);
+            Ok(maybe_event_id)
+        })
+        .await
+    }
+
+    async fn database_size(&self) -> anyhow::Result<u64> {
+        self.row(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            (),
+            |row| {
+                let size: i64 = row.get(0);
+                Ok(size.try_into().expect("database size must be non-negative"))
+            },
+        )
+        .await
+    }
+
+    async fn first_event_at(&self, timestamp_ms: u64) -> anyhow::Result<Option<EventId>> {
+        let timestamp_ms = i64::try_from(timestamp_ms).unwrap_or(i64::MAX);
+        self.row(
+            "SELECT MIN(id) FROM events WHERE timestamp_ms >= ?1",
+            timestamp_ms,
+            |row| {
+                let maybe_event_id: Option<EventId> = row.get(0);
+                Ok(maybe_event_id)
+            },
+        )
+        .await
+    }
+
+    async fn count_events_since(&self, last_event_id: EventId) -> anyhow::Result<u64> {
+        self.row(
+            "SELECT COUNT(*) FROM events WHERE deleted = 0 AND id > ?1",
+            last_event_id,
+            |row| {
+                let count: i64 = row.get(0);
+                Ok(count.try_into().expect("count must be non-negative"))
+            },
+        )
+        .await
+    }
+
+    async fn delete_events_up_to(&self, last_event_id: EventId) -> anyhow::Result<()> {
+        self.transaction(move |conn| {
+            conn.execute("DELETE FROM events WHERE id <= ?1", last_event_id)
+        })
+        .await
+    }
+
+    async fn insert_event(
+        &self,
+        event: &Event,
+        encoding: ContentEncoding,
+    ) -> anyhow::Result<InsertOutcome> {
         let event = event.clone();
-        self.transaction(move |conn| insert_event(conn, &event))
+        self.transaction(move |conn| insert_event(conn, &event, encoding))
             .await
     }
+
+    async fn set_pinned(
+        &self,
+        message_id: MessageId,
+        pinned: bool,
+    ) -> anyhow::Result<Option<EventId>> {
+        let event_ids = self
+            .rows_vec(
+                "UPDATE events SET pinned = ?2 WHERE message_id = ?1 AND deleted = 0 RETURNING id",
+                (message_id, i64::from(pinned)),
+                |row| {
+                    let event_id: EventId = row.get(0);
+                    Ok(event_id)
+                },
+            )
+            .await?;
+        // Message ids are unique, so at most one event is affected.
+        Ok(event_ids.into_iter().next())
+    }
+
+    async fn pinned_events(&self) -> anyhow::Result<Vec<Event>> {
+        fetch_events(self, FETCH_PINNED_EVENTS, ()).await
+    }
+
+    async fn events_by_sender(
+        &self,
+        sender: UserId,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Event>> {
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        fetch_events(self, FETCH_EVENTS_BY_SENDER, (sender, last_event_id, limit)).await
+    }
+
+    async fn tombstone_sender(&self, sender: UserId) -> anyhow::Result<Vec<Tombstone>> {
+        self.transaction(move |conn| {
+            conn.rows_vec(TOMBSTONE_SENDER, sender, |row| {
+                Ok(Tombstone {
+                    event_id: row.get(0),
+                    message_id: row.get(1),
+                })
+            })
+        })
+        .await
+    }
+
+    async fn tombstone_expired(
+        &self,
+        now_ms: u64,
+        sent_before_ms: Option<u64>,
+    ) -> anyhow::Result<Vec<Tombstone>> {
+        let now_ms = i64::try_from(now_ms).unwrap_or(i64::MAX);
+        let sent_before_ms = sent_before_ms.map(|ms| i64::try_from(ms).unwrap_or(i64::MAX));
+        self.transaction(move |conn| {
+            conn.rows_vec(TOMBSTONE_EXPIRED, (now_ms, sent_before_ms), |row| {
+                Ok(Tombstone {
+                    event_id: row.get(0),
+                    message_id: row.get(1),
+                })
+            })
+        })
+        .await
+    }
+
+    async fn senders(&self) -> anyhow::Result<Vec<UserId>> {
+        self.rows_vec(
+            "SELECT DISTINCT author_id FROM events WHERE deleted = 0",
+            (),
+            |row| {
+                let sender: UserId = row.get(0);
+                Ok(sender)
+            },
+        )
+        .await
+    }
+
+    async fn is_recorded(&self, message_id: MessageId) -> anyhow::Result<bool> {
+        self.row(
+            "SELECT EXISTS (SELECT 1 FROM events WHERE message_id = ?1)",
+            message_id,
+            |row| {
+                let exists: i64 = row.get(0);
+                Ok(exists != 0)
+            },
+        )
+        .await
+    }
+
+    async fn probe(&self, message: Message) -> anyhow::Result<(InsertOutcome, Option<String>)> {
+        let (outcome, stored) = self
+            .transaction(move |conn| {
+                let latest: Option<EventId> =
+                    conn.row("SELECT MAX(id) FROM events", (), |row| Ok(row.get(0)))?;
+                let event =
+                    Event::new(latest.unwrap_or(EventId::before_all()).successor(), message);
+                let outcome = insert_event(conn, &event, ContentEncoding::Plain)?;
+                let stored = conn.rows_vec(
+                    "SELECT CAST(content AS BLOB), content_encoding FROM events \
+                    WHERE deleted = 0 AND id = ?1",
+                    event.id,
+                    |row| {
+                        let content: Vec<u8> = row.get(0);
+                        let content_encoding: i64 = row.get(1);
+                        Ok((content, content_encoding))
+                    },
+                )?;
+                conn.execute("DELETE FROM events WHERE id = ?1", event.id)?;
+                Ok((outcome, stored.into_iter().next()))
+            })
+            .await?;
+        let content = stored
+            .map(|(content, content_encoding)| decode_content(content, content_encoding))
+            .transpose()?;
+        Ok((outcome, content))
+    }
+}
+
+/// Selects the events with an id greater than `?1`, at most `?2` of them. The content is selected
+/// as bytes, since it is stored either as text or compressed. Like all queries reading events, it
+/// skips tombstoned ones.
+const FETCH_EVENTS_SINCE: &str = "SELECT events.id, message_id, events.author_id, \
+    CAST(content AS BLOB), content_encoding, timestamp_ms, pinned, expires_at_ms \
+    FROM events \
+    WHERE deleted = 0 AND events.id > ?1 ORDER BY events.id LIMIT ?2";
+
+/// Records an event. Parameters are id, message id, author id, content, content encoding, timestamp
+/// and expiry.
+const INSERT_EVENT: &str = "INSERT INTO events \
+    (id, message_id, author_id, content, content_encoding, timestamp_ms, expires_at_ms) \
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+
+/// Selects the events with a timestamp between `?1` and `?2` (inclusive), ordered by id, at most
+/// `?3` of them.
+const FETCH_EVENTS_IN_WINDOW: &str = "SELECT events.id, message_id, events.author_id, \
+    CAST(content AS BLOB), content_encoding, timestamp_ms, pinned, expires_at_ms \
+    FROM events \
+    WHERE deleted = 0 AND timestamp_ms BETWEEN ?1 AND ?2 ORDER BY events.id LIMIT ?3";
+
+/// Selects all pinned events, ordered by id. Only few messages are pinned, so scanning the table is
+/// acceptable.
+const FETCH_PINNED_EVENTS: &str = "SELECT events.id, message_id, events.author_id, \
+    CAST(content AS BLOB), content_encoding, timestamp_ms, pinned, expires_at_ms \
+    FROM events \
+    WHERE deleted = 0 AND pinned = 1 ORDER BY events.id";
+
+/// Selects up to `?3` events of the messages sent by `?1` after the event with id `?2`, ordered by
+/// id.
+const FETCH_EVENTS_BY_SENDER: &str = "SELECT events.id, message_id, events.author_id, \
+    CAST(content AS BLOB), content_encoding, timestamp_ms, pinned, expires_at_ms \
+    FROM events \
+    WHERE deleted = 0 AND author_id = ?1 AND events.id > ?2 ORDER BY events.id LIMIT ?3";
+
+/// Tombstones the messages sent by `?1`. The content is erased, rather than just hidden, since
+/// tombstoning serves requests to erase personal data. Tombstoned messages are no longer pinned
+/// either.
+const TOMBSTONE_SENDER: &str = "UPDATE events \
+    SET deleted = 1, content = X'', content_encoding = 0, pinned = 0 \
+    WHERE deleted = 0 AND author_id = ?1 \
+    RETURNING id, message_id";
+
+/// Tombstones the messages which expired at or before `?1`, like [`TOMBSTONE_SENDER`]. Messages
+/// without an expiry of their own expire, if recorded before `?2`. `NULL` for `?2` spares them.
+/// Pinned messages do not expire, so announcements stay at hand.
+const TOMBSTONE_EXPIRED: &str = "UPDATE events \
+    SET deleted = 1, content = X'', content_encoding = 0 \
+    WHERE deleted = 0 AND pinned = 0 \
+    AND (expires_at_ms <= ?1 OR (expires_at_ms IS NULL AND timestamp_ms < ?2)) \
+    RETURNING id, message_id";
+
+/// Marks content in the `content_encoding` column, which is stored as text.
+const CONTENT_PLAIN: i64 = 0;
+/// Marks content in the `content_encoding` column, which is compressed with zstd.
+const CONTENT_ZSTD: i64 = 1;
+
+/// Prepares the statements for reading events and recording messages, which run for almost every
+/// request to the chat.
+pub fn warm_up_chat_persistence<C>(conn: &C) -> Result<(), C::Error>
+where
+    C: ExecuteSqlSync,
+{
+    conn.prepare(FETCH_EVENTS_SINCE)?;
+    conn.prepare(INSERT_EVENT)?;
+    Ok(())
+}
+
+/// Rows of events since `last_event_id` (exclusive) in the order they have been recorded. At most
+/// `limit` rows are returned, unless `limit` is negative.
+async fn fetch_rows_since<P>(
+    persistence: &P,
+    last_event_id: EventId,
+    limit: i64,
+) -> anyhow::Result<Vec<EventRow>>
+where
+    P: ExecuteSqlAsync,
+{
+    fetch_rows(persistence, FETCH_EVENTS_SINCE, (last_event_id, limit)).await
+}
+
+/// Runs `query`, which must select the same columns as [`FETCH_EVENTS_SINCE`], and decodes the
+/// resulting events.
+async fn fetch_events<P>(
+    persistence: &P,
+    query: &'static str,
+    args: impl Arguments + Send + Sync + 'static,
+) -> anyhow::Result<Vec<Event>>
+where
+    P: ExecuteSqlAsync,
+{
+    let rows = fetch_rows(persistence, query, args).await?;
+    Ok(decode_events(rows))
+}
+
+/// An event as selected by [`FETCH_EVENTS_SINCE`], with its content still encoded.
+type EventRow = (
+    EventId,
+    MessageId,
+    UserId,
+    Vec<u8>,
+    i64,
+    u64,
+    bool,
+    Option<u64>,
+);
+
+/// Runs `query`, which must select the same columns as [`FETCH_EVENTS_SINCE`]. Dropping the returned
+/// future interrupts the query.
+async fn fetch_rows<P>(
+    persistence: &P,
+    query: &'static str,
+    args: impl Arguments + Send + Sync + 'static,
+) -> anyhow::Result<Vec<EventRow>>
+where
+    P: ExecuteSqlAsync,
+{
+    let map = |row: &P::Row<'_>| {
+        let event_id: EventId = row.get(0);
+        let message_id: MessageId = row.get(1);
+        let author: UserId = row.get(2);
+        let content: Vec<u8> = row.get(3);
+        let content_encoding: i64 = row.get(4);
+        let timestamp_ms: i64 = row.get(5);
+        let timestamp_ms: u64 = timestamp_ms.try_into().unwrap();
+        let pinned: i64 = row.get(6);
+        let expires_at_ms: Option<i64> = row.get(7);
+        let expires_at_ms = expires_at_ms.map(|ms| ms.try_into().unwrap());
+        Ok((
+            event_id,
+            message_id,
+            author,
+            content,
+            content_encoding,
+            timestamp_ms,
+            pinned != 0,
+            expires_at_ms,
+        ))
+    };
+    persistence.interruptible_rows_vec(query, args, map).await
+}
+
+/// Decodes the content of the events. Decompressing happens outside of the database thread, so it
+/// is not blocked for other queries.
+fn decode_events(rows: impl IntoIterator<Item = EventRow>) -> Vec<Event> {
+    rows.into_iter()
+        .filter_map(
+            |(
+                event_id,
+                message_id,
+                author,
+                content,
+                content_encoding,
+                timestamp_ms,
+                pinned,
+                expires_at_ms,
+            )| {
+                // A single malformed row, e.g. in a tampered database, must not fail every read
+                // which includes it. Clients resuming before it would never get past it otherwise.
+                let content = decode_content(content, content_encoding)
+                    .inspect_err(|err| {
+                        error!(
+                            target: "persistence",
+                            event_id = event_id.0,
+                            error = %err,
+                            "Skipping event with undecodable content"
+                        );
+                    })
+                    .ok()?;
+                let message = Message {
+                    id: message_id,
+                    author,
+                    content,
+                    expires_at_ms,
+                };
+                Some(Event {
+                    id: event_id,
+                    message,
+                    timestamp_ms,
+                    pinned,
+                })
+            },
+        )
+        .collect()
+}
+
+/// Content of a message as it is stored in the `content` column.
+enum StoredContent<'a> {
+    Plain(&'a str),
+    Zstd(Vec<u8>),
+}
+
+impl<'a> StoredContent<'a> {
+    fn encode(content: &'a str, encoding: ContentEncoding) -> Self {
+        match encoding {
+            ContentEncoding::Plain => StoredContent::Plain(content),
+            ContentEncoding::Zstd => {
+                let compressed =
+                    zstd::encode_all(content.as_bytes(), zstd::DEFAULT_COMPRESSION_LEVEL)
+                        .expect("Compressing in memory must not fail");
+                // Short messages grow if compressed. These are better stored as they are.
+                if compressed.len() < content.len() {
+                    StoredContent::Zstd(compressed)
+                } else {
+                    StoredContent::Plain(content)
+                }
+            }
+        }
+    }
+
+    /// Value of the `content_encoding` column.
+    fn marker(&self) -> i64 {
+        match self {
+            StoredContent::Plain(_) => CONTENT_PLAIN,
+            StoredContent::Zstd(_) => CONTENT_ZSTD,
+        }
+    }
+}
+
+impl AsArgument for StoredContent<'_> {
+    fn as_argument(&self) -> Argument<'_> {
+        match self {
+            StoredContent::Plain(text) => text.as_argument(),
+            StoredContent::Zstd(bytes) => bytes.as_argument(),
+        }
+    }
+}
+
+/// Restores the content of a message from the `content` and `content_encoding` columns.
+fn decode_content(content: Vec<u8>, content_encoding: i64) -> anyhow::Result<String> {
+    let bytes = match content_encoding {
+        CONTENT_PLAIN => content,
+        CONTENT_ZSTD => zstd::decode_all(content.as_slice())?,
+        unknown => bail!("Unknown content encoding {unknown}"),
+    };
+    Ok(String::from_utf8(bytes)?)
 }
 
 pub fn migrate_chat_persistence<C>(conn: &C, from_version: u32) -> Result<(), C::Error>
@@ -94,6 +616,18 @@ where
         1 => {
             migrate_v1_to_v2(conn)?;
         }
+        2 => {
+            migrate_v2_to_v3(conn)?;
+        }
+        3 => {
+            migrate_v3_to_v4(conn)?;
+        }
+        4 => {
+            migrate_v4_to_v5(conn)?;
+        }
+        5 => {
+            migrate_v5_to_v6(conn)?;
+        }
         _ => (),
     }
     Ok(())
@@ -143,42 +677,185 @@ where
     Ok(())
 }
 
-fn create_schema_from_scratch<C>(conn: &C) -> Result<(), C::Error>
+/// Adds the `content_encoding` column. Existing content is stored as text.
+fn migrate_v2_to_v3<C>(conn: &C) -> Result<(), C::Error>
 where
     C: ExecuteSqlSync,
 {
+    conn.execute("ALTER TABLE events RENAME TO events_old", ())?;
+    // Schema of version 3. Spelled out, since `CREATE_EVENTS_TABLE` follows the current version.
     conn.execute(
         "CREATE TABLE events (
             id INTEGER PRIMARY KEY,
             message_id BLOB UNIQUE NOT NULL,
             author_id BLOB NOT NULL,
-            content TEXT NOT NULL,
+            content BLOB NOT NULL,
+            content_encoding INTEGER NOT NULL,
             timestamp_ms INTEGER NOT NULL
         )",
         (),
     )?;
+    conn.execute(
+        "INSERT INTO events (id, message_id, author_id, content, content_encoding, timestamp_ms) \
+            SELECT id, message_id, author_id, content, 0, timestamp_ms \
+            FROM events_old",
+        (),
+    )?;
+    conn.execute("DROP TABLE events_old", ())?;
+    Ok(())
+}
+
+/// Adds the `pinned` column. No message is pinned yet.
+fn migrate_v3_to_v4<C>(conn: &C) -> Result<(), C::Error>
+where
+    C: ExecuteSqlSync,
+{
+    conn.execute("ALTER TABLE events RENAME TO events_old", ())?;
+    // Schema of version 4. Spelled out, since `CREATE_EVENTS_TABLE` follows the current version.
+    conn.execute(
+        "CREATE TABLE events (
+            id INTEGER PRIMARY KEY,
+            message_id BLOB UNIQUE NOT NULL,
+            author_id BLOB NOT NULL,
+            content BLOB NOT NULL,
+            content_encoding INTEGER NOT NULL,
+            timestamp_ms INTEGER NOT NULL,
+            pinned INTEGER NOT NULL DEFAULT 0
+        )",
+        (),
+    )?;
+    conn.execute(
+        "INSERT INTO events \
+            (id, message_id, author_id, content, content_encoding, timestamp_ms, pinned) \
+            SELECT id, message_id, author_id, content, content_encoding, timestamp_ms, 0 \
+            FROM events_old",
+        (),
+    )?;
+    conn.execute("DROP TABLE events_old", ())?;
     Ok(())
 }
 
-fn insert_event<C>(conn: &C, event: &Event) -> Result<InsertOutcome, C::Error>
+/// Adds the `deleted` column. No message is tombstoned yet.
+fn migrate_v4_to_v5<C>(conn: &C) -> Result<(), C::Error>
 where
     C: ExecuteSqlSync,
 {
+    conn.execute("ALTER TABLE events RENAME TO events_old", ())?;
+    // Schema of version 5. Spelled out, since `CREATE_EVENTS_TABLE` follows the current version.
+    conn.execute(
+        "CREATE TABLE events (
+            id INTEGER PRIMARY KEY,
+            message_id BLOB UNIQUE NOT NULL,
+            author_id BLOB NOT NULL,
+            content BLOB NOT NULL,
+            content_encoding INTEGER NOT NULL,
+            timestamp_ms INTEGER NOT NULL,
+            pinned INTEGER NOT NULL DEFAULT 0,
+            deleted INTEGER NOT NULL DEFAULT 0
+        )",
+        (),
+    )?;
+    conn.execute(
+        "INSERT INTO events \
+            (id, message_id, author_id, content, content_encoding, timestamp_ms, pinned, deleted) \
+            SELECT id, message_id, author_id, content, content_encoding, timestamp_ms, pinned, 0 \
+            FROM events_old",
+        (),
+    )?;
+    conn.execute("DROP TABLE events_old", ())?;
+    Ok(())
+}
+
+/// Adds the `expires_at_ms` column. No message expires on its own yet.
+fn migrate_v5_to_v6<C>(conn: &C) -> Result<(), C::Error>
+where
+    C: ExecuteSqlSync,
+{
+    conn.execute("ALTER TABLE events RENAME TO events_old", ())?;
+    // Schema of version 6. Spelled out, since `CREATE_EVENTS_TABLE` follows the current version.
+    conn.execute(
+        "CREATE TABLE events (
+            id INTEGER PRIMARY KEY,
+            message_id BLOB UNIQUE NOT NULL,
+            author_id BLOB NOT NULL,
+            content BLOB NOT NULL,
+            content_encoding INTEGER NOT NULL,
+            timestamp_ms INTEGER NOT NULL,
+            pinned INTEGER NOT NULL DEFAULT 0,
+            deleted INTEGER NOT NULL DEFAULT 0,
+            expires_at_ms INTEGER
+        )",
+        (),
+    )?;
+    conn.execute(
+        "INSERT INTO events \
+            (id, message_id, author_id, content, content_encoding, timestamp_ms, pinned, deleted, \
+            expires_at_ms) \
+            SELECT id, message_id, author_id, content, content_encoding, timestamp_ms, pinned, \
+            deleted, NULL \
+            FROM events_old",
+        (),
+    )?;
+    conn.execute("DROP TABLE events_old", ())?;
+    Ok(())
+}
+
+/// The `content` column holds either text or compressed bytes, depending on `content_encoding`.
+/// `pinned` is `1` for pinned messages and `0` otherwise. `deleted` is `1` for tombstoned
+/// messages, whose content has been erased. `expires_at_ms` is `NULL` for messages which do not
+/// expire on their own.
+const CREATE_EVENTS_TABLE: &str = "CREATE TABLE events (
+            id INTEGER PRIMARY KEY,
+            message_id BLOB UNIQUE NOT NULL,
+            author_id BLOB NOT NULL,
+            content BLOB NOT NULL,
+            content_encoding INTEGER NOT NULL,
+            timestamp_ms INTEGER NOT NULL,
+            pinned INTEGER NOT NULL DEFAULT 0,
+            deleted INTEGER NOT NULL DEFAULT 0,
+            expires_at_ms INTEGER
+        )";
+
+fn create_schema_from_scratch<C>(conn: &C) -> Result<(), C::Error>
+where
+    C: ExecuteSqlSync,
+{
+    conn.execute(CREATE_EVENTS_TABLE, ())?;
+    Ok(())
+}
+
+fn insert_event<C>(
+    conn: &C,
+    event: &Event,
+    encoding: ContentEncoding,
+) -> Result<InsertOutcome, C::Error>
+where
+    C: ExecuteSqlSync,
+{
+    let content = StoredContent::encode(&event.message.content, encoding);
+    let content_encoding = content.marker();
     let Err(err) = conn.execute(
-        "INSERT INTO events (id, message_id, author_id, content, timestamp_ms) \
-        VALUES (?1, ?2, ?3, ?4, ?5)",
+        INSERT_EVENT,
         (
             event.id,
             event.message.id,
             event.message.author,
-            event.message.content.as_str(),
+            content,
+            content_encoding,
             event.timestamp_ms as i64,
+            event.message.expires_at_ms.map(|ms| ms as i64),
         ),
     ) else {
         // Message successfully inserted, let's return.
         return Ok(InsertOutcome::New);
     };
 
+    // A full disk is reported separately, so operators and clients can tell it apart from other
+    // errors. It is likely to go away once space is freed up.
+    if err.is_storage_full() {
+        return Ok(InsertOutcome::StorageFull);
+    }
+
     // We had an error, but did something go wrong with accesing the database or is due to a message
     // id being already present?
     if !err.is_unique_constraint_violation() {
@@ -186,18 +863,39 @@ where
         return Err(err);
     }
 
-    // So it is a unique constraint violation, but is it a duplicate or a conflict?
-    let (author, content) = conn.row(
-        "SELECT author_id, content FROM events WHERE message_id = ?1",
+    // So it is a unique constraint violation, but is it a duplicate or a conflict? Content is
+    // compared decompressed, since the recorded message might have been stored using a different
+    // encoding.
+    let (recorded_id, author, content, content_encoding, timestamp_ms, pinned) = conn.row(
+        "SELECT id, author_id, CAST(content AS BLOB), content_encoding, timestamp_ms, pinned \
+        FROM events WHERE message_id = ?1",
         event.message.id,
         |row| {
-            let author: UserId = row.get(0);
-            let content: String = row.get(1);
-            Ok((author, content))
+            let recorded_id: EventId = row.get(0);
+            let author: UserId = row.get(1);
+            let content: Vec<u8> = row.get(2);
+            let content_encoding: i64 = row.get(3);
+            let timestamp_ms: i64 = row.get(4);
+            let timestamp_ms: u64 = timestamp_ms.try_into().unwrap();
+            let pinned: i64 = row.get(5);
+            Ok((
+                recorded_id,
+                author,
+                content,
+                content_encoding,
+                timestamp_ms,
+                pinned != 0,
+            ))
         },
     )?;
-    if author == event.message.author && content == event.message.content {
-        Ok(InsertOutcome::Duplicate)
+    let content = decode_content(content, content_encoding).ok();
+    if author == event.message.author && content.as_ref() == Some(&event.message.content) {
+        Ok(InsertOutcome::Duplicate(Event {
+            id: recorded_id,
+            message: event.message.clone(),
+            timestamp_ms,
+            pinned,
+        }))
     } else {
         Ok(InsertOutcome::Conflict)
     }
@@ -205,7 +903,7 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::time::SystemTime;
+    use std::time::{Duration, SystemTime};
 
     use async_sqlite::ClientBuilder;
 
@@ -214,27 +912,39 @@ mod tests {
         user::UserId,
     };
 
-    use super::{ChatPersistence, InsertOutcome, migrate_chat_persistence};
+    use super::{ChatPersistence, ContentEncoding, InsertOutcome, migrate_chat_persistence};
 
     #[tokio::test]
-    async fn events_since_excludes_events_up_to_last_event_id() {
+    async fn events_page_excludes_events_up_to_last_event_id() {
         // Given three recorded events
         let persistence = persistence_fake().await;
         persistence
-            .insert_event(&dummy_event(EventId(1), MessageId::ALPHA))
+            .insert_event(
+                &dummy_event(EventId(1), MessageId::ALPHA),
+                ContentEncoding::Plain,
+            )
             .await
             .unwrap();
         persistence
-            .insert_event(&dummy_event(EventId(2), MessageId::BETA))
+            .insert_event(
+                &dummy_event(EventId(2), MessageId::BETA),
+                ContentEncoding::Plain,
+            )
             .await
             .unwrap();
         persistence
-            .insert_event(&dummy_event(EventId(3), MessageId::GAMMA))
+            .insert_event(
+                &dummy_event(EventId(3), MessageId::GAMMA),
+                ContentEncoding::Plain,
+            )
             .await
             .unwrap();
 
         // When retrieving events since event 1
-        let events = persistence.events_since(EventId(1)).await.unwrap();
+        let (events, _) = persistence
+            .events_page(EventId(1), usize::MAX)
+            .await
+            .unwrap();
 
         // Then only events 2 and 3 are returned
         assert_eq!(events.len(), 2);
@@ -243,21 +953,486 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn events_since_beyond_all_events_returns_empty() {
+    async fn events_page_beyond_all_events_returns_empty() {
         // Given a single recorded event
         let persistence = persistence_fake().await;
         persistence
-            .insert_event(&dummy_event(EventId(1), MessageId::ALPHA))
+            .insert_event(
+                &dummy_event(EventId(1), MessageId::ALPHA),
+                ContentEncoding::Plain,
+            )
             .await
             .unwrap();
 
         // When retrieving events since an id beyond the history
-        let events = persistence.events_since(EventId(2)).await.unwrap();
+        let (events, _) = persistence
+            .events_page(EventId(2), usize::MAX)
+            .await
+            .unwrap();
 
         // Then no events are returned
         assert!(events.is_empty());
     }
 
+    #[tokio::test]
+    async fn events_page_has_more_if_events_exceed_limit() {
+        // Given three recorded events
+        let persistence = persistence_fake().await;
+        for (id, message_id) in [
+            (EventId(1), MessageId::ALPHA),
+            (EventId(2), MessageId::BETA),
+            (EventId(3), MessageId::GAMMA),
+        ] {
+            persistence
+                .insert_event(&dummy_event(id, message_id), ContentEncoding::Plain)
+                .await
+                .unwrap();
+        }
+
+        // When retrieving a page of two events, i.e. one less than there are
+        let (events, has_more) = persistence
+            .events_page(EventId::before_all(), 2)
+            .await
+            .unwrap();
+
+        // Then the page is trimmed to the limit and indicates that there are more events
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].id, EventId(1));
+        assert_eq!(events[1].id, EventId(2));
+        assert!(has_more);
+    }
+
+    #[tokio::test]
+    async fn events_page_has_no_more_if_events_match_limit() {
+        // Given two recorded events
+        let persistence = persistence_fake().await;
+        persistence
+            .insert_event(
+                &dummy_event(EventId(1), MessageId::ALPHA),
+                ContentEncoding::Plain,
+            )
+            .await
+            .unwrap();
+        persistence
+            .insert_event(
+                &dummy_event(EventId(2), MessageId::BETA),
+                ContentEncoding::Plain,
+            )
+            .await
+            .unwrap();
+
+        // When retrieving a page of exactly two events
+        let (events, has_more) = persistence
+            .events_page(EventId::before_all(), 2)
+            .await
+            .unwrap();
+
+        // Then all events are returned and there are no more
+        assert_eq!(events.len(), 2);
+        assert!(!has_more);
+    }
+
+    #[tokio::test]
+    async fn first_event_at_is_first_event_not_older_than_timestamp() {
+        // Given three events recorded one second apart
+        let persistence = persistence_fake().await;
+        for (id, message_id, secs) in [
+            (EventId(1), MessageId::ALPHA, 10),
+            (EventId(2), MessageId::BETA, 11),
+            (EventId(3), MessageId::GAMMA, 12),
+        ] {
+            let event = Event {
+                timestamp_ms: secs * 1_000,
+                ..dummy_event(id, message_id)
+            };
+            persistence
+                .insert_event(&event, ContentEncoding::Plain)
+                .await
+                .unwrap();
+        }
+
+        // When looking for the first event at or after a timestamp between the first two events
+        let first = persistence.first_event_at(10_500).await.unwrap();
+
+        // Then the second event is found
+        assert_eq!(first, Some(EventId(2)));
+        // An event exactly at the timestamp is included
+        assert_eq!(
+            persistence.first_event_at(12_000).await.unwrap(),
+            Some(EventId(3))
+        );
+    }
+
+    #[tokio::test]
+    async fn first_event_at_is_none_if_all_events_are_older() {
+        // Given a single event
+        let persistence = persistence_fake().await;
+        persistence
+            .insert_event(
+                &dummy_event(EventId(1), MessageId::ALPHA),
+                ContentEncoding::Plain,
+            )
+            .await
+            .unwrap();
+
+        // When looking for events after it has been recorded
+        let first = persistence.first_event_at(1_000).await.unwrap();
+
+        // Then there is none
+        assert_eq!(first, None);
+    }
+
+    #[tokio::test]
+    async fn count_events_since_skips_earlier_and_tombstoned_events() {
+        // Given two messages of Alice and one of Bob, whose messages have been tombstoned
+        let persistence = persistence_fake().await;
+        for (id, message_id, author) in [
+            (EventId(1), MessageId::ALPHA, UserId::ALICE),
+            (EventId(2), MessageId::BETA, UserId::ALICE),
+            (EventId(3), MessageId::GAMMA, UserId::BOB),
+        ] {
+            persistence
+                .insert_event(
+                    &authored_event(id, message_id, author),
+                    ContentEncoding::Plain,
+                )
+                .await
+                .unwrap();
+        }
+        persistence.tombstone_sender(UserId::BOB).await.unwrap();
+
+        // When counting the events after the first one
+        let count = persistence.count_events_since(EventId(1)).await.unwrap();
+
+        // Then neither the first nor the tombstoned one are counted
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn events_in_window_include_boundaries_and_respect_limit() {
+        // Given three events recorded one second apart
+        let persistence = persistence_fake().await;
+        for (id, message_id, secs) in [
+            (EventId(1), MessageId::ALPHA, 10),
+            (EventId(2), MessageId::BETA, 11),
+            (EventId(3), MessageId::GAMMA, 12),
+        ] {
+            let event = Event {
+                timestamp_ms: secs * 1_000,
+                ..dummy_event(id, message_id)
+            };
+            persistence
+                .insert_event(&event, ContentEncoding::Plain)
+                .await
+                .unwrap();
+        }
+
+        // When asking for the window spanning exactly the last two events
+        let events = persistence
+            .events_in_window(11_000, 12_000, 10)
+            .await
+            .unwrap();
+
+        // Then both are included, events on the boundaries alike
+        let ids: Vec<_> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [EventId(2), EventId(3)]);
+        // The limit cuts off the most recent events
+        let events = persistence.events_in_window(0, u64::MAX, 2).await.unwrap();
+        let ids: Vec<_> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [EventId(1), EventId(2)]);
+    }
+
+    #[tokio::test]
+    async fn pinned_events_are_listed_until_unpinned() {
+        // Given three recorded events
+        let persistence = persistence_fake().await;
+        for (id, message_id) in [
+            (EventId(1), MessageId::ALPHA),
+            (EventId(2), MessageId::BETA),
+            (EventId(3), MessageId::GAMMA),
+        ] {
+            persistence
+                .insert_event(&dummy_event(id, message_id), ContentEncoding::Plain)
+                .await
+                .unwrap();
+        }
+
+        // When pinning the last and the first one
+        let pinned_gamma = persistence
+            .set_pinned(MessageId::GAMMA, true)
+            .await
+            .unwrap();
+        persistence
+            .set_pinned(MessageId::ALPHA, true)
+            .await
+            .unwrap();
+
+        // Then both are listed in order of their ids and marked as pinned in the history
+        assert_eq!(Some(EventId(3)), pinned_gamma);
+        let pinned = persistence.pinned_events().await.unwrap();
+        let ids: Vec<_> = pinned.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [EventId(1), EventId(3)]);
+        assert!(pinned.iter().all(|e| e.pinned));
+        let (events, _) = persistence
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+        let flags: Vec<_> = events.iter().map(|e| e.pinned).collect();
+        assert_eq!(flags, [true, false, true]);
+        // Unpinned events are no longer listed
+        persistence
+            .set_pinned(MessageId::ALPHA, false)
+            .await
+            .unwrap();
+        let pinned = persistence.pinned_events().await.unwrap();
+        let ids: Vec<_> = pinned.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [EventId(3)]);
+    }
+
+    #[tokio::test]
+    async fn pinning_unknown_message_yields_none() {
+        // Given an empty history
+        let persistence = persistence_fake().await;
+
+        // When pinning a message
+        let event_id = persistence
+            .set_pinned(MessageId::ALPHA, true)
+            .await
+            .unwrap();
+
+        // Then there is no event it has been recorded as
+        assert_eq!(None, event_id);
+    }
+
+    #[tokio::test]
+    async fn pinning_tombstoned_message_yields_none() {
+        // Given a message of Alice, whose messages have been tombstoned
+        let persistence = persistence_fake().await;
+        persistence
+            .insert_event(
+                &authored_event(EventId(1), MessageId::ALPHA, UserId::ALICE),
+                ContentEncoding::Plain,
+            )
+            .await
+            .unwrap();
+        persistence.tombstone_sender(UserId::ALICE).await.unwrap();
+
+        // When pinning the message
+        let event_id = persistence
+            .set_pinned(MessageId::ALPHA, true)
+            .await
+            .unwrap();
+
+        // Then it is treated like an unknown one and is not pinned
+        assert_eq!(None, event_id);
+        assert!(persistence.pinned_events().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn events_by_sender_are_only_those_of_the_sender() {
+        // Given a message of Alice, one of Bob and another one of Alice
+        let persistence = persistence_fake().await;
+        for (id, message_id, author) in [
+            (EventId(1), MessageId::ALPHA, UserId::ALICE),
+            (EventId(2), MessageId::BETA, UserId::BOB),
+            (EventId(3), MessageId::GAMMA, UserId::ALICE),
+        ] {
+            persistence
+                .insert_event(
+                    &authored_event(id, message_id, author),
+                    ContentEncoding::Plain,
+                )
+                .await
+                .unwrap();
+        }
+
+        // When fetching the events of Alice
+        let events = persistence
+            .events_by_sender(UserId::ALICE, EventId::before_all(), 10)
+            .await
+            .unwrap();
+
+        // Then only her messages are returned
+        let ids: Vec<_> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [EventId(1), EventId(3)]);
+    }
+
+    #[tokio::test]
+    async fn events_by_sender_are_paged_by_event_id() {
+        // Given three messages of Alice
+        let persistence = persistence_fake().await;
+        for (id, message_id) in [
+            (EventId(1), MessageId::ALPHA),
+            (EventId(2), MessageId::BETA),
+            (EventId(3), MessageId::GAMMA),
+        ] {
+            persistence
+                .insert_event(
+                    &authored_event(id, message_id, UserId::ALICE),
+                    ContentEncoding::Plain,
+                )
+                .await
+                .unwrap();
+        }
+
+        // When fetching a page of one event after the first one
+        let events = persistence
+            .events_by_sender(UserId::ALICE, EventId(1), 1)
+            .await
+            .unwrap();
+
+        // Then only the second one is returned
+        let ids: Vec<_> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [EventId(2)]);
+    }
+
+    #[tokio::test]
+    async fn tombstoned_messages_are_no_longer_read() {
+        // Given a message of Alice, one of Bob and another one of Alice
+        let persistence = persistence_fake().await;
+        for (id, message_id, author) in [
+            (EventId(1), MessageId::ALPHA, UserId::ALICE),
+            (EventId(2), MessageId::BETA, UserId::BOB),
+            (EventId(3), MessageId::GAMMA, UserId::ALICE),
+        ] {
+            persistence
+                .insert_event(
+                    &authored_event(id, message_id, author),
+                    ContentEncoding::Plain,
+                )
+                .await
+                .unwrap();
+        }
+
+        // When tombstoning the messages of Alice twice
+        let tombstones = persistence.tombstone_sender(UserId::ALICE).await.unwrap();
+        let again = persistence.tombstone_sender(UserId::ALICE).await.unwrap();
+
+        // Then her messages are tombstoned once and only the one of Bob is still read
+        let ids: Vec<_> = tombstones.iter().map(|t| t.event_id).collect();
+        assert_eq!(ids, [EventId(1), EventId(3)]);
+        assert_eq!(tombstones[0].message_id, MessageId::ALPHA);
+        assert!(again.is_empty());
+        let (events, _) = persistence
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+        let ids: Vec<_> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [EventId(2)]);
+        assert_eq!(Some(EventId(3)), persistence.max_event_id().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn expired_messages_are_tombstoned_unless_pinned() {
+        // Given a message recorded at 1s, a pinned one at 2s and another one at 3s
+        let persistence = persistence_fake().await;
+        for (id, message_id, secs) in [
+            (EventId(1), MessageId::ALPHA, 1),
+            (EventId(2), MessageId::BETA, 2),
+            (EventId(3), MessageId::GAMMA, 3),
+        ] {
+            let event = Event::with_timestamp(
+                id,
+                Message {
+                    id: message_id,
+                    ..Message::dummy()
+                },
+                SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+            );
+            persistence
+                .insert_event(&event, ContentEncoding::Plain)
+                .await
+                .unwrap();
+        }
+        persistence.set_pinned(MessageId::BETA, true).await.unwrap();
+
+        // When tombstoning the messages recorded before 3s
+        let tombstones = persistence.tombstone_expired(0, Some(3000)).await.unwrap();
+
+        // Then only the first one is tombstoned
+        let ids: Vec<_> = tombstones.iter().map(|t| t.event_id).collect();
+        assert_eq!(ids, [EventId(1)]);
+        let (events, _) = persistence
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+        let ids: Vec<_> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [EventId(2), EventId(3)]);
+    }
+
+    #[tokio::test]
+    async fn messages_expire_at_their_own_expiry() {
+        // Given three messages recorded at 1s. The first one expires at 5s, the second one has no
+        // expiry of its own and the third one expires at 20s.
+        let persistence = persistence_fake().await;
+        for (id, message_id, expires_at_ms) in [
+            (EventId(1), MessageId::ALPHA, Some(5000)),
+            (EventId(2), MessageId::BETA, None),
+            (EventId(3), MessageId::GAMMA, Some(20_000)),
+        ] {
+            let event = Event::with_timestamp(
+                id,
+                Message {
+                    id: message_id,
+                    expires_at_ms,
+                    ..Message::dummy()
+                },
+                SystemTime::UNIX_EPOCH + Duration::from_secs(1),
+            );
+            persistence
+                .insert_event(&event, ContentEncoding::Plain)
+                .await
+                .unwrap();
+        }
+
+        // When tombstoning the messages expired at 10s, first without and then with a chat wide
+        // TTL which expires messages recorded before 3s
+        let own = persistence.tombstone_expired(10_000, None).await.unwrap();
+        let fallback = persistence
+            .tombstone_expired(10_000, Some(3000))
+            .await
+            .unwrap();
+
+        // Then the first one expires on its own, the second one by the chat wide TTL, and the
+        // third one outlives the chat wide TTL, due to its own expiry
+        let ids: Vec<_> = own.iter().map(|t| t.event_id).collect();
+        assert_eq!(ids, [EventId(1)]);
+        let ids: Vec<_> = fallback.iter().map(|t| t.event_id).collect();
+        assert_eq!(ids, [EventId(2)]);
+        let (events, _) = persistence
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(1, events.len());
+        assert_eq!(Some(20_000), events[0].message.expires_at_ms);
+    }
+
+    #[tokio::test]
+    async fn senders_are_distinct_and_exclude_tombstoned_ones() {
+        // Given two messages of Alice and one of Bob, whose messages have been tombstoned
+        let persistence = persistence_fake().await;
+        for (id, message_id, author) in [
+            (EventId(1), MessageId::ALPHA, UserId::ALICE),
+            (EventId(2), MessageId::BETA, UserId::BOB),
+            (EventId(3), MessageId::GAMMA, UserId::ALICE),
+        ] {
+            persistence
+                .insert_event(
+                    &authored_event(id, message_id, author),
+                    ContentEncoding::Plain,
+                )
+                .await
+                .unwrap();
+        }
+        persistence.tombstone_sender(UserId::BOB).await.unwrap();
+
+        // When fetching the senders
+        let senders = persistence.senders().await.unwrap();
+
+        // Then Alice is the only one, listed once
+        assert_eq!(senders, [UserId::ALICE]);
+    }
+
     #[tokio::test]
     async fn insert_new_message() {
         // Given
@@ -265,7 +1440,10 @@ mod tests {
 
         // When
         let outcome = persistence
-            .insert_event(&dummy_event(EventId(1), MessageId::ALPHA))
+            .insert_event(
+                &dummy_event(EventId(1), MessageId::ALPHA),
+                ContentEncoding::Plain,
+            )
             .await
             .unwrap();
 
@@ -281,28 +1459,27 @@ mod tests {
             id: MessageId::ALPHA,
             author: UserId::ALICE,
             content: "Hello".to_owned(),
+            expires_at_ms: None,
         };
         persistence
-            .insert_event(&Event::with_timestamp(
-                EventId(1),
-                message.clone(),
-                SystemTime::UNIX_EPOCH,
-            ))
+            .insert_event(
+                &Event::with_timestamp(EventId(1), message.clone(), SystemTime::UNIX_EPOCH),
+                ContentEncoding::Plain,
+            )
             .await
             .unwrap();
 
         // When recording the exact same message again under a new event id, as a retry would
         let outcome = persistence
-            .insert_event(&Event::with_timestamp(
-                EventId(2),
-                message,
-                SystemTime::UNIX_EPOCH,
-            ))
+            .insert_event(
+                &Event::with_timestamp(EventId(2), message, SystemTime::UNIX_EPOCH),
+                ContentEncoding::Plain,
+            )
             .await
             .unwrap();
 
-        // Then it is reported as a duplicate
-        assert!(matches!(outcome, InsertOutcome::Duplicate));
+        // Then it is reported as a duplicate of the event it has been recorded as first
+        assert!(matches!(outcome, InsertOutcome::Duplicate(event) if event.id == EventId(1)));
     }
 
     #[tokio::test]
@@ -310,29 +1487,37 @@ mod tests {
         // Given a recorded event
         let persistence = persistence_fake().await;
         persistence
-            .insert_event(&Event::with_timestamp(
-                EventId(1),
-                Message {
-                    id: MessageId::ALPHA,
-                    author: UserId::ALICE,
-                    content: "Hello".to_owned(),
-                },
-                SystemTime::UNIX_EPOCH,
-            ))
+            .insert_event(
+                &Event::with_timestamp(
+                    EventId(1),
+                    Message {
+                        id: MessageId::ALPHA,
+                        author: UserId::ALICE,
+                        content: "Hello".to_owned(),
+                        expires_at_ms: None,
+                    },
+                    SystemTime::UNIX_EPOCH,
+                ),
+                ContentEncoding::Plain,
+            )
             .await
             .unwrap();
 
         // When recording a different message under the same message id
         let outcome = persistence
-            .insert_event(&Event::with_timestamp(
-                EventId(2),
-                Message {
-                    id: MessageId::ALPHA,
-                    author: UserId::ALICE,
-                    content: "Goodbye".to_owned(),
-                },
-                SystemTime::UNIX_EPOCH,
-            ))
+            .insert_event(
+                &Event::with_timestamp(
+                    EventId(2),
+                    Message {
+                        id: MessageId::ALPHA,
+                        author: UserId::ALICE,
+                        content: "Goodbye".to_owned(),
+                        expires_at_ms: None,
+                    },
+                    SystemTime::UNIX_EPOCH,
+                ),
+                ContentEncoding::Plain,
+            )
             .await
             .unwrap();
 
@@ -340,6 +1525,140 @@ mod tests {
         assert!(matches!(outcome, InsertOutcome::Conflict));
     }
 
+    #[tokio::test]
+    async fn events_with_invalid_utf8_content_are_skipped() {
+        // Given three events, the second one with content which is not valid UTF-8
+        let client = ClientBuilder::new().open().await.unwrap();
+        client
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        for (id, message_id) in [
+            (EventId(1), MessageId::ALPHA),
+            (EventId(2), MessageId::BETA),
+            (EventId(3), MessageId::GAMMA),
+        ] {
+            client
+                .insert_event(&dummy_event(id, message_id), ContentEncoding::Plain)
+                .await
+                .unwrap();
+        }
+        client
+            .conn(|conn| conn.execute("UPDATE events SET content = x'C328' WHERE id = 2", ()))
+            .await
+            .unwrap();
+
+        // When reading the events
+        let (events, _) = client
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+
+        // Then the malformed one is skipped, the others are still read. A page ending with it still
+        // tells about the events beyond.
+        let ids: Vec<_> = events.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [EventId(1), EventId(3)]);
+        let (page, has_more) = client.events_page(EventId::before_all(), 2).await.unwrap();
+        assert_eq!(1, page.len());
+        assert!(has_more);
+    }
+
+    #[tokio::test]
+    async fn compressed_content_round_trips() {
+        // Given a long repetitive message, recorded with compression
+        let client = ClientBuilder::new().open().await.unwrap();
+        client
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let event = Event {
+            message: Message {
+                content: "All work and no play makes Jack a dull boy. ".repeat(1000),
+                ..Message::dummy()
+            },
+            ..dummy_event(EventId(1), MessageId::ALPHA)
+        };
+        client
+            .insert_event(&event, ContentEncoding::Zstd)
+            .await
+            .unwrap();
+
+        // When reading it back
+        let (events, _) = client
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+
+        // Then it is identical to the recorded message, yet has been stored compressed
+        assert_eq!(vec![event.clone()], events);
+        let stored_bytes: i64 = client
+            .conn(|conn| conn.query_row("SELECT length(content) FROM events", (), |row| row.get(0)))
+            .await
+            .unwrap();
+        assert!(stored_bytes < event.message.content.len() as i64 / 10);
+    }
+
+    #[tokio::test]
+    async fn retrying_compressed_message_is_a_duplicate() {
+        // Given a recorded message, stored compressed
+        let persistence = persistence_fake().await;
+        let message = Message {
+            content: "Hello ".repeat(100),
+            ..Message::dummy()
+        };
+        persistence
+            .insert_event(
+                &Event::with_timestamp(EventId(1), message.clone(), SystemTime::UNIX_EPOCH),
+                ContentEncoding::Zstd,
+            )
+            .await
+            .unwrap();
+
+        // When recording the same message again, without compression
+        let outcome = persistence
+            .insert_event(
+                &Event::with_timestamp(EventId(2), message, SystemTime::UNIX_EPOCH),
+                ContentEncoding::Plain,
+            )
+            .await
+            .unwrap();
+
+        // Then the decompressed content is compared and it is reported as a duplicate
+        assert!(matches!(outcome, InsertOutcome::Duplicate(_)));
+    }
+
+    #[tokio::test]
+    async fn insert_into_full_database_reports_full_storage() {
+        // Given a database which may not grow any further
+        let client = ClientBuilder::new().open().await.unwrap();
+        client
+            .conn(|conn| {
+                migrate_chat_persistence(conn, 0)?;
+                let page_count: i64 = conn.query_row("PRAGMA page_count", (), |row| row.get(0))?;
+                conn.query_row(&format!("PRAGMA max_page_count = {page_count}"), (), |_| {
+                    Ok(())
+                })
+            })
+            .await
+            .unwrap();
+
+        // When inserting an event with more content than fits into the remaining space
+        let event = Event {
+            message: Message {
+                content: "a".repeat(64 * 1024),
+                ..Message::dummy()
+            },
+            ..dummy_event(EventId(1), MessageId::ALPHA)
+        };
+        let outcome = client
+            .insert_event(&event, ContentEncoding::Plain)
+            .await
+            .unwrap();
+
+        // Then the storage is reported to be full
+        assert!(matches!(outcome, InsertOutcome::StorageFull));
+    }
+
     fn dummy_event(id: EventId, message_id: MessageId) -> Event {
         Event::with_timestamp(
             id,
@@ -351,6 +1670,18 @@ mod tests {
         )
     }
 
+    fn authored_event(id: EventId, message_id: MessageId, author: UserId) -> Event {
+        Event::with_timestamp(
+            id,
+            Message {
+                id: message_id,
+                author,
+                ..Message::dummy()
+            },
+            SystemTime::UNIX_EPOCH,
+        )
+    }
+
     async fn persistence_fake() -> impl ChatPersistence {
         let client = ClientBuilder::new().open().await.unwrap();
         client
@@ -1,19 +1,45 @@
-use std::pin::pin;
+use std::{
+    fmt,
+    num::NonZeroUsize,
+    pin::pin,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use async_stream::try_stream;
+use async_stream::{stream, try_stream};
 use futures_util::{Stream, future::Either};
 use tokio::{
-    sync::{broadcast, mpsc, oneshot},
+    select,
+    sync::{Semaphore, broadcast, broadcast::error::RecvError, mpsc, oneshot},
     task::JoinHandle,
+    time::{Instant, Interval, MissedTickBehavior, interval_at},
 };
 use tokio_stream::{StreamExt, wrappers::BroadcastStream};
+use tracing::warn;
+
+use crate::{task::spawn_named, user::UserId};
 
 use super::{
-    chat_store::{ChatError, ChatStore},
-    event::{Event, EventId},
-    message::Message,
+    chat_store::{AddOutcome, ChatError, ChatStore},
+    event::{Event, EventId, Tombstone},
+    message::{Message, MessageId},
 };
 
+/// Maximum number of historic events read at once for an events stream. Bounds the memory a single
+/// client catching up with a long history can claim.
+const HISTORY_PAGE_SIZE: usize = 1000;
+
+/// Maximum number of pending messages recorded before their events are broadcast together.
+const MAX_WRITE_BATCH_SIZE: usize = 100;
+
+/// Number of batches retained for subscribers of the live events. Subscribers falling further
+/// behind lag and must recover from the history.
+const BROADCAST_CAPACITY: usize = 10;
+
+/// Events broadcast together, in the order of their ids. Shared, so subscribers do not need to clone
+/// the events of a batch.
+type Batch = Arc<[Event]>;
+
 /// A shared chat. Allows multiple clients to communicate with each other by writing and reading
 /// messages to the same chat.
 #[cfg_attr(test, double_trait::dummies)]
@@ -29,51 +55,202 @@ pub trait Chat: Sized {
     ///   events will always be delivered.
     fn events(self, last_event_id: EventId) -> impl Stream<Item = anyhow::Result<Event>> + Send;
 
+    /// Up to `limit` events recorded so far with an id greater than `last_event_id`. In contrast to
+    /// [`Self::events`] this does not wait for future events. The flag is `true` if there are more
+    /// events beyond the returned ones.
+    fn history(
+        self,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> impl Future<Output = anyhow::Result<(Vec<Event>, bool)>> + Send;
+
+    /// Up to `limit` events recorded between `from_ms` and `to_ms` (inclusive, milliseconds since
+    /// Unix epoch). The window filters on the timestamps of the events, yet they are ordered by
+    /// id. Timestamps are taken from the system clock, so they are not guaranteed to increase with
+    /// the ids.
+    fn events_in_window(
+        &self,
+        from_ms: u64,
+        to_ms: u64,
+        limit: usize,
+    ) -> impl Future<Output = anyhow::Result<Vec<Event>>> + Send;
+
+    /// The id of the last event recorded before `timestamp_ms` (milliseconds since Unix epoch).
+    /// Passing it as `last_event_id` resumes with the first event recorded at or after
+    /// `timestamp_ms`. Timestamps are taken from the system clock, which may jump, so this is only
+    /// approximate.
+    fn last_event_before(
+        &self,
+        timestamp_ms: u64,
+    ) -> impl Future<Output = anyhow::Result<EventId>> + Send;
+
+    /// Number of events recorded so far with an id greater than `last_event_id`, i.e. the number of
+    /// historic events [`Self::events`] replays before delivering new ones.
+    fn count_events_since(
+        &self,
+        last_event_id: EventId,
+    ) -> impl Future<Output = anyhow::Result<u64>> + Send;
+
+    /// Id of the latest event recorded so far. A client which has received a later one, has seen a
+    /// history which has since been lost, e.g. because the server has been restarted with an
+    /// in-memory database.
+    fn latest_event_id(&self) -> impl Future<Output = anyhow::Result<EventId>> + Send;
+
+    /// Id of the last event deleted, because only the most recent events are retained. Unlike
+    /// tombstoned events, the client has missed something, if it has not seen these before.
+    fn pruned_up_to(&self) -> impl Future<Output = anyhow::Result<EventId>> + Send;
+
     /// Add a new message to the chat.
     fn add_message(
         &mut self,
         message: Message,
-    ) -> impl Future<Output = Result<(), ChatError>> + Send;
+    ) -> impl Future<Output = Result<AddOutcome, ChatError>> + Send;
+
+    /// Pins or unpins the message with `message_id`. Returns the id of the event the message has
+    /// been recorded as. Fails with [`ChatError::NotFound`] if there is no such message.
+    fn pin_message(
+        &mut self,
+        message_id: MessageId,
+        pinned: bool,
+    ) -> impl Future<Output = Result<EventId, ChatError>> + Send;
+
+    /// All currently pinned events, ordered by id.
+    fn pinned(&self) -> impl Future<Output = anyhow::Result<Vec<Event>>> + Send;
+
+    /// Up to `limit` events of messages sent by `sender` since the event with the given
+    /// `last_event_id` (exclusive), ordered by id. Allows to page through all of them, without
+    /// holding them in memory at once.
+    fn messages_by_sender(
+        &self,
+        sender: UserId,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> impl Future<Output = anyhow::Result<Vec<Event>>> + Send;
+
+    /// Deletes all messages sent by `sender` at once, leaving tombstones in their place. Returns
+    /// the tombstones, ordered by id. Their events are no longer part of the history.
+    fn delete_messages_by_sender(
+        &mut self,
+        sender: UserId,
+    ) -> impl Future<Output = Result<Vec<Tombstone>, ChatError>> + Send;
+
+    /// Yields the tombstones of messages as they expire, if the runtime has a TTL for them. Ends
+    /// once the chat has been shut down. Tombstones of messages which expire while the stream is
+    /// not polled fast enough may be skipped.
+    fn expired_messages(self) -> impl Stream<Item = Vec<Tombstone>> + Send;
+
+    /// Yields the original events of duplicates as they are added again, if the runtime echoes
+    /// duplicates. Events streams never forward an event twice, so this is how clients relying on
+    /// the echo of their messages learn that a retried message has been recorded. Ends once the
+    /// chat has been shut down. Echoes of duplicates added while the stream is not polled fast
+    /// enough may be skipped.
+    fn echoed_duplicates(self) -> impl Stream<Item = Vec<Event>> + Send;
+}
+
+/// What an events stream does, if its client falls so far behind, that live events it has not yet
+/// received are dropped.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnLag {
+    /// Silently catch up by reading the missed events from the history.
+    #[default]
+    Resume,
+    /// End the stream with a [`Lagged`] error. Clients may reconnect with the last event id they
+    /// received.
+    Error,
+}
+
+/// The client of an events stream fell behind the live events. See [`OnLag::Error`].
+#[derive(Debug)]
+pub struct Lagged;
+
+impl fmt::Display for Lagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Client fell behind the live events of the chat")
+    }
 }
 
+impl std::error::Error for Lagged {}
+
+/// Options of the chat runtime, which must be known before its actor is started.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct ActorOptions {
+    /// Reads at most this many from the chat store at once. Further reads wait for one of them to
+    /// finish. `None` does not limit reads.
+    pub max_concurrent_reads: Option<NonZeroUsize>,
+    /// Announce the event a duplicate has originally been recorded as once more, for clients
+    /// relying on the echo of their messages to confirm them. See [`Chat::echoed_duplicates`].
+    pub echo_duplicates: bool,
+    /// Messages without an expiry of their own are tombstoned once they are older. Pinned messages
+    /// do not expire. `None` keeps them until they are deleted otherwise.
+    pub message_ttl: Option<Duration>,
+    /// How often to look for expired messages. `None` uses [`DEFAULT_EXPIRY_SWEEP_INTERVAL`].
+    pub expiry_sweep_interval: Option<Duration>,
+}
+
+/// Interval between looking for expired messages, if no other one is configured.
+const DEFAULT_EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Can be used to create multiple instances of [`ChatClient`] which provide an API to interact with
 /// a shared chat. The runtime takes care that messages are forwarded between different clients.
 pub struct ChatRuntime {
     sender: mpsc::Sender<ActorMsg>,
     join_handle: JoinHandle<()>,
+    on_lag: OnLag,
 }
 
 impl ChatRuntime {
-    /// Construct runtime with any chat store.
+    /// Construct runtime with any chat store and default options.
+    #[cfg(test)]
+    pub(super) fn with_chat_store(history: impl ChatStore + Send + Sync + 'static) -> Self {
+        Self::with_actor_options(history, ActorOptions::default())
+    }
+
+    /// Construct runtime with any chat store, with options fixed for the lifetime of the actor.
     ///
     /// This flexibility makes it well testable and enforces the implementation of runtime aspects
     /// to be independent of `ChatStore`'s implemenation. The visibility is super since the decision
     /// which `ChatStore` to use in production, belongs to the `chat` parent module.
-    pub(super) fn with_chat_store(history: impl ChatStore + Send + 'static) -> Self {
+    pub(super) fn with_actor_options(
+        history: impl ChatStore + Send + Sync + 'static,
+        options: ActorOptions,
+    ) -> Self {
         let (sender, receiver) = mpsc::channel(5);
-        let actor = Actor::new(history, receiver);
-        let join_handle = tokio::spawn(async move { actor.run().await });
+        let actor = Actor::new(history, receiver, options);
+        let join_handle = spawn_named("chat", async move { actor.run().await });
         ChatRuntime {
             sender,
             join_handle,
+            on_lag: OnLag::default(),
         }
     }
 
+    /// How events streams of clients created afterwards deal with falling behind.
+    pub fn with_on_lag(mut self, on_lag: OnLag) -> Self {
+        self.on_lag = on_lag;
+        self
+    }
+
     /// A client which implements the [`SharedChat`] trait.
     pub fn client(&self) -> ChatClient {
         ChatClient {
             sender: self.sender.clone(),
+            on_lag: self.on_lag,
         }
     }
 
     /// Shuts down the chat runtime. In order for this to complete, all clients must have been
-    /// dropped.
+    /// dropped. Open event streams do not need to be dropped. They end once the shutdown
+    /// completes.
     pub async fn shutdown(self) {
         // At this point we should be the only owner of the sender, since all clients should have
         // been dropped. This might be unecessary restrictive if we want to shutdown things in
         // parallel. Right now however the invariant holds. The panic might save us some time if we
         // forget to clean up all senders in a test.
-        debug_assert_eq!(self.sender.strong_count(), 1);
+        debug_assert_eq!(
+            self.sender.strong_count(),
+            1,
+            "All clients of the chat must be dropped before shutting it down"
+        );
         // We drop the sender, to signal to the actor thread that it can no longer receive messages
         // and should stop.
         drop(self.sender);
@@ -84,6 +261,7 @@ impl ChatRuntime {
 #[derive(Clone)]
 pub struct ChatClient {
     sender: mpsc::Sender<ActorMsg>,
+    on_lag: OnLag,
 }
 
 impl Chat for ChatClient {
@@ -91,16 +269,30 @@ impl Chat for ChatClient {
         self,
         mut last_event_id: EventId,
     ) -> impl Stream<Item = anyhow::Result<Event>> + Send {
+        // Open streams must not keep the actor alive, so they do not block a shutdown. Instead the
+        // stream ends once the runtime has been shut down.
+        let actor = self.sender.downgrade();
+        let on_lag = self.on_lag;
         try_stream! {
             loop {
-                let (responder, response) = oneshot::channel();
-                self.sender
-                    .send(ActorMsg::ReadEvents{ responder, last_event_id})
-                    .await
-                    .expect("Actor must outlive client.");
-                let events = response.await.unwrap()?.into_stream();
+                let Some(sender) = actor.upgrade() else {
+                    break;
+                };
+                let response = request(&sender, |responder| ActorMsg::ReadEvents {
+                    responder,
+                    last_event_id,
+                })
+                .await;
+                drop(sender);
+                let events = response??.into_stream(on_lag);
                 let mut events = pin!(events);
                 while let Some(event) = events.next().await {
+                    let event = event?;
+                    // A live event may already have been part of the history we have read, if it
+                    // has been recorded while we have been reading.
+                    if event.id <= last_event_id {
+                        continue;
+                    }
                     last_event_id = event.id;
                     yield event;
                 }
@@ -108,13 +300,174 @@ impl Chat for ChatClient {
         }
     }
 
-    async fn add_message(&mut self, message: Message) -> Result<(), ChatError> {
+    async fn history(
+        self,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> anyhow::Result<(Vec<Event>, bool)> {
+        let response = request(&self.sender, |responder| ActorMsg::ReadHistory {
+            responder,
+            last_event_id,
+            limit,
+        });
+        response.await?
+    }
+
+    async fn events_in_window(
+        &self,
+        from_ms: u64,
+        to_ms: u64,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Event>> {
+        let response = request(&self.sender, |responder| ActorMsg::ReadEventsInWindow {
+            responder,
+            from_ms,
+            to_ms,
+            limit,
+        });
+        response.await?
+    }
+
+    async fn last_event_before(&self, timestamp_ms: u64) -> anyhow::Result<EventId> {
+        let response = request(&self.sender, |responder| ActorMsg::ReadLastEventBefore {
+            responder,
+            timestamp_ms,
+        });
+        response.await?
+    }
+
+    async fn count_events_since(&self, last_event_id: EventId) -> anyhow::Result<u64> {
+        let response = request(&self.sender, |responder| ActorMsg::CountEventsSince {
+            responder,
+            last_event_id,
+        });
+        response.await?
+    }
+
+    async fn latest_event_id(&self) -> anyhow::Result<EventId> {
+        let response = request(&self.sender, |responder| ActorMsg::ReadLatestEventId {
+            responder,
+        });
+        Ok(response.await?)
+    }
+
+    async fn pruned_up_to(&self) -> anyhow::Result<EventId> {
+        let response = request(&self.sender, |responder| ActorMsg::ReadPrunedUpTo {
+            responder,
+        });
+        response.await?
+    }
+
+    async fn add_message(&mut self, message: Message) -> Result<AddOutcome, ChatError> {
+        request(&self.sender, |responder| ActorMsg::AddMessage {
+            message,
+            responder,
+        })
+        .await?
+    }
+
+    async fn pin_message(
+        &mut self,
+        message_id: MessageId,
+        pinned: bool,
+    ) -> Result<EventId, ChatError> {
+        let response = request(&self.sender, |responder| ActorMsg::PinMessage {
+            responder,
+            message_id,
+            pinned,
+        });
+        response.await?
+    }
+
+    async fn pinned(&self) -> anyhow::Result<Vec<Event>> {
+        let response = request(&self.sender, |responder| ActorMsg::ReadPinned { responder });
+        response.await?
+    }
+
+    async fn messages_by_sender(
+        &self,
+        sender: UserId,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Event>> {
+        let response = request(&self.sender, |responder| ActorMsg::ReadBySender {
+            responder,
+            sender,
+            last_event_id,
+            limit,
+        });
+        response.await?
+    }
+
+    async fn delete_messages_by_sender(
+        &mut self,
+        sender: UserId,
+    ) -> Result<Vec<Tombstone>, ChatError> {
+        let response = request(&self.sender, |responder| ActorMsg::TombstoneSender {
+            responder,
+            sender,
+        });
+        response.await?
+    }
+
+    fn expired_messages(self) -> impl Stream<Item = Vec<Tombstone>> + Send {
+        // The messages are gone either way. Clients reading the history afterwards no longer see
+        // them, so lagging behind is fine.
+        subscription(self.sender.downgrade(), |responder| {
+            ActorMsg::SubscribeExpired { responder }
+        })
+    }
+
+    fn echoed_duplicates(self) -> impl Stream<Item = Vec<Event>> + Send {
+        // Clients retry messages which have not been confirmed, so missed echoes are not lost for
+        // good.
+        subscription(self.sender.downgrade(), |responder| {
+            ActorMsg::SubscribeEchoes { responder }
+        })
+    }
+}
+
+/// Sends the message built by `build` to the actor and waits for its response. A client may outlive
+/// the actor, e.g. if a request is still being handled while the server shuts down. Rather than
+/// panicking, this fails with [`ChatError::ShuttingDown`].
+async fn request<T>(
+    actor: &mpsc::Sender<ActorMsg>,
+    build: impl FnOnce(oneshot::Sender<T>) -> ActorMsg,
+) -> Result<T, ChatError> {
+    let (responder, response) = oneshot::channel();
+    actor
+        .send(build(responder))
+        .await
+        .map_err(|_| ChatError::ShuttingDown)?;
+    response.await.map_err(|_| ChatError::ShuttingDown)
+}
+
+/// Yields the items of a broadcast of the actor, subscribed to with the message built by
+/// `subscribe`. Like events streams, the stream must not keep the actor alive. Ends once the actor
+/// is gone. Items missed by lagging behind are skipped.
+fn subscription<T: Clone + Send + 'static>(
+    actor: mpsc::WeakSender<ActorMsg>,
+    subscribe: impl FnOnce(oneshot::Sender<broadcast::Receiver<T>>) -> ActorMsg + Send + 'static,
+) -> impl Stream<Item = T> + Send {
+    stream! {
+        let Some(sender) = actor.upgrade() else {
+            return;
+        };
         let (responder, response) = oneshot::channel();
-        self.sender
-            .send(ActorMsg::AddMessage { message, responder })
-            .await
-            .expect("Actor must outlive client.");
-        response.await.unwrap()
+        if sender.send(subscribe(responder)).await.is_err() {
+            return;
+        }
+        drop(sender);
+        let Ok(mut receiver) = response.await else {
+            return;
+        };
+        loop {
+            match receiver.recv().await {
+                Ok(item) => yield item,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
     }
 }
 
@@ -123,60 +476,213 @@ enum ActorMsg {
         responder: oneshot::Sender<anyhow::Result<Events>>,
         last_event_id: EventId,
     },
+    ReadHistory {
+        responder: oneshot::Sender<anyhow::Result<(Vec<Event>, bool)>>,
+        last_event_id: EventId,
+        limit: usize,
+    },
+    ReadEventsInWindow {
+        responder: oneshot::Sender<anyhow::Result<Vec<Event>>>,
+        from_ms: u64,
+        to_ms: u64,
+        limit: usize,
+    },
+    ReadLastEventBefore {
+        responder: oneshot::Sender<anyhow::Result<EventId>>,
+        timestamp_ms: u64,
+    },
+    CountEventsSince {
+        responder: oneshot::Sender<anyhow::Result<u64>>,
+        last_event_id: EventId,
+    },
+    ReadLatestEventId {
+        responder: oneshot::Sender<EventId>,
+    },
+    ReadPrunedUpTo {
+        responder: oneshot::Sender<anyhow::Result<EventId>>,
+    },
     AddMessage {
         message: Message,
-        responder: oneshot::Sender<Result<(), ChatError>>,
+        responder: oneshot::Sender<Result<AddOutcome, ChatError>>,
+    },
+    PinMessage {
+        responder: oneshot::Sender<Result<EventId, ChatError>>,
+        message_id: MessageId,
+        pinned: bool,
+    },
+    ReadPinned {
+        responder: oneshot::Sender<anyhow::Result<Vec<Event>>>,
+    },
+    ReadBySender {
+        responder: oneshot::Sender<anyhow::Result<Vec<Event>>>,
+        sender: UserId,
+        last_event_id: EventId,
+        limit: usize,
+    },
+    TombstoneSender {
+        responder: oneshot::Sender<Result<Vec<Tombstone>, ChatError>>,
+        sender: UserId,
+    },
+    SubscribeExpired {
+        responder: oneshot::Sender<broadcast::Receiver<Vec<Tombstone>>>,
+    },
+    SubscribeEchoes {
+        responder: oneshot::Sender<broadcast::Receiver<Vec<Event>>>,
     },
 }
 
 /// Transports a set of events from the actor to the client.
 enum Events {
     History(Vec<Event>),
-    Current(broadcast::Receiver<Event>),
+    Current(broadcast::Receiver<Batch>),
+    /// The last page of the history, followed by live events.
+    HistoryThenCurrent(Vec<Event>, broadcast::Receiver<Batch>),
 }
 
 impl Events {
-    pub fn into_stream(self) -> impl Stream<Item = Event> + Send {
+    /// The stream ends after yielding an error.
+    pub fn into_stream(self, on_lag: OnLag) -> impl Stream<Item = Result<Event, Lagged>> + Send {
         match self {
             Events::History(history) => Either::Left(Self::history_stream(history)),
-            Events::Current(current) => Either::Right(Self::live_stream(current)),
+            Events::Current(current) => {
+                Either::Right(Either::Left(Self::live_stream(current, on_lag)))
+            }
+            Events::HistoryThenCurrent(history, current) => Either::Right(Either::Right(
+                Self::history_stream(history).chain(Self::live_stream(current, on_lag)),
+            )),
         }
     }
 
-    fn history_stream(history: Vec<Event>) -> impl Stream<Item = Event> + Send {
-        tokio_stream::iter(history)
+    fn history_stream(history: Vec<Event>) -> impl Stream<Item = Result<Event, Lagged>> + Send {
+        tokio_stream::iter(history).map(Ok)
     }
 
-    fn live_stream(current: broadcast::Receiver<Event>) -> impl Stream<Item = Event> + Send {
-        BroadcastStream::new(current)
-            // In case of a Slow Receiver, i.e. Receiver is lagging and messages have been dropped.
-            // Stopping the live stream allows us to recover from history.
-            .map_while(Result::ok)
+    fn live_stream(
+        current: broadcast::Receiver<Batch>,
+        on_lag: OnLag,
+    ) -> impl Stream<Item = Result<Event, Lagged>> + Send {
+        let mut lagged = false;
+        let batches = BroadcastStream::new(current).map_while(move |result| {
+            match (result, on_lag) {
+                _ if lagged => None,
+                (Ok(batch), _) => Some(Ok(batch)),
+                // In case of a Slow Receiver, i.e. Receiver is lagging and messages have been
+                // dropped. Stopping the live stream allows us to recover from history.
+                (Err(_), OnLag::Resume) => None,
+                (Err(_), OnLag::Error) => {
+                    lagged = true;
+                    Some(Err(Lagged))
+                }
+            }
+        });
+        futures_util::StreamExt::flat_map(batches, |batch| match batch {
+            Ok(batch) => Either::Left(tokio_stream::iter(batch.to_vec()).map(Ok)),
+            Err(lagged) => Either::Right(tokio_stream::once(Err(lagged))),
+        })
     }
 }
 
 struct Actor<H> {
     /// The chat's persistent state.
-    history: H,
+    history: Arc<H>,
     /// Used to broadcast new events to clients who have caught up with the chat.
-    current: broadcast::Sender<Event>,
+    current: broadcast::Sender<Batch>,
     receiver: mpsc::Receiver<ActorMsg>,
+    /// Messages are recorded by a dedicated task, so a slow write does not delay reads.
+    writer: mpsc::Sender<WriteMsg>,
+    writer_handle: JoinHandle<()>,
+    /// `None` if reads are not limited.
+    read_limit: Option<ReadLimit>,
+    /// Used to announce the tombstones of expired messages.
+    expired: broadcast::Sender<Vec<Tombstone>>,
+    /// Used to announce the original events of duplicates. Nothing is ever sent, unless duplicates
+    /// are echoed.
+    echoes: broadcast::Sender<Vec<Event>>,
+    /// Messages may carry an expiry of their own, so we always look for expired ones.
+    expiry: ExpirySweep,
 }
 
-impl<H: ChatStore> Actor<H> {
-    pub fn new(history: H, receiver: mpsc::Receiver<ActorMsg>) -> Self {
-        let (current, _) = broadcast::channel(10);
+impl<H: ChatStore + Send + Sync + 'static> Actor<H> {
+    pub fn new(history: H, receiver: mpsc::Receiver<ActorMsg>, options: ActorOptions) -> Self {
+        let history = Arc::new(history);
+        let (current, _) = broadcast::channel(BROADCAST_CAPACITY);
+        let (writer, writer_receiver) = mpsc::channel(5);
+        let (echoes, _) = broadcast::channel(BROADCAST_CAPACITY);
+        let writer_task = Writer {
+            history: history.clone(),
+            current: current.clone(),
+            receiver: writer_receiver,
+            echoes: options.echo_duplicates.then(|| echoes.clone()),
+            pressure: BroadcastPressure::new(BROADCAST_CAPACITY),
+        };
+        let read_limit = options.max_concurrent_reads.map(ReadLimit::new);
+        let writer_handle = spawn_named("chat writer", writer_task.run());
+        let (expired, _) = broadcast::channel(BROADCAST_CAPACITY);
+        let interval = options
+            .expiry_sweep_interval
+            .unwrap_or(DEFAULT_EXPIRY_SWEEP_INTERVAL);
+        let expiry = ExpirySweep::new(options.message_ttl, interval);
         Actor {
             receiver,
             history,
             current,
+            writer,
+            writer_handle,
+            read_limit,
+            expired,
+            echoes,
+            expiry,
         }
     }
 
+    /// Spawns a task for `read`. If reads are limited, the task waits for one of the concurrent
+    /// reads to become available first. Only the task waits, so the actor keeps handing messages to
+    /// the writer in the meantime.
+    fn spawn_read(&mut self, name: &str, read: impl Future<Output = ()> + Send + 'static) {
+        let Some(read_limit) = &mut self.read_limit else {
+            spawn_named(name, read);
+            return;
+        };
+        let permits = read_limit.acquire();
+        spawn_named(name, async move {
+            let _permit = permits
+                .acquire_owned()
+                .await
+                .expect("Permits of reads must never be closed");
+            read.await;
+        });
+    }
+
     pub async fn run(mut self) {
-        while let Some(msg) = self.receiver.recv().await {
-            self.handle_message(msg).await;
+        loop {
+            select! {
+                msg = self.receiver.recv() => match msg {
+                    Some(msg) => self.handle_message(msg).await,
+                    None => break,
+                },
+                deadline = self.expiry.next() => self.tombstone_expired(deadline),
+            }
         }
+        // Let the writer finish recording the messages it already accepted.
+        drop(self.writer);
+        self.writer_handle.await.unwrap();
+    }
+
+    /// Tombstones the messages expired by `deadline` and announces them. Like tombstoning the
+    /// messages of a sender, this need not be ordered with the writes.
+    fn tombstone_expired(&self, deadline: ExpiryDeadline) {
+        let history = self.history.clone();
+        let expired = self.expired.clone();
+        spawn_named("chat tombstone expired", async move {
+            if let Ok(tombstones) = history
+                .tombstone_expired(deadline.now_ms, deadline.sent_before_ms)
+                .await
+                && !tombstones.is_empty()
+            {
+                // Only fails if nobody is interested in expired messages, which is fine.
+                let _ = expired.send(tombstones);
+            }
+        });
     }
 
     pub async fn handle_message(&mut self, msg: ActorMsg) {
@@ -185,35 +691,342 @@ impl<H: ChatStore> Actor<H> {
                 responder,
                 last_event_id,
             } => {
-                let events = match self.history.events_since(last_event_id).await {
-                    Ok(history) if history.is_empty() => {
-                        let current_receiver = self.current.subscribe();
-                        Ok(Events::Current(current_receiver))
-                    }
-                    Ok(history) => Ok(Events::History(history)),
-                    Err(err) => Err(err),
-                };
-                // We ignore send errors, since it only happens if the receiver has been dropped. In
-                // that case the receiver is no longer interested in the response, anyway.
-                let _ = responder.send(events);
+                // We subscribe before reading the history. Otherwise an event recorded after
+                // reading, but before subscribing, would be missed.
+                let current_receiver = self.current.subscribe();
+                let history = self.history.clone();
+                self.spawn_read("chat read events", async move {
+                    let events = match history.events_page(last_event_id, HISTORY_PAGE_SIZE).await {
+                        Ok((page, _)) if page.is_empty() => Ok(Events::Current(current_receiver)),
+                        // The client comes back for the next page
+                        Ok((page, true)) => Ok(Events::History(page)),
+                        // This is the last page, so the client can continue with live events
+                        // right away.
+                        Ok((page, false)) => Ok(Events::HistoryThenCurrent(page, current_receiver)),
+                        Err(err) => Err(err),
+                    };
+                    // We ignore send errors, since it only happens if the receiver has been
+                    // dropped. In that case the receiver is no longer interested in the response,
+                    // anyway.
+                    let _ = responder.send(events);
+                });
+            }
+            ActorMsg::ReadHistory {
+                responder,
+                last_event_id,
+                limit,
+            } => {
+                let history = self.history.clone();
+                self.spawn_read("chat read history", async move {
+                    let history = history.events_page(last_event_id, limit).await;
+                    let _ = responder.send(history);
+                });
+            }
+            ActorMsg::ReadEventsInWindow {
+                responder,
+                from_ms,
+                to_ms,
+                limit,
+            } => {
+                let history = self.history.clone();
+                self.spawn_read("chat read events in window", async move {
+                    let events = history.events_in_window(from_ms, to_ms, limit).await;
+                    let _ = responder.send(events);
+                });
+            }
+            ActorMsg::ReadLastEventBefore {
+                responder,
+                timestamp_ms,
+            } => {
+                let history = self.history.clone();
+                self.spawn_read("chat read last event before", async move {
+                    let last_event_id = history.last_event_before(timestamp_ms).await;
+                    let _ = responder.send(last_event_id);
+                });
+            }
+            ActorMsg::CountEventsSince {
+                responder,
+                last_event_id,
+            } => {
+                let history = self.history.clone();
+                self.spawn_read("chat count events since", async move {
+                    let count = history.count_events_since(last_event_id).await;
+                    let _ = responder.send(count);
+                });
+            }
+            ActorMsg::ReadPrunedUpTo { responder } => {
+                let history = self.history.clone();
+                self.spawn_read("chat read pruned up to", async move {
+                    let _ = responder.send(history.pruned_up_to().await);
+                });
+            }
+            ActorMsg::ReadLatestEventId { responder } => {
+                let history = self.history.clone();
+                spawn_named("chat read latest event id", async move {
+                    let _ = responder.send(history.latest_event_id().await);
+                });
             }
             ActorMsg::AddMessage { message, responder } => {
-                let result = match self.history.record_message(message).await {
-                    // New message — broadcast to listening clients. Only fails if there are no
-                    // active receivers, which is fine.
-                    Ok(Some(event)) => {
-                        let _ = self.current.send(event);
-                        Ok(())
+                self.writer
+                    .send(WriteMsg { message, responder })
+                    .await
+                    .expect("Writer must outlive actor.");
+            }
+            ActorMsg::PinMessage {
+                responder,
+                message_id,
+                pinned,
+            } => {
+                // Pinning does not create a new event, so it need not be ordered with the writes.
+                let history = self.history.clone();
+                spawn_named("chat pin message", async move {
+                    let result = history.pin_message(message_id, pinned).await;
+                    let _ = responder.send(result);
+                });
+            }
+            ActorMsg::ReadPinned { responder } => {
+                let history = self.history.clone();
+                self.spawn_read("chat read pinned", async move {
+                    let pinned = history.pinned_events().await;
+                    let _ = responder.send(pinned);
+                });
+            }
+            ActorMsg::ReadBySender {
+                responder,
+                sender,
+                last_event_id,
+                limit,
+            } => {
+                let history = self.history.clone();
+                self.spawn_read("chat read by sender", async move {
+                    let events = history.events_by_sender(sender, last_event_id, limit).await;
+                    let _ = responder.send(events);
+                });
+            }
+            ActorMsg::TombstoneSender { responder, sender } => {
+                // Tombstoning does not create a new event, so it need not be ordered with the
+                // writes. The store keeps it consistent with messages recorded at the same time.
+                let history = self.history.clone();
+                spawn_named("chat tombstone sender", async move {
+                    let tombstones = history.tombstone_sender(sender).await;
+                    let _ = responder.send(tombstones);
+                });
+            }
+            ActorMsg::SubscribeExpired { responder } => {
+                let _ = responder.send(self.expired.subscribe());
+            }
+            ActorMsg::SubscribeEchoes { responder } => {
+                let _ = responder.send(self.echoes.subscribe());
+            }
+        }
+    }
+}
+
+/// Tells the actor when to look for expired messages, and which messages have expired by then.
+struct ExpirySweep {
+    /// Applies to messages without an expiry of their own. `None` if they do not expire.
+    ttl: Option<Duration>,
+    ticks: Interval,
+    /// Relates tokio's monotonic clock to the wall clock, which timestamps events. Fixed at
+    /// construction, so sweeps follow tokio's clock, even if it is paused in tests.
+    tokio_origin: Instant,
+    wall_origin: SystemTime,
+}
+
+impl ExpirySweep {
+    fn new(ttl: Option<Duration>, interval: Duration) -> Self {
+        let tokio_origin = Instant::now();
+        let mut ticks = interval_at(tokio_origin + interval, interval);
+        // A sweep catches up with every message expired in the meantime, so missed ones need not
+        // be made up for.
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ExpirySweep {
+            ttl,
+            ticks,
+            tokio_origin,
+            wall_origin: SystemTime::now(),
+        }
+    }
+
+    /// Completes once it is time for the next sweep, telling which messages have expired by then.
+    async fn next(&mut self) -> ExpiryDeadline {
+        let now = self.ticks.tick().await;
+        let now = self.wall_origin + now.duration_since(self.tokio_origin);
+        let sent_before = self
+            .ttl
+            .map(|ttl| now.checked_sub(ttl).unwrap_or(UNIX_EPOCH));
+        ExpiryDeadline {
+            now_ms: unix_ms(now),
+            sent_before_ms: sent_before.map(unix_ms),
+        }
+    }
+}
+
+/// Messages which have expired as of a sweep. Timestamps are milliseconds since Unix epoch.
+#[derive(Clone, Copy, Debug)]
+struct ExpiryDeadline {
+    /// Messages with an expiry of their own have expired, if it is not after this.
+    now_ms: u64,
+    /// Messages without an expiry of their own have expired, if recorded before this. `None` if
+    /// they do not expire.
+    sent_before_ms: Option<u64>,
+}
+
+/// Milliseconds since Unix epoch.
+fn unix_ms(time: SystemTime) -> u64 {
+    // u64 covers ~584 million years since epoch, so we can afford to downcast from u128.
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Bounds the number of reads from the chat store running at once, so a flood of clients can not
+/// pile up an unbounded number of queries in front of the database.
+struct ReadLimit {
+    max_concurrent_reads: NonZeroUsize,
+    permits: Arc<Semaphore>,
+    /// All permits have been taken, as the last read has been spawned. Warnings are only logged
+    /// as reads become saturated, not for every read waiting.
+    saturated: bool,
+}
+
+impl ReadLimit {
+    fn new(max_concurrent_reads: NonZeroUsize) -> Self {
+        ReadLimit {
+            max_concurrent_reads,
+            permits: Arc::new(Semaphore::new(max_concurrent_reads.get())),
+            saturated: false,
+        }
+    }
+
+    /// The permits a new read must acquire one of. Warns operators once reads start to wait, so
+    /// they can tell whether the limit is sized right.
+    fn acquire(&mut self) -> Arc<Semaphore> {
+        let saturated = self.permits.available_permits() == 0;
+        if saturated && !self.saturated {
+            warn!(
+                target: "chat",
+                max_concurrent_reads = self.max_concurrent_reads.get(),
+                "Reads are waiting for one of the concurrent reads to finish"
+            );
+        }
+        self.saturated = saturated;
+        self.permits.clone()
+    }
+}
+
+/// Tells operators if the buffer of the live events stays full. Each broadcast into a full buffer
+/// makes its slowest subscribers lag, and lagging subscribers fall back to reading the history.
+/// Occasional laggards are fine, but if the buffer stays full, the capacity is too small for the
+/// load and every client is about to take the expensive path.
+struct BroadcastPressure {
+    capacity: usize,
+    /// Consecutive broadcasts leaving the buffer full.
+    full_broadcasts: usize,
+    /// Warnings are only logged as the pressure becomes sustained, not for every broadcast.
+    warned: bool,
+}
+
+impl BroadcastPressure {
+    fn new(capacity: usize) -> Self {
+        BroadcastPressure {
+            capacity,
+            full_broadcasts: 0,
+            warned: false,
+        }
+    }
+
+    /// To be called after each broadcast with the number of batches retained by the channel. The
+    /// pressure is sustained once the buffer stayed full for as many broadcasts as it can hold,
+    /// i.e. its slowest subscribers missed the entire buffer.
+    fn observe(&mut self, retained: usize) {
+        if retained < self.capacity {
+            self.full_broadcasts = 0;
+            self.warned = false;
+            return;
+        }
+        self.full_broadcasts += 1;
+        if self.full_broadcasts >= self.capacity && !self.warned {
+            warn!(
+                target: "chat",
+                broadcast_capacity = self.capacity,
+                "Buffer of live events stays full. Subscribers are lagging and recover from history"
+            );
+            self.warned = true;
+        }
+    }
+}
+
+struct WriteMsg {
+    message: Message,
+    responder: oneshot::Sender<Result<AddOutcome, ChatError>>,
+}
+
+/// Records messages and broadcasts the resulting events. Messages are processed one after another,
+/// so events are broadcast in the order of their ids.
+///
+/// Messages which queued up while recording the previous ones, are recorded as one batch. Their
+/// events are broadcast together, so under heavy load subscribers wake up once per batch, rather
+/// than once per message. Waiting for more messages to arrive would add latency, so we never do.
+struct Writer<H> {
+    history: Arc<H>,
+    current: broadcast::Sender<Batch>,
+    receiver: mpsc::Receiver<WriteMsg>,
+    /// Used to announce the original events of duplicates. `None` if duplicates are not echoed.
+    echoes: Option<broadcast::Sender<Vec<Event>>>,
+    pressure: BroadcastPressure,
+}
+
+impl<H: ChatStore> Writer<H> {
+    async fn run(mut self) {
+        let mut pending = Vec::with_capacity(MAX_WRITE_BATCH_SIZE);
+        while self
+            .receiver
+            .recv_many(&mut pending, MAX_WRITE_BATCH_SIZE)
+            .await
+            != 0
+        {
+            let mut events = Vec::with_capacity(pending.len());
+            let mut echoes = Vec::new();
+            let mut responses = Vec::with_capacity(pending.len());
+            for WriteMsg { message, responder } in pending.drain(..) {
+                let result = self.history.record_message(message).await;
+                // New message — broadcast to listening clients. Duplicates are accepted, but there
+                // is nothing new to broadcast, unless they are echoed. Errors are forwarded to the
+                // client.
+                match &result {
+                    Ok(AddOutcome::New(event)) => events.push(event.clone()),
+                    Ok(AddOutcome::Duplicate(event)) if self.echoes.is_some() => {
+                        echoes.push(event.clone())
                     }
-                    // Duplicate — silently accepted, nothing to broadcast
-                    Ok(None) => Ok(()),
-                    // Conflict — forward error to the client
-                    Err(err) => Err(err),
-                };
+                    _ => (),
+                }
+                responses.push((responder, result));
+            }
+            if !events.is_empty() {
+                self.broadcast(events.into());
+            }
+            // Not part of the live events, since events streams never forward an event twice.
+            if let Some(sender) = &self.echoes
+                && !echoes.is_empty()
+            {
+                echoes.sort_by_key(|event| event.id);
+                // Only fails if nobody is interested in echoes, which is fine.
+                let _ = sender.send(echoes);
+            }
+            // Respond only after broadcasting, so clients which have been told their message has
+            // been added, can rely on it being part of the live stream.
+            for (responder, result) in responses {
                 let _ = responder.send(result);
             }
         }
     }
+
+    fn broadcast(&mut self, batch: Batch) {
+        // Only fails if there are no active receivers, which is fine.
+        let _ = self.current.send(batch);
+        self.pressure.observe(self.current.len());
+    }
 }
 
 #[cfg(test)]
@@ -221,16 +1034,178 @@ mod tests {
     use crate::chat::{event::EventId, message::MessageId};
 
     use super::*;
-    use crate::user::UserId;
+    use crate::{tracing::CapturedLogs, user::UserId};
     use anyhow::bail;
     use double_trait::Dummy;
-    use futures_util::{StreamExt, TryStreamExt};
+    use futures_util::{FutureExt, StreamExt, TryStreamExt};
     use std::{
         mem::take,
         sync::{Arc, Mutex},
         time::{Duration, SystemTime},
     };
-    use tokio::time::timeout;
+    use tokio::{sync::Notify, time::timeout};
+
+    #[tokio::test(start_paused = true)]
+    async fn messages_are_tombstoned_once_expired() {
+        // Given a chat with a message recorded just now, whose messages expire after 10s, looking
+        // for expired ones every second
+        struct OneMessage {
+            recorded_ms: u64,
+            tombstoned: Mutex<bool>,
+        }
+        impl ChatStore for OneMessage {
+            async fn tombstone_expired(
+                &self,
+                _now_ms: u64,
+                sent_before_ms: Option<u64>,
+            ) -> Result<Vec<Tombstone>, ChatError> {
+                let mut tombstoned = self.tombstoned.lock().unwrap();
+                if *tombstoned || Some(self.recorded_ms) >= sent_before_ms {
+                    return Ok(Vec::new());
+                }
+                *tombstoned = true;
+                Ok(vec![Tombstone {
+                    event_id: EventId(1),
+                    message_id: MessageId::ALPHA,
+                }])
+            }
+        }
+        let ttl = Duration::from_secs(10);
+        let options = ActorOptions {
+            message_ttl: Some(ttl),
+            expiry_sweep_interval: Some(Duration::from_secs(1)),
+            ..ActorOptions::default()
+        };
+        let start = tokio::time::Instant::now();
+        let recorded = SystemTime::now();
+        let history = OneMessage {
+            recorded_ms: recorded.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64,
+            tombstoned: Mutex::new(false),
+        };
+        let chat = ChatRuntime::with_actor_options(history, options);
+
+        // When waiting for messages to expire
+        let mut expired = pin!(chat.client().expired_messages());
+        let tombstones = expired.next().await.unwrap();
+
+        // Then the message is tombstoned, once its TTL has elapsed
+        assert!(start.elapsed() >= ttl);
+        assert_eq!(EventId(1), tombstones[0].event_id);
+
+        // Cleanup
+        chat.shutdown().await;
+        assert!(expired.next().await.is_none(), "Stream ends with the chat");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn messages_are_tombstoned_once_their_own_expiry_has_passed() {
+        // Given a chat without a chat wide TTL, with a message expiring 10s from now, looking for
+        // expired ones every second
+        struct EphemeralMessage {
+            expires_at_ms: u64,
+            tombstoned: Mutex<bool>,
+        }
+        impl ChatStore for EphemeralMessage {
+            async fn tombstone_expired(
+                &self,
+                now_ms: u64,
+                sent_before_ms: Option<u64>,
+            ) -> Result<Vec<Tombstone>, ChatError> {
+                assert_eq!(None, sent_before_ms);
+                let mut tombstoned = self.tombstoned.lock().unwrap();
+                if *tombstoned || now_ms < self.expires_at_ms {
+                    return Ok(Vec::new());
+                }
+                *tombstoned = true;
+                Ok(vec![Tombstone {
+                    event_id: EventId(1),
+                    message_id: MessageId::ALPHA,
+                }])
+            }
+        }
+        let ttl = Duration::from_secs(10);
+        let options = ActorOptions {
+            expiry_sweep_interval: Some(Duration::from_secs(1)),
+            ..ActorOptions::default()
+        };
+        let start = tokio::time::Instant::now();
+        let history = EphemeralMessage {
+            expires_at_ms: unix_ms(SystemTime::now() + ttl),
+            tombstoned: Mutex::new(false),
+        };
+        let chat = ChatRuntime::with_actor_options(history, options);
+
+        // When waiting for messages to expire
+        let mut expired = pin!(chat.client().expired_messages());
+        let tombstones = expired.next().await.unwrap();
+
+        // Then the message is tombstoned and announced, once its expiry has passed
+        assert!(start.elapsed() >= ttl);
+        assert_eq!(EventId(1), tombstones[0].event_id);
+
+        // Cleanup
+        chat.shutdown().await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_reads_are_limited() {
+        // Given a chat store taking 100ms for every read, tracking the reads running at once
+        #[derive(Default)]
+        struct SlowHistory {
+            running: Mutex<(usize, usize)>,
+        }
+        impl ChatStore for Arc<SlowHistory> {
+            async fn events_page(
+                &self,
+                _last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
+                {
+                    let mut running = self.running.lock().unwrap();
+                    running.0 += 1;
+                    running.1 = running.1.max(running.0);
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                self.running.lock().unwrap().0 -= 1;
+                let event = Event::with_timestamp(EventId(1), Message::dummy(), SystemTime::now());
+                Ok((vec![event], false))
+            }
+        }
+
+        // When eight clients start reading their events at once, without and with a limit of two
+        // concurrent reads
+        async fn read_concurrently(
+            max_concurrent_reads: Option<NonZeroUsize>,
+        ) -> (Duration, usize) {
+            let history = Arc::new(SlowHistory::default());
+            let options = ActorOptions {
+                max_concurrent_reads,
+                ..ActorOptions::default()
+            };
+            let chat = ChatRuntime::with_actor_options(history.clone(), options);
+            let start = tokio::time::Instant::now();
+            let reads = (0..8).map(|_| {
+                let client = chat.client();
+                async move {
+                    let mut events = pin!(client.events(EventId::before_all()));
+                    events.next().await.unwrap().unwrap();
+                }
+            });
+            futures_util::future::join_all(reads).await;
+            let elapsed = start.elapsed();
+            chat.shutdown().await;
+            let max_running = history.running.lock().unwrap().1;
+            (elapsed, max_running)
+        }
+        let (unlimited, unlimited_running) = read_concurrently(None).await;
+        let (limited, limited_running) = read_concurrently(NonZeroUsize::new(2)).await;
+
+        // Then without a limit all reads run at once, with it at most two do, taking four rounds
+        assert_eq!(8, unlimited_running);
+        assert!(unlimited < Duration::from_millis(200), "{unlimited:?}");
+        assert_eq!(2, limited_running);
+        assert!(limited >= Duration::from_millis(400), "{limited:?}");
+    }
 
     #[tokio::test]
     async fn events_forwards_history() {
@@ -242,6 +1217,7 @@ mod tests {
                     id: "019c0ab6-9d11-75ef-ab02-60f070b1582a".parse().unwrap(),
                     author: UserId::ALICE,
                     content: "One".to_string(),
+                    expires_at_ms: None,
                 },
                 SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000),
             ),
@@ -251,14 +1227,19 @@ mod tests {
                     id: "019c0ab6-9d11-7a5b-abde-cb349e5fd995".parse().unwrap(),
                     author: UserId::BOB,
                     content: "Two".to_string(),
+                    expires_at_ms: None,
                 },
                 SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_001),
             ),
         ];
         struct HistoryStub(Vec<Event>);
         impl ChatStore for HistoryStub {
-            async fn events_since(&self, _last_event_id: EventId) -> anyhow::Result<Vec<Event>> {
-                Ok(self.0.clone())
+            async fn events_page(
+                &self,
+                _last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
+                Ok((self.0.clone(), false))
             }
         }
         let chat = ChatRuntime::with_chat_store(HistoryStub(canned.clone()));
@@ -279,6 +1260,100 @@ mod tests {
         chat.shutdown().await;
     }
 
+    #[tokio::test]
+    async fn history_forwards_events_page() {
+        // Given
+        let history = HistorySpy::new();
+        let spy = history.clone();
+        let chat = ChatRuntime::with_chat_store(history);
+
+        // When requesting the history since event 42
+        let (events, has_more) = chat.client().history(EventId(42), 10).await.unwrap();
+
+        // Then the history is queried with the same id and the result is forwarded
+        assert_eq!(spy.take_observed_last_event_ids(), vec![EventId(42)]);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, EventId(43));
+        assert!(has_more);
+
+        // Cleanup
+        chat.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn last_event_before_forwards_to_store() {
+        // Given a store which knows the last event before a timestamp
+        struct LastEventBeforeStub;
+        impl ChatStore for LastEventBeforeStub {
+            async fn last_event_before(&self, timestamp_ms: u64) -> anyhow::Result<EventId> {
+                assert_eq!(timestamp_ms, 1_000);
+                Ok(EventId(3))
+            }
+        }
+        let chat = ChatRuntime::with_chat_store(LastEventBeforeStub);
+
+        // When asking for the last event before the timestamp
+        let last_event_id = chat.client().last_event_before(1_000).await.unwrap();
+
+        // Then the answer of the store is forwarded
+        assert_eq!(last_event_id, EventId(3));
+
+        // Cleanup
+        chat.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn reads_are_not_blocked_by_slow_write() {
+        // Given a chat store which does not finish recording a message until released
+        struct SlowWriteStub {
+            started: Arc<Notify>,
+            release: Arc<Notify>,
+        }
+        impl ChatStore for SlowWriteStub {
+            async fn events_page(
+                &self,
+                _: EventId,
+                _: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
+                Ok((Vec::new(), false))
+            }
+            async fn record_message(&self, message: Message) -> Result<AddOutcome, ChatError> {
+                self.started.notify_one();
+                self.release.notified().await;
+                Ok(AddOutcome::New(Event::with_timestamp(
+                    EventId(1),
+                    message,
+                    SystemTime::UNIX_EPOCH,
+                )))
+            }
+        }
+        let started = Arc::new(Notify::new());
+        let release = Arc::new(Notify::new());
+        let chat = ChatRuntime::with_chat_store(SlowWriteStub {
+            started: started.clone(),
+            release: release.clone(),
+        });
+        let mut writer = chat.client();
+        let write = tokio::spawn(async move { writer.add_message(Message::dummy()).await });
+        started.notified().await;
+
+        // When reading the history while the write is in flight
+        let history = timeout(
+            Duration::from_secs(1),
+            chat.client().history(EventId::before_all(), 10),
+        )
+        .await;
+
+        // Then the read completes without waiting for the write
+        let (history, _) = history.expect("Read must not wait for write").unwrap();
+        assert!(history.is_empty());
+
+        // Cleanup
+        release.notify_one();
+        write.await.unwrap().unwrap();
+        chat.shutdown().await;
+    }
+
     #[tokio::test]
     async fn add_message_forwards_to_history() {
         // Given
@@ -290,6 +1365,7 @@ mod tests {
             id: "019c0ab6-9d11-75ef-ab02-60f070b1582a".parse().unwrap(),
             author: UserId::ALICE,
             content: "Hello".to_string(),
+            expires_at_ms: None,
         };
         chat.client().add_message(msg.clone()).await.unwrap();
 
@@ -307,21 +1383,26 @@ mod tests {
         // Given a history that treats one specific message ID as a duplicate
         struct HistoryStub;
         impl ChatStore for HistoryStub {
-            async fn events_since(&self, _last_event_id: EventId) -> anyhow::Result<Vec<Event>> {
-                Ok(Vec::new())
+            async fn events_page(
+                &self,
+                _last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
+                Ok((Vec::new(), false))
             }
-            async fn record_message(
-                &mut self,
-                message: Message,
-            ) -> Result<Option<Event>, ChatError> {
+            async fn record_message(&self, message: Message) -> Result<AddOutcome, ChatError> {
                 if message.id == MessageId::ALPHA {
-                    Ok(None)
-                } else {
-                    Ok(Some(Event::with_timestamp(
+                    Ok(AddOutcome::Duplicate(Event::with_timestamp(
                         EventId(1),
                         message,
                         SystemTime::UNIX_EPOCH,
                     )))
+                } else {
+                    Ok(AddOutcome::New(Event::with_timestamp(
+                        EventId(2),
+                        message,
+                        SystemTime::UNIX_EPOCH,
+                    )))
                 }
             }
         }
@@ -334,14 +1415,14 @@ mod tests {
 
         // When a sender sends a duplicate followed by a fresh message
         let mut sender = chat.client();
-        sender
+        let duplicate = sender
             .add_message(Message {
                 id: MessageId::ALPHA,
                 ..Message::dummy()
             })
             .await
             .unwrap();
-        sender
+        let fresh = sender
             .add_message(Message {
                 id: MessageId::BETA,
                 ..Message::dummy()
@@ -349,7 +1430,10 @@ mod tests {
             .await
             .unwrap();
 
-        // Then the first event received is the fresh message — the duplicate was not broadcast
+        // Then the sender learns about the outcomes
+        assert!(matches!(duplicate, AddOutcome::Duplicate(event) if event.id == EventId(1)));
+        assert!(matches!(fresh, AddOutcome::New(event) if event.id == EventId(2)));
+        // and the first event received is the fresh message — the duplicate was not broadcast
         let event = timeout(Duration::from_secs(1), next_event)
             .await
             .expect("timed out waiting for event")
@@ -368,7 +1452,7 @@ mod tests {
         // Given a chat that reports any message as a conflict
         struct ChatSaboteur;
         impl ChatStore for ChatSaboteur {
-            async fn record_message(&mut self, _: Message) -> Result<Option<Event>, ChatError> {
+            async fn record_message(&self, _: Message) -> Result<AddOutcome, ChatError> {
                 Err(ChatError::Conflict)
             }
         }
@@ -390,12 +1474,54 @@ mod tests {
         chat.shutdown().await;
     }
 
+    #[tokio::test]
+    async fn adding_message_after_actor_is_gone_fails_cleanly() {
+        // Given a client lingering after the actor of its runtime has stopped
+        let chat = ChatRuntime::with_chat_store(Dummy);
+        let mut client = chat.client();
+        chat.join_handle.abort();
+        let _ = chat.join_handle.await;
+
+        // When adding a message
+        let result = client.add_message(Message::dummy()).await;
+
+        // Then it is rejected, rather than panicking
+        assert!(matches!(result, Err(ChatError::ShuttingDown)));
+    }
+
+    #[tokio::test]
+    async fn requests_after_actor_is_gone_fail_cleanly() {
+        // Given a client lingering after the actor of its runtime has stopped
+        let chat = ChatRuntime::with_chat_store(Dummy);
+        let mut client = chat.client();
+        chat.join_handle.abort();
+        let _ = chat.join_handle.await;
+
+        // When reading from and writing to the chat
+        let history = client.clone().history(EventId::before_all(), 10).await;
+        let latest = client.latest_event_id().await;
+        let pinned = client.pin_message(MessageId::ALPHA, true).await;
+        let deleted = client.delete_messages_by_sender(UserId::ALICE).await;
+
+        // Then every request tells the chat is shutting down, rather than panicking
+        let shutting_down =
+            |error: anyhow::Error| matches!(error.downcast(), Ok(ChatError::ShuttingDown));
+        assert!(history.is_err_and(shutting_down));
+        assert!(latest.is_err_and(shutting_down));
+        assert!(matches!(pinned, Err(ChatError::ShuttingDown)));
+        assert!(matches!(deleted, Err(ChatError::ShuttingDown)));
+    }
+
     #[tokio::test]
     async fn events_stream_forwards_error_from_history() {
         // Given a history that fails to read events
         struct SaboteurHistory;
         impl ChatStore for SaboteurHistory {
-            async fn events_since(&self, _: EventId) -> anyhow::Result<Vec<Event>> {
+            async fn events_page(
+                &self,
+                _: EventId,
+                _: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
                 bail!("test error")
             }
         }
@@ -429,6 +1555,22 @@ mod tests {
         assert!(result.is_ok(), "Shutdown did not complete within 1 second");
     }
 
+    #[tokio::test]
+    async fn shutdown_completes_with_open_event_stream() {
+        // Given an open event stream, which waits for new events
+        let chat = ChatRuntime::with_chat_store(FakeHistory::new());
+        let mut events = chat.client().events(EventId::before_all()).boxed();
+        let no_event = timeout(Duration::from_millis(10), events.next()).await;
+        assert!(no_event.is_err(), "There must be no new event");
+
+        // When shutting down without dropping the stream
+        let result = timeout(Duration::from_secs(1), chat.shutdown()).await;
+
+        // Then the shutdown completes and the stream ends
+        assert!(result.is_ok(), "Shutdown did not complete within 1 second");
+        assert!(events.next().await.is_none());
+    }
+
     #[tokio::test]
     async fn event_stream_seamlessly_transitions_from_history_replay_to_live_broadcast() {
         // Given a history with one event
@@ -439,6 +1581,7 @@ mod tests {
                     id: "019c0ab6-9d11-75ef-ab02-60f070b1582a".parse().unwrap(),
                     author: UserId::ALICE,
                     content: "One".to_string(),
+                    expires_at_ms: None,
                 },
                 SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000),
             )
@@ -446,18 +1589,19 @@ mod tests {
 
         struct HistoryDouble;
         impl ChatStore for HistoryDouble {
-            async fn events_since(&self, last_event_id: EventId) -> anyhow::Result<Vec<Event>> {
+            async fn events_page(
+                &self,
+                last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
                 if last_event_id == EventId::before_all() {
-                    Ok(vec![canned_event()])
+                    Ok((vec![canned_event()], false))
                 } else {
-                    Ok(Vec::new())
+                    Ok((Vec::new(), false))
                 }
             }
-            async fn record_message(
-                &mut self,
-                message: Message,
-            ) -> Result<Option<Event>, ChatError> {
-                Ok(Some(Event::with_timestamp(
+            async fn record_message(&self, message: Message) -> Result<AddOutcome, ChatError> {
+                Ok(AddOutcome::New(Event::with_timestamp(
                     EventId(2),
                     message,
                     SystemTime::UNIX_EPOCH,
@@ -481,6 +1625,7 @@ mod tests {
             id: "019c0ab6-9d11-7a5b-abde-cb349e5fd995".parse().unwrap(),
             author: UserId::BOB,
             content: "Two".to_string(),
+            expires_at_ms: None,
         };
         chat.client().add_message(live_msg.clone()).await.unwrap();
 
@@ -503,10 +1648,14 @@ mod tests {
 
     #[tokio::test]
     async fn events_stream_delivers_new_history_on_re_request() {
-        // Given: a history that grows between requests
+        // Given: a history which is read in pages of a single event
         struct HistoryStub;
         impl ChatStore for HistoryStub {
-            async fn events_since(&self, last_event_id: EventId) -> anyhow::Result<Vec<Event>> {
+            async fn events_page(
+                &self,
+                last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
                 let events = match last_event_id {
                     EventId(0) => vec![Event::with_timestamp(
                         EventId(1),
@@ -514,6 +1663,7 @@ mod tests {
                             id: "019c0ab6-9d11-75ef-ab02-60f070b1582a".parse().unwrap(),
                             author: UserId::ALICE,
                             content: "One".to_string(),
+                            expires_at_ms: None,
                         },
                         SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000),
                     )],
@@ -523,12 +1673,14 @@ mod tests {
                             id: "019c0ab6-9d11-7a5b-abde-cb349e5fd995".parse().unwrap(),
                             author: UserId::BOB,
                             content: "Two".to_string(),
+                            expires_at_ms: None,
                         },
                         SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_001),
                     )],
                     _ => Vec::new(),
                 };
-                Ok(events)
+                let has_more = last_event_id < EventId(1);
+                Ok((events, has_more))
             }
         }
         let chat = ChatRuntime::with_chat_store(HistoryStub);
@@ -568,6 +1720,169 @@ mod tests {
         chat.shutdown().await;
     }
 
+    #[tokio::test]
+    async fn events_stream_replays_history_spanning_multiple_pages() {
+        // Given a history with one event more than fits into a single page
+        let history = FakeHistory::new();
+        for _ in 0..=HISTORY_PAGE_SIZE {
+            history.record_message(Message::dummy()).await.unwrap();
+        }
+        let chat = ChatRuntime::with_chat_store(history);
+
+        // When replaying the history
+        let events: Vec<_> = timeout(
+            Duration::from_secs(1),
+            chat.client()
+                .events(EventId::before_all())
+                .take(HISTORY_PAGE_SIZE + 1)
+                .try_collect::<Vec<_>>(),
+        )
+        .await
+        .expect("timed out waiting for history")
+        .unwrap();
+
+        // Then every event is delivered exactly once and in order
+        let ids: Vec<_> = events.iter().map(|event| event.id.0).collect();
+        let expected: Vec<_> = (1..=HISTORY_PAGE_SIZE as u64 + 1).collect();
+        assert_eq!(ids, expected);
+
+        // Cleanup
+        chat.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn pending_messages_are_broadcast_as_one_batch() {
+        // Given a writer with three messages waiting in its mailbox
+        let (sender, receiver) = mpsc::channel(5);
+        let (current, mut subscriber) = broadcast::channel(10);
+        let mut responses = Vec::new();
+        for id in [MessageId::ALPHA, MessageId::BETA, MessageId::GAMMA] {
+            let (responder, response) = oneshot::channel();
+            let message = Message {
+                id,
+                ..Message::dummy()
+            };
+            sender.send(WriteMsg { message, responder }).await.unwrap();
+            responses.push(response);
+        }
+        drop(sender);
+        let writer = Writer {
+            history: Arc::new(FakeHistory::new()),
+            current,
+            receiver,
+            echoes: None,
+            pressure: BroadcastPressure::new(10),
+        };
+
+        // When the writer processes its mailbox
+        writer.run().await;
+
+        // Then all events are broadcast at once, in order, and every message is acknowledged
+        let batch = subscriber.recv().await.unwrap();
+        let ids: Vec<_> = batch.iter().map(|event| event.id).collect();
+        assert_eq!(vec![EventId(1), EventId(2), EventId(3)], ids);
+        assert!(
+            subscriber.recv().await.is_err(),
+            "There must be no further broadcast"
+        );
+        for response in responses {
+            assert!(response.await.unwrap().is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicates_are_echoed_if_configured() {
+        // Chat store treating every message as duplicate of event 1
+        struct OneEvent;
+        impl ChatStore for OneEvent {
+            async fn events_page(
+                &self,
+                _last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
+                Ok((Vec::new(), false))
+            }
+
+            async fn record_message(&self, message: Message) -> Result<AddOutcome, ChatError> {
+                let event = Event::with_timestamp(EventId(1), message, SystemTime::UNIX_EPOCH);
+                Ok(AddOutcome::Duplicate(event))
+            }
+        }
+        for echo_duplicates in [true, false] {
+            // Given a runtime and a client listening for echoes, which already has seen event 1
+            let options = ActorOptions {
+                echo_duplicates,
+                ..ActorOptions::default()
+            };
+            let chat = ChatRuntime::with_actor_options(OneEvent, options);
+            let mut client = chat.client();
+            let mut events = pin!(client.clone().events(EventId(1)));
+            let mut echoes = pin!(client.clone().echoed_duplicates());
+            // Polled once, so the subscription is requested before the message is added
+            assert!(echoes.next().now_or_never().is_none());
+
+            // When the client adds a duplicate
+            client.add_message(Message::dummy()).await.unwrap();
+
+            // Then the original event is echoed, only if configured. Never on the events stream,
+            // which already has forwarded it.
+            let echo = timeout(Duration::from_millis(100), echoes.next()).await;
+            if echo_duplicates {
+                let echo = echo.unwrap().unwrap();
+                assert_eq!(1, echo.len());
+                assert_eq!(EventId(1), echo[0].id);
+            } else {
+                assert!(echo.is_err(), "Duplicate must not be echoed");
+            }
+            assert!(
+                timeout(Duration::from_millis(10), events.next())
+                    .await
+                    .is_err(),
+                "Events stream must not forward the event again"
+            );
+            drop(client);
+            chat.shutdown().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn sustained_lag_of_subscribers_is_logged() {
+        // Given a writer broadcasting to a subscriber which never reads
+        let logs = CapturedLogs::default();
+        let _guard = logs.capture(false);
+        let (sender, receiver) = mpsc::channel(5);
+        let (current, _idle) = broadcast::channel(2);
+        let writer = Writer {
+            history: Arc::new(FakeHistory::new()),
+            current,
+            receiver,
+            echoes: None,
+            pressure: BroadcastPressure::new(2),
+        };
+        let writer = tokio::spawn(writer.run());
+
+        // When messages are broadcast one after another
+        for _ in 0..5 {
+            let (responder, response) = oneshot::channel();
+            let message = Message {
+                id: MessageId::random(),
+                ..Message::dummy()
+            };
+            sender.send(WriteMsg { message, responder }).await.unwrap();
+            response.await.unwrap().unwrap();
+        }
+        drop(sender);
+        writer.await.unwrap();
+
+        // Then operators are warned once about the buffer staying full
+        let logs = logs.text();
+        assert_eq!(
+            1,
+            logs.matches("Buffer of live events stays full").count(),
+            "{logs}"
+        );
+    }
+
     #[tokio::test]
     async fn state_is_shared_between_clients() {
         // Given two clients from the same runtime
@@ -582,11 +1897,13 @@ mod tests {
             id: "019c0ab6-9d11-75ef-ab02-60f070b1582a".parse().unwrap(),
             author: UserId::ALICE,
             content: "From Alice".to_string(),
+            expires_at_ms: None,
         };
         let msg_b = Message {
             id: "019c0ab6-9d11-7a5b-abde-cb349e5fd995".parse().unwrap(),
             author: UserId::BOB,
             content: "From Bob".to_string(),
+            expires_at_ms: None,
         };
         client_a.add_message(msg_a.clone()).await.unwrap();
         client_b.add_message(msg_b.clone()).await.unwrap();
@@ -621,6 +1938,7 @@ mod tests {
                 id: MessageId::new(),
                 author: UserId::ALICE,
                 content: "Initial message".to_string(),
+                expires_at_ms: None,
             })
             .await
             .unwrap();
@@ -664,6 +1982,95 @@ mod tests {
         chat.shutdown().await;
     }
 
+    #[tokio::test]
+    async fn slow_receiver_is_told_it_lagged_if_configured() {
+        // Given a chat which ends the events streams of lagging clients with an error, and a client
+        // which has an event stream open
+        let chat = ChatRuntime::with_chat_store(FakeHistory::new()).with_on_lag(OnLag::Error);
+        let mut sender_client = chat.client();
+        let receiver_client = chat.client();
+        sender_client.add_message(Message::dummy()).await.unwrap();
+        let mut events_stream = receiver_client.events(EventId::before_all()).boxed();
+        events_stream.next().await.unwrap().unwrap();
+
+        // When a burst of messages is sent while the reader does not pull them
+        const NUM_MESSAGES_IN_BURST: usize = 1000;
+        for _ in 0..NUM_MESSAGES_IN_BURST {
+            let msg = Message {
+                id: MessageId::new(),
+                ..Message::dummy()
+            };
+            sender_client.add_message(msg).await.unwrap();
+        }
+
+        // Then the stream ends with a lagged error, before delivering all of them
+        let received = timeout(Duration::from_secs(2), events_stream.collect::<Vec<_>>())
+            .await
+            .expect("Stream must end after lagging");
+        let (last, delivered) = received.split_last().unwrap();
+        assert!(last.as_ref().unwrap_err().is::<Lagged>());
+        assert!(delivered.len() < NUM_MESSAGES_IN_BURST);
+        assert!(delivered.iter().all(Result::is_ok));
+
+        // Cleanup
+        drop(sender_client);
+        chat.shutdown().await;
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn no_event_is_delivered_twice_while_writes_interleave_with_transition_to_live() {
+        // Given a chat, which several clients write to concurrently
+        const NUM_WRITERS: usize = 4;
+        const MESSAGES_PER_WRITER: usize = 100;
+        const NUM_MESSAGES: usize = NUM_WRITERS * MESSAGES_PER_WRITER;
+        let chat = ChatRuntime::with_chat_store(FakeHistory::new());
+        let writers: Vec<_> = (0..NUM_WRITERS)
+            .map(|_| {
+                let mut client = chat.client();
+                tokio::spawn(async move {
+                    for _ in 0..MESSAGES_PER_WRITER {
+                        let message = Message {
+                            id: MessageId::new(),
+                            ..Message::dummy()
+                        };
+                        client.add_message(message).await.unwrap();
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+
+        // When readers subscribe at different points in time, each transitioning from the history
+        // to the live events while messages are still being written
+        let mut readers = Vec::new();
+        for _ in 0..8 {
+            let events = chat.client().events(EventId::before_all());
+            readers.push(tokio::spawn(async move {
+                timeout(
+                    Duration::from_secs(5),
+                    events.take(NUM_MESSAGES).try_collect::<Vec<_>>(),
+                )
+                .await
+                .expect("timed out waiting for events")
+                .unwrap()
+            }));
+            tokio::task::yield_now().await;
+        }
+
+        // Then every reader receives every event exactly once and in order
+        for writer in writers {
+            writer.await.unwrap();
+        }
+        let expected: Vec<_> = (1..=NUM_MESSAGES as u64).map(EventId).collect();
+        for reader in readers {
+            let ids: Vec<_> = reader.await.unwrap().iter().map(|e| e.id).collect();
+            assert_eq!(expected, ids);
+        }
+
+        // Cleanup
+        chat.shutdown().await;
+    }
+
     #[derive(Clone)]
     struct HistorySpy {
         recorded_messages: Arc<Mutex<Vec<Message>>>,
@@ -688,7 +2095,11 @@ mod tests {
     }
 
     impl ChatStore for HistorySpy {
-        async fn events_since(&self, last_event_id: EventId) -> anyhow::Result<Vec<Event>> {
+        async fn events_page(
+            &self,
+            last_event_id: EventId,
+            _limit: usize,
+        ) -> anyhow::Result<(Vec<Event>, bool)> {
             self.observed_last_event_ids
                 .lock()
                 .unwrap()
@@ -698,39 +2109,53 @@ mod tests {
                 Message::dummy(),
                 SystemTime::UNIX_EPOCH,
             )];
-            Ok(events)
+            // There is always one more event
+            Ok((events, true))
         }
 
-        async fn record_message(&mut self, message: Message) -> Result<Option<Event>, ChatError> {
+        async fn record_message(&self, message: Message) -> Result<AddOutcome, ChatError> {
             self.recorded_messages.lock().unwrap().push(message.clone());
-            Ok(Some(Event::new(EventId(1), message)))
+            Ok(AddOutcome::New(Event::new(EventId(1), message)))
         }
     }
 
     struct FakeHistory {
-        events: Vec<Event>,
+        events: Mutex<Vec<Event>>,
     }
 
     impl FakeHistory {
         fn new() -> Self {
-            FakeHistory { events: Vec::new() }
+            FakeHistory {
+                events: Mutex::new(Vec::new()),
+            }
         }
     }
 
     impl ChatStore for FakeHistory {
-        async fn events_since(&self, last_event_id: EventId) -> anyhow::Result<Vec<Event>> {
-            let start = (last_event_id.0 as usize).min(self.events.len());
-            Ok(self.events[start..].to_vec())
+        async fn events_page(
+            &self,
+            last_event_id: EventId,
+            limit: usize,
+        ) -> anyhow::Result<(Vec<Event>, bool)> {
+            let mut events = {
+                let events = self.events.lock().unwrap();
+                let start = (last_event_id.0 as usize).min(events.len());
+                events[start..].to_vec()
+            };
+            let has_more = events.len() > limit;
+            events.truncate(limit);
+            Ok((events, has_more))
         }
 
-        async fn record_message(&mut self, message: Message) -> Result<Option<Event>, ChatError> {
+        async fn record_message(&self, message: Message) -> Result<AddOutcome, ChatError> {
+            let mut events = self.events.lock().unwrap();
             let event = Event::with_timestamp(
-                EventId(self.events.len() as u64 + 1),
+                EventId(events.len() as u64 + 1),
                 message,
                 SystemTime::UNIX_EPOCH,
             );
-            self.events.push(event.clone());
-            Ok(Some(event))
+            events.push(event.clone());
+            Ok(AddOutcome::New(event))
         }
     }
 }
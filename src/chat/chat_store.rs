@@ -1,24 +1,126 @@
 use super::{
-    chat_persistence::{ChatPersistence, InsertOutcome},
-    event::{Event, EventId},
-    message::Message,
+    chat_persistence::{ChatPersistence, ContentEncoding, InsertOutcome},
+    event::{Event, EventId, Tombstone},
+    message::{Message, MessageId},
 };
-use std::future::Future;
+use crate::user::UserId;
+use anyhow::anyhow;
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt,
+    future::Future,
+    num::{NonZeroU64, NonZeroUsize},
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+/// Hard ceiling for the size of the content of a single message in bytes. Enforced by the store, so
+/// the invariant holds independent of the entry point the message has been submitted through.
+pub const MAX_CONTENT_BYTES: usize = 64 * 1024;
+
+/// Number of writes after which the size of the database is queried again, if its size is capped.
+/// Querying it for every write would slow down recording messages.
+const SIZE_CHECK_INTERVAL: u32 = 16;
 
 #[cfg_attr(test, double_trait::dummies)]
 pub trait ChatStore {
-    /// All events since the event with the given `last_event_id` (exclusive).
-    fn events_since(
+    /// Up to `limit` events since the event with the given `last_event_id` (exclusive). The flag is
+    /// `true` if there are more events beyond the returned page.
+    fn events_page(
         &self,
         last_event_id: EventId,
+        limit: usize,
+    ) -> impl Future<Output = anyhow::Result<(Vec<Event>, bool)>> + Send;
+
+    /// Up to `limit` events with a timestamp between `from_ms` and `to_ms` (inclusive), ordered by
+    /// id.
+    fn events_in_window(
+        &self,
+        from_ms: u64,
+        to_ms: u64,
+        limit: usize,
     ) -> impl Future<Output = anyhow::Result<Vec<Event>>> + Send;
 
-    /// Record a message and return the corresponding event. `None` indiactes that no event should
-    /// be emitted due to the message being a duplicate of an already recorded message.
+    /// The id of the last event recorded before `timestamp_ms`. Resuming from it yields the events
+    /// recorded at or after `timestamp_ms`.
+    fn last_event_before(
+        &self,
+        timestamp_ms: u64,
+    ) -> impl Future<Output = anyhow::Result<EventId>> + Send;
+
+    /// Number of events since the event with the given `last_event_id` (exclusive).
+    fn count_events_since(
+        &self,
+        last_event_id: EventId,
+    ) -> impl Future<Output = anyhow::Result<u64>> + Send;
+
+    /// Record a message and return the corresponding event. If the message is a duplicate of an
+    /// already recorded message, no new event should be emitted. The event it has been recorded as
+    /// is returned instead. Depending on the configuration, a message with the same sender and
+    /// content as one recorded just before is considered a duplicate, too, even if its id differs.
+    ///
+    /// Takes `&self`, so events can be read while a message is being recorded.
     fn record_message(
-        &mut self,
+        &self,
         message: Message,
-    ) -> impl Future<Output = Result<Option<Event>, ChatError>> + Send;
+    ) -> impl Future<Output = Result<AddOutcome, ChatError>> + Send;
+
+    /// Pins or unpins the message with `message_id` and returns the id of the event it has been
+    /// recorded as. Fails with [`ChatError::NotFound`] if there is no such message.
+    fn pin_message(
+        &self,
+        message_id: MessageId,
+        pinned: bool,
+    ) -> impl Future<Output = Result<EventId, ChatError>> + Send;
+
+    /// All pinned events, ordered by id.
+    fn pinned_events(&self) -> impl Future<Output = anyhow::Result<Vec<Event>>> + Send;
+
+    /// Up to `limit` events of messages sent by `sender` since the event with the given
+    /// `last_event_id` (exclusive), ordered by id.
+    fn events_by_sender(
+        &self,
+        sender: UserId,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> impl Future<Output = anyhow::Result<Vec<Event>>> + Send;
+
+    /// Tombstones all messages sent by `sender` at once. Their events are no longer read. Returns
+    /// the tombstones, ordered by id.
+    fn tombstone_sender(
+        &self,
+        sender: UserId,
+    ) -> impl Future<Output = Result<Vec<Tombstone>, ChatError>> + Send;
+
+    /// Tombstones all messages which expired at or before `now_ms` (milliseconds since Unix epoch)
+    /// at once. Messages without an expiry of their own expire, if recorded before
+    /// `sent_before_ms`. Pinned messages are spared. Returns the tombstones, ordered by id.
+    fn tombstone_expired(
+        &self,
+        now_ms: u64,
+        sent_before_ms: Option<u64>,
+    ) -> impl Future<Output = Result<Vec<Tombstone>, ChatError>> + Send;
+
+    /// Id of the latest event recorded so far, including events which have since been pruned or
+    /// tombstoned. [`EventId::before_all`] if none has been recorded.
+    fn latest_event_id(&self) -> impl Future<Output = EventId> + Send;
+
+    /// Id of the last event which has been pruned, because only the most recent events are
+    /// retained. [`EventId::before_all`] if none has been pruned.
+    fn pruned_up_to(&self) -> impl Future<Output = anyhow::Result<EventId>> + Send;
+}
+
+/// Tells whether an added message has been new to the chat. Either way it carries the event the
+/// message is recorded as, so clients can reconcile their view with the one of the server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AddOutcome {
+    /// The message has been recorded and broadcast.
+    New(Event),
+    /// Exactly the same message had been added before, e.g. by a retry. It has not been broadcast
+    /// again. Holds the event it has originally been recorded as. Within the dedup window, a
+    /// message resent under a fresh id counts as the same message.
+    Duplicate(Event),
 }
 
 #[derive(Debug)]
@@ -28,40 +130,286 @@ pub enum ChatError {
     /// itself is different. This makes it different from a duplicate which can occur than retrying
     /// a message. The message has not been recorded.
     Conflict,
+    /// The content of the message exceeds [`MAX_CONTENT_BYTES`]. The message has not been
+    /// recorded.
+    TooLarge,
+    /// There is no room left in the storage, e.g. because the disk is full. The message has not been
+    /// recorded. Other than [`Self::Internal`] this is likely to resolve once the operator frees up
+    /// space.
+    StorageFull,
+    /// There is no message with the given id, e.g. because it has been pruned or never been
+    /// recorded.
+    NotFound,
+    /// The sender has not posted before, and as many other senders as allowed already have. The
+    /// message has not been recorded.
+    SeatLimitReached,
+    /// The chat has been shut down, or is shutting down, and no longer accepts messages. The
+    /// message has not been recorded.
+    ShuttingDown,
     /// An error caused by the runtime, due to accidential complexity. E.g. a failing I/O operation.
     /// The nature of the internal error is relevant for the operater. It can be assumed an error
     /// has been logged.
     Internal,
 }
 
+impl fmt::Display for ChatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            ChatError::Conflict => "A different message with the same id has already been recorded",
+            ChatError::TooLarge => "Message content is too large",
+            ChatError::StorageFull => "No storage left for new messages",
+            ChatError::NotFound => "There is no message with this id",
+            ChatError::SeatLimitReached => "Seat limit of the chat has been reached",
+            ChatError::ShuttingDown => "Chat is shutting down",
+            ChatError::Internal => "Internal error of the chat",
+        };
+        write!(f, "{description}")
+    }
+}
+
+impl std::error::Error for ChatError {}
+
 impl<P> ChatStore for PersistentChat<P>
 where
     P: ChatPersistence + Sync + Send,
 {
-    async fn events_since(&self, last_event_id: EventId) -> anyhow::Result<Vec<Event>> {
-        self.persistence.events_since(last_event_id).await
+    async fn events_page(
+        &self,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> anyhow::Result<(Vec<Event>, bool)> {
+        if let Some(page) = self.recent_events_since(last_event_id, limit) {
+            return Ok(page);
+        }
+        let query = self.bounded(self.persistence.events_page(last_event_id, limit));
+        self.timed("fetch", query).await
+    }
+
+    async fn events_in_window(
+        &self,
+        from_ms: u64,
+        to_ms: u64,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Event>> {
+        let query = self.persistence.events_in_window(from_ms, to_ms, limit);
+        self.timed("fetch", query).await
     }
 
-    async fn record_message(&mut self, message: Message) -> Result<Option<Event>, ChatError> {
-        let event_id = self.last_event_id.successor();
+    async fn last_event_before(&self, timestamp_ms: u64) -> anyhow::Result<EventId> {
+        let first = self.persistence.first_event_at(timestamp_ms);
+        if let Some(first) = self.timed("fetch", first).await? {
+            return Ok(EventId(first.0 - 1));
+        }
+        // All events are older, so the client only wants to see new ones.
+        let max_event_id = self.timed("fetch", self.persistence.max_event_id()).await?;
+        Ok(max_event_id.unwrap_or_else(EventId::before_all))
+    }
+
+    async fn count_events_since(&self, last_event_id: EventId) -> anyhow::Result<u64> {
+        let query = self.persistence.count_events_since(last_event_id);
+        self.timed("fetch", query).await
+    }
+
+    async fn record_message(&self, message: Message) -> Result<AddOutcome, ChatError> {
+        if message.content.len() > MAX_CONTENT_BYTES {
+            return Err(ChatError::TooLarge);
+        }
+        // Held until the event is persisted, so concurrent calls can not claim the same id.
+        let mut last_event_id = self.last_event_id.lock().await;
+        let event_id = last_event_id.successor();
         let event = Event::new(event_id, message);
-        let result = self.persistence.insert_event(&event).await;
+        let resend = self
+            .resends
+            .as_ref()
+            .and_then(|resends| resends.lock().unwrap().resend_of(&event));
+        if let Some(recorded) = resend {
+            // A message reusing the id of another one conflicts with it, even if its content
+            // matches a recent message. Recording it tells a conflict from a duplicate.
+            let reuses_id = recorded.message.id != event.message.id
+                && self
+                    .timed("fetch", self.persistence.is_recorded(event.message.id))
+                    .await
+                    .map_err(|_err| ChatError::Internal)?;
+            if !reuses_id {
+                return Ok(AddOutcome::Duplicate(recorded));
+            }
+        }
+        if let Some(seats) = &self.seats {
+            let mut seats = seats.lock().await;
+            seats
+                .load(&self.persistence)
+                .await
+                .map_err(|_err| ChatError::Internal)?;
+            if !seats.admits(event.message.author) {
+                return Err(ChatError::SeatLimitReached);
+            }
+        }
+        if let Some(budget) = &self.budget {
+            let mut budget = budget.lock().await;
+            budget
+                .check(&self.persistence)
+                .await
+                .map_err(|_err| ChatError::Internal)?;
+            if budget.is_exceeded() {
+                error!(
+                    target: "persistence",
+                    max_bytes = budget.max_bytes,
+                    "Database reached its maximum size. Messages can not be recorded."
+                );
+                return Err(ChatError::StorageFull);
+            }
+        }
+        let insert = self.persistence.insert_event(&event, self.content_encoding);
+        let result = self.timed("record", insert).await;
         match result {
             Ok(InsertOutcome::New) => {
-                self.last_event_id = event_id;
-                Ok(Some(event))
+                *last_event_id = event_id;
+                if let Some(recent) = &self.recent {
+                    recent.lock().unwrap().push(event.clone());
+                }
+                if let Some(resends) = &self.resends {
+                    resends.lock().unwrap().push(event.clone());
+                }
+                if let Some(seats) = &self.seats {
+                    seats.lock().await.take(event.message.author);
+                }
+                self.prune(event_id).await;
+                Ok(AddOutcome::New(event))
             }
-            Ok(InsertOutcome::Duplicate) => Ok(None),
+            Ok(InsertOutcome::Duplicate(recorded)) => Ok(AddOutcome::Duplicate(recorded)),
             Ok(InsertOutcome::Conflict) => Err(ChatError::Conflict),
+            Ok(InsertOutcome::StorageFull) => {
+                error!(
+                    target: "persistence",
+                    "Storage is full. Messages can not be recorded until space is freed up."
+                );
+                Err(ChatError::StorageFull)
+            }
             Err(_err) => Err(ChatError::Internal),
         }
     }
+
+    async fn pin_message(&self, message_id: MessageId, pinned: bool) -> Result<EventId, ChatError> {
+        let event_id = self
+            .timed("pin", self.persistence.set_pinned(message_id, pinned))
+            .await
+            .map_err(|_err| ChatError::Internal)?
+            .ok_or(ChatError::NotFound)?;
+        // Resuming clients answered from memory must see the same state as those answered from the
+        // database.
+        if let Some(recent) = &self.recent {
+            recent.lock().unwrap().set_pinned(event_id, pinned);
+        }
+        Ok(event_id)
+    }
+
+    async fn pinned_events(&self) -> anyhow::Result<Vec<Event>> {
+        self.timed("fetch", self.persistence.pinned_events()).await
+    }
+
+    async fn events_by_sender(
+        &self,
+        sender: UserId,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> anyhow::Result<Vec<Event>> {
+        self.timed(
+            "fetch",
+            self.persistence
+                .events_by_sender(sender, last_event_id, limit),
+        )
+        .await
+    }
+
+    async fn latest_event_id(&self) -> EventId {
+        *self.last_event_id.lock().await
+    }
+
+    async fn pruned_up_to(&self) -> anyhow::Result<EventId> {
+        // Events are pruned from the oldest one onwards. Tombstoned events keep their rows, so
+        // every id before the oldest row is pruned.
+        let oldest = self.timed("fetch", self.persistence.min_event_id()).await?;
+        match oldest {
+            Some(oldest) => Ok(EventId(oldest.0 - 1)),
+            // No event is retained, so every event recorded so far has been pruned.
+            None => Ok(*self.last_event_id.lock().await),
+        }
+    }
+
+    async fn tombstone_sender(&self, sender: UserId) -> Result<Vec<Tombstone>, ChatError> {
+        // Held, so no message of the sender is put into memory after the ones in memory have been
+        // forgotten.
+        let _last_event_id = self.last_event_id.lock().await;
+        let tombstones = self
+            .timed("tombstone", self.persistence.tombstone_sender(sender))
+            .await
+            .map_err(|_err| ChatError::Internal)?;
+        if let Some(recent) = &self.recent {
+            recent.lock().unwrap().forget_sender(sender);
+        }
+        if let Some(resends) = &self.resends {
+            resends.lock().unwrap().forget_sender(sender);
+        }
+        if let Some(seats) = &self.seats {
+            seats.lock().await.release(sender);
+        }
+        Ok(tombstones)
+    }
+
+    async fn tombstone_expired(
+        &self,
+        now_ms: u64,
+        sent_before_ms: Option<u64>,
+    ) -> Result<Vec<Tombstone>, ChatError> {
+        // Held, so no expired message is put into memory after the ones in memory have been
+        // forgotten.
+        let _last_event_id = self.last_event_id.lock().await;
+        let tombstones = self
+            .timed(
+                "tombstone",
+                self.persistence.tombstone_expired(now_ms, sent_before_ms),
+            )
+            .await
+            .map_err(|_err| ChatError::Internal)?;
+        if let Some(recent) = &self.recent {
+            recent.lock().unwrap().forget_tombstoned(&tombstones);
+        }
+        if let Some(resends) = &self.resends {
+            resends.lock().unwrap().forget_tombstoned(&tombstones);
+        }
+        // Senders whose last message expired no longer hold a seat, just like after a restart. The
+        // database tells which ones, once the seats are loaded again with the next message.
+        if let Some(seats) = &self.seats
+            && !tombstones.is_empty()
+        {
+            seats.lock().await.unload();
+        }
+        Ok(tombstones)
+    }
 }
 
 pub struct PersistentChat<P> {
     persistence: P,
     /// Identifying the event which has last been emited.
-    last_event_id: EventId,
+    last_event_id: Mutex<EventId>,
+    /// Caps the size of the database. `None` if there is no cap.
+    budget: Option<Mutex<StorageBudget>>,
+    /// How the content of new messages is stored.
+    content_encoding: ContentEncoding,
+    /// Only the most recent events are retained. `None` retains all of them.
+    max_events: Option<NonZeroU64>,
+    /// The most recent events, so clients resuming from a recent event do not need to query the
+    /// database. `None` if disabled.
+    recent: Option<std::sync::Mutex<RecentEvents>>,
+    /// Events recorded within the dedup window, to recognize messages resent under a fresh id.
+    /// `None` if disabled.
+    resends: Option<std::sync::Mutex<RecentResends>>,
+    /// Database operations taking longer are logged. `None` if disabled.
+    slow_query_threshold: Option<Duration>,
+    /// Reading events since an event fails once it takes longer. `None` waits as long as it takes.
+    query_timeout: Option<Duration>,
+    /// Senders who have posted, if their number is limited. `None` admits any sender.
+    seats: Option<Mutex<Seats>>,
 }
 
 impl<P> PersistentChat<P>
@@ -75,61 +423,567 @@ where
             .unwrap_or_else(EventId::before_all);
         let new = PersistentChat {
             persistence,
-            last_event_id,
+            last_event_id: Mutex::new(last_event_id),
+            budget: None,
+            content_encoding: ContentEncoding::Plain,
+            max_events: None,
+            recent: None,
+            resends: None,
+            slow_query_threshold: None,
+            query_timeout: None,
+            seats: None,
         };
         Ok(new)
     }
+
+    /// Reject new messages with [`ChatError::StorageFull`] once the database has grown to
+    /// `max_bytes`. Reading events is not affected. `None` does not cap the size.
+    pub fn with_max_bytes(self, max_bytes: Option<u64>) -> Self {
+        PersistentChat {
+            budget: max_bytes.map(|max_bytes| Mutex::new(StorageBudget::new(max_bytes))),
+            ..self
+        }
+    }
+
+    /// Store the content of new messages compressed. Messages recorded before are still read, no
+    /// matter how they have been stored.
+    pub fn with_compression(self, compress: bool) -> Self {
+        let content_encoding = if compress {
+            ContentEncoding::Zstd
+        } else {
+            ContentEncoding::Plain
+        };
+        PersistentChat {
+            content_encoding,
+            ..self
+        }
+    }
+
+    /// Retain only the `max_events` most recent events. Older ones are deleted as new messages are
+    /// recorded. Event ids are not reused, so clients can still resume from the events they have
+    /// seen. `None` retains all events.
+    pub fn with_max_events(self, max_events: Option<NonZeroU64>) -> Self {
+        PersistentChat { max_events, ..self }
+    }
+
+    /// Keep the `capacity` most recent events in memory. Most resuming clients are only slightly
+    /// behind, so their events are answered without querying the database. `None` always queries
+    /// the database.
+    pub fn with_recent_events(mut self, capacity: Option<NonZeroUsize>) -> Self {
+        let last_event_id = *self.last_event_id.get_mut();
+        let recent = capacity
+            .map(|capacity| std::sync::Mutex::new(RecentEvents::new(capacity, last_event_id)));
+        PersistentChat { recent, ..self }
+    }
+
+    /// Treat a message with the same sender and content as one recorded less than `window` ago as a
+    /// duplicate, even if its id differs. Clients retrying after a timeout with a fresh id would
+    /// otherwise show the same message twice. `None` only treats messages with the same id as
+    /// duplicates.
+    pub fn with_dedup_window(self, window: Option<Duration>) -> Self {
+        let resends = window.map(|window| std::sync::Mutex::new(RecentResends::new(window)));
+        PersistentChat { resends, ..self }
+    }
+
+    /// Log a warning for every database operation taking longer than `threshold`, to tell which
+    /// ones are slow on a struggling disk. `None` does not log them.
+    pub fn with_slow_query_threshold(self, threshold: Option<Duration>) -> Self {
+        PersistentChat {
+            slow_query_threshold: threshold,
+            ..self
+        }
+    }
+
+    /// Fail reading events since an event, once the query takes longer than `timeout`. The query is
+    /// dropped, so a pathological read answers the client with an error rather than keeping it
+    /// waiting indefinitely. `None` waits as long as the query takes.
+    pub fn with_query_timeout(self, timeout: Option<Duration>) -> Self {
+        PersistentChat {
+            query_timeout: timeout,
+            ..self
+        }
+    }
+
+    /// Reject messages of new senders with [`ChatError::SeatLimitReached`], once `max_senders`
+    /// distinct senders have posted. Senders who have posted before may continue to do so. Deleting
+    /// all messages of a sender frees their seat. `None` admits any number of senders.
+    pub fn with_max_senders(self, max_senders: Option<NonZeroUsize>) -> Self {
+        PersistentChat {
+            seats: max_senders.map(|max_senders| Mutex::new(Seats::new(max_senders))),
+            ..self
+        }
+    }
+
+    /// Awaits `query`, warning if it takes longer than the slow query threshold. `operation` names
+    /// the kind of query, e.g. "record" or "fetch". Neither arguments nor results are logged, since
+    /// they may carry message content.
+    async fn timed<O>(&self, operation: &'static str, query: impl Future<Output = O>) -> O {
+        let Some(threshold) = self.slow_query_threshold else {
+            return query.await;
+        };
+        let start = Instant::now();
+        let output = query.await;
+        let elapsed = start.elapsed();
+        if elapsed > threshold {
+            warn!(
+                target: "persistence",
+                operation,
+                elapsed_ms = elapsed.as_millis(),
+                "Slow query"
+            );
+        }
+        output
+    }
+
+    /// Awaits `query`, failing if it takes longer than the query timeout. Dropping `query` on
+    /// timeout interrupts it on the database, so it does not keep the connection busy.
+    async fn bounded<O>(
+        &self,
+        query: impl Future<Output = anyhow::Result<O>>,
+    ) -> anyhow::Result<O> {
+        let Some(timeout) = self.query_timeout else {
+            return query.await;
+        };
+        tokio::time::timeout(timeout, query)
+            .await
+            .unwrap_or_else(|_elapsed| {
+                error!(
+                    target: "persistence",
+                    timeout_ms = timeout.as_millis(),
+                    "Query timed out"
+                );
+                Err(anyhow!("Query did not complete within {timeout:?}"))
+            })
+    }
+
+    /// Up to `limit` events since `last_event_id` (exclusive) from memory. `None` if some of them
+    /// are not kept in memory.
+    fn recent_events_since(
+        &self,
+        last_event_id: EventId,
+        limit: usize,
+    ) -> Option<(Vec<Event>, bool)> {
+        self.recent
+            .as_ref()?
+            .lock()
+            .unwrap()
+            .since(last_event_id, limit)
+    }
+
+    /// Deletes the events which are no longer retained, after `newest` has been recorded. Event ids
+    /// are consecutive, so there is something to delete only if `newest` exceeds the maximum.
+    async fn prune(&self, newest: EventId) {
+        let Some(max_events) = self.max_events else {
+            return;
+        };
+        let Some(last_pruned) = newest.0.checked_sub(max_events.get()) else {
+            return;
+        };
+        // Deleted events must not be served from memory either
+        if let Some(recent) = &self.recent {
+            recent.lock().unwrap().forget_up_to(EventId(last_pruned));
+        }
+        // The message has been recorded anyway, so we do not fail it. Pruning is attempted again
+        // with the next message.
+        let delete = self.persistence.delete_events_up_to(EventId(last_pruned));
+        if let Err(err) = self.timed("prune", delete).await {
+            error!(target: "persistence", error = %err, "Failed to delete events beyond MAX_EVENTS");
+        }
+    }
+}
+
+/// The most recent events in order of their ids. Since every new event is pushed, these are all the
+/// events after `after`.
+struct RecentEvents {
+    events: VecDeque<Event>,
+    capacity: NonZeroUsize,
+    /// Id of the event preceding the first one kept in memory.
+    after: EventId,
+}
+
+impl RecentEvents {
+    /// `last_event_id` is the id of the latest event recorded so far.
+    fn new(capacity: NonZeroUsize, last_event_id: EventId) -> Self {
+        RecentEvents {
+            events: VecDeque::with_capacity(capacity.get()),
+            capacity,
+            after: last_event_id,
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        if self.events.len() == self.capacity.get() {
+            let oldest = self.events.pop_front().expect("capacity must not be zero");
+            self.after = oldest.id;
+        }
+        self.events.push_back(event);
+    }
+
+    /// Up to `limit` events since `last_event_id` (exclusive). The flag is `true` if there are more
+    /// events beyond the returned ones. `None` if older events than the ones in memory are asked
+    /// for.
+    fn since(&self, last_event_id: EventId, limit: usize) -> Option<(Vec<Event>, bool)> {
+        if last_event_id < self.after {
+            return None;
+        }
+        let mut newer = self
+            .events
+            .iter()
+            .skip_while(|event| event.id <= last_event_id);
+        let events: Vec<_> = newer.by_ref().take(limit).cloned().collect();
+        Some((events, newer.next().is_some()))
+    }
+
+    /// Updates the pinned state of the event with `event_id`, if it is kept in memory.
+    fn set_pinned(&mut self, event_id: EventId, pinned: bool) {
+        if let Some(event) = self.events.iter_mut().find(|event| event.id == event_id) {
+            event.pinned = pinned;
+        }
+    }
+
+    /// Drops the events of messages sent by `sender`, after they have been tombstoned.
+    fn forget_sender(&mut self, sender: UserId) {
+        self.events.retain(|event| event.message.author != sender);
+    }
+
+    /// Drops the events of `tombstones`, after they have been tombstoned.
+    fn forget_tombstoned(&mut self, tombstones: &[Tombstone]) {
+        self.events
+            .retain(|event| tombstones.iter().all(|t| t.event_id != event.id));
+    }
+
+    /// Drops the events up to `last_deleted` (inclusive), after they have been deleted.
+    fn forget_up_to(&mut self, last_deleted: EventId) {
+        while self
+            .events
+            .front()
+            .is_some_and(|event| event.id <= last_deleted)
+        {
+            let event = self.events.pop_front().unwrap();
+            self.after = event.id;
+        }
+        self.after = self.after.max(last_deleted);
+    }
+}
+
+/// The events recorded within the dedup window, oldest first.
+struct RecentResends {
+    window_ms: u64,
+    events: VecDeque<Event>,
+}
+
+impl RecentResends {
+    fn new(window: Duration) -> Self {
+        RecentResends {
+            window_ms: window.as_millis() as u64,
+            events: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        self.events.push_back(event);
+    }
+
+    /// Drops the events of messages sent by `sender`, after they have been tombstoned. Resending
+    /// them afterwards records them again.
+    fn forget_sender(&mut self, sender: UserId) {
+        self.events.retain(|event| event.message.author != sender);
+    }
+
+    /// Drops the events of `tombstones`, after they have been tombstoned.
+    fn forget_tombstoned(&mut self, tombstones: &[Tombstone]) {
+        self.events
+            .retain(|event| tombstones.iter().all(|t| t.event_id != event.id));
+    }
+
+    /// The event a message with the same sender and content as `event` has been recorded as within
+    /// the window before it, if any. Forgets about events which have left the window.
+    fn resend_of(&mut self, event: &Event) -> Option<Event> {
+        let oldest_ms = event.timestamp_ms.saturating_sub(self.window_ms);
+        while self
+            .events
+            .front()
+            .is_some_and(|recorded| recorded.timestamp_ms < oldest_ms)
+        {
+            self.events.pop_front();
+        }
+        self.events
+            .iter()
+            .find(|recorded| {
+                recorded.message.author == event.message.author
+                    && recorded.message.content == event.message.content
+            })
+            .cloned()
+    }
+}
+
+/// Keeps track of the size of the database, without querying it for every write.
+struct StorageBudget {
+    max_bytes: u64,
+    /// Size of the database, as of the last time we have asked.
+    size_bytes: u64,
+    /// Checks left until the size is queried again.
+    checks_until_refresh: u32,
+}
+
+impl StorageBudget {
+    fn new(max_bytes: u64) -> Self {
+        StorageBudget {
+            max_bytes,
+            size_bytes: 0,
+            // Learn about the size right away on the first write
+            checks_until_refresh: 0,
+        }
+    }
+
+    /// Queries the size of the database every [`SIZE_CHECK_INTERVAL`] calls.
+    async fn check(&mut self, persistence: &impl ChatPersistence) -> anyhow::Result<()> {
+        if self.checks_until_refresh == 0 {
+            self.size_bytes = persistence.database_size().await?;
+            self.checks_until_refresh = SIZE_CHECK_INTERVAL;
+        }
+        self.checks_until_refresh -= 1;
+        Ok(())
+    }
+
+    fn is_exceeded(&self) -> bool {
+        self.size_bytes >= self.max_bytes
+    }
+}
+
+/// The senders who have taken one of a limited number of seats by posting.
+struct Seats {
+    max_senders: NonZeroUsize,
+    /// `None` until loaded from the database, on the first message recorded.
+    taken: Option<HashSet<UserId>>,
+}
+
+impl Seats {
+    fn new(max_senders: NonZeroUsize) -> Self {
+        Seats {
+            max_senders,
+            taken: None,
+        }
+    }
+
+    /// Learns about the senders who have posted so far, unless already known. From then on, every
+    /// new sender is taken note of as their messages are recorded.
+    async fn load(&mut self, persistence: &impl ChatPersistence) -> anyhow::Result<()> {
+        if self.taken.is_none() {
+            self.taken = Some(persistence.senders().await?.into_iter().collect());
+        }
+        Ok(())
+    }
+
+    /// `true` if `sender` already holds a seat, or there is one left. Seats must have been loaded.
+    fn admits(&self, sender: UserId) -> bool {
+        let taken = self
+            .taken
+            .as_ref()
+            .expect("Seats must be loaded before admitting senders");
+        taken.contains(&sender) || taken.len() < self.max_senders.get()
+    }
+
+    fn take(&mut self, sender: UserId) {
+        if let Some(taken) = &mut self.taken {
+            taken.insert(sender);
+        }
+    }
+
+    fn release(&mut self, sender: UserId) {
+        if let Some(taken) = &mut self.taken {
+            taken.remove(&sender);
+        }
+    }
+
+    /// Forgets the senders who have posted, so they are loaded from the database again.
+    fn unload(&mut self) {
+        self.taken = None;
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::time::{SystemTime, UNIX_EPOCH};
+    use std::{
+        num::{NonZeroU64, NonZeroUsize},
+        sync::atomic::{AtomicUsize, Ordering},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use async_sqlite::ClientBuilder;
+    use double_trait::Dummy;
 
-    use super::{ChatPersistence, ChatStore as _, Event, InsertOutcome, PersistentChat};
+    use super::{
+        AddOutcome, ChatPersistence, ChatStore as _, ContentEncoding, Event, InsertOutcome,
+        MAX_CONTENT_BYTES, PersistentChat, RecentEvents,
+    };
     use crate::{
-        chat::{ChatError, EventId, Message, MessageId},
+        chat::{ChatError, EventId, Message, MessageId, migrate_chat_persistence},
+        tracing::CapturedLogs,
         user::UserId,
     };
 
     #[tokio::test]
-    async fn events_since_forwards_to_persistence() {
+    async fn events_page_forwards_to_persistence() {
         // Given a persistence layer that returns a canned event for a given last_event_id
-        struct EventsSinceMock;
-        impl ChatPersistence for EventsSinceMock {
-            async fn events_since(&self, last_event_id: EventId) -> anyhow::Result<Vec<Event>> {
+        struct EventsPageMock;
+        impl ChatPersistence for EventsPageMock {
+            async fn events_page(
+                &self,
+                last_event_id: EventId,
+                limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
                 // Expect being called with same arguments
                 assert_eq!(last_event_id, EventId(7));
+                assert_eq!(limit, 10);
 
                 let event = Event::with_timestamp(EventId(8), Message::dummy(), UNIX_EPOCH);
-                Ok(vec![event])
+                Ok((vec![event], true))
             }
         }
-        let history = PersistentChat::new(EventsSinceMock).await.unwrap();
+        let history = PersistentChat::new(EventsPageMock).await.unwrap();
 
         // When
-        let events = history.events_since(EventId(7)).await.unwrap();
+        let (events, has_more) = history.events_page(EventId(7), 10).await.unwrap();
 
         // Then the persistence's response is forwarded unchanged
         assert_eq!(events.len(), 1);
         assert_eq!(events[0].id, EventId(8));
+        assert!(has_more);
     }
 
     #[tokio::test]
-    async fn emit_no_events_for_duplicates() {
-        // Given
+    async fn slow_queries_are_logged_with_their_operation() {
+        // Given a persistence layer taking 20ms to fetch events and a threshold of 1ms
+        struct SlowEventsPage;
+        impl ChatPersistence for SlowEventsPage {
+            async fn events_page(
+                &self,
+                _last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok((Vec::new(), false))
+            }
+        }
+        let logs = CapturedLogs::default();
+        let _guard = logs.capture(false);
+        let history = PersistentChat::new(SlowEventsPage)
+            .await
+            .unwrap()
+            .with_slow_query_threshold(Some(Duration::from_millis(1)));
+
+        // When fetching events
+        history.events_page(EventId(7), 10).await.unwrap();
+
+        // Then a warning names the operation
+        let logs = logs.text();
+        assert!(logs.contains("Slow query"), "{logs}");
+        assert!(logs.contains("operation=\"fetch\""), "{logs}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reading_events_fails_once_query_timeout_is_exceeded() {
+        // Given a persistence layer which never completes fetching events and a timeout of 1s
+        struct HangingEventsPage;
+        impl ChatPersistence for HangingEventsPage {
+            async fn max_event_id(&self) -> anyhow::Result<Option<EventId>> {
+                Ok(None)
+            }
+
+            async fn events_page(
+                &self,
+                _last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
+                std::future::pending().await
+            }
+        }
+        let history = PersistentChat::new(HangingEventsPage)
+            .await
+            .unwrap()
+            .with_query_timeout(Some(Duration::from_secs(1)));
+
+        // When fetching a page of events
+        let result = history.events_page(EventId(0), 10).await;
+
+        // Then reading fails, rather than waiting forever
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn last_event_before_precedes_first_event_at_timestamp() {
+        // Given a persistence layer whose first event at the timestamp is event 5
+        struct FirstEventAtStub;
+        impl ChatPersistence for FirstEventAtStub {
+            async fn max_event_id(&self) -> anyhow::Result<Option<EventId>> {
+                Ok(Some(EventId(9)))
+            }
+
+            async fn first_event_at(&self, timestamp_ms: u64) -> anyhow::Result<Option<EventId>> {
+                assert_eq!(timestamp_ms, 1_000);
+                Ok(Some(EventId(5)))
+            }
+        }
+        let history = PersistentChat::new(FirstEventAtStub).await.unwrap();
+
+        // When
+        let last_event_id = history.last_event_before(1_000).await.unwrap();
+
+        // Then resuming from the returned id starts with event 5
+        assert_eq!(last_event_id, EventId(4));
+    }
+
+    #[tokio::test]
+    async fn last_event_before_is_latest_event_if_all_events_are_older() {
+        // Given a persistence layer without events at or after the timestamp
+        struct AllOlderStub;
+        impl ChatPersistence for AllOlderStub {
+            async fn max_event_id(&self) -> anyhow::Result<Option<EventId>> {
+                Ok(Some(EventId(9)))
+            }
+
+            async fn first_event_at(&self, _timestamp_ms: u64) -> anyhow::Result<Option<EventId>> {
+                Ok(None)
+            }
+        }
+        let history = PersistentChat::new(AllOlderStub).await.unwrap();
+
+        // When
+        let last_event_id = history.last_event_before(1_000).await.unwrap();
+
+        // Then resuming from the returned id only yields new events
+        assert_eq!(last_event_id, EventId(9));
+    }
+
+    #[tokio::test]
+    async fn duplicate_yields_originally_recorded_event() {
+        // Given a persistence layer which recorded the message as event 3 before
         struct DuplicateStub;
         impl ChatPersistence for DuplicateStub {
-            async fn insert_event(&self, _event: &Event) -> anyhow::Result<InsertOutcome> {
-                Ok(InsertOutcome::Duplicate)
+            async fn insert_event(
+                &self,
+                event: &Event,
+                _encoding: ContentEncoding,
+            ) -> anyhow::Result<InsertOutcome> {
+                Ok(InsertOutcome::Duplicate(Event::with_timestamp(
+                    EventId(3),
+                    event.message.clone(),
+                    UNIX_EPOCH,
+                )))
             }
         }
-        let mut history = PersistentChat::new(DuplicateStub).await.unwrap();
+        let history = PersistentChat::new(DuplicateStub).await.unwrap();
 
         // When inserting a message reported to be a duplicate
-        let maybe_event = history.record_message(Message::dummy()).await.unwrap();
+        let outcome = history.record_message(Message::dummy()).await.unwrap();
 
-        // Then no event is emitted
-        assert!(maybe_event.is_none());
+        // Then no new event is emitted, but the original one is reported
+        let AddOutcome::Duplicate(event) = outcome else {
+            panic!("Message must be reported as duplicate");
+        };
+        assert_eq!(EventId(3), event.id);
+        assert_eq!(0, event.timestamp_ms);
     }
 
     #[tokio::test]
@@ -137,11 +991,15 @@ mod tests {
         // Given a persistence layer reporting the message as conflicting
         struct ConflictStub;
         impl ChatPersistence for ConflictStub {
-            async fn insert_event(&self, _event: &Event) -> anyhow::Result<InsertOutcome> {
+            async fn insert_event(
+                &self,
+                _event: &Event,
+                _encoding: ContentEncoding,
+            ) -> anyhow::Result<InsertOutcome> {
                 Ok(InsertOutcome::Conflict)
             }
         }
-        let mut history = PersistentChat::new(ConflictStub).await.unwrap();
+        let history = PersistentChat::new(ConflictStub).await.unwrap();
 
         // When recording the message which is reported as conflict
         let result = history.record_message(Message::dummy()).await;
@@ -150,16 +1008,472 @@ mod tests {
         assert!(matches!(result, Err(ChatError::Conflict)));
     }
 
+    #[tokio::test]
+    async fn full_storage_emits_error() {
+        // Given a persistence layer without any room left
+        struct StorageFullStub;
+        impl ChatPersistence for StorageFullStub {
+            async fn insert_event(
+                &self,
+                _event: &Event,
+                _encoding: ContentEncoding,
+            ) -> anyhow::Result<InsertOutcome> {
+                Ok(InsertOutcome::StorageFull)
+            }
+        }
+        let history = PersistentChat::new(StorageFullStub).await.unwrap();
+
+        // When recording a message
+        let result = history.record_message(Message::dummy()).await;
+
+        // Then the full storage is reported distinct from other internal errors
+        assert!(matches!(result, Err(ChatError::StorageFull)));
+    }
+
+    #[tokio::test]
+    async fn oversized_content_is_rejected() {
+        // Given
+        let history = PersistentChat::new(Dummy).await.unwrap();
+
+        // When recording a message with content exceeding the ceiling by one byte
+        let result = history
+            .record_message(Message {
+                content: "a".repeat(MAX_CONTENT_BYTES + 1),
+                ..Message::dummy()
+            })
+            .await;
+
+        // Then it is rejected as too large
+        assert!(matches!(result, Err(ChatError::TooLarge)));
+    }
+
+    #[tokio::test]
+    async fn rapid_resends_under_different_ids_are_duplicates_within_dedup_window() {
+        // Given a chat deduplicating messages within five seconds
+        let persistence = ClientBuilder::new().open().await.unwrap();
+        persistence
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let history = PersistentChat::new(persistence)
+            .await
+            .unwrap()
+            .with_dedup_window(Some(Duration::from_secs(5)));
+
+        // When the same sender sends the same content twice, each time under a fresh id
+        let first = history
+            .record_message(Message {
+                id: MessageId::new(),
+                ..Message::dummy()
+            })
+            .await
+            .unwrap();
+        let second = history
+            .record_message(Message {
+                id: MessageId::new(),
+                ..Message::dummy()
+            })
+            .await
+            .unwrap();
+
+        // Then the resend yields the event of the first message, which is the only one recorded
+        let AddOutcome::New(first) = first else {
+            panic!("First message must be new");
+        };
+        assert_eq!(AddOutcome::Duplicate(first), second);
+        let (events, _) = history
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(1, events.len());
+    }
+
+    #[tokio::test]
+    async fn reused_id_conflicts_even_if_content_matches_recent_message() {
+        // Given a chat deduplicating messages within five seconds, with two messages recorded
+        let persistence = ClientBuilder::new().open().await.unwrap();
+        persistence
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let history = PersistentChat::new(persistence)
+            .await
+            .unwrap()
+            .with_dedup_window(Some(Duration::from_secs(5)));
+        for (id, content) in [(MessageId::ALPHA, "Hello"), (MessageId::BETA, "World")] {
+            history
+                .record_message(Message {
+                    id,
+                    content: content.to_owned(),
+                    ..Message::dummy()
+                })
+                .await
+                .unwrap();
+        }
+
+        // When the id of the second message is sent again, with the content of the first one
+        let result = history
+            .record_message(Message {
+                id: MessageId::BETA,
+                content: "Hello".to_owned(),
+                ..Message::dummy()
+            })
+            .await;
+
+        // Then it is reported as conflict, rather than as duplicate of the first one
+        assert!(matches!(result, Err(ChatError::Conflict)));
+    }
+
+    #[tokio::test]
+    async fn different_content_is_recorded_within_dedup_window() {
+        // Given a chat deduplicating messages within five seconds
+        let persistence = ClientBuilder::new().open().await.unwrap();
+        persistence
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let history = PersistentChat::new(persistence)
+            .await
+            .unwrap()
+            .with_dedup_window(Some(Duration::from_secs(5)));
+
+        // When the same sender sends two different messages right after each other
+        for content in ["Hello", "World"] {
+            history
+                .record_message(Message {
+                    id: MessageId::new(),
+                    content: content.to_owned(),
+                    ..Message::dummy()
+                })
+                .await
+                .unwrap();
+        }
+
+        // Then both are recorded
+        let (events, _) = history
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(2, events.len());
+    }
+
+    #[tokio::test]
+    async fn tombstoned_events_are_forgotten_in_memory() {
+        // Given a chat keeping events in memory, with a message of Alice and one of Bob
+        let persistence = ClientBuilder::new().open().await.unwrap();
+        persistence
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let history = PersistentChat::new(persistence)
+            .await
+            .unwrap()
+            .with_recent_events(NonZeroUsize::new(10));
+        for author in [UserId::ALICE, UserId::BOB] {
+            history
+                .record_message(Message {
+                    id: MessageId::new(),
+                    author,
+                    ..Message::dummy()
+                })
+                .await
+                .unwrap();
+        }
+
+        // When tombstoning the messages of Alice
+        let tombstones = history.tombstone_sender(UserId::ALICE).await.unwrap();
+
+        // Then only the message of Bob is read, even if answered from memory
+        assert_eq!(1, tombstones.len());
+        let (events, _) = history
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+        let authors: Vec<_> = events.iter().map(|e| e.message.author).collect();
+        assert_eq!(vec![UserId::BOB], authors);
+    }
+
+    #[tokio::test]
+    async fn new_senders_are_rejected_once_seats_are_taken() {
+        // Given a chat with two seats
+        let persistence = ClientBuilder::new().open().await.unwrap();
+        persistence
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let history = PersistentChat::new(persistence)
+            .await
+            .unwrap()
+            .with_max_senders(NonZeroUsize::new(2));
+        let message_of = |author| Message {
+            id: MessageId::new(),
+            author,
+            ..Message::dummy()
+        };
+
+        // When Alice and Bob post, followed by a third sender and Alice again
+        let alice = history.record_message(message_of(UserId::ALICE)).await;
+        let bob = history.record_message(message_of(UserId::BOB)).await;
+        let third = history.record_message(message_of(UserId::nil())).await;
+        let alice_again = history.record_message(message_of(UserId::ALICE)).await;
+
+        // Then only the third sender is rejected
+        assert!(matches!(alice, Ok(AddOutcome::New(_))));
+        assert!(matches!(bob, Ok(AddOutcome::New(_))));
+        assert!(matches!(third, Err(ChatError::SeatLimitReached)));
+        assert!(matches!(alice_again, Ok(AddOutcome::New(_))));
+    }
+
+    #[tokio::test]
+    async fn seats_are_released_once_messages_of_sender_expire() {
+        // Given a chat with one seat, taken by Alice
+        let persistence = ClientBuilder::new().open().await.unwrap();
+        persistence
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let history = PersistentChat::new(persistence)
+            .await
+            .unwrap()
+            .with_max_senders(NonZeroUsize::new(1));
+        let message_of = |author| Message {
+            id: MessageId::new(),
+            author,
+            ..Message::dummy()
+        };
+        history
+            .record_message(message_of(UserId::ALICE))
+            .await
+            .unwrap();
+
+        // When all of her messages expire
+        history.tombstone_expired(0, Some(u64::MAX)).await.unwrap();
+
+        // Then Bob may take her seat
+        let bob = history.record_message(message_of(UserId::BOB)).await;
+        assert!(matches!(bob, Ok(AddOutcome::New(_))));
+    }
+
+    #[tokio::test]
+    async fn only_most_recent_events_are_retained() {
+        // Given a chat retaining three events
+        let persistence = ClientBuilder::new().open().await.unwrap();
+        persistence
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let history = PersistentChat::new(persistence)
+            .await
+            .unwrap()
+            .with_max_events(NonZeroU64::new(3));
+
+        // When recording six messages
+        for _ in 0..6 {
+            history
+                .record_message(Message {
+                    id: MessageId::new(),
+                    ..Message::dummy()
+                })
+                .await
+                .unwrap();
+        }
+
+        // Then only the last three remain, and new events continue with the next id
+        let ids: Vec<_> = history
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap()
+            .0
+            .into_iter()
+            .map(|event| event.id)
+            .collect();
+        assert_eq!(vec![EventId(4), EventId(5), EventId(6)], ids);
+        let next = history
+            .record_message(Message {
+                id: MessageId::new(),
+                ..Message::dummy()
+            })
+            .await
+            .unwrap();
+        let AddOutcome::New(next) = next else {
+            panic!("Message must be new");
+        };
+        assert_eq!(EventId(7), next.id);
+    }
+
+    #[tokio::test]
+    async fn messages_are_rejected_once_database_reaches_maximum_size() {
+        // Given a database capped to a few pages beyond its initial size
+        let persistence = ClientBuilder::new().open().await.unwrap();
+        persistence
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let initial_size = persistence.database_size().await.unwrap();
+        let history = PersistentChat::new(persistence)
+            .await
+            .unwrap()
+            .with_max_bytes(Some(initial_size + 16 * 1024));
+
+        // When recording messages until one is rejected
+        let mut recorded = 0;
+        let result = loop {
+            let result = history
+                .record_message(Message {
+                    id: MessageId::new(),
+                    content: "a".repeat(1024),
+                    ..Message::dummy()
+                })
+                .await;
+            if result.is_err() || recorded == 1000 {
+                break result;
+            }
+            recorded += 1;
+        };
+
+        // Then the storage is reported to be full, yet all recorded messages can still be read
+        assert!(matches!(result, Err(ChatError::StorageFull)));
+        assert!(
+            recorded > 0,
+            "Messages must be accepted until the cap is reached"
+        );
+        let (events, _) = history
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(recorded, events.len());
+    }
+
+    #[tokio::test]
+    async fn recent_resume_is_answered_from_memory() {
+        // Given a chat keeping recent events in memory, which recorded two messages
+        #[derive(Default)]
+        struct EventsPageSpy {
+            events_page_calls: AtomicUsize,
+        }
+        impl ChatPersistence for EventsPageSpy {
+            async fn max_event_id(&self) -> anyhow::Result<Option<EventId>> {
+                Ok(None)
+            }
+
+            async fn insert_event(
+                &self,
+                _event: &Event,
+                _encoding: ContentEncoding,
+            ) -> anyhow::Result<InsertOutcome> {
+                Ok(InsertOutcome::New)
+            }
+
+            async fn events_page(
+                &self,
+                _last_event_id: EventId,
+                _limit: usize,
+            ) -> anyhow::Result<(Vec<Event>, bool)> {
+                self.events_page_calls.fetch_add(1, Ordering::SeqCst);
+                Ok((Vec::new(), false))
+            }
+        }
+        let history = PersistentChat::new(EventsPageSpy::default())
+            .await
+            .unwrap()
+            .with_recent_events(NonZeroUsize::new(10));
+        for (id, content) in [(MessageId::ALPHA, "Hello"), (MessageId::BETA, "World")] {
+            let message = Message {
+                id,
+                author: UserId::ALICE,
+                content: content.to_owned(),
+                expires_at_ms: None,
+            };
+            history.record_message(message).await.unwrap();
+        }
+
+        // When a client resumes after the first message
+        let (events, more) = history.events_page(EventId(1), 100).await.unwrap();
+
+        // Then the second message is served without querying the persistence
+        assert_eq!(1, events.len());
+        assert_eq!(EventId(2), events[0].id);
+        assert!(!more);
+        assert_eq!(
+            0,
+            history.persistence.events_page_calls.load(Ordering::SeqCst)
+        );
+    }
+
+    #[test]
+    fn resume_before_events_in_memory_falls_back_to_persistence() {
+        // Given two events kept in memory, after the first one has been evicted
+        let mut recent = RecentEvents::new(NonZeroUsize::new(2).unwrap(), EventId(0));
+        for id in 1..=3 {
+            recent.push(Event::with_timestamp(
+                EventId(id),
+                Message::dummy(),
+                UNIX_EPOCH,
+            ));
+        }
+
+        // When resuming before the evicted event, and right after it
+        let before_evicted = recent.since(EventId(0), 100);
+        let after_evicted = recent.since(EventId(1), 100);
+
+        // Then only the latter can be answered from memory
+        assert!(before_evicted.is_none());
+        let (events, _) = after_evicted.unwrap();
+        assert_eq!(
+            vec![EventId(2), EventId(3)],
+            events.iter().map(|e| e.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn pinning_is_reflected_by_events_in_memory() {
+        // Given a chat keeping recent events in memory, which recorded a message
+        let persistence = ClientBuilder::new().open().await.unwrap();
+        persistence
+            .conn(|conn| migrate_chat_persistence(conn, 0))
+            .await
+            .unwrap();
+        let history = PersistentChat::new(persistence)
+            .await
+            .unwrap()
+            .with_recent_events(NonZeroUsize::new(10));
+        history
+            .record_message(Message {
+                id: MessageId::ALPHA,
+                ..Message::dummy()
+            })
+            .await
+            .unwrap();
+
+        // When pinning the message
+        let event_id = history.pin_message(MessageId::ALPHA, true).await.unwrap();
+
+        // Then clients resuming from memory see it pinned
+        assert_eq!(EventId(1), event_id);
+        let (events, _) = history
+            .events_page(EventId::before_all(), 10)
+            .await
+            .unwrap();
+        assert!(events[0].pinned);
+        // Unknown messages can not be pinned
+        let result = history.pin_message(MessageId::BETA, true).await;
+        assert!(matches!(result, Err(ChatError::NotFound)));
+    }
+
     #[tokio::test]
     async fn inserting_new_message() {
         // Given
         struct NewStub;
         impl ChatPersistence for NewStub {
-            async fn insert_event(&self, _event: &Event) -> anyhow::Result<InsertOutcome> {
+            async fn insert_event(
+                &self,
+                _event: &Event,
+                _encoding: ContentEncoding,
+            ) -> anyhow::Result<InsertOutcome> {
                 Ok(InsertOutcome::New)
             }
         }
-        let mut history = PersistentChat::new(NewStub).await.unwrap();
+        let history = PersistentChat::new(NewStub).await.unwrap();
         let start = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -170,6 +1484,7 @@ mod tests {
             id: MessageId::ALPHA,
             author: UserId::ALICE,
             content: "Hello".to_owned(),
+            expires_at_ms: None,
         };
         let event = history.record_message(message.clone()).await.unwrap();
 
@@ -178,8 +1493,9 @@ mod tests {
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_millis() as u64;
-        assert!(event.is_some());
-        let event = event.unwrap();
+        let AddOutcome::New(event) = event else {
+            panic!("Message must be new");
+        };
         assert_eq!(EventId(1), event.id);
         assert_eq!(message, event.message);
         assert!(start <= event.timestamp_ms && event.timestamp_ms <= stop);
@@ -191,11 +1507,16 @@ mod tests {
         struct InsertEventMock;
 
         impl ChatPersistence for InsertEventMock {
-            async fn insert_event(&self, event: &Event) -> anyhow::Result<InsertOutcome> {
+            async fn insert_event(
+                &self,
+                event: &Event,
+                _encoding: ContentEncoding,
+            ) -> anyhow::Result<InsertOutcome> {
                 let expected = Message {
                     id: MessageId::ALPHA,
                     author: UserId::ALICE,
                     content: "Hello".to_owned(),
+                    expires_at_ms: None,
                 };
 
                 assert_eq!(event.message, expected);
@@ -203,7 +1524,7 @@ mod tests {
                 Ok(InsertOutcome::New)
             }
         }
-        let mut history = PersistentChat::new(InsertEventMock).await.unwrap();
+        let history = PersistentChat::new(InsertEventMock).await.unwrap();
 
         // When recording a message
         history
@@ -211,6 +1532,7 @@ mod tests {
                 id: MessageId::ALPHA,
                 author: UserId::ALICE,
                 content: "Hello".to_owned(),
+                expires_at_ms: None,
             })
             .await
             .unwrap();
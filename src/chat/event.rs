@@ -5,9 +5,11 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::persistence::{Argument, AsArgument, FromField, GetFieldNative};
 
-use super::message::Message;
+use super::message::{Message, MessageId};
 
 /// A message as it is stored and represented as part of a chat.
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -16,6 +18,17 @@ pub struct Event {
     pub message: Message,
     /// Milliseconds since Unix epoch
     pub timestamp_ms: u64,
+    /// Pinned messages are listed separately, e.g. to keep announcements at hand. Unlike the rest
+    /// of the event, this may change after the event has been recorded.
+    pub pinned: bool,
+}
+
+/// What remains of an event once its message has been deleted, e.g. on request of its sender. The
+/// content is gone and the event is no longer read, yet its id is not reused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Tombstone {
+    pub event_id: EventId,
+    pub message_id: MessageId,
 }
 
 impl Event {
@@ -29,20 +42,24 @@ impl Event {
             id,
             message,
             timestamp_ms,
+            pinned: false,
         }
     }
 
+    #[cfg(test)]
     pub fn with_timestamp(id: EventId, message: Message, timestamp: SystemTime) -> Self {
         let timestamp_ms = timestamp.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
         Event {
             id,
             message,
             timestamp_ms,
+            pinned: false,
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct EventId(pub u64);
 
 impl EventId {
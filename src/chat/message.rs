@@ -27,6 +27,21 @@ impl MessageId {
         Self::from_uuid(Uuid::now_v7())
     }
 
+    /// A random message id, for messages originating from the server rather than a client.
+    pub fn random() -> Self {
+        Self::from_uuid(Uuid::new_v4())
+    }
+
+    /// The nil UUID. Never generated, so it is most likely a client forgetting to set the id.
+    pub fn is_nil(&self) -> bool {
+        self.0.is_nil()
+    }
+
+    /// UUID v7, i.e. ordered by the time it has been generated.
+    pub fn is_v7(&self) -> bool {
+        self.0.get_version_num() == 7
+    }
+
     #[cfg(test)]
     pub fn nil() -> Self {
         Self::from_uuid(Uuid::nil())
@@ -91,6 +106,9 @@ pub struct Message {
     pub author: UserId,
     /// Text content of the message. I.e. the actual message
     pub content: String,
+    /// Milliseconds since Unix epoch, after which the message is tombstoned. `None` if the sender
+    /// did not ask for the message to expire.
+    pub expires_at_ms: Option<u64>,
 }
 
 impl Message {
@@ -100,6 +118,7 @@ impl Message {
             id: MessageId::nil(),
             author: UserId::nil(),
             content: "dummy".to_owned(),
+            expires_at_ms: None,
         }
     }
 }
@@ -0,0 +1,277 @@
+use std::{collections::HashSet, ops::Range};
+
+use futures_util::future::{BoxFuture, FutureExt as _};
+
+use super::Message;
+
+/// Decides about every message before it is recorded. Implement it to plug custom moderation into
+/// the chat API, e.g. one asking an external service.
+pub trait Moderator: Send + Sync {
+    fn moderate<'a>(&'a self, message: &'a Message) -> BoxFuture<'a, Verdict>;
+}
+
+/// Decision of a [`Moderator`] about a message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Record the message as it is.
+    Allow,
+    /// Do not record the message. The reason is told to the sender.
+    Reject { reason: String },
+    /// Record the message with its content replaced.
+    Transform { content: String },
+}
+
+/// What the [`WordlistModerator`] does with messages containing a listed word.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WordlistAction {
+    /// The message is rejected.
+    #[default]
+    Reject,
+    /// Listed words are replaced with asterisks, before the message is recorded.
+    Mask,
+}
+
+/// Moderates messages based on a list of words. Words are matched case insensitive and only as a
+/// whole, so listing "ass" does not affect "class".
+pub struct WordlistModerator {
+    /// Lowercase
+    words: HashSet<String>,
+    action: WordlistAction,
+}
+
+impl WordlistModerator {
+    pub fn new(words: impl IntoIterator<Item = String>, action: WordlistAction) -> Self {
+        WordlistModerator {
+            words: words.into_iter().map(|word| word.to_lowercase()).collect(),
+            action,
+        }
+    }
+
+    fn verdict(&self, content: &str) -> Verdict {
+        let listed: Vec<_> = words(content)
+            .filter(|range| self.words.contains(&content[range.clone()].to_lowercase()))
+            .collect();
+        if listed.is_empty() {
+            return Verdict::Allow;
+        }
+        match self.action {
+            WordlistAction::Reject => Verdict::Reject {
+                reason: "Message contains a word which is not allowed in this chat".to_owned(),
+            },
+            WordlistAction::Mask => {
+                let mut masked = String::with_capacity(content.len());
+                let mut end_of_last = 0;
+                for range in listed {
+                    masked.push_str(&content[end_of_last..range.start]);
+                    let chars = content[range.clone()].chars().count();
+                    masked.extend(std::iter::repeat_n('*', chars));
+                    end_of_last = range.end;
+                }
+                masked.push_str(&content[end_of_last..]);
+                Verdict::Transform { content: masked }
+            }
+        }
+    }
+}
+
+impl Moderator for WordlistModerator {
+    fn moderate<'a>(&'a self, message: &'a Message) -> BoxFuture<'a, Verdict> {
+        let verdict = self.verdict(&message.content);
+        async move { verdict }.boxed()
+    }
+}
+
+/// Kinds of content refused in locked-down chats, e.g. an announcement-only room. Unlike a
+/// [`Moderator`], which decides about what is said, the policy concerns the form of a message, so
+/// the sender is told the message is invalid for this chat.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContentPolicy {
+    /// Refuse messages containing URLs. Recognizes common obfuscations like `hxxp://` or
+    /// `www[.]example.com`.
+    pub disallow_urls: bool,
+    /// Refuse messages mentioning someone with `@name`. E-mail addresses are not mentions.
+    pub disallow_mentions: bool,
+}
+
+impl ContentPolicy {
+    /// Why `content` is refused, or `None` if it complies with the policy.
+    pub fn violation(&self, content: &str) -> Option<&'static str> {
+        if self.disallow_urls && contains_url(content) {
+            Some("Message contains a URL, which is not allowed in this chat")
+        } else if self.disallow_mentions && contains_mention(content) {
+            Some("Message mentions someone, which is not allowed in this chat")
+        } else {
+            None
+        }
+    }
+}
+
+/// `true` if `content` contains anything following a scheme, like `https://`, or a host starting
+/// with `www.`. Defanged forms, as shared to keep links from being clicked, count as well.
+fn contains_url(content: &str) -> bool {
+    let content = content
+        .to_lowercase()
+        .replace("hxxp", "http")
+        .replace("[.]", ".")
+        .replace("(.)", ".")
+        .replace("[:]", ":");
+    let scheme = content.match_indices("://").any(|(index, _)| {
+        content[..index]
+            .chars()
+            .next_back()
+            .is_some_and(|char| char.is_ascii_alphabetic())
+    });
+    scheme
+        || words(&content)
+            .any(|range| &content[range.clone()] == "www" && content[range.end..].starts_with('.'))
+}
+
+/// `true` if `content` contains an `@` followed by a name, which is not part of a word like in an
+/// e-mail address.
+fn contains_mention(content: &str) -> bool {
+    content.match_indices('@').any(|(index, _)| {
+        let after_word = content[..index]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric);
+        let names = content[index + 1..]
+            .chars()
+            .next()
+            .is_some_and(|char| char.is_alphanumeric() || char == '_');
+        !after_word && names
+    })
+}
+
+/// Byte ranges of the words in `content`, i.e. of the runs of alphanumeric characters.
+fn words(content: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut start = None;
+    content
+        .char_indices()
+        .chain([(content.len(), ' ')])
+        .filter_map(move |(index, char)| match (start, char.is_alphanumeric()) {
+            (None, true) => {
+                start = Some(index);
+                None
+            }
+            (Some(begin), false) => {
+                start = None;
+                Some(begin..index)
+            }
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chat::Message;
+
+    use super::{ContentPolicy, Moderator as _, Verdict, WordlistAction, WordlistModerator};
+
+    #[tokio::test]
+    async fn messages_without_listed_words_are_allowed() {
+        // Given a moderator rejecting "darn"
+        let moderator = WordlistModerator::new(["darn".to_owned()], WordlistAction::Reject);
+
+        // When moderating a message containing it only as part of another word
+        let verdict = moderator.moderate(&message("Darning socks")).await;
+
+        // Then the message is allowed
+        assert_eq!(Verdict::Allow, verdict);
+    }
+
+    #[tokio::test]
+    async fn messages_with_listed_words_are_rejected() {
+        // Given a moderator rejecting "darn"
+        let moderator = WordlistModerator::new(["darn".to_owned()], WordlistAction::Reject);
+
+        // When moderating a message containing it, in upper case
+        let verdict = moderator.moderate(&message("DARN it!")).await;
+
+        // Then the message is rejected
+        assert!(matches!(verdict, Verdict::Reject { .. }));
+    }
+
+    #[tokio::test]
+    async fn listed_words_are_masked() {
+        // Given a moderator masking "darn"
+        let moderator = WordlistModerator::new(["darn".to_owned()], WordlistAction::Mask);
+
+        // When moderating a message containing it twice
+        let verdict = moderator.moderate(&message("Darn, darn it!")).await;
+
+        // Then both occurrences are replaced with asterisks
+        assert_eq!(
+            Verdict::Transform {
+                content: "****, **** it!".to_owned()
+            },
+            verdict
+        );
+    }
+
+    #[test]
+    fn content_without_urls_or_mentions_complies_with_strictest_policy() {
+        // Given a policy refusing both URLs and mentions
+        let policy = ContentPolicy {
+            disallow_urls: true,
+            disallow_mentions: true,
+        };
+
+        // When checking plain text, an e-mail address and a ratio
+        let violations = [
+            "Meeting moved to 3pm",
+            "Write to alice@example.com",
+            "Aspect ratio 16:9, see www",
+        ]
+        .map(|content| policy.violation(content));
+
+        // Then all of them comply
+        assert_eq!([None, None, None], violations);
+    }
+
+    #[test]
+    fn urls_are_refused_if_disallowed() {
+        // Given a policy refusing URLs, but not mentions
+        let policy = ContentPolicy {
+            disallow_urls: true,
+            disallow_mentions: false,
+        };
+
+        // When checking plain, defanged and scheme-less URLs
+        let urls = [
+            "See https://example.com",
+            "See hxxps://example[.]com",
+            "See WWW.example.com",
+        ];
+
+        // Then each of them is refused, yet mentions are not
+        for content in urls {
+            assert!(policy.violation(content).is_some(), "{content}");
+        }
+        assert_eq!(None, policy.violation("Hi @alice"));
+    }
+
+    #[test]
+    fn mentions_are_refused_if_disallowed() {
+        // Given a policy refusing mentions, but not URLs
+        let policy = ContentPolicy {
+            disallow_urls: false,
+            disallow_mentions: true,
+        };
+
+        // When checking mentions at the start and within a message
+        let mentions = ["@alice hi", "Thanks, @bob_1!"];
+
+        // Then each of them is refused, yet URLs are not
+        for content in mentions {
+            assert!(policy.violation(content).is_some(), "{content}");
+        }
+        assert_eq!(None, policy.violation("See https://example.com"));
+    }
+
+    fn message(content: &str) -> Message {
+        Message {
+            content: content.to_owned(),
+            ..Message::dummy()
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use pulldown_cmark::{Options, Parser, html::push_html};
+
+/// Renders the markdown content of a message to HTML. The result is sanitized, so it can be
+/// inserted into a page as is. Raw HTML in the content, like `<script>` or event handler
+/// attributes, is stripped.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH);
+    let mut html = String::with_capacity(markdown.len() * 3 / 2);
+    push_html(&mut html, parser);
+    ammonia::clean(&html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::markdown_to_html;
+
+    #[test]
+    fn markdown_is_converted() {
+        // When rendering emphasis and a link
+        let html = markdown_to_html("**Hello** [world](https://example.com)");
+
+        // Then they are converted to the corresponding elements
+        assert_eq!(
+            "<p><strong>Hello</strong> <a href=\"https://example.com\" \
+            rel=\"noopener noreferrer\">world</a></p>\n",
+            html
+        );
+    }
+
+    #[test]
+    fn scripts_are_stripped() {
+        // When rendering content with a script and an event handler
+        let html = markdown_to_html("Hi<script>alert(1)</script> <img src=x onerror=alert(1)>");
+
+        // Then neither survives
+        assert!(!html.contains("script"));
+        assert!(!html.contains("alert"));
+        assert!(!html.contains("onerror"));
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fmt::Write as _, str::FromStr, sync::Arc};
+
+use hmac::{Hmac, Mac as _};
+use serde::{Deserialize, Deserializer, de::Error as _};
+use sha2::Sha256;
+
+use crate::user::UserId;
+
+use super::EventId;
+
+/// Signs the positions clients resume the event stream from, so a client can only resume from
+/// positions issued to the same user. Otherwise any client could pass any event id as
+/// `Last-Event-ID`.
+#[derive(Clone)]
+pub struct ResumeSigner {
+    key: Arc<[u8]>,
+}
+
+impl ResumeSigner {
+    pub fn new(key: &[u8]) -> Self {
+        ResumeSigner { key: key.into() }
+    }
+
+    fn mac(&self, event_id: EventId, user_id: UserId) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC must accept keys of any length");
+        mac.update(&event_id.0.to_be_bytes());
+        mac.update(user_id.as_bytes());
+        mac
+    }
+}
+
+/// Issues and checks the positions a single user resumes the event stream from.
+pub struct ResumeIds {
+    /// `None` if resume positions are not signed, i.e. they are plain event ids.
+    signer: Option<ResumeSigner>,
+    user_id: UserId,
+}
+
+impl ResumeIds {
+    pub fn new(signer: Option<ResumeSigner>, user_id: UserId) -> Self {
+        ResumeIds { signer, user_id }
+    }
+
+    /// `true` if positions are signed, rather than plain event ids.
+    pub fn is_signed(&self) -> bool {
+        self.signer.is_some()
+    }
+
+    /// The position to resume after `event_id` from. Formatted `<event id>.<hex encoded HMAC>` if
+    /// signed.
+    pub fn issue(&self, event_id: EventId) -> String {
+        let Some(signer) = &self.signer else {
+            return event_id.to_string();
+        };
+        let signature = signer.mac(event_id, self.user_id).finalize().into_bytes();
+        let mut token = format!("{event_id}.");
+        for byte in signature {
+            write!(token, "{byte:02x}").expect("Writing to a string must not fail");
+        }
+        token
+    }
+
+    /// The event id to resume after. `None` if the position has not been issued to this user,
+    /// i.e. it has been tampered with or is lacking its signature. Signatures are ignored, if
+    /// positions are not signed.
+    pub fn verify(&self, position: &ResumePosition) -> Option<EventId> {
+        let Some(signer) = &self.signer else {
+            return Some(position.event_id);
+        };
+        let signature = position.signature.as_deref()?;
+        signer
+            .mac(position.event_id, self.user_id)
+            .verify_slice(signature)
+            .ok()?;
+        Some(position.event_id)
+    }
+}
+
+/// A position in the event stream as passed by a client in `Last-Event-ID`, or to the poll route.
+/// Either a plain event id, or a signed one as issued by [`ResumeIds::issue`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResumePosition {
+    event_id: EventId,
+    signature: Option<Vec<u8>>,
+}
+
+/// Neither a plain event id, nor a signed one.
+#[derive(Debug)]
+pub struct InvalidResumePosition;
+
+impl FromStr for ResumePosition {
+    type Err = InvalidResumePosition;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (event_id, signature) = match s.split_once('.') {
+            Some((event_id, signature)) => (event_id, Some(decode_hex(signature)?)),
+            None => (s, None),
+        };
+        let event_id = event_id.parse().map_err(|_| InvalidResumePosition)?;
+        Ok(ResumePosition {
+            event_id,
+            signature,
+        })
+    }
+}
+
+/// Deserialized from its string representation, e.g. as query parameter.
+impl<'de> Deserialize<'de> for ResumePosition {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let position = String::deserialize(deserializer)?;
+        position
+            .parse()
+            .map_err(|_| D::Error::custom("neither a plain nor a signed event id"))
+    }
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, InvalidResumePosition> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(InvalidResumePosition);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| InvalidResumePosition)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{chat::EventId, user::UserId};
+
+    use super::{ResumeIds, ResumePosition, ResumeSigner};
+
+    #[test]
+    fn issued_positions_are_verified() {
+        // Given signed resume positions
+        let ids = ResumeIds::new(Some(ResumeSigner::new(b"secret")), UserId::ALICE);
+
+        // When parsing and verifying an issued position
+        let position: ResumePosition = ids.issue(EventId(42)).parse().unwrap();
+
+        // Then it resumes after the event it has been issued for
+        assert_eq!(Some(EventId(42)), ids.verify(&position));
+    }
+
+    #[test]
+    fn tampered_positions_are_rejected() {
+        // Given signed resume positions
+        let ids = ResumeIds::new(Some(ResumeSigner::new(b"secret")), UserId::ALICE);
+
+        // When replacing the event id of an issued position
+        let issued = ids.issue(EventId(42));
+        let (_, signature) = issued.split_once('.').unwrap();
+        let tampered: ResumePosition = format!("41.{signature}").parse().unwrap();
+
+        // Then it is rejected, and so are a plain event id and a position issued to another user
+        assert_eq!(None, ids.verify(&tampered));
+        assert_eq!(None, ids.verify(&"42".parse().unwrap()));
+        let other = ResumeIds::new(Some(ResumeSigner::new(b"secret")), UserId::BOB);
+        assert_eq!(None, other.verify(&issued.parse().unwrap()));
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::bail;
+
+use crate::user::UserId;
+
+use super::{
+    Message, MessageId,
+    chat_persistence::{ChatPersistence, InsertOutcome},
+};
+
+/// Content of the probe message recorded by [`self_check`].
+const PROBE_CONTENT: &str = "klatsch self check";
+
+/// Records a probe message, reads it back and deletes it again. Fails if any of these steps does
+/// not work, e.g. because the database is read only. Catches a misconfigured storage during
+/// startup, rather than with the first message of a user. The probe goes to the storage directly
+/// within one transaction, so it neither takes a seat of a sender, nor prunes or skips events. It
+/// is sent by the reserved [`UserId::SELF_CHECK`].
+pub async fn self_check(persistence: &impl ChatPersistence) -> anyhow::Result<()> {
+    let probe = Message {
+        id: MessageId::random(),
+        author: UserId::SELF_CHECK,
+        content: PROBE_CONTENT.to_owned(),
+        expires_at_ms: None,
+    };
+    let (outcome, content) = match persistence.probe(probe).await {
+        Ok(probed) => probed,
+        Err(err) => bail!("Failed to record self check probe: {err:?}"),
+    };
+    match outcome {
+        InsertOutcome::New => (),
+        InsertOutcome::Duplicate(_) | InsertOutcome::Conflict => {
+            bail!("Self check probe has been recorded before")
+        }
+        InsertOutcome::StorageFull => bail!("No room left in the storage for self check probe"),
+    }
+    if content.as_deref() != Some(PROBE_CONTENT) {
+        bail!("Self check probe has not been read back after recording it");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::{NonZeroU64, NonZeroUsize};
+
+    use crate::{
+        chat::{
+            AddOutcome, Chat, ChatRuntime, ChatStoreOptions, EventId, Message, MessageId,
+            chat_persistence::{ChatPersistence, InsertOutcome},
+        },
+        persistence::{SqlitePersistence, migrate},
+        user::UserId,
+    };
+
+    use super::self_check;
+
+    #[tokio::test]
+    async fn healthy_database_passes_self_check() {
+        // Given a healthy database
+        let persistence = SqlitePersistence::new(None, migrate).await.unwrap();
+
+        // When running the self check
+        let result = self_check(&persistence.client()).await;
+
+        // Then it passes, without leaving the probe behind
+        assert!(result.is_ok(), "{result:?}");
+        let (events, _) = persistence
+            .client()
+            .events_page(EventId::before_all(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn self_check_neither_takes_a_seat_nor_prunes_events() {
+        // Given a chat retaining a single event of a single sender, which Alice already posted
+        let persistence = SqlitePersistence::new(None, migrate).await.unwrap();
+        let options = ChatStoreOptions {
+            max_events: Some(NonZeroU64::new(1).unwrap()),
+            max_senders: Some(NonZeroUsize::new(1).unwrap()),
+            ..ChatStoreOptions::default()
+        };
+        let chat = ChatRuntime::new(persistence.client(), options)
+            .await
+            .unwrap();
+        let mut client = chat.client();
+        let hello = Message {
+            id: MessageId::ALPHA,
+            author: UserId::ALICE,
+            content: "Hello".to_owned(),
+            expires_at_ms: None,
+        };
+        client.add_message(hello.clone()).await.unwrap();
+
+        // When running the self check
+        let result = self_check(&persistence.client()).await;
+
+        // Then it passes, Alice's message is retained, and her next one follows it without gap
+        assert!(result.is_ok(), "{result:?}");
+        let (history, _) = client
+            .clone()
+            .history(EventId::before_all(), 10)
+            .await
+            .unwrap();
+        assert_eq!(1, history.len());
+        assert_eq!(hello, history[0].message);
+        let next = Message {
+            id: MessageId::BETA,
+            ..hello
+        };
+        let Ok(AddOutcome::New(event)) = client.add_message(next).await else {
+            panic!("Alice must still be able to post");
+        };
+        assert_eq!(EventId(2), event.id);
+
+        // Cleanup
+        drop(client);
+        chat.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn read_only_database_fails_self_check() {
+        // Given a read only database
+        let persistence = SqlitePersistence::new(None, migrate).await.unwrap();
+        persistence
+            .client()
+            .conn(|conn| conn.pragma_update(None, "query_only", true))
+            .await
+            .unwrap();
+
+        // When running the self check
+        let result = self_check(&persistence.client()).await;
+
+        // Then it fails
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn losing_the_probe_fails_self_check() {
+        // Given a storage which acknowledges messages, but does not keep them
+        struct Forgetful;
+        impl ChatPersistence for Forgetful {
+            async fn probe(
+                &self,
+                _message: Message,
+            ) -> anyhow::Result<(InsertOutcome, Option<String>)> {
+                Ok((InsertOutcome::New, None))
+            }
+        }
+
+        // When running the self check
+        let result = self_check(&Forgetful).await;
+
+        // Then it fails
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use crate::user::UserId;
+
+/// Keeps track of the open event streams, so operators can tell who is connected.
+#[derive(Default)]
+pub struct Subscribers {
+    next_key: AtomicU64,
+    connected: Mutex<HashMap<u64, Subscriber>>,
+}
+
+/// A client following the event stream.
+#[derive(Clone, Copy, Debug)]
+pub struct Subscriber {
+    pub user_id: UserId,
+    pub connected_at: Instant,
+}
+
+impl Subscribers {
+    /// Registers a subscriber until the returned guard is dropped.
+    pub fn register(self: &Arc<Self>, user_id: UserId) -> SubscriberGuard {
+        let key = self.next_key.fetch_add(1, Ordering::Relaxed);
+        let subscriber = Subscriber {
+            user_id,
+            connected_at: Instant::now(),
+        };
+        self.connected.lock().unwrap().insert(key, subscriber);
+        SubscriberGuard {
+            subscribers: self.clone(),
+            key,
+        }
+    }
+
+    /// The subscribers connected right now, longest connected first.
+    pub fn snapshot(&self) -> Vec<Subscriber> {
+        let mut subscribers: Vec<_> = self.connected.lock().unwrap().values().copied().collect();
+        subscribers.sort_by_key(|subscriber| subscriber.connected_at);
+        subscribers
+    }
+}
+
+/// Unregisters its subscriber once dropped, i.e. once the event stream is closed.
+pub struct SubscriberGuard {
+    subscribers: Arc<Subscribers>,
+    key: u64,
+}
+
+impl Drop for SubscriberGuard {
+    fn drop(&mut self) {
+        self.subscribers.connected.lock().unwrap().remove(&self.key);
+    }
+}
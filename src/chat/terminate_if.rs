@@ -1,9 +1,7 @@
-use std::pin::pin;
+use std::future::pending;
 
-use async_stream::stream;
 use futures_util::Stream;
-use tokio::{select, sync::watch};
-use tokio_stream::StreamExt;
+use tokio::sync::watch;
 
 /// Wrap a stream to terminate when the watch signal becomes `true`. If the sender is dropped the
 /// remaining items are forwarded.
@@ -11,43 +9,29 @@ pub fn terminate_if<I>(
     org: impl Stream<Item = I>,
     mut signal: watch::Receiver<bool>,
 ) -> impl Stream<Item = I> {
-    stream! {
-        let mut org = pin!(org);
+    let terminated = async move {
         loop {
-            select! {
-                biased;
-                result = signal.changed() => {
-                    match result {
-                        // Signal true; Terminate stream.
-                        Ok(()) if *signal.borrow_and_update() => break,
-                        // Signal false; Do nothing.
-                        Ok(()) => {}
-                        // Sender dropped; Assume signal never becomes `true`. Forward remaining
-                        // items.
-                        Err(_) => {
-                            while let Some(item) = org.next().await {
-                                yield item;
-                            }
-                            break;
-                        }
-                    }
-                }
-                maybe_item = org.next() => {
-                    if let Some(item) = maybe_item {
-                        yield item;
-                    } else {
-                        break;
-                    }
-                }
+            match signal.changed().await {
+                // Signal true; Terminate stream.
+                Ok(()) if *signal.borrow_and_update() => break,
+                // Signal false; Do nothing.
+                Ok(()) => {}
+                // Sender dropped; Assume signal never becomes `true`. Forward remaining items.
+                Err(_) => pending().await,
             }
         }
-    }
+    };
+    // The signal is checked before each item, so termination is immediate. Unlike wrapping the
+    // stream in a generator, this does not hold a second copy of it.
+    futures_util::StreamExt::take_until(org, terminated)
 }
 
 #[cfg(test)]
 mod tests {
     use std::time::Duration;
 
+    use std::pin::pin;
+
     use super::*;
     use futures_util::stream;
     use tokio::time::timeout;
@@ -0,0 +1,97 @@
+use std::{
+    num::NonZeroU32,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Limits how often something happens to `refill_per_sec` times a second on average, while allowing
+/// bursts of up to `capacity`. The bucket starts out full.
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: Mutex<Tokens>,
+}
+
+struct Tokens {
+    available: f64,
+    /// Point in time `available` has last been refilled.
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: NonZeroU32, refill_per_sec: NonZeroU32, now: Instant) -> Self {
+        let capacity = f64::from(capacity.get());
+        TokenBucket {
+            capacity,
+            refill_per_sec: f64::from(refill_per_sec.get()),
+            tokens: Mutex::new(Tokens {
+                available: capacity,
+                refilled: now,
+            }),
+        }
+    }
+
+    /// Takes a token, if one is available at `now`. Otherwise the error tells how long it takes
+    /// until the next one is.
+    pub fn try_take(&self, now: Instant) -> Result<(), Duration> {
+        let mut tokens = self.tokens.lock().unwrap();
+        let elapsed = now.saturating_duration_since(tokens.refilled);
+        tokens.available =
+            (tokens.available + elapsed.as_secs_f64() * self.refill_per_sec).min(self.capacity);
+        tokens.refilled = now;
+        if tokens.available >= 1.0 {
+            tokens.available -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - tokens.available;
+            Err(Duration::from_secs_f64(missing / self.refill_per_sec))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        num::NonZeroU32,
+        time::{Duration, Instant},
+    };
+
+    use super::TokenBucket;
+
+    #[test]
+    fn bursts_up_to_capacity_are_allowed() {
+        // Given a full bucket of three tokens, refilled with one token per second
+        let start = Instant::now();
+        let bucket = TokenBucket::new(
+            NonZeroU32::new(3).unwrap(),
+            NonZeroU32::new(1).unwrap(),
+            start,
+        );
+
+        // When taking four tokens at once
+        let taken: Vec<_> = (0..4).map(|_| bucket.try_take(start)).collect();
+
+        // Then the first three succeed and the last one is told to wait for the refill
+        assert!(taken[..3].iter().all(Result::is_ok));
+        assert_eq!(Err(Duration::from_secs(1)), taken[3]);
+    }
+
+    #[test]
+    fn tokens_are_refilled_over_time() {
+        // Given an empty bucket, refilled with two tokens per second
+        let start = Instant::now();
+        let bucket = TokenBucket::new(
+            NonZeroU32::new(1).unwrap(),
+            NonZeroU32::new(2).unwrap(),
+            start,
+        );
+        bucket.try_take(start).unwrap();
+
+        // When waiting for half a second
+        let later = start + Duration::from_millis(500);
+
+        // Then one more token is available, but not two
+        assert_eq!(Ok(()), bucket.try_take(later));
+        assert!(bucket.try_take(later).is_err());
+    }
+}
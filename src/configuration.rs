@@ -1,13 +1,30 @@
 use std::{
+    collections::HashSet,
     env::{self, VarError},
+    fs,
+    num::{NonZeroU32, NonZeroU64, NonZeroUsize},
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Arc,
     time::Duration,
 };
 
 use anyhow::{Context, anyhow};
+use axum::http::HeaderValue;
 
-use crate::sessions::SessionExpiry;
+use crate::{
+    chat::{
+        ChatHttpOptions, ContentPolicy, MessageRate, Moderator, OnLag, ResumeSigner, UuidPolicy,
+        WordlistAction, WordlistModerator,
+    },
+    persistence::Durability,
+    server::{RootResponse, ServerOptions, TcpKeepalive},
+    sessions::SessionExpiry,
+    user::NameNormalization,
+};
+
+/// Backlog of the listening socket if LISTEN_BACKLOG is not set. Same as the default of tokio.
+const DEFAULT_LISTEN_BACKLOG: u32 = 1024;
 
 /// Session idle timeout if SESSION_IDLE_TIMEOUT is not set.
 const DEFAULT_SESSION_IDLE_TIMEOUT: Duration = Duration::from_hours(3 * 24);
@@ -18,21 +35,100 @@ const DEFAULT_SESSION_MAX_LIFETIME: Duration = Duration::from_hours(30 * 24);
 /// All static configuration for the application. I.e. configuration which does not change during
 /// the runtime without a restart.
 pub struct Configuration {
-    /// The port we bind to.
-    port: u16,
-    /// Host name or IP address to bind to.
-    host: String,
+    /// Host names or IP addresses and ports to bind to. Each of them serves the same application.
+    listen_addrs: Vec<(String, u16)>,
+    /// Options for accepting and serving connections.
+    server_options: ServerOptions,
     /// Directory for persistent storage. If not set, the database is in-memory only.
     persistence_dir: Option<PathBuf>,
     /// When sessions expire.
     session_expiry: SessionExpiry,
+    /// Options shaping the chat API.
+    chat_http_options: ChatHttpOptions,
+    /// Interval for checkpointing the write ahead log in the background. `None` leaves checkpoints
+    /// to SQLite.
+    wal_checkpoint_interval: Option<Duration>,
+    /// When writes to the database are acknowledged.
+    durability: Durability,
+    /// Prepare frequently used statements during startup, rather than on first use.
+    db_warmup: bool,
+    /// Record, read back and delete a probe message during startup.
+    startup_selfcheck: bool,
+    /// Back up the database before migrating its schema.
+    migration_backup: bool,
+    /// New messages are rejected once the database reaches this size in bytes. `None` does not cap
+    /// the size.
+    max_db_bytes: Option<u64>,
+    /// Store the content of new messages compressed.
+    db_compress_content: bool,
+    /// Only the most recent events are retained. `None` retains all of them.
+    max_events: Option<NonZeroU64>,
+    /// Number of most recent events kept in memory, if any.
+    recent_events: Option<NonZeroUsize>,
+    /// Messages resent with the same content within this window are duplicates, if set.
+    dedup_window: Option<Duration>,
+    /// Database operations taking longer are logged, if set.
+    slow_query_threshold: Option<Duration>,
+    /// Reading events since an event fails once it takes longer, if set.
+    query_timeout: Option<Duration>,
+    /// Maximum number of distinct senders, if limited.
+    max_senders: Option<NonZeroUsize>,
+    /// Maximum number of reads from the database running at once, if limited.
+    max_concurrent_reads: Option<NonZeroUsize>,
+    /// Broadcast the original event of a duplicate message again.
+    echo_duplicates: bool,
+    /// Messages without an expiry of their own are tombstoned once they are older, if set.
+    message_ttl: Option<Duration>,
+    /// How often to look for expired messages, if set.
+    expiry_sweep_interval: Option<Duration>,
+    /// What events streams do, if their client falls behind the live events.
+    on_lag: OnLag,
+    /// How user names are normalized before signup and login.
+    name_normalization: NameNormalization,
+    /// Withhold the content and sender of messages from the logs.
+    log_redact_content: bool,
 }
 
 impl Configuration {
     /// Load the configuration from the environment variables.
     pub fn from_env() -> anyhow::Result<Self> {
-        let host = extract_env_var("HOST")?.unwrap_or_else(|| "0.0.0.0".to_owned());
-        let port = extract_env_var("PORT")?.unwrap_or(3000);
+        let listen_addrs = match extract_env_var::<String>("LISTEN_ADDRS")? {
+            Some(value) => parse_listen_addrs(&value)?,
+            None => {
+                let host = extract_env_var("HOST")?.unwrap_or_else(|| "0.0.0.0".to_owned());
+                let port = extract_env_var("PORT")?.unwrap_or(3000);
+                vec![(host, port)]
+            }
+        };
+        let tcp_keepalive = match extract_duration_env_var("TCP_KEEPALIVE_IDLE")? {
+            Some(idle) => Some(TcpKeepalive {
+                idle,
+                interval: extract_duration_env_var("TCP_KEEPALIVE_INTERVAL")?,
+            }),
+            None => None,
+        };
+        let log_redact_content = extract_bool_env_var("LOG_REDACT_CONTENT")?.unwrap_or(false);
+        let server_options = ServerOptions {
+            listen_backlog: extract_env_var("LISTEN_BACKLOG")?.unwrap_or(DEFAULT_LISTEN_BACKLOG),
+            reuse_port: extract_bool_env_var("REUSE_PORT")?.unwrap_or(false),
+            idle_timeout: extract_duration_env_var("IDLE_TIMEOUT")?,
+            write_timeout: extract_duration_env_var("WRITE_TIMEOUT")?,
+            trust_proxy: extract_bool_env_var("TRUST_PROXY")?.unwrap_or(false),
+            ui_dir: extract_env_var("UI_DIR")?,
+            robots_txt: extract_env_var::<PathBuf>("ROBOTS_TXT")?
+                .map(|path| {
+                    fs::read_to_string(&path).with_context(|| {
+                        format!("Failed to read ROBOTS_TXT file '{}'", path.display())
+                    })
+                })
+                .transpose()?,
+            noindex: extract_bool_env_var("NOINDEX")?.unwrap_or(false),
+            tcp_nodelay: extract_bool_env_var("TCP_NODELAY")?.unwrap_or(true),
+            tcp_keepalive,
+            root_response: extract_root_response_env_var("ROOT_RESPONSE")?.unwrap_or_default(),
+            // Request URIs may carry senders and content as well.
+            redact_uris: log_redact_content,
+        };
         let persistence = extract_bool_env_var("PERSISTENCE")?.unwrap_or(true);
         let persistence_dir = if persistence {
             Some(extract_env_var("PERSISTENCE_DIRECTORY")?.unwrap_or_else(|| "data".into()))
@@ -47,18 +143,129 @@ impl Configuration {
                 .unwrap_or(DEFAULT_SESSION_MAX_LIFETIME),
         };
 
+        let allowed_senders = extract_env_var::<String>("SENDERS_ALLOWLIST")?
+            .map(|value| parse_allowlist("SENDERS_ALLOWLIST", &value))
+            .transpose()?
+            .map(Arc::new);
+        let moderator = match extract_env_var::<String>("MODERATION_WORDLIST")? {
+            Some(value) => {
+                let words = parse_allowlist("MODERATION_WORDLIST", &value)?;
+                let action =
+                    extract_wordlist_action_env_var("MODERATION_ACTION")?.unwrap_or_default();
+                Some(Arc::new(WordlistModerator::new(words, action)) as Arc<dyn Moderator>)
+            }
+            None => None,
+        };
+        let reserved_senders = extract_env_var::<String>("RESERVED_SENDERS")?
+            .map(|value| parse_allowlist("RESERVED_SENDERS", &value))
+            .transpose()?
+            .map(|names| Arc::new(names.iter().map(|name| name.to_lowercase()).collect()));
+        let admins = extract_env_var::<String>("ADMINS")?
+            .map(|value| parse_allowlist("ADMINS", &value))
+            .transpose()?
+            .map(Arc::new);
+        let message_rate = match extract_env_var::<NonZeroU32>("MESSAGE_RATE_PER_SECOND")? {
+            Some(per_second) => Some(MessageRate {
+                per_second,
+                burst: extract_env_var("MESSAGE_RATE_BURST")?.unwrap_or(per_second),
+            }),
+            None => None,
+        };
+        let chat_http_options = ChatHttpOptions {
+            sender_color: extract_bool_env_var("SENDER_COLOR")?.unwrap_or(false),
+            bot_prefix: extract_env_var::<String>("SENDER_BOT_PREFIX")?.map(Into::into),
+            bot_rate_limit_exempt: extract_bool_env_var("BOT_RATE_LIMIT_EXEMPT")?.unwrap_or(false),
+            allowed_senders,
+            reserved_senders,
+            motd: extract_env_var::<String>("MOTD")?.map(Into::into),
+            cors_origins: extract_env_var::<String>("CORS_ALLOWED_ORIGINS")?
+                .map(|value| parse_origins(&value))
+                .transpose()?
+                .unwrap_or_default(),
+            cors_allow_credentials: extract_bool_env_var("CORS_ALLOW_CREDENTIALS")?
+                .unwrap_or(false),
+            server_timing: extract_bool_env_var("SERVER_TIMING")?.unwrap_or(false),
+            heartbeat: extract_duration_env_var("HEARTBEAT_INTERVAL")?,
+            max_concurrent_writes: extract_env_var("MAX_CONCURRENT_WRITES")?,
+            message_rate,
+            admins,
+            moderator,
+            render_html: extract_bool_env_var("RENDER_HTML")?.unwrap_or(false),
+            trim_content: extract_bool_env_var("TRIM_CONTENT")?.unwrap_or(false),
+            resume_signer: extract_env_var::<String>("RESUME_SIGNING")?
+                .map(|key| ResumeSigner::new(key.as_bytes())),
+            public_url: extract_env_var::<String>("PUBLIC_URL")?
+                .map(|url| url.trim_end_matches('/').into()),
+            poll_max_events: extract_env_var("POLL_MAX_EVENTS")?,
+            uuid_policy: extract_uuid_policy_env_var("UUID_POLICY")?.unwrap_or_default(),
+            content_policy: ContentPolicy {
+                disallow_urls: extract_bool_env_var("DISALLOW_URLS")?.unwrap_or(false),
+                disallow_mentions: extract_bool_env_var("DISALLOW_MENTIONS")?.unwrap_or(false),
+            },
+        };
+
+        let wal_checkpoint_interval = extract_duration_env_var("WAL_CHECKPOINT_INTERVAL")?;
+        let durability = extract_durability_env_var("DURABILITY")?.unwrap_or_default();
+
+        let db_warmup = extract_bool_env_var("DB_WARMUP")?.unwrap_or(false);
+        let startup_selfcheck = extract_bool_env_var("STARTUP_SELFCHECK")?.unwrap_or(false);
+        let migration_backup = extract_bool_env_var("MIGRATION_BACKUP")?.unwrap_or(false);
+        let max_db_bytes = extract_env_var("MAX_DB_BYTES")?;
+        let db_compress_content = extract_bool_env_var("DB_COMPRESS_CONTENT")?.unwrap_or(false);
+        let max_events = extract_env_var("MAX_EVENTS")?;
+        let recent_events = extract_env_var("RECENT_EVENTS_CACHE")?;
+        let dedup_window = extract_duration_env_var("DEDUP_WINDOW")?;
+        let slow_query_threshold =
+            extract_env_var::<u64>("SLOW_QUERY_MS")?.map(Duration::from_millis);
+        let query_timeout = extract_duration_env_var("QUERY_TIMEOUT")?;
+        let max_senders = extract_env_var("MAX_SENDERS")?;
+        let max_concurrent_reads = extract_env_var("MAX_CONCURRENT_READS")?;
+        let echo_duplicates = extract_bool_env_var("ECHO_DUPLICATES")?.unwrap_or(false);
+        let message_ttl = extract_duration_env_var("MESSAGE_TTL")?;
+        let expiry_sweep_interval = extract_duration_env_var("EXPIRY_SWEEP_INTERVAL")?;
+        let on_lag = extract_on_lag_env_var("ON_LAG")?.unwrap_or_default();
+
+        let name_normalization =
+            extract_name_normalization_env_var("SENDER_NORMALIZE")?.unwrap_or_default();
+
         let cfg = Configuration {
-            host,
-            port,
+            listen_addrs,
+            server_options,
             persistence_dir,
             session_expiry,
+            chat_http_options,
+            wal_checkpoint_interval,
+            durability,
+            db_warmup,
+            startup_selfcheck,
+            migration_backup,
+            max_db_bytes,
+            db_compress_content,
+            max_events,
+            recent_events,
+            dedup_window,
+            slow_query_threshold,
+            query_timeout,
+            max_senders,
+            max_concurrent_reads,
+            echo_duplicates,
+            message_ttl,
+            expiry_sweep_interval,
+            on_lag,
+            name_normalization,
+            log_redact_content,
         };
         Ok(cfg)
     }
 
-    /// The address the server should bind to.
-    pub fn socket_addr(&self) -> (&str, u16) {
-        (&self.host, self.port)
+    /// The addresses the server should bind to.
+    pub fn listen_addrs(&self) -> &[(String, u16)] {
+        &self.listen_addrs
+    }
+
+    /// Options for accepting and serving connections.
+    pub fn server_options(&self) -> ServerOptions {
+        self.server_options.clone()
     }
 
     /// Directory for persistent storage, if configured.
@@ -70,6 +277,114 @@ impl Configuration {
     pub fn session_expiry(&self) -> SessionExpiry {
         self.session_expiry
     }
+
+    /// Options shaping the chat API.
+    pub fn chat_http_options(&self) -> ChatHttpOptions {
+        self.chat_http_options.clone()
+    }
+
+    /// Interval for checkpointing the write ahead log in the background, if configured.
+    pub fn wal_checkpoint_interval(&self) -> Option<Duration> {
+        self.wal_checkpoint_interval
+    }
+
+    /// When writes to the database are acknowledged. Either after they have been synced to disk, or
+    /// already before.
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    /// Prepare frequently used statements during startup, rather than on first use.
+    pub fn db_warmup(&self) -> bool {
+        self.db_warmup
+    }
+
+    /// Record, read back and delete a probe message during startup, failing it if that does not
+    /// work.
+    pub fn startup_selfcheck(&self) -> bool {
+        self.startup_selfcheck
+    }
+
+    /// Back up the database before migrating its schema.
+    pub fn migration_backup(&self) -> bool {
+        self.migration_backup
+    }
+
+    /// New messages are rejected once the database reaches this size in bytes, if set.
+    pub fn max_db_bytes(&self) -> Option<u64> {
+        self.max_db_bytes
+    }
+
+    /// Store the content of new messages compressed.
+    pub fn db_compress_content(&self) -> bool {
+        self.db_compress_content
+    }
+
+    /// Number of most recent events retained, if capped.
+    pub fn max_events(&self) -> Option<NonZeroU64> {
+        self.max_events
+    }
+
+    /// Number of most recent events kept in memory, so resuming clients do not query the database.
+    pub fn recent_events(&self) -> Option<NonZeroUsize> {
+        self.recent_events
+    }
+
+    /// Window within which a message resent by the same sender with the same content under a fresh
+    /// id is treated as a duplicate, if enabled.
+    pub fn dedup_window(&self) -> Option<Duration> {
+        self.dedup_window
+    }
+
+    /// Database operations taking longer than this are logged as a warning, if enabled.
+    pub fn slow_query_threshold(&self) -> Option<Duration> {
+        self.slow_query_threshold
+    }
+
+    /// Reading events since an event fails once it takes longer than this, if set.
+    pub fn query_timeout(&self) -> Option<Duration> {
+        self.query_timeout
+    }
+
+    /// Once as many distinct senders have posted, messages of new senders are rejected, if set.
+    pub fn max_senders(&self) -> Option<NonZeroUsize> {
+        self.max_senders
+    }
+
+    /// Maximum number of reads from the database running at once, if limited.
+    pub fn max_concurrent_reads(&self) -> Option<NonZeroUsize> {
+        self.max_concurrent_reads
+    }
+
+    /// Announce the original event of a duplicate message again, for clients relying on echoes.
+    pub fn echo_duplicates(&self) -> bool {
+        self.echo_duplicates
+    }
+
+    /// Messages without an expiry of their own are tombstoned once they are older, if set.
+    pub fn message_ttl(&self) -> Option<Duration> {
+        self.message_ttl
+    }
+
+    /// How often to look for expired messages, if set.
+    pub fn expiry_sweep_interval(&self) -> Option<Duration> {
+        self.expiry_sweep_interval
+    }
+
+    /// What events streams do, if their client falls behind the live events.
+    pub fn on_lag(&self) -> OnLag {
+        self.on_lag
+    }
+
+    /// How user names are normalized before signup and login.
+    pub fn name_normalization(&self) -> NameNormalization {
+        self.name_normalization
+    }
+
+    /// Withhold the content and sender of messages from the logs.
+    pub fn log_redact_content(&self) -> bool {
+        self.log_redact_content
+    }
 }
 
 fn handle_invalid_unicode(result: Result<String, VarError>) -> anyhow::Result<Option<String>> {
@@ -120,6 +435,144 @@ fn extract_bool_env_var(var_name: &str) -> anyhow::Result<Option<bool>> {
     }
 }
 
+/// Interprets the value of `SENDERS_ALLOWLIST`. If it starts with `@`, the names are read from the
+/// file at the path following it, e.g. `@/etc/klatsch/allowlist.txt`. Otherwise the value itself is
+/// the list. Names are separated by commas or line breaks.
+fn parse_allowlist(var: &str, value: &str) -> anyhow::Result<HashSet<String>> {
+    let list = match value.strip_prefix('@') {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {var} file '{path}'"))?,
+        None => value.to_owned(),
+    };
+    let names = list
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect();
+    Ok(names)
+}
+
+/// Interprets the value of `LISTEN_ADDRS` as a comma separated list of `host:port` pairs. IPv6
+/// addresses are enclosed in brackets, e.g. `[::1]:3000`.
+fn parse_listen_addrs(value: &str) -> anyhow::Result<Vec<(String, u16)>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|addr| !addr.is_empty())
+        .map(|addr| {
+            let invalid = || anyhow!("LISTEN_ADDRS must contain 'host:port' pairs, got '{addr}'");
+            let (host, port) = addr.rsplit_once(':').ok_or_else(invalid)?;
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let port = port.parse().map_err(|_| invalid())?;
+            Ok((host.to_owned(), port))
+        })
+        .collect()
+}
+
+/// Interprets the value of `CORS_ALLOWED_ORIGINS` as a comma separated list of origins.
+fn parse_origins(value: &str) -> anyhow::Result<Vec<HeaderValue>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            HeaderValue::from_str(origin)
+                .with_context(|| format!("CORS_ALLOWED_ORIGINS contains invalid origin '{origin}'"))
+        })
+        .collect()
+}
+
+fn extract_name_normalization_env_var(var_name: &str) -> anyhow::Result<Option<NameNormalization>> {
+    let value = handle_invalid_unicode(env::var(var_name))?;
+    match value.as_deref() {
+        None => Ok(None),
+        Some(s) if s.eq_ignore_ascii_case("verbatim") => Ok(Some(NameNormalization::Verbatim)),
+        Some(s) if s.eq_ignore_ascii_case("normalize") => Ok(Some(NameNormalization::Normalize)),
+        Some(s) if s.eq_ignore_ascii_case("strict") => Ok(Some(NameNormalization::Strict)),
+        Some(s) => Err(anyhow!(
+            "{var_name} must be 'verbatim', 'normalize' or 'strict' (case insensitive), got '{s}'"
+        )),
+    }
+}
+
+fn extract_on_lag_env_var(var_name: &str) -> anyhow::Result<Option<OnLag>> {
+    let value = handle_invalid_unicode(env::var(var_name))?;
+    match value.as_deref() {
+        None => Ok(None),
+        Some(s) if s.eq_ignore_ascii_case("resume") => Ok(Some(OnLag::Resume)),
+        Some(s) if s.eq_ignore_ascii_case("error") => Ok(Some(OnLag::Error)),
+        Some(s) => Err(anyhow!(
+            "{var_name} must be 'resume' or 'error' (case insensitive), got '{s}'"
+        )),
+    }
+}
+
+fn extract_durability_env_var(var_name: &str) -> anyhow::Result<Option<Durability>> {
+    let value = handle_invalid_unicode(env::var(var_name))?;
+    match value.as_deref() {
+        None => Ok(None),
+        Some(s) if s.eq_ignore_ascii_case("normal") => Ok(Some(Durability::Normal)),
+        Some(s) if s.eq_ignore_ascii_case("full") => Ok(Some(Durability::Full)),
+        Some(s) => Err(anyhow!(
+            "{var_name} must be 'normal' or 'full' (case insensitive), got '{s}'"
+        )),
+    }
+}
+
+fn extract_uuid_policy_env_var(var_name: &str) -> anyhow::Result<Option<UuidPolicy>> {
+    let value = handle_invalid_unicode(env::var(var_name))?;
+    match value.as_deref() {
+        None => Ok(None),
+        Some(s) if s.eq_ignore_ascii_case("lenient") => Ok(Some(UuidPolicy::Lenient)),
+        Some(s) if s.eq_ignore_ascii_case("strict") => Ok(Some(UuidPolicy::Strict)),
+        Some(s) => Err(anyhow!(
+            "{var_name} must be 'lenient' or 'strict' (case insensitive), got '{s}'"
+        )),
+    }
+}
+
+/// `ui`, `info`, `redirect:<url>` or `text:<message>`.
+fn extract_root_response_env_var(var_name: &str) -> anyhow::Result<Option<RootResponse>> {
+    let value = handle_invalid_unicode(env::var(var_name))?;
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    let (mode, argument) = match value.split_once(':') {
+        Some((mode, argument)) => (mode, Some(argument)),
+        None => (value.as_str(), None),
+    };
+    let root_response = match (mode.to_ascii_lowercase().as_str(), argument) {
+        ("ui", None) => RootResponse::Ui,
+        ("info", None) => RootResponse::Info,
+        ("redirect", Some(url)) => {
+            HeaderValue::from_str(url)
+                .with_context(|| format!("{var_name} must redirect to a valid URL, got '{url}'"))?;
+            RootResponse::Redirect(url.to_owned())
+        }
+        ("text", Some(text)) => RootResponse::Text(text.to_owned()),
+        _ => {
+            return Err(anyhow!(
+                "{var_name} must be 'ui', 'info', 'redirect:<url>' or 'text:<message>', got \
+                '{value}'"
+            ));
+        }
+    };
+    Ok(Some(root_response))
+}
+
+fn extract_wordlist_action_env_var(var_name: &str) -> anyhow::Result<Option<WordlistAction>> {
+    let value = handle_invalid_unicode(env::var(var_name))?;
+    match value.as_deref() {
+        None => Ok(None),
+        Some(s) if s.eq_ignore_ascii_case("reject") => Ok(Some(WordlistAction::Reject)),
+        Some(s) if s.eq_ignore_ascii_case("mask") => Ok(Some(WordlistAction::Mask)),
+        Some(s) => Err(anyhow!(
+            "{var_name} must be 'reject' or 'mask' (case insensitive), got '{s}'"
+        )),
+    }
+}
+
 fn extract_env_var<T>(var_name: &str) -> anyhow::Result<Option<T>>
 where
     T: FromStr,
@@ -159,6 +612,76 @@ mod tests {
         assert!(error.source().is_none(), "message must stand alone");
     }
 
+    #[test]
+    fn allowlist_from_comma_separated_names() {
+        let names = parse_allowlist("SENDERS_ALLOWLIST", "Alice, Bob,,Charlie").unwrap();
+
+        assert_eq!(
+            HashSet::from(["Alice", "Bob", "Charlie"].map(str::to_owned)),
+            names
+        );
+    }
+
+    #[test]
+    fn listen_addrs_from_comma_separated_list() {
+        let addrs = parse_listen_addrs("10.0.0.2:3000, [::1]:3001,").unwrap();
+
+        assert_eq!(
+            vec![("10.0.0.2".to_owned(), 3000), ("::1".to_owned(), 3001)],
+            addrs
+        );
+    }
+
+    #[test]
+    fn listen_addrs_without_port_are_rejected() {
+        let result = parse_listen_addrs("localhost");
+
+        assert_eq!(
+            "LISTEN_ADDRS must contain 'host:port' pairs, got 'localhost'",
+            result.unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn origins_from_comma_separated_list() {
+        let origins = parse_origins("https://a.example, https://b.example:8443,").unwrap();
+
+        assert_eq!(vec!["https://a.example", "https://b.example:8443"], origins);
+    }
+
+    #[test]
+    fn allowlist_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("allowlist.txt");
+        fs::write(&path, "Alice\nBob\n").unwrap();
+
+        let value = format!("@{}", path.to_str().unwrap());
+        let names = parse_allowlist("SENDERS_ALLOWLIST", &value).unwrap();
+
+        assert_eq!(HashSet::from(["Alice", "Bob"].map(str::to_owned)), names);
+    }
+
+    #[test]
+    fn allowlist_is_not_read_from_file_without_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("allowlist.txt");
+        fs::write(&path, "Alice\nBob\n").unwrap();
+
+        let names = parse_allowlist("SENDERS_ALLOWLIST", path.to_str().unwrap()).unwrap();
+
+        assert_eq!(HashSet::from([path.to_str().unwrap().to_owned()]), names);
+    }
+
+    #[test]
+    fn allowlist_from_missing_file_is_rejected() {
+        let result = parse_allowlist("SENDERS_ALLOWLIST", "@/does/not/exist.txt");
+
+        assert_eq!(
+            "Failed to read SENDERS_ALLOWLIST file '/does/not/exist.txt'",
+            result.unwrap_err().to_string()
+        );
+    }
+
     #[test]
     fn test_handle_invalid_unicode() {
         let result = Err(VarError::NotUnicode(OsString::from("Hello")));
@@ -2,10 +2,14 @@
 
 mod authenticate;
 mod http_error;
+mod json_body;
 mod last_event_id;
+mod lifecycle;
 
 pub use self::{
     authenticate::{AuthenticateRequest, AuthenticatedUser},
     http_error::HttpError,
+    json_body::JsonBody,
     last_event_id::LastEventId,
+    lifecycle::Lifecycle,
 };
@@ -1,10 +1,12 @@
 use std::borrow::Cow;
 
 use axum::{
+    extract::rejection::JsonRejection,
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 
+#[derive(Debug)]
 pub struct HttpError {
     pub status_code: StatusCode,
     pub message: Cow<'static, str>,
@@ -15,3 +17,22 @@ impl IntoResponse for HttpError {
         (self.status_code, self.message).into_response()
     }
 }
+
+impl From<JsonRejection> for HttpError {
+    fn from(rejection: JsonRejection) -> Self {
+        match rejection {
+            JsonRejection::MissingJsonContentType(_) => HttpError {
+                status_code: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                message: "Expected request with `Content-Type: application/json`".into(),
+            },
+            JsonRejection::JsonSyntaxError(err) => HttpError {
+                status_code: StatusCode::BAD_REQUEST,
+                message: format!("Request body is not valid JSON: {}", err.body_text()).into(),
+            },
+            other => HttpError {
+                status_code: other.status(),
+                message: other.body_text().into(),
+            },
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use axum::{
+    Json,
+    extract::{FromRequest, Request, rejection::JsonRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::HttpError;
+
+/// Like [`Json`], but if the body does not match `T`, clients are told which field is at fault and
+/// why. E.g. `field: "id", message: "UUID parsing failed: ..."`.
+#[derive(Debug)]
+pub struct JsonBody<T>(pub T);
+
+impl<S, T> FromRequest<S> for JsonBody<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = JsonBodyRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        // Content type and syntax are checked by `Json` already. Only the mapping to `T` is left.
+        let Json(value) = Json::<serde_json::Value>::from_request(req, state)
+            .await
+            .map_err(JsonBodyRejection::Json)?;
+        serde_path_to_error::deserialize(value)
+            .map(JsonBody)
+            .map_err(|err| {
+                let path = err.path().to_string();
+                JsonBodyRejection::Field(FieldError {
+                    error: "invalid_field",
+                    // The path of the body itself is `.`, e.g. if a field is missing.
+                    field: (path != ".").then_some(path),
+                    message: err.into_inner().to_string(),
+                })
+            })
+    }
+}
+
+/// Rejection of [`JsonBody`].
+#[derive(Debug)]
+pub enum JsonBodyRejection {
+    /// The body is not JSON at all, or it has not been declared as such.
+    Json(JsonRejection),
+    /// The body is valid JSON, but does not match the expected shape.
+    Field(FieldError),
+}
+
+impl IntoResponse for JsonBodyRejection {
+    fn into_response(self) -> Response {
+        match self {
+            JsonBodyRejection::Json(rejection) => HttpError::from(rejection).into_response(),
+            JsonBodyRejection::Field(error) => {
+                (StatusCode::BAD_REQUEST, Json(error)).into_response()
+            }
+        }
+    }
+}
+
+/// Body of the response, if a field of the request body could not be deserialized.
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    /// Machine readable kind of the error, allowing clients to branch on it.
+    error: &'static str,
+    /// Path to the offending field, e.g. `id`. Absent if the body as a whole is at fault, e.g.
+    /// because a field is missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
+    /// Human readable description of what is wrong with the field.
+    message: String,
+}
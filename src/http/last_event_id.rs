@@ -1,33 +1,91 @@
-use std::{convert::Infallible, str::FromStr};
+use std::str::FromStr;
 
-use axum::{extract::FromRequestParts, http::request::Parts};
+use axum::{
+    extract::{self, FromRequestParts, Query},
+    http::{StatusCode, request::Parts},
+};
+use serde::Deserialize;
 
-/// Extractor for the `Last-Event-ID` header used by EventSource clients.
+use super::HttpError;
+
+/// Longest `Last-Event-ID` header we bother to parse. The number of digits of `u64::MAX`, followed by
+/// a dot and a hex encoded SHA-256 signature, as issued if resume positions are signed. Longer values
+/// can not be a valid id, so we reject them before parsing.
+const MAX_LAST_EVENT_ID_LEN: usize = 20 + 1 + 64;
+
+/// Extractor for the `Last-Event-ID` header used by EventSource clients. Clients which can not set
+/// headers easily, may pass the `last_event_id` query parameter instead. The header takes
+/// precedence if both are present. Extract `Option<LastEventId<T>>` to tell whether the client
+/// passed an id at all.
 #[derive(Clone, Copy, Debug)]
 pub struct LastEventId<T>(pub T);
 
+#[derive(Deserialize)]
+struct LastEventIdQuery {
+    last_event_id: Option<String>,
+}
+
 impl<S, T> FromRequestParts<S> for LastEventId<T>
 where
     S: Send + Sync,
     T: Default + FromStr,
 {
-    type Rejection = Infallible;
+    type Rejection = HttpError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let id = parts
-            .headers
-            .get("last-event-id")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|s| s.parse::<T>().ok())
-            .unwrap_or_default();
-        Ok(LastEventId(id))
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let maybe_id =
+            <Self as extract::OptionalFromRequestParts<S>>::from_request_parts(parts, state)
+                .await?;
+        Ok(maybe_id.unwrap_or(LastEventId(T::default())))
+    }
+}
+
+impl<S, T> extract::OptionalFromRequestParts<S> for LastEventId<T>
+where
+    S: Send + Sync,
+    T: Default + FromStr,
+{
+    type Rejection = HttpError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        if let Some(header) = parts.headers.get("last-event-id") {
+            if header.len() > MAX_LAST_EVENT_ID_LEN {
+                return Err(HttpError {
+                    status_code: StatusCode::BAD_REQUEST,
+                    message: "Last-Event-ID header is too long".into(),
+                });
+            }
+            let id = header
+                .to_str()
+                .ok()
+                .and_then(|s| s.parse::<T>().ok())
+                .unwrap_or_default();
+            return Ok(Some(LastEventId(id)));
+        }
+        let invalid_query = || HttpError {
+            status_code: StatusCode::BAD_REQUEST,
+            message: "Invalid last_event_id query parameter".into(),
+        };
+        let Query(query) =
+            Query::<LastEventIdQuery>::try_from_uri(&parts.uri).map_err(|_| invalid_query())?;
+        let maybe_id = query
+            .last_event_id
+            .map(|s| s.parse::<T>().map_err(|_| invalid_query()))
+            .transpose()?;
+        Ok(maybe_id.map(LastEventId))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use axum::{body::Body, http::Request};
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
 
     #[tokio::test]
     async fn parses_header() {
@@ -43,6 +101,78 @@ mod tests {
         assert_eq!(extractor.0, 2u64);
     }
 
+    #[tokio::test]
+    async fn parses_query_parameter() {
+        let req = Request::builder()
+            .uri("/?last_event_id=3")
+            .body(Body::empty())
+            .unwrap();
+        let mut parts = req.into_parts().0;
+        let extractor = LastEventId::<u64>::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+        assert_eq!(extractor.0, 3u64);
+    }
+
+    #[tokio::test]
+    async fn header_takes_precedence_over_query_parameter() {
+        let req = Request::builder()
+            .uri("/?last_event_id=3")
+            .header("Last-Event-ID", "2")
+            .body(Body::empty())
+            .unwrap();
+        let mut parts = req.into_parts().0;
+        let extractor = LastEventId::<u64>::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+        assert_eq!(extractor.0, 2u64);
+    }
+
+    #[tokio::test]
+    async fn rejects_overlong_header() {
+        let req = Request::builder()
+            .uri("/")
+            .header("Last-Event-ID", "1".repeat(10 * 1024))
+            .body(Body::empty())
+            .unwrap();
+        let mut parts = req.into_parts().0;
+        let result = LastEventId::<u64>::from_request_parts(&mut parts, &()).await;
+        let Err(err) = result else {
+            panic!("Must reject overlong header");
+        };
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn accepts_signed_resume_position_of_maximum_length() {
+        let position = format!("{}.{}", u64::MAX, "f".repeat(64));
+        let req = Request::builder()
+            .uri("/")
+            .header("Last-Event-ID", &position)
+            .body(Body::empty())
+            .unwrap();
+        let mut parts = req.into_parts().0;
+        let extractor = LastEventId::<String>::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+        assert_eq!(MAX_LAST_EVENT_ID_LEN, extractor.0.len());
+        assert_eq!(position, extractor.0);
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_query_parameter() {
+        let req = Request::builder()
+            .uri("/?last_event_id=banana")
+            .body(Body::empty())
+            .unwrap();
+        let mut parts = req.into_parts().0;
+        let result = LastEventId::<u64>::from_request_parts(&mut parts, &()).await;
+        let Err(err) = result else {
+            panic!("Must reject malformed query parameter");
+        };
+        assert_eq!(err.status_code, StatusCode::BAD_REQUEST);
+    }
+
     #[tokio::test]
     async fn defaults_to_zero() {
         let req = Request::builder().uri("/").body(Body::empty()).unwrap();
@@ -52,4 +182,18 @@ mod tests {
             .unwrap();
         assert_eq!(extractor.0, 0);
     }
+
+    #[tokio::test]
+    async fn absent_if_neither_header_nor_query_parameter_is_passed() {
+        let req = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let mut parts = req.into_parts().0;
+        let extractor =
+            <LastEventId<u64> as extract::OptionalFromRequestParts<()>>::from_request_parts(
+                &mut parts,
+                &(),
+            )
+            .await
+            .unwrap();
+        assert!(extractor.is_none());
+    }
 }
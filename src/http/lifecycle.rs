@@ -0,0 +1,26 @@
+use tokio::sync::watch;
+
+/// Tells handlers which phase of its lifecycle the server is in, so long-lived requests can wrap
+/// up, before the server shuts down.
+#[derive(Clone, Debug)]
+pub struct Lifecycle {
+    /// `true` once the server is about to shut down. Long-lived requests like event streams watch
+    /// this in order to finish early, so the graceful shutdown does not wait for them.
+    pub shutting_down: watch::Receiver<bool>,
+    /// `true` while the server is draining. I.e. it still serves events, but rejects new messages,
+    /// so clients can move on to another instance before it shuts down.
+    pub draining: watch::Receiver<bool>,
+}
+
+#[cfg(test)]
+impl Lifecycle {
+    /// A server which is running, and stays so.
+    pub fn running() -> Self {
+        let (_, shutting_down) = watch::channel(false);
+        let (_, draining) = watch::channel(false);
+        Self {
+            shutting_down,
+            draining,
+        }
+    }
+}
@@ -1,7 +1,11 @@
+use std::net::SocketAddr;
+
+use anyhow::Context as _;
+
 use crate::{
-    chat::ChatRuntime,
+    chat::{ChatRuntime, ChatStoreOptions, self_check},
     configuration::Configuration,
-    persistence::{SqlitePersistence, migrate},
+    persistence::{SqlitePersistence, migrate, warm_up},
     server::Server,
     sessions::SessionsRuntime,
     user::UserStore,
@@ -17,20 +21,64 @@ pub struct Klatsch {
 
 impl Klatsch {
     pub async fn new(cfg: &Configuration) -> anyhow::Result<Self> {
-        let persistence = SqlitePersistence::new(cfg.persistence_dir(), migrate).await?;
+        let mut persistence = SqlitePersistence::with_migration_backup(
+            cfg.persistence_dir(),
+            migrate,
+            cfg.migration_backup(),
+        )
+        .await?;
+        persistence.set_durability(cfg.durability()).await?;
+        if let Some(interval) = cfg.wal_checkpoint_interval() {
+            persistence.checkpoint_wal_periodically(interval);
+        }
+        if cfg.db_warmup() {
+            persistence.warm_up(warm_up).await?;
+        }
 
         // users and history share the same persistence backend. This makes life easier for the
         // operators.
-        let users = UserStore::new(persistence.client());
+        let users =
+            UserStore::new(persistence.client()).with_name_normalization(cfg.name_normalization());
 
         // Forward messages between peers in the chat
-        let chat = ChatRuntime::new(persistence.client()).await?;
+        let chat = ChatRuntime::new(
+            persistence.client(),
+            ChatStoreOptions {
+                max_db_bytes: cfg.max_db_bytes(),
+                compress_content: cfg.db_compress_content(),
+                max_events: cfg.max_events(),
+                recent_events: cfg.recent_events(),
+                dedup_window: cfg.dedup_window(),
+                slow_query_threshold: cfg.slow_query_threshold(),
+                query_timeout: cfg.query_timeout(),
+                max_senders: cfg.max_senders(),
+                max_concurrent_reads: cfg.max_concurrent_reads(),
+                echo_duplicates: cfg.echo_duplicates(),
+                message_ttl: cfg.message_ttl(),
+                expiry_sweep_interval: cfg.expiry_sweep_interval(),
+            },
+        )
+        .await?
+        .with_on_lag(cfg.on_lag());
+        if cfg.startup_selfcheck() {
+            self_check(&persistence.client())
+                .await
+                .context("Startup self check failed")?;
+        }
 
         let sessions = SessionsRuntime::new(cfg.session_expiry());
 
         // Answer incoming HTTP requests
-        let server =
-            Server::new(cfg.socket_addr(), chat.client(), users, sessions.client()).await?;
+        let server = Server::new(
+            cfg.listen_addrs(),
+            chat.client(),
+            users,
+            sessions.client(),
+            persistence.client(),
+            cfg.chat_http_options(),
+            cfg.server_options(),
+        )
+        .await?;
 
         Ok(Self {
             chat,
@@ -40,6 +88,17 @@ impl Klatsch {
         })
     }
 
+    /// The addresses the HTTP server listens on.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        self.server.local_addrs()
+    }
+
+    /// Stop accepting new messages, while still serving events. Gives clients and load balancers
+    /// time to move on to another instance, before shutting down.
+    pub fn drain(&self) {
+        self.server.drain();
+    }
+
     pub async fn shutdown(self) {
         // Gracefully shutdown the http server.
         self.server.shutdown().await;
@@ -49,3 +108,89 @@ impl Klatsch {
         tokio::join!(self.chat.shutdown(), self.sessions.shutdown());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use crate::{
+        chat::{ChatHttpOptions, ChatRuntime, ChatStoreOptions},
+        persistence::{SqlitePersistence, migrate},
+        server::{RootResponse, Server, ServerOptions},
+        sessions::{SessionExpiry, SessionsRuntime},
+        user::UserStore,
+    };
+
+    use super::Klatsch;
+
+    #[tokio::test]
+    async fn shutdown_completes_within_one_second() {
+        // Given a running application
+        let app = klatsch().await;
+
+        // When shutting it down
+        let result = timeout(Duration::from_secs(1), app.shutdown()).await;
+
+        // Then the server releases its clients, before the runtimes wait for them
+        assert!(result.is_ok(), "Shutdown did not complete within 1 second");
+    }
+
+    #[cfg(debug_assertions)]
+    #[tokio::test]
+    #[should_panic(expected = "All clients of the chat must be dropped before shutting it down")]
+    async fn shutting_down_chat_before_server_is_caught() {
+        // Given a running application
+        let Klatsch { chat, server, .. } = klatsch().await;
+
+        // When shutting down the chat runtime, while the server still holds clients of it
+        chat.shutdown().await;
+
+        // Then the shutdown fails fast, rather than hanging. Unreachable.
+        server.shutdown().await;
+    }
+
+    /// Assembles the application with an in-memory database, listening on a free port.
+    async fn klatsch() -> Klatsch {
+        let persistence = SqlitePersistence::new(None, migrate).await.unwrap();
+        let users = UserStore::new(persistence.client());
+        let chat = ChatRuntime::new(persistence.client(), ChatStoreOptions::default())
+            .await
+            .unwrap();
+        let sessions = SessionsRuntime::new(SessionExpiry {
+            idle_timeout: Duration::from_secs(60),
+            max_lifetime: Duration::from_secs(60),
+        });
+        let server = Server::new(
+            ["127.0.0.1:0"],
+            chat.client(),
+            users,
+            sessions.client(),
+            persistence.client(),
+            ChatHttpOptions::default(),
+            ServerOptions {
+                listen_backlog: 16,
+                reuse_port: false,
+                idle_timeout: None,
+                write_timeout: None,
+                trust_proxy: false,
+                ui_dir: None,
+                robots_txt: None,
+                noindex: false,
+                tcp_nodelay: true,
+                tcp_keepalive: None,
+                root_response: RootResponse::Ui,
+                redact_uris: false,
+            },
+        )
+        .await
+        .unwrap();
+        Klatsch {
+            chat,
+            sessions,
+            server,
+            _persistence: persistence,
+        }
+    }
+}
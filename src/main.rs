@@ -7,36 +7,51 @@ mod persistence;
 mod server;
 mod sessions;
 mod shutdown;
+mod task;
 mod tracing;
 mod user;
 
+use std::pin::pin;
+
 use dotenvy::dotenv;
 
 use ::tracing::info;
 
 use crate::{
-    configuration::Configuration, klatsch::Klatsch, shutdown::shutdown_signal,
+    configuration::Configuration,
+    klatsch::Klatsch,
+    shutdown::{drain_signal, shutdown_signal},
     tracing::init_tracing,
 };
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Register shutdown signal handler
+    // Register shutdown and drain signal handlers
     let shutdown = shutdown_signal().await;
+    let drain = drain_signal().await;
 
     // Source environment from .env file and load configuration. Errors during sourcing the .env
     // file are ignored. In case of it not existing we intend to use the plain environment.
     dotenv().ok();
     let cfg = Configuration::from_env()?;
 
-    init_tracing();
+    init_tracing(cfg.log_redact_content());
 
     info!(target: "app", "Starting");
     let app = Klatsch::new(&cfg).await?;
-    info!(target: "app", "Ready");
-
-    // Run our application until a shutdown signal is received
-    shutdown.await;
+    info!(target: "app", addresses = ?app.local_addrs(), "Ready");
+
+    // Run our application until a shutdown signal is received. A drain signal received before that
+    // stops accepting new messages, but the application keeps running.
+    let mut shutdown = pin!(shutdown);
+    tokio::select! {
+        () = drain => {
+            info!(target: "app", "Drain signal received");
+            app.drain();
+            shutdown.await;
+        }
+        () = &mut shutdown => {}
+    }
 
     info!(target: "app", "Shutdown signal received");
     app.shutdown().await;
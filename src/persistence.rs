@@ -1,13 +1,15 @@
 mod arguments;
 mod migrate;
 mod sqlite;
+mod warm_up;
 
 use uuid::Uuid;
 
 pub use self::{
     arguments::{Argument, Arguments, AsArgument},
     migrate::migrate,
-    sqlite::SqlitePersistence,
+    sqlite::{Durability, SqlitePersistence},
+    warm_up::warm_up,
 };
 
 pub trait ExecuteSqlAsync {
@@ -39,11 +41,28 @@ pub trait ExecuteSqlAsync {
     ) -> impl Future<Output = anyhow::Result<Vec<O>>> + Send
     where
         O: Send + 'static;
+
+    /// Like [`Self::rows_vec`], but dropping the returned future interrupts the query, or keeps it
+    /// from starting at all. Otherwise an abandoned query would keep the database busy until it
+    /// completes. Meant for reads only, since a write could be interrupted halfway.
+    fn interruptible_rows_vec<O>(
+        &self,
+        query: &'static str,
+        args: impl Arguments + Send + Sync + 'static,
+        map: impl Fn(&Self::Row<'_>) -> Result<O, Self::Error> + Send + 'static,
+    ) -> impl Future<Output = anyhow::Result<Vec<O>>> + Send
+    where
+        O: Send + 'static;
 }
 
 /// Rows allow access to types natively supported by persistence
 pub trait GetFieldNative:
-    GetField<i64> + GetField<Uuid> + GetField<Option<i64>> + GetField<String> + GetField<Option<String>>
+    GetField<i64>
+    + GetField<Uuid>
+    + GetField<Option<i64>>
+    + GetField<String>
+    + GetField<Option<String>>
+    + GetField<Vec<u8>>
 {
 }
 
@@ -74,6 +93,9 @@ pub trait ExecuteSqlSync {
 
     fn execute(&self, query: &str, args: impl Arguments) -> Result<(), Self::Error>;
 
+    /// Compiles `query` without executing it, so later executions of the same query do not have to.
+    fn prepare(&self, query: &str) -> Result<(), Self::Error>;
+
     fn row<O>(
         &self,
         query: &'static str,
@@ -89,7 +111,29 @@ pub trait ExecuteSqlSync {
     ) -> Result<Vec<O>, Self::Error>;
 }
 
+/// Tells whether the schema of the opened database is the one this binary has been built for.
+#[cfg_attr(test, double_trait::dummies)]
+pub trait SchemaStatus {
+    fn schema_version(&self) -> impl Future<Output = anyhow::Result<SchemaVersion>> + Send;
+}
+
+/// Schema version found in the database, next to the one this binary supports.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SchemaVersion {
+    pub found: u32,
+    pub supported: u32,
+}
+
+impl SchemaVersion {
+    pub fn is_current(&self) -> bool {
+        self.found == self.supported
+    }
+}
+
 #[cfg_attr(test, double_trait::dummies)]
 pub trait PersistenceError {
     fn is_unique_constraint_violation(&self) -> bool;
+
+    /// The storage, e.g. the disk, has no room left for the data to write.
+    fn is_storage_full(&self) -> bool;
 }
@@ -12,6 +12,7 @@ use uuid::Uuid;
 pub enum Argument<'a> {
     I64(i64),
     Text(Cow<'a, str>),
+    Blob(Cow<'a, [u8]>),
     Uuid(Uuid),
     Null,
 }
@@ -40,6 +41,18 @@ impl AsArgument for String {
     }
 }
 
+impl AsArgument for &[u8] {
+    fn as_argument(&self) -> Argument<'_> {
+        Argument::Blob(Cow::Borrowed(*self))
+    }
+}
+
+impl AsArgument for Vec<u8> {
+    fn as_argument(&self) -> Argument<'_> {
+        Argument::Blob(Cow::Borrowed(self.as_slice()))
+    }
+}
+
 impl AsArgument for &Uuid {
     fn as_argument(&self) -> Argument<'_> {
         Argument::Uuid(**self)
@@ -136,6 +149,8 @@ impl_arguments_for_tuple! { A B }
 impl_arguments_for_tuple! { A B C }
 impl_arguments_for_tuple! { A B C D }
 impl_arguments_for_tuple! { A B C D E }
+impl_arguments_for_tuple! { A B C D E F }
+impl_arguments_for_tuple! { A B C D E F G }
 
 #[cfg(test)]
 mod tests {
@@ -1,38 +1,97 @@
-use crate::persistence::GetField;
+use crate::{persistence::GetField, task::spawn_named};
 
 use super::{
     Argument, Arguments, ExecuteSqlAsync, ExecuteSqlSync, GetFieldNative, PersistenceError,
+    SchemaStatus, SchemaVersion,
 };
 use anyhow::{anyhow, bail};
 use async_sqlite::{
     Client, ClientBuilder, JournalMode,
     rusqlite::{
-        self, Params, Row, ToSql, ffi, params_from_iter,
+        self, InterruptHandle, Params, Row, ToSql, ffi, params_from_iter,
         types::{ToSqlOutput, Value},
     },
 };
 use fs2::{FileExt as _, lock_contended_error};
-use std::{fs::File, path::Path};
-use tokio::fs::create_dir_all;
-use tracing::{error, info};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    fs::create_dir_all,
+    task::AbortHandle,
+    time::{MissedTickBehavior, interval},
+};
+use tracing::{debug, error, info};
 use uuid::Uuid;
 
-const CURRENT_SCHEMA_VERSION: u32 = 2;
+const CURRENT_SCHEMA_VERSION: u32 = 6;
+
+/// When a write counts as done. Maps to the `synchronous` pragma of SQLite.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// Writes are acknowledged before the write ahead log is synced to disk. They survive a crash
+    /// of the process, but the last ones may be lost on power loss. Faster writes.
+    Normal,
+    /// Writes are acknowledged only after the write ahead log has been synced to disk, so they
+    /// survive a power loss. Default of SQLite.
+    #[default]
+    Full,
+}
+
+impl Durability {
+    /// Value of the `synchronous` pragma
+    fn synchronous(self) -> &'static str {
+        match self {
+            Durability::Normal => "NORMAL",
+            Durability::Full => "FULL",
+        }
+    }
+}
 
 pub struct SqlitePersistence {
     conn: Client,
     /// Held for the lifetime of the struct to prevent concurrent instances on the same directory.
     /// `None` for in-memory databases.
     _lock_file: Option<File>,
+    /// Background task periodically checkpointing the write ahead log, if enabled. Aborted then
+    /// dropped.
+    wal_checkpoints: Option<AbortHandle>,
 }
 
 impl SqlitePersistence {
+    /// Like [`Self::with_migration_backup`], without ever backing up the database.
+    #[cfg(test)]
     pub async fn new(
         directory: Option<&Path>,
         migrate: impl for<'any> Fn(&rusqlite::Connection, u32) -> Result<(), rusqlite::Error>
         + Send
         + 'static,
     ) -> anyhow::Result<Self> {
+        Self::with_migration_backup(directory, migrate, false).await
+    }
+
+    /// Opens the database in `directory`, or an in-memory database if `None`, and migrates its
+    /// schema to the current version with `migrate`. If `backup` is set and the schema of an
+    /// existing database is about to be migrated, the database is copied to
+    /// `klatsch.db.bak.<seconds since Unix epoch>` first. So operators can go back, should the
+    /// migration go wrong. In-memory databases are not backed up.
+    pub async fn with_migration_backup(
+        directory: Option<&Path>,
+        migrate: impl for<'any> Fn(&rusqlite::Connection, u32) -> Result<(), rusqlite::Error>
+        + Send
+        + 'static,
+        backup: bool,
+    ) -> anyhow::Result<Self> {
+        let backup_path = directory.filter(|_| backup).map(|dir| {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            dir.join(format!("klatsch.db.bak.{timestamp}"))
+        });
         let mut builder = ClientBuilder::new();
         let mut lock = None;
         if let Some(dir) = directory {
@@ -49,7 +108,7 @@ impl SqlitePersistence {
         )?;
 
         let outcome = conn
-            .conn_mut(move |conn| migrate_to_current(conn, migrate))
+            .conn_mut(move |conn| migrate_to_current(conn, migrate, backup_path))
             .await
             .inspect_err(
                 |err| error!(target: "persistence", error=%err, "failed to migrate database"),
@@ -59,6 +118,7 @@ impl SqlitePersistence {
         let persistence = SqlitePersistence {
             conn,
             _lock_file: lock,
+            wal_checkpoints: None,
         };
         Ok(persistence)
     }
@@ -66,6 +126,73 @@ impl SqlitePersistence {
     pub fn client(&self) -> Client {
         self.conn.clone()
     }
+
+    /// Sets when writes are acknowledged, trading write latency for surviving a power loss.
+    pub async fn set_durability(&self, durability: Durability) -> anyhow::Result<()> {
+        self.conn
+            .conn(move |conn| conn.pragma_update(None, "synchronous", durability.synchronous()))
+            .await
+            .inspect_err(
+                |err| error!(target: "persistence", error=%err, "Failed to set durability"),
+            )?;
+        Ok(())
+    }
+
+    /// Prepares the statements `warm_up` asks for and keeps them in the statement cache of the
+    /// connection. Otherwise the first request after boot pays for compiling them.
+    pub async fn warm_up(
+        &self,
+        warm_up: impl FnOnce(&rusqlite::Connection) -> Result<(), rusqlite::Error> + Send + 'static,
+    ) -> anyhow::Result<()> {
+        self.conn.conn(warm_up).await.inspect_err(
+            |err| error!(target: "persistence", error=%err, "Failed to warm up database connection"),
+        )?;
+        Ok(())
+    }
+
+    /// Spawns a background task which checkpoints the write ahead log every `period`. SQLite
+    /// checkpoints automatically once the WAL exceeds 1000 pages. Under steady writes the WAL may
+    /// still grow between these, increasing recovery time. We use passive checkpoints, which never
+    /// wait for readers or writers, so the checkpoint does not block new messages.
+    pub fn checkpoint_wal_periodically(&mut self, period: Duration) {
+        let conn = self.conn.clone();
+        let task = spawn_named("wal checkpoints", async move {
+            let mut ticks = interval(period);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // First tick completes immediately. Nothing to checkpoint right after startup.
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                match conn.conn_mut(checkpoint_wal_passive).await {
+                    Ok((busy, log, checkpointed)) => debug!(
+                        target: "persistence",
+                        busy, log, checkpointed,
+                        "WAL checkpoint"
+                    ),
+                    Err(err) => error!(target: "persistence", error=%err, "WAL checkpoint failed"),
+                }
+            }
+        });
+        if let Some(previous) = self.wal_checkpoints.replace(task.abort_handle()) {
+            previous.abort();
+        }
+    }
+}
+
+impl Drop for SqlitePersistence {
+    fn drop(&mut self) {
+        if let Some(wal_checkpoints) = self.wal_checkpoints.take() {
+            wal_checkpoints.abort();
+        }
+    }
+}
+
+/// Runs a passive WAL checkpoint. Returns whether the checkpoint has been blocked (`1`) or not
+/// (`0`), the number of frames in the WAL and the number of frames checkpointed into the database.
+fn checkpoint_wal_passive(conn: &mut rusqlite::Connection) -> rusqlite::Result<(i64, i64, i64)> {
+    conn.query_row("PRAGMA wal_checkpoint(PASSIVE)", [], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    })
 }
 
 impl ExecuteSqlAsync for Client {
@@ -83,7 +210,11 @@ impl ExecuteSqlAsync for Client {
         self.conn_mut(move |conn| {
             let transaction = conn.transaction()?;
             let out = f(&transaction)?;
-            transaction.commit()?;
+            // Some errors, like a full disk, roll back the transaction right away. If `f` handled
+            // such an error, there is nothing left to commit.
+            if !transaction.is_autocommit() {
+                transaction.commit()?;
+            }
             Ok(out)
         })
         .await
@@ -136,6 +267,96 @@ impl ExecuteSqlAsync for Client {
             .inspect_err(|err| error!(target: "persistence", error=%err, "Failed to read rows"))
             .map_err(Into::into)
     }
+
+    async fn interruptible_rows_vec<O>(
+        &self,
+        query: &'static str,
+        params: impl Arguments + Send + Sync + 'static,
+        map: impl Fn(&Row<'_>) -> Result<O, rusqlite::Error> + Send + 'static,
+    ) -> anyhow::Result<Vec<O>>
+    where
+        O: Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(QueryState::Queued));
+        let _interrupt = InterruptOnDrop(state.clone());
+        let fetch_rows = move |conn: &rusqlite::Connection| {
+            run_interruptible(&state, conn, |conn| {
+                let mut stmt = conn
+                    .prepare_cached(query)
+                    .expect("hardcoded SQL must be valid");
+                let params = to_rusqlite_params(&params);
+                stmt.query_map(params, map)?.collect()
+            })
+        };
+
+        self.conn(fetch_rows)
+            .await
+            .inspect_err(|err| error!(target: "persistence", error=%err, "Failed to read rows"))
+            .map_err(Into::into)
+    }
+}
+
+/// Progress of an interruptible query, shared between the connection thread running it and the
+/// future awaiting its result.
+enum QueryState {
+    /// Waiting for the connection to finish the queries sent before it.
+    Queued,
+    /// Running on the connection, which the handle is able to interrupt.
+    Running(InterruptHandle),
+    /// Completed, or abandoned before it started. Nothing left to interrupt.
+    Done,
+}
+
+/// Interrupts the query once the future awaiting it is dropped, e.g. because it timed out.
+struct InterruptOnDrop(Arc<Mutex<QueryState>>);
+
+impl Drop for InterruptOnDrop {
+    fn drop(&mut self) {
+        let mut state = self.0.lock().unwrap();
+        // The lock keeps the query from completing in the meantime. Otherwise we might interrupt
+        // whatever statement runs next on the same connection.
+        if let QueryState::Running(handle) = &*state {
+            handle.interrupt();
+        }
+        *state = QueryState::Done;
+    }
+}
+
+/// Runs `query` on `conn`, so dropping the [`InterruptOnDrop`] sharing `state` interrupts it. Fails
+/// right away, if the query has been abandoned while it was still queued.
+fn run_interruptible<O>(
+    state: &Mutex<QueryState>,
+    conn: &rusqlite::Connection,
+    query: impl FnOnce(&rusqlite::Connection) -> Result<O, rusqlite::Error>,
+) -> Result<O, rusqlite::Error> {
+    {
+        let mut state = state.lock().unwrap();
+        if let QueryState::Done = *state {
+            return Err(rusqlite::Error::SqliteFailure(
+                ffi::Error::new(ffi::SQLITE_INTERRUPT),
+                Some("Query abandoned before it started".to_owned()),
+            ));
+        }
+        *state = QueryState::Running(conn.get_interrupt_handle());
+    }
+    let out = query(conn);
+    *state.lock().unwrap() = QueryState::Done;
+    out
+}
+
+impl SchemaStatus for Client {
+    async fn schema_version(&self) -> anyhow::Result<SchemaVersion> {
+        let found = self
+            .conn(|conn| conn.pragma_query_value(None, "user_version", |row| row.get(0)))
+            .await
+            .inspect_err(
+                |err| error!(target: "persistence", error=%err, "Failed to read schema version"),
+            )?;
+        Ok(SchemaVersion {
+            found,
+            supported: CURRENT_SCHEMA_VERSION,
+        })
+    }
 }
 
 /// Convert arguments as defined by the persistent trait, into `Params` as defined by rusqlite.
@@ -175,6 +396,12 @@ impl GetField<Option<String>> for rusqlite::Row<'_> {
     }
 }
 
+impl GetField<Vec<u8>> for rusqlite::Row<'_> {
+    fn get(&self, index: usize) -> Vec<u8> {
+        self.get(index).unwrap()
+    }
+}
+
 impl GetField<Uuid> for rusqlite::Row<'_> {
     fn get(&self, index: usize) -> Uuid {
         self.get(index).unwrap()
@@ -193,6 +420,12 @@ impl ExecuteSqlSync for rusqlite::Connection {
         Ok(())
     }
 
+    fn prepare(&self, query: &str) -> Result<(), Self::Error> {
+        // Dropping the statement returns it to the cache of the connection.
+        self.prepare_cached(query)?;
+        Ok(())
+    }
+
     fn row<O>(
         &self,
         query: &str,
@@ -231,13 +464,28 @@ impl PersistenceError for rusqlite::Error {
             )
         )
     }
+
+    fn is_storage_full(&self) -> bool {
+        matches!(
+            self,
+            rusqlite::Error::SqliteFailure(
+                ffi::Error {
+                    code: ffi::ErrorCode::DiskFull,
+                    ..
+                },
+                _,
+            )
+        )
+    }
 }
 
+#[derive(Debug, PartialEq, Eq)]
 enum MigrationOutcome {
     /// Found an empty database and created the schema from scratch.
     Created,
-    /// Found an old schema and migrated it to the current version.
-    Migrated,
+    /// Found an old schema and migrated it to the current version. One step per version, each in
+    /// its own transaction.
+    Migrated { from_version: u32, steps: u32 },
     /// Found a recent schema. No migration was necessary.
     NoMigration,
     /// Found a future schema version. Aborted to prevent data loss.
@@ -251,8 +499,17 @@ impl MigrationOutcome {
                 info!(target: "persistence", "New database created");
                 Ok(())
             }
-            MigrationOutcome::Migrated => {
-                info!(target: "persistence", "Database migrated");
+            MigrationOutcome::Migrated {
+                from_version,
+                steps,
+            } => {
+                info!(
+                    target: "persistence",
+                    from_version,
+                    to_version = CURRENT_SCHEMA_VERSION,
+                    steps,
+                    "Database migrated"
+                );
                 Ok(())
             }
             MigrationOutcome::NoMigration => Ok(()),
@@ -282,10 +539,12 @@ fn acquire_lock(dir: &Path) -> anyhow::Result<File> {
     }
 }
 
-/// Migration function running in the actor thread of async-sqlite
+/// Migration function running in the actor thread of async-sqlite. An old schema is backed up to
+/// `backup`, if given, before it is migrated.
 fn migrate_to_current(
     conn: &mut rusqlite::Connection,
     migrate: impl Fn(&rusqlite::Connection, u32) -> Result<(), rusqlite::Error>,
+    backup: Option<PathBuf>,
 ) -> Result<MigrationOutcome, rusqlite::Error> {
     let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
     // Version 0 is the initial version of an empty database. We regard creating a new database as a
@@ -299,14 +558,22 @@ fn migrate_to_current(
             tx.commit()?;
             MigrationOutcome::Created
         }
+        // Old schema. Apply the steps in order, one version at a time. Each step is committed on
+        // its own, so an interrupted migration resumes with the step which failed.
         found_version @ (1..CURRENT_SCHEMA_VERSION) => {
+            if let Some(backup) = backup {
+                back_up(conn, &backup)?;
+            }
             for from in found_version..CURRENT_SCHEMA_VERSION {
                 let tx = conn.transaction()?;
                 migrate(&tx, from)?;
                 tx.pragma_update(None, "user_version", from + 1)?;
                 tx.commit()?;
             }
-            MigrationOutcome::Migrated
+            MigrationOutcome::Migrated {
+                from_version: found_version,
+                steps: CURRENT_SCHEMA_VERSION - found_version,
+            }
         }
         // Current version, do nothing.
         CURRENT_SCHEMA_VERSION => MigrationOutcome::NoMigration,
@@ -318,11 +585,24 @@ fn migrate_to_current(
     Ok(outcome)
 }
 
+/// Writes a copy of the database to `path`. Unlike copying the file, this includes changes still in
+/// the write ahead log and yields a consistent copy.
+fn back_up(conn: &rusqlite::Connection, path: &Path) -> Result<(), rusqlite::Error> {
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])?;
+    info!(
+        target: "persistence",
+        path = %path.display(),
+        "Database backed up before migration"
+    );
+    Ok(())
+}
+
 impl ToSql for Argument<'_> {
     fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
         match self {
             Argument::I64(i) => i.to_sql(),
             Argument::Text(s) => s.to_sql(),
+            Argument::Blob(b) => b.to_sql(),
             Argument::Uuid(id) => id.to_sql(),
             Argument::Null => Ok(ToSqlOutput::Owned(Value::Null)),
         }
@@ -333,7 +613,80 @@ impl ToSql for Argument<'_> {
 mod tests {
     use crate::persistence::GetField;
 
-    use super::{ClientBuilder, ExecuteSqlAsync, JournalMode, SqlitePersistence, rusqlite};
+    use std::{cell::RefCell, time::Duration};
+    use tokio::time::timeout;
+
+    use super::{
+        CURRENT_SCHEMA_VERSION, ClientBuilder, Durability, ExecuteSqlAsync, JournalMode,
+        MigrationOutcome, SqlitePersistence, migrate_to_current, rusqlite,
+    };
+    use crate::persistence::migrate;
+
+    #[test]
+    fn empty_database_is_created_from_scratch() {
+        // Given an empty database
+        let mut conn = database_at_version(0);
+
+        // When migrating it
+        let (outcome, steps) = migrate_recording_steps(&mut conn);
+
+        // Then the schema is created in one go
+        assert_eq!(MigrationOutcome::Created, outcome);
+        assert_eq!(vec![0], steps);
+        assert_eq!(CURRENT_SCHEMA_VERSION, user_version(&conn));
+    }
+
+    #[test]
+    fn old_schema_is_migrated_step_by_step() {
+        // Given a database with the first schema version
+        let mut conn = database_at_version(1);
+
+        // When migrating it
+        let (outcome, steps) = migrate_recording_steps(&mut conn);
+
+        // Then every step up to the current version is applied in order
+        assert_eq!(
+            MigrationOutcome::Migrated {
+                from_version: 1,
+                steps: CURRENT_SCHEMA_VERSION - 1
+            },
+            outcome
+        );
+        assert_eq!((1..CURRENT_SCHEMA_VERSION).collect::<Vec<_>>(), steps);
+        assert_eq!(CURRENT_SCHEMA_VERSION, user_version(&conn));
+    }
+
+    #[test]
+    fn current_schema_is_not_migrated() {
+        // Given a database with the current schema version
+        let mut conn = database_at_version(CURRENT_SCHEMA_VERSION);
+
+        // When migrating it
+        let (outcome, steps) = migrate_recording_steps(&mut conn);
+
+        // Then nothing happens
+        assert_eq!(MigrationOutcome::NoMigration, outcome);
+        assert!(steps.is_empty());
+    }
+
+    #[test]
+    fn future_schema_is_left_untouched() {
+        // Given a database with a schema version newer than supported
+        let mut conn = database_at_version(CURRENT_SCHEMA_VERSION + 1);
+
+        // When migrating it
+        let (outcome, steps) = migrate_recording_steps(&mut conn);
+
+        // Then no step is applied and the version is reported
+        assert_eq!(
+            MigrationOutcome::Future {
+                version: CURRENT_SCHEMA_VERSION + 1
+            },
+            outcome
+        );
+        assert!(steps.is_empty());
+        assert_eq!(CURRENT_SCHEMA_VERSION + 1, user_version(&conn));
+    }
 
     #[tokio::test]
     async fn creates_missing_persistence_directory() {
@@ -351,6 +704,35 @@ mod tests {
         assert!(missing_dir.join("klatsch.db").exists());
     }
 
+    #[tokio::test]
+    async fn abandoned_query_is_interrupted() {
+        // Given a query which never completes on its own
+        let dummy_migration = |_conn: &rusqlite::Connection, _from_version: u32| Ok(());
+        let persistence = SqlitePersistence::new(None, dummy_migration).await.unwrap();
+        let client = persistence.client();
+        let endless = client.interruptible_rows_vec(
+            "WITH RECURSIVE numbers(n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM numbers) \
+                SELECT COUNT(*) FROM numbers",
+            (),
+            |row| Ok(<rusqlite::Row as GetField<i64>>::get(row, 0)),
+        );
+
+        // When abandoning it, because it timed out
+        let result = timeout(Duration::from_millis(100), endless).await;
+        assert!(result.is_err());
+
+        // Then the connection is free to run the next query
+        let next = timeout(
+            Duration::from_secs(5),
+            client.row("SELECT 1", (), |row| {
+                Ok(<rusqlite::Row as GetField<i64>>::get(row, 0))
+            }),
+        )
+        .await
+        .expect("Abandoned query must not keep the connection busy");
+        assert_eq!(1, next.unwrap());
+    }
+
     #[tokio::test]
     async fn second_instance_on_same_directory_is_rejected() {
         // Given a persistence instance backed by a directory in the file system
@@ -402,6 +784,108 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn migration_is_preceded_by_backup_if_configured() {
+        // Given a directory with a database of the first schema version
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::copy("tests/v1.db", dir.path().join("klatsch.db")).unwrap();
+
+        // When opening it twice with backups before migrations
+        for _ in 0..2 {
+            SqlitePersistence::with_migration_backup(Some(dir.path()), migrate, true)
+                .await
+                .unwrap();
+        }
+
+        // Then only the first open, which migrated the database, backed up the first version
+        let backups: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("klatsch.db.bak.")
+            })
+            .collect();
+        assert_eq!(1, backups.len(), "{backups:?}");
+        let backup = rusqlite::Connection::open(&backups[0]).unwrap();
+        assert_eq!(1, user_version(&backup));
+    }
+
+    #[tokio::test]
+    async fn periodic_wal_checkpoints_keep_wal_bounded() {
+        // Given a file backed database with automatic checkpoints disabled and periodic checkpoints
+        // every 10ms.
+        let dir = tempfile::tempdir().unwrap();
+        let create_schema = |connection: &rusqlite::Connection, _from_version: u32| {
+            connection.pragma_update(None, "wal_autocheckpoint", 0)?;
+            connection.execute("CREATE TABLE my_table (data TEXT)", ())?;
+            Ok(())
+        };
+        let mut persistence = SqlitePersistence::new(Some(dir.path()), create_schema)
+            .await
+            .unwrap();
+        persistence.checkpoint_wal_periodically(Duration::from_millis(10));
+        let wal_size = || {
+            std::fs::metadata(dir.path().join("klatsch.db-wal"))
+                .unwrap()
+                .len()
+        };
+
+        // When writing several bursts of data, with time for checkpoints in between
+        let mut sizes = Vec::new();
+        for _ in 0..5 {
+            for _ in 0..50 {
+                persistence
+                    .client()
+                    .transaction(|conn| {
+                        conn.execute("INSERT INTO my_table (data) VALUES (zeroblob(1024))", ())
+                    })
+                    .await
+                    .unwrap();
+            }
+            sizes.push(wal_size());
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        // Then the WAL is reused after each checkpoint, rather than growing with every burst
+        let first = sizes[0];
+        let last = *sizes.last().unwrap();
+        assert!(last < 2 * first, "WAL grew from {first} to {last} bytes");
+    }
+
+    #[tokio::test]
+    async fn full_durability_syncs_writes() {
+        // Given a file backed database
+        let dir = tempfile::tempdir().unwrap();
+        let create_schema = |connection: &rusqlite::Connection, _from_version: u32| {
+            connection.execute("CREATE TABLE my_table (data TEXT)", ())?;
+            Ok(())
+        };
+        let persistence = SqlitePersistence::new(Some(dir.path()), create_schema)
+            .await
+            .unwrap();
+
+        // When relaxing durability and then asking for full durability again
+        persistence
+            .set_durability(Durability::Normal)
+            .await
+            .unwrap();
+        let normal = synchronous(&persistence).await;
+        persistence.set_durability(Durability::Full).await.unwrap();
+        let full = synchronous(&persistence).await;
+
+        // Then the pragma follows, and writes still succeed
+        assert_eq!(1, normal);
+        assert_eq!(2, full);
+        persistence
+            .client()
+            .transaction(|conn| conn.execute("INSERT INTO my_table (data) VALUES ('Hello')", ()))
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     async fn persistence() {
         // Given a directory
@@ -448,4 +932,38 @@ mod tests {
             .unwrap();
         assert_eq!([(1i64, "Hello, World!".to_owned())].as_slice(), &after);
     }
+
+    async fn synchronous(persistence: &SqlitePersistence) -> i64 {
+        persistence
+            .client()
+            .conn(|conn| conn.pragma_query_value(None, "synchronous", |row| row.get(0)))
+            .await
+            .unwrap()
+    }
+
+    fn database_at_version(version: u32) -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", version).unwrap();
+        conn
+    }
+
+    fn user_version(conn: &rusqlite::Connection) -> u32 {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    /// Migrates `conn` and returns the versions the migration has been invoked with.
+    fn migrate_recording_steps(conn: &mut rusqlite::Connection) -> (MigrationOutcome, Vec<u32>) {
+        let steps = RefCell::new(Vec::new());
+        let outcome = migrate_to_current(
+            conn,
+            |_conn: &rusqlite::Connection, from_version| {
+                steps.borrow_mut().push(from_version);
+                Ok(())
+            },
+            None,
+        )
+        .unwrap();
+        (outcome, steps.into_inner())
+    }
 }
@@ -0,0 +1,42 @@
+use crate::{chat::warm_up_chat_persistence, persistence::ExecuteSqlSync};
+
+/// Prepares the statements used to answer frequent requests, for the entire klatsch application.
+pub fn warm_up<C>(conn: &C) -> Result<(), C::Error>
+where
+    C: ExecuteSqlSync,
+{
+    // Only the chat is hot. Users are looked up rarely, during login and signup.
+    warm_up_chat_persistence(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chat::{Chat as _, ChatRuntime, ChatStoreOptions, EventId, Message},
+        persistence::{SqlitePersistence, migrate},
+    };
+
+    use super::warm_up;
+
+    #[tokio::test]
+    async fn chat_works_after_warm_up() {
+        // Given a warmed up persistence
+        let persistence = SqlitePersistence::new(None, migrate).await.unwrap();
+        persistence.warm_up(warm_up).await.unwrap();
+
+        // When recording and reading a message
+        let chat = ChatRuntime::new(persistence.client(), ChatStoreOptions::default())
+            .await
+            .unwrap();
+        let mut client = chat.client();
+        client.add_message(Message::dummy()).await.unwrap();
+        let (events, _) = client.history(EventId::before_all(), 10).await.unwrap();
+
+        // Then the message is part of the history
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].message, Message::dummy());
+
+        // Cleanup
+        chat.shutdown().await;
+    }
+}
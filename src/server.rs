@@ -1,44 +1,150 @@
 mod api;
+mod client_ip;
+mod health;
+mod idle_timeout;
 mod session_cookie;
 mod ui;
 
-use std::time::Duration;
+use std::{io, net::SocketAddr, path::PathBuf, time::Duration};
 
 use axum::{
-    Router,
-    http::{HeaderMap, Request, Response},
+    Json, Router,
+    extract::{ConnectInfo, MatchedPath},
+    http::{HeaderMap, HeaderName, HeaderValue, Request, Response},
+    response::Redirect,
     routing::get,
+    serve::ListenerExt as _,
 };
+use serde::Serialize;
+use socket2::SockRef;
 
 use tokio::{
-    net::{TcpListener, ToSocketAddrs},
+    net::{TcpListener, TcpSocket, TcpStream, ToSocketAddrs, lookup_host},
     sync::watch,
     task::JoinHandle,
 };
-use tower_http::{classify::ServerErrorsFailureClass, trace::TraceLayer};
-use tracing::{Span, debug, debug_span, error, info};
+use tower_http::{
+    classify::ServerErrorsFailureClass, set_header::SetResponseHeaderLayer, trace::TraceLayer,
+};
+use tracing::{Span, debug, debug_span, error, field, info};
+
+use crate::{
+    chat::{Chat, ChatHttpOptions},
+    http::{AuthenticateRequest, Lifecycle},
+    persistence::SchemaStatus,
+    sessions::SessionLifecycle,
+    task::spawn_named,
+    user::Users,
+};
+
+use self::{
+    api::api_router,
+    client_ip::{PeerAddr, client_ip},
+    health::health_router,
+    idle_timeout::IdleTimeoutListener,
+    ui::ui_router,
+};
+
+/// Options for accepting and serving connections.
+#[derive(Clone, Debug)]
+pub struct ServerOptions {
+    /// Maximum number of pending connections, which have not yet been accepted.
+    pub listen_backlog: u32,
+    /// Set `SO_REUSEPORT` on the listening sockets, so a new instance can bind the same port while
+    /// the old one is still draining. Only supported on unix, ignored elsewhere.
+    pub reuse_port: bool,
+    /// Connections are dropped, once nothing has been sent to the peer for this long. Must exceed
+    /// the interval of keep-alive events and the timeout of the poll route. `None` keeps idle
+    /// connections indefinitely.
+    pub idle_timeout: Option<Duration>,
+    /// Connections are dropped, once the peer has not accepted any data for this long, while there
+    /// is data to send. Unlike the idle timeout, it may be shorter than the interval of keep-alive
+    /// events. `None` waits for stalled peers indefinitely.
+    pub write_timeout: Option<Duration>,
+    /// Whether klatsch runs behind a reverse proxy, whose forwarding headers tell the address of
+    /// the client.
+    pub trust_proxy: bool,
+    /// Serve the UI from this directory, rather than from the assets embedded at build time.
+    /// Intended for frontend development.
+    pub ui_dir: Option<PathBuf>,
+    /// Content of `/robots.txt`. `None` asks all crawlers to stay away from the whole site.
+    pub robots_txt: Option<String>,
+    /// Tell search engines not to index the UI, via the `X-Robots-Tag` header.
+    pub noindex: bool,
+    /// Disable Nagle's algorithm on accepted connections, so small frames of the event stream are
+    /// sent right away, rather than being held back to be coalesced with later ones.
+    pub tcp_nodelay: bool,
+    /// Probe idle connections, so the operating system detects peers which vanished without
+    /// closing them. `None` leaves keepalive disabled.
+    pub tcp_keepalive: Option<TcpKeepalive>,
+    /// What `/` is answered with. Anything but the UI runs klatsch as a pure API.
+    pub root_response: RootResponse,
+    /// Log requests by their route, rather than their URI. Path parameters and queries may identify
+    /// senders or carry message content, so they are withheld from the logs.
+    pub redact_uris: bool,
+}
 
-use crate::{chat::Chat, http::AuthenticateRequest, sessions::SessionLifecycle, user::Users};
+/// What the server answers requests to `/` with.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum RootResponse {
+    /// Serve the UI, at `/` and any other path not taken by the API.
+    #[default]
+    Ui,
+    /// Do not serve the UI. `/` is answered with the name and version of the service as JSON.
+    Info,
+    /// Do not serve the UI. `/` redirects to this URL, e.g. the documentation of the API.
+    Redirect(String),
+    /// Do not serve the UI. `/` is answered with this text.
+    Text(String),
+}
+
+/// Configures the keepalive probes of TCP connections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TcpKeepalive {
+    /// How long a connection must be idle, before the first probe is sent.
+    pub idle: Duration,
+    /// Time between probes. `None` uses the default of the operating system.
+    pub interval: Option<Duration>,
+}
+
+/// Served as `/robots.txt`, unless configured otherwise.
+const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nDisallow: /\n";
 
-use self::{api::api_router, ui::ui_router};
+const X_ROBOTS_TAG: HeaderName = HeaderName::from_static("x-robots-tag");
 
 pub struct Server {
     /// Indicates whether the server is about to shut down. Long-lived requests like event streams
     /// watch this in order to short circut and allow the the graceful shutdown to complete faster.
     shutting_down: watch::Sender<bool>,
-    join_handle: JoinHandle<()>,
+    /// Indicates whether the server is draining. I.e. it still serves events, but rejects new
+    /// messages, so clients can move on to another instance before it shuts down.
+    draining: watch::Sender<bool>,
+    /// One task serving requests for each listener.
+    join_handles: Vec<JoinHandle<()>>,
+    /// The addresses the listeners are bound to. Differ from the requested ones in case we asked
+    /// the operating system to choose a free port.
+    local_addrs: Vec<SocketAddr>,
 }
 
 impl Server {
     /// Starts the HTTP server providing both the API and UI to clients. While the server runs in
-    /// its own thread, the TCP socket is already opened and listened to once this function returns.
+    /// its own thread, the TCP sockets are already opened and listened to once this function
+    /// returns. Every one of `socket_addresses` serves the same routes.
     pub async fn new(
-        socket_address: impl ToSocketAddrs,
+        socket_addresses: impl IntoIterator<Item = impl ToSocketAddrs>,
         chat: impl Chat + Send + Sync + Clone + 'static,
         users: impl Users + Send + Sync + Clone + 'static,
         sessions: impl SessionLifecycle + AuthenticateRequest + Send + Sync + Clone + 'static,
+        schema: impl SchemaStatus + Send + Sync + Clone + 'static,
+        chat_options: ChatHttpOptions,
+        options: ServerOptions,
     ) -> anyhow::Result<Server> {
-        let listener = TcpListener::bind(socket_address).await?;
+        let mut listeners = Vec::new();
+        for socket_address in socket_addresses {
+            let listener =
+                bind_listener(socket_address, options.listen_backlog, options.reuse_port).await?;
+            listeners.push(listener);
+        }
 
         // The "Listening" in the event log would indicate to operators that we can do accept
         // incoming connections. Before creating the listener they would have been refused with a
@@ -48,58 +154,228 @@ impl Server {
         // chooses a free port for us. Only through this log message then the operator will learn
         // on which port the server listens. The integration tests utilize binding to port `0` in
         // order to run in parallel without clashing on ports.
-        info!(
-            target: "server",
-            port = listener
-                .local_addr()
-                .expect("Listener must have local address after binding")
-                .port(),
-            "Listening"
+        let local_addrs: Vec<_> = listeners
+            .iter()
+            .map(|listener| {
+                listener
+                    .local_addr()
+                    .expect("Listener must have local address after binding")
+            })
+            .collect();
+        for local_addr in &local_addrs {
+            info!(target: "server", address = %local_addr.ip(), port = local_addr.port(), "Listening");
+        }
+        let (shutting_down_sender, shutting_down_receiver) = watch::channel(false);
+        let (draining_sender, draining_receiver) = watch::channel(false);
+        let lifecycle = Lifecycle {
+            shutting_down: shutting_down_receiver.clone(),
+            draining: draining_receiver,
+        };
+        let router = router(
+            chat,
+            users,
+            sessions,
+            schema,
+            lifecycle,
+            chat_options,
+            &options,
         );
-        let (shutting_down_sender, mut shutting_down_receiver) = watch::channel(false);
-        let join_handle = tokio::spawn(async move {
-            let router = router(chat, users, sessions, shutting_down_receiver.clone());
-            axum::serve(listener, router)
-                .with_graceful_shutdown(async move {
-                    shutting_down_receiver
-                        .wait_for(|&is_shutting_down| is_shutting_down)
+        let join_handles = listeners
+            .into_iter()
+            .map(|listener| {
+                let (nodelay, keepalive) = (options.tcp_nodelay, options.tcp_keepalive);
+                let listener =
+                    listener.tap_io(move |tcp| configure_connection(tcp, nodelay, keepalive));
+                let listener = IdleTimeoutListener::new(listener, options.idle_timeout)
+                    .with_write_timeout(options.write_timeout);
+                let service = router
+                    .clone()
+                    .into_make_service_with_connect_info::<PeerAddr>();
+                let mut shutting_down_receiver = shutting_down_receiver.clone();
+                spawn_named("server", async move {
+                    axum::serve(listener, service)
+                        .with_graceful_shutdown(async move {
+                            shutting_down_receiver
+                                .wait_for(|&is_shutting_down| is_shutting_down)
+                                .await
+                                .expect(
+                                    "Sender for shutdown sender must not be dropped before used.",
+                                );
+                        })
                         .await
-                        .expect("Sender for shutdown sender must not be dropped before used.");
+                        .expect("axum::serve must not return an error");
                 })
-                .await
-                .expect("axum::serve must not return an error");
-        });
+            })
+            .collect();
         let server = Server {
             shutting_down: shutting_down_sender,
-            join_handle,
+            draining: draining_sender,
+            join_handles,
+            local_addrs,
         };
         Ok(server)
     }
 
+    /// The addresses the server listens on. In case the server has been asked to bind to port `0`,
+    /// this tells which ports the operating system has chosen.
+    pub fn local_addrs(&self) -> &[SocketAddr] {
+        &self.local_addrs
+    }
+
+    /// Stop accepting new messages, while still serving events.
+    pub fn drain(&self) {
+        self.draining.send_replace(true);
+    }
+
     pub async fn shutdown(self) {
         self.shutting_down.send(true).expect("Receiver must exist");
-        self.join_handle.await.unwrap();
+        for join_handle in self.join_handles {
+            join_handle.await.unwrap();
+        }
+    }
+}
+
+/// Binds a listener to the first of the resolved addresses which works. Similar to
+/// [`TcpListener::bind`], but allows for a custom backlog and sets `SO_REUSEADDR`. The latter allows
+/// us to rebind right after a restart, while the previous socket still lingers in `TIME_WAIT`.
+/// `reuse_port` sets `SO_REUSEPORT` as well, so several sockets may listen on the same port at once,
+/// as long as all of them set it.
+async fn bind_listener(
+    socket_address: impl ToSocketAddrs,
+    backlog: u32,
+    reuse_port: bool,
+) -> io::Result<TcpListener> {
+    let mut last_err = None;
+    for address in lookup_host(socket_address).await? {
+        match bind_listener_to(address, backlog, reuse_port) {
+            Ok(listener) => return Ok(listener),
+            Err(err) => last_err = Some(err),
+        }
     }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any address",
+        )
+    }))
+}
+
+fn bind_listener_to(
+    address: SocketAddr,
+    backlog: u32,
+    reuse_port: bool,
+) -> io::Result<TcpListener> {
+    let socket = if address.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    // On Windows `SO_REUSEADDR` would allow other processes to bind to the same port while we are
+    // still listening. Windows does not keep listening ports blocked in `TIME_WAIT` anyway.
+    #[cfg(not(windows))]
+    socket.set_reuseaddr(true)?;
+    #[cfg(unix)]
+    socket.set_reuseport(reuse_port)?;
+    #[cfg(not(unix))]
+    let _ = reuse_port;
+    socket.bind(address)?;
+    socket.listen(backlog)
 }
 
-fn router<C, U, S>(chat: C, users: U, sessions: S, shutting_down: watch::Receiver<bool>) -> Router
+/// Applies the socket options to an accepted connection. Failing to do so is not worth dropping the
+/// connection over, e.g. the peer may already be gone.
+fn configure_connection(tcp: &mut TcpStream, nodelay: bool, keepalive: Option<TcpKeepalive>) {
+    if let Err(err) = tcp.set_nodelay(nodelay) {
+        debug!(target: "server", error = %err, "Failed to set TCP_NODELAY");
+    }
+    let Some(TcpKeepalive { idle, interval }) = keepalive else {
+        return;
+    };
+    let mut params = socket2::TcpKeepalive::new().with_time(idle);
+    if let Some(interval) = interval {
+        params = params.with_interval(interval);
+    }
+    if let Err(err) = SockRef::from(&*tcp).set_tcp_keepalive(&params) {
+        debug!(target: "server", error = %err, "Failed to enable TCP keepalive");
+    }
+}
+
+fn router<C, U, S, M>(
+    chat: C,
+    users: U,
+    sessions: S,
+    schema: M,
+    lifecycle: Lifecycle,
+    chat_options: ChatHttpOptions,
+    options: &ServerOptions,
+) -> Router
 where
     C: Chat + Send + Sync + Clone + 'static,
     U: Users + Send + Sync + Clone + 'static,
     S: SessionLifecycle + AuthenticateRequest + Send + Sync + Clone + 'static,
+    M: SchemaStatus + Send + Sync + Clone + 'static,
 {
+    let robots_txt = options
+        .robots_txt
+        .clone()
+        .unwrap_or_else(|| DEFAULT_ROBOTS_TXT.to_owned());
     let router = Router::new()
-        .route("/health", get(|| async { "OK" }))
-        .merge(api_router(chat, users, sessions, shutting_down))
-        .merge(ui_router());
+        .merge(health_router(schema))
+        .route("/robots.txt", get(|| async move { robots_txt }))
+        .merge(api_router(chat, users, sessions, lifecycle, chat_options));
+    let router = match &options.root_response {
+        // As fallback, so our `/robots.txt` takes precedence over one shipped with the UI assets.
+        RootResponse::Ui => router.fallback_service(ui(options)),
+        RootResponse::Info => router.route(
+            "/",
+            get(|| async {
+                Json(ServiceInfo {
+                    service: "klatsch",
+                    version: env!("CARGO_PKG_VERSION"),
+                })
+            }),
+        ),
+        RootResponse::Redirect(url) => {
+            let redirect = Redirect::temporary(url);
+            router.route("/", get(|| async move { redirect }))
+        }
+        RootResponse::Text(text) => {
+            let text = text.clone();
+            router.route("/", get(|| async move { text }))
+        }
+    };
+
+    add_tracing_layer(router, options.trust_proxy, options.redact_uris)
+}
+
+/// Answer to `/`, if the UI is not served and [`RootResponse::Info`] is configured.
+#[derive(Serialize)]
+struct ServiceInfo {
+    service: &'static str,
+    version: &'static str,
+}
 
-    add_tracing_layer(router)
+/// Router for the UI. Responses carry `X-Robots-Tag: noindex`, if configured.
+fn ui(options: &ServerOptions) -> Router {
+    let ui = ui_router(options.ui_dir.as_deref());
+    if !options.noindex {
+        return ui;
+    }
+    ui.layer(SetResponseHeaderLayer::overriding(
+        X_ROBOTS_TAG,
+        HeaderValue::from_static("noindex"),
+    ))
 }
 
 /// Extends the router with a tracing layer. We want to log request spans as part of the http
 /// target. Function operates on `Router` as the types for Tracing layers or the constraints on
 /// Layer traits are rather verbose.
-fn add_tracing_layer(router: Router) -> Router {
+///
+/// Spans carry the IP address of the client. Behind a trusted reverse proxy, it is taken from the
+/// forwarding headers. If `redact_uris` is set, spans carry the route, e.g.
+/// `/api/v0/admin/senders/{sender}/export`, instead of the URI. Requests not matching any route,
+/// i.e. those to the UI, carry their path without the query.
+fn add_tracing_layer(router: Router, trust_proxy: bool, redact_uris: bool) -> Router {
     // Mostly we want to replace targets like tower_http::trace::on_request with our own "http"
     // target. We imagine not only developers operating klatsch. Therfore what modules and libraries
     // we use should be an implementation detail.
@@ -108,12 +384,27 @@ fn add_tracing_layer(router: Router) -> Router {
     // chosen here.
     router.layer(
         TraceLayer::new_for_http()
-            .make_span_with(|request: &Request<_>| {
+            .make_span_with(move |request: &Request<_>| {
+                let client_ip = request
+                    .extensions()
+                    .get::<ConnectInfo<PeerAddr>>()
+                    .map(|ConnectInfo(PeerAddr(peer))| {
+                        client_ip(request.headers(), peer.ip(), trust_proxy)
+                    });
+                let uri = if redact_uris {
+                    match request.extensions().get::<MatchedPath>() {
+                        Some(route) => route.as_str().to_owned(),
+                        None => request.uri().path().to_owned(),
+                    }
+                } else {
+                    request.uri().to_string()
+                };
                 debug_span!(
                     target: "http",
                     "request",
                     method = %request.method(),
-                    uri = %request.uri(),
+                    uri = %uri,
+                    client_ip = client_ip.map(field::display),
                 )
             })
             .on_request(|_: &Request<_>, _: &Span| {
@@ -142,3 +433,352 @@ fn add_tracing_layer(router: Router) -> Router {
             ),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use double_trait::Dummy;
+    use http_body_util::BodyExt as _;
+    use socket2::SockRef;
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _},
+        net::TcpStream,
+        time::timeout,
+    };
+    use tower::ServiceExt as _;
+
+    use crate::{chat::ChatHttpOptions, http::Lifecycle, tracing::CapturedLogs, user::UserId};
+
+    use super::{
+        RootResponse, Server, ServerOptions, TcpKeepalive, bind_listener, configure_connection,
+        router,
+    };
+
+    #[tokio::test]
+    async fn serve_on_multiple_addresses() {
+        // Given a server listening on two addresses
+        let server = Server::new(
+            ["127.0.0.1:0", "127.0.0.1:0"],
+            Dummy,
+            Dummy,
+            Dummy,
+            Dummy,
+            ChatHttpOptions::default(),
+            server_options(),
+        )
+        .await
+        .unwrap();
+        let addresses = server.local_addrs().to_vec();
+
+        // When requesting the health check on each of them
+        let mut responses = Vec::new();
+        for address in &addresses {
+            let mut client = TcpStream::connect(address).await.unwrap();
+            client
+                .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            client.read_to_string(&mut response).await.unwrap();
+            responses.push(response);
+        }
+
+        // Then both answer, and both stop listening once the server is shut down
+        assert_eq!(2, addresses.len());
+        assert_ne!(addresses[0], addresses[1]);
+        for response in responses {
+            assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        }
+        server.shutdown().await;
+        for address in &addresses {
+            assert!(TcpStream::connect(address).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn rebind_right_after_closing_connections() {
+        // Given a listener which closed a connection from its side. This leaves the port in
+        // `TIME_WAIT`.
+        let listener = bind_listener("127.0.0.1:0", 16, false).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let mut client = TcpStream::connect(address).await.unwrap();
+        let (server_side, _) = listener.accept().await.unwrap();
+        drop(server_side);
+        // Wait for the client to observe the close, so the server side closed first.
+        let mut buf = [0u8; 1];
+        assert_eq!(0, client.read(&mut buf).await.unwrap());
+        drop(client);
+        drop(listener);
+
+        // When binding to the same port again immediately
+        let result = bind_listener(address, 16, false).await;
+
+        // Then there is no "address already in use" error
+        assert!(result.is_ok(), "{:?}", result.err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn listeners_reusing_port_share_incoming_connections() {
+        // Given two listeners bound to the same port, both reusing it
+        let first = bind_listener("127.0.0.1:0", 64, true).await.unwrap();
+        let address = first.local_addr().unwrap();
+        let second = bind_listener(address, 64, true).await.unwrap();
+
+        // When many clients connect to that port
+        let mut clients = Vec::new();
+        for _ in 0..64 {
+            clients.push(TcpStream::connect(address).await.unwrap());
+        }
+
+        // Then both listeners accept some of them
+        for listener in [first, second] {
+            let accepted = timeout(Duration::from_secs(1), listener.accept()).await;
+            assert!(matches!(accepted, Ok(Ok(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn accepted_connections_carry_configured_socket_options() {
+        // Given an accepted connection
+        let listener = bind_listener("127.0.0.1:0", 16, false).await.unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut connection, _) = listener.accept().await.unwrap();
+
+        // When configuring it with nodelay and keepalive
+        let keepalive = TcpKeepalive {
+            idle: Duration::from_secs(60),
+            interval: Some(Duration::from_secs(10)),
+        };
+        configure_connection(&mut connection, true, Some(keepalive));
+
+        // Then the options are set on the socket
+        assert!(connection.nodelay().unwrap());
+        assert!(SockRef::from(&connection).keepalive().unwrap());
+    }
+
+    #[tokio::test]
+    async fn request_uris_are_logged() {
+        // Given captured logs and a server which does not redact URIs
+        let logs = CapturedLogs::default();
+        let _guard = logs.capture(false);
+        let app = test_router(server_options());
+
+        // When requesting a route which carries a sender both in its path and its query
+        let response = app.oneshot(sender_request()).await.unwrap();
+
+        // Then the URI appears in the logs
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let logs = logs.text();
+        assert!(
+            logs.contains(&format!("sender={}", UserId::ALICE)),
+            "{logs}"
+        );
+    }
+
+    #[tokio::test]
+    async fn redacted_request_logs_contain_no_sender() {
+        // Given captured logs and a server which redacts URIs
+        let logs = CapturedLogs::default();
+        let _guard = logs.capture(true);
+        let app = test_router(ServerOptions {
+            redact_uris: true,
+            ..server_options()
+        });
+
+        // When requesting a route which carries a sender both in its path and its query
+        let response = app.oneshot(sender_request()).await.unwrap();
+
+        // Then the request is logged by its route, without the sender
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let logs = logs.text();
+        assert!(
+            logs.contains("/api/v0/admin/senders/{sender}/export"),
+            "{logs}"
+        );
+        assert!(!logs.contains(&UserId::ALICE.to_string()), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn robots_txt_disallows_everything_by_default() {
+        // Given a server with default options
+        let app = test_router(server_options());
+
+        // When crawlers ask for robots.txt
+        let response = app
+            .oneshot(Request::get("/robots.txt").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then they are asked to stay away from the whole site
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"User-agent: *\nDisallow: /\n");
+    }
+
+    #[tokio::test]
+    async fn ui_carries_noindex_header_if_configured() {
+        // Given a server configured to keep the UI out of search engines
+        let app = test_router(ServerOptions {
+            noindex: true,
+            ..server_options()
+        });
+
+        // When requesting the UI
+        let response = app
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then search engines are told not to index it
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-robots-tag"], "noindex");
+    }
+
+    #[tokio::test]
+    async fn ui_carries_no_noindex_header_by_default() {
+        // Given a server with default options
+        let app = test_router(server_options());
+
+        // When requesting the UI
+        let response = app
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then search engines are free to index it
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key("x-robots-tag"));
+    }
+
+    #[tokio::test]
+    async fn unknown_api_paths_are_not_answered_with_ui() {
+        // Given a server with default options
+        let app = test_router(server_options());
+
+        // When requesting an API endpoint which does not exist
+        let response = app
+            .oneshot(Request::get("/api/v0/unknown").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then it is not found, rather than answered with the index page of the UI
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn root_answers_service_info_if_configured() {
+        // Given a server running as pure API, telling about itself at the root
+        let app = test_router(ServerOptions {
+            root_response: RootResponse::Info,
+            ..server_options()
+        });
+
+        // When requesting the root
+        let response = app
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then it is answered with the name and version of the service
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let info: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            serde_json::json!({"service": "klatsch", "version": env!("CARGO_PKG_VERSION")}),
+            info
+        );
+    }
+
+    #[tokio::test]
+    async fn root_redirects_if_configured() {
+        // Given a server running as pure API, redirecting from its root
+        let app = test_router(ServerOptions {
+            root_response: RootResponse::Redirect("https://example.com/docs".to_owned()),
+            ..server_options()
+        });
+
+        // When requesting the root
+        let response = app
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then the client is sent to the configured URL
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()["location"], "https://example.com/docs");
+    }
+
+    #[tokio::test]
+    async fn root_answers_text_if_configured() {
+        // Given a server running as pure API, with a message at its root
+        let app = test_router(ServerOptions {
+            root_response: RootResponse::Text("Nothing to see here".to_owned()),
+            ..server_options()
+        });
+
+        // When requesting the root
+        let response = app
+            .clone()
+            .oneshot(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then it is answered with the message, while other paths are no longer served by the UI
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"Nothing to see here");
+        let response = app
+            .oneshot(Request::get("/room/abc").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    fn test_router(options: ServerOptions) -> Router {
+        router(
+            Dummy,
+            Dummy,
+            Dummy,
+            Dummy,
+            Lifecycle::running(),
+            ChatHttpOptions::default(),
+            &options,
+        )
+    }
+
+    /// Export of Alice's messages, filtered by Alice as sender, without a session.
+    fn sender_request() -> Request<Body> {
+        let uri = format!(
+            "/api/v0/admin/senders/{}/export?sender={}",
+            UserId::ALICE,
+            UserId::ALICE
+        );
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn server_options() -> ServerOptions {
+        ServerOptions {
+            listen_backlog: 16,
+            reuse_port: false,
+            idle_timeout: None,
+            write_timeout: None,
+            trust_proxy: false,
+            ui_dir: None,
+            robots_txt: None,
+            noindex: false,
+            tcp_nodelay: true,
+            tcp_keepalive: None,
+            root_response: RootResponse::Ui,
+            redact_uris: false,
+        }
+    }
+}
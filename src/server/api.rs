@@ -1,18 +1,18 @@
 use super::session_cookie::session_routes;
 use crate::{
-    chat::{Chat, chat_routes},
-    http::AuthenticateRequest,
+    chat::{Chat, ChatHttpOptions, chat_routes},
+    http::{AuthenticateRequest, HttpError, Lifecycle},
     sessions::SessionLifecycle,
     user::{Users, user_routes},
 };
-use axum::Router;
-use tokio::sync::watch;
+use axum::{Router, http::StatusCode, routing::any};
 
 pub fn api_router<C, U, S>(
     chat: C,
     users: U,
     sessions: S,
-    shutting_down: watch::Receiver<bool>,
+    lifecycle: Lifecycle,
+    chat_options: ChatHttpOptions,
 ) -> Router
 where
     C: Chat + Send + Sync + Clone + 'static,
@@ -20,7 +20,23 @@ where
     S: SessionLifecycle + AuthenticateRequest + Send + Sync + Clone + 'static,
 {
     Router::new()
-        .merge(chat_routes(chat, sessions.clone(), shutting_down))
+        .merge(chat_routes(
+            chat,
+            users.clone(),
+            sessions.clone(),
+            lifecycle,
+            chat_options,
+        ))
         .merge(session_routes(users.clone(), sessions.clone()))
         .merge(user_routes(users, sessions))
+        // Otherwise unknown API paths would be answered with the UI, which serves `index.html` for
+        // any path, to support client-side routing.
+        .route("/api/{*path}", any(api_not_found))
+}
+
+async fn api_not_found() -> HttpError {
+    HttpError {
+        status_code: StatusCode::NOT_FOUND,
+        message: "Unknown API endpoint".into(),
+    }
 }
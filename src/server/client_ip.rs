@@ -0,0 +1,178 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::connect_info::Connected,
+    http::{HeaderMap, HeaderName, header::FORWARDED},
+    serve::{IncomingStream, Listener},
+};
+
+use super::idle_timeout::IdleTimeoutListener;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Longest element of a forwarding header we bother to parse. Plenty for a single address with port
+/// and parameters. Clients can prepend arbitrary entries, so only the rightmost element is looked
+/// at, however long the header is.
+const MAX_FORWARDED_ELEMENT_LEN: usize = 256;
+
+/// Address of the peer of a connection. Made available to requests via `ConnectInfo`.
+#[derive(Clone, Copy, Debug)]
+pub struct PeerAddr(pub SocketAddr);
+
+impl<L> Connected<IncomingStream<'_, IdleTimeoutListener<L>>> for PeerAddr
+where
+    L: Listener<Addr = SocketAddr>,
+    L::Io: Unpin,
+{
+    fn connect_info(stream: IncomingStream<'_, IdleTimeoutListener<L>>) -> Self {
+        PeerAddr(*stream.remote_addr())
+    }
+}
+
+/// The IP address of the client which sent the request. Behind a reverse proxy, the peer of the
+/// connection is the proxy, not the client. If we `trust_proxy`, the address is taken from the
+/// rightmost entry of `X-Forwarded-For`, or `Forwarded`, which is the one added by our proxy.
+/// Entries further to the left are provided by the client and could be spoofed. Without a trusted
+/// proxy these headers are ignored, since any client could set them.
+pub fn client_ip(headers: &HeaderMap, peer: IpAddr, trust_proxy: bool) -> IpAddr {
+    if !trust_proxy {
+        return peer;
+    }
+    forwarded_for(headers)
+        .or_else(|| forwarded(headers))
+        .unwrap_or(peer)
+}
+
+/// Rightmost address in `X-Forwarded-For`, e.g. `203.0.113.7, 10.0.0.1`.
+fn forwarded_for(headers: &HeaderMap) -> Option<IpAddr> {
+    last_element(headers, X_FORWARDED_FOR)?.trim().parse().ok()
+}
+
+/// Rightmost `for` parameter in `Forwarded`, e.g. `for=203.0.113.7, for="[2001:db8::1]:4711"`.
+fn forwarded(headers: &HeaderMap) -> Option<IpAddr> {
+    let element = last_element(headers, FORWARDED)?;
+    let node = element.split(';').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("for")
+            .then(|| value.trim().trim_matches('"'))
+    })?;
+    parse_node(node)
+}
+
+/// Rightmost comma separated element of the last `name` header, which is the one added by our
+/// proxy. Found by scanning backwards from the end, so the entries prepended by the client are
+/// never parsed. `None` if it is longer than [`MAX_FORWARDED_ELEMENT_LEN`].
+fn last_element(headers: &HeaderMap, name: HeaderName) -> Option<&str> {
+    let last = headers.get_all(name).iter().next_back()?;
+    let element = last.as_bytes().rsplit(|&byte| byte == b',').next()?;
+    if element.len() > MAX_FORWARDED_ELEMENT_LEN {
+        return None;
+    }
+    str::from_utf8(element).ok()
+}
+
+/// Parses a node of the `Forwarded` header. IPv6 addresses are enclosed in brackets and either
+/// kind of address may carry a port.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _port) = rest.split_once(']')?;
+        return ip.parse().ok();
+    }
+    let ip = node.split_once(':').map_or(node, |(ip, _port)| ip);
+    ip.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use axum::http::HeaderMap;
+
+    use super::client_ip;
+
+    const PEER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    #[test]
+    fn forwarded_for_is_used_if_proxy_is_trusted() {
+        // Given a request forwarded by our proxy, with a spoofed entry added by the client
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Forwarded-For",
+            "192.0.2.66, 203.0.113.7".parse().unwrap(),
+        );
+
+        // When determining the client ip with trust in the proxy
+        let ip = client_ip(&headers, PEER, true);
+
+        // Then the entry added by our proxy is used
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)), ip);
+    }
+
+    #[test]
+    fn forwarded_is_used_if_proxy_is_trusted() {
+        // Given a request forwarded by our proxy using the standardized header
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "Forwarded",
+            "for=192.0.2.66, for=\"[2001:db8::1]:4711\";proto=https"
+                .parse()
+                .unwrap(),
+        );
+
+        // When determining the client ip with trust in the proxy
+        let ip = client_ip(&headers, PEER, true);
+
+        // Then the rightmost node is used
+        assert_eq!(
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            ip
+        );
+    }
+
+    #[test]
+    fn socket_ip_is_used_if_proxy_is_not_trusted() {
+        // Given a request claiming to be forwarded
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-For", "203.0.113.7".parse().unwrap());
+
+        // When determining the client ip without trust in a proxy
+        let ip = client_ip(&headers, PEER, false);
+
+        // Then the header is ignored
+        assert_eq!(PEER, ip);
+    }
+
+    #[test]
+    fn overlong_forwarding_headers_still_yield_rightmost_entry() {
+        // Given a request whose forwarding headers have been padded by the client
+        let padding = "192.0.2.66, ".repeat(1024);
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "X-Forwarded-For",
+            format!("{padding}203.0.113.7").parse().unwrap(),
+        );
+        headers.insert(
+            "Forwarded",
+            format!("{padding}for=203.0.113.7").parse().unwrap(),
+        );
+
+        // When determining the client ip with trust in the proxy
+        let ip = client_ip(&headers, PEER, true);
+
+        // Then the entry added by our proxy is used, rather than the ip of the proxy itself
+        assert_eq!(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)), ip);
+    }
+
+    #[test]
+    fn socket_ip_is_used_if_forwarded_header_is_missing() {
+        // Given a request without forwarding headers
+        let headers = HeaderMap::new();
+
+        // When determining the client ip with trust in the proxy
+        let ip = client_ip(&headers, PEER, true);
+
+        // Then the ip of the peer is used
+        assert_eq!(PEER, ip);
+    }
+}
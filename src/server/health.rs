@@ -0,0 +1,105 @@
+use axum::{
+    Json, Router,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Serialize;
+
+use crate::persistence::SchemaStatus;
+
+/// `/health` tells whether the process is alive. `/health/ready` additionally tells whether the
+/// database is at the schema version this binary supports. A load balancer should not route
+/// traffic to an instance which e.g. opened a database left at an older version.
+pub fn health_router<M>(schema: M) -> Router
+where
+    M: SchemaStatus + Send + Sync + Clone + 'static,
+{
+    Router::new()
+        .route("/health", get(|| async { "OK" }))
+        .route("/health/ready", get(move || ready(schema.clone())))
+}
+
+async fn ready(schema: impl SchemaStatus) -> Response {
+    match schema.schema_version().await {
+        Ok(version) => {
+            let status = if version.is_current() {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+            let body = Readiness {
+                schema_version: version.found,
+                supported_schema_version: version.supported,
+            };
+            (status, Json(body)).into_response()
+        }
+        Err(_) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Failed to read schema version",
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Serialize)]
+struct Readiness {
+    schema_version: u32,
+    supported_schema_version: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use async_sqlite::ClientBuilder;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use http_body_util::BodyExt as _;
+    use serde_json::{Value, json};
+    use tower::ServiceExt as _;
+
+    use crate::persistence::{SqlitePersistence, migrate};
+
+    use super::health_router;
+
+    #[tokio::test]
+    async fn ready_if_database_is_at_supported_schema_version() {
+        // Given a freshly migrated database
+        let persistence = SqlitePersistence::new(None, migrate).await.unwrap();
+        let app = health_router(persistence.client());
+
+        // When asking whether the server is ready
+        let response = app
+            .oneshot(Request::get("/health/ready").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then it is
+        assert_eq!(StatusCode::OK, response.status());
+    }
+
+    #[tokio::test]
+    async fn not_ready_if_database_is_at_older_schema_version() {
+        // Given a database left at an older schema version, which has not been migrated
+        let client = ClientBuilder::new().open().await.unwrap();
+        client
+            .conn(|conn| conn.pragma_update(None, "user_version", 1))
+            .await
+            .unwrap();
+        let app = health_router(client);
+
+        // When asking whether the server is ready
+        let response = app
+            .oneshot(Request::get("/health/ready").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        // Then it is not, and both versions are reported
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, response.status());
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(1, body["schema_version"]);
+        assert_ne!(json!(1), body["supported_schema_version"]);
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use axum::serve::Listener;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{Instant, Sleep, sleep},
+};
+
+/// Wraps every connection accepted by `listener` in an [`IdleTimeout`]. Event streams of clients
+/// which stopped reading would otherwise stay alive for as long as the connection does. Together
+/// with keep-alive events this also reclaims streams of peers which vanished without closing the
+/// connection, once the operating system stops accepting further writes for them. Idle keep-alive
+/// connections, waiting for a request which never comes, are reclaimed as well.
+///
+/// Optionally connections are also wrapped with a write timeout, which only runs while the peer does
+/// not accept any data. It reclaims the streams of stalled readers far sooner than the idle timeout,
+/// which must exceed the interval of keep-alive events and the timeout of the poll route.
+pub struct IdleTimeoutListener<L> {
+    listener: L,
+    timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+}
+
+impl<L> IdleTimeoutListener<L> {
+    /// `None` disables the timeout.
+    pub fn new(listener: L, timeout: Option<Duration>) -> Self {
+        Self {
+            listener,
+            timeout,
+            write_timeout: None,
+        }
+    }
+
+    /// Fail connections whose writes have been pending for `write_timeout`. `None` disables it.
+    pub fn with_write_timeout(self, write_timeout: Option<Duration>) -> Self {
+        Self {
+            write_timeout,
+            ..self
+        }
+    }
+}
+
+impl<L> Listener for IdleTimeoutListener<L>
+where
+    L: Listener,
+    L::Io: Unpin,
+{
+    type Io = IdleTimeout<L::Io>;
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        let (io, addr) = self.listener.accept().await;
+        let io = IdleTimeout::new(io, self.timeout).with_write_timeout(self.write_timeout);
+        (io, addr)
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.listener.local_addr()
+    }
+}
+
+/// Fails reads and writes with [`io::ErrorKind::TimedOut`], once nothing has been sent to the peer
+/// for `timeout`. Hyper drops the connection, and with it the response body, once either fails.
+/// Only sending counts, since a peer which vanished can not tell us so.
+///
+/// With a write timeout, writes also fail with [`io::ErrorKind::TimedOut`], once they have been
+/// pending for that long, i.e. the peer has not accepted any data. Unlike the idle timeout, it does
+/// not run while there is nothing to send.
+pub struct IdleTimeout<T> {
+    io: T,
+    /// `None` if the connection may stay idle indefinitely.
+    idle: Option<Idle>,
+    /// `None` if writes may be pending indefinitely.
+    stalled: Option<Stalled>,
+}
+
+/// Point in time the connection is considered idle, pushed back whenever data is sent.
+struct Idle {
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+}
+
+/// Point in time a pending write is considered stalled. Set once a write becomes pending.
+struct Stalled {
+    timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+    /// `true` while a write is pending. Otherwise the deadline is not polled.
+    pending: bool,
+}
+
+impl<T> IdleTimeout<T> {
+    pub fn new(io: T, timeout: Option<Duration>) -> Self {
+        let idle = timeout.map(|timeout| Idle {
+            timeout,
+            deadline: Box::pin(sleep(timeout)),
+        });
+        Self {
+            io,
+            idle,
+            stalled: None,
+        }
+    }
+
+    /// Fail writes which have been pending for `write_timeout`. `None` disables it.
+    pub fn with_write_timeout(self, write_timeout: Option<Duration>) -> Self {
+        let stalled = write_timeout.map(|timeout| Stalled {
+            timeout,
+            deadline: Box::pin(sleep(timeout)),
+            pending: false,
+        });
+        Self { stalled, ..self }
+    }
+
+    /// Pushes the deadline back, after data has been sent.
+    fn sent(&mut self) {
+        if let Some(idle) = &mut self.idle {
+            let deadline = Instant::now() + idle.timeout;
+            idle.deadline.as_mut().reset(deadline);
+        }
+    }
+
+    /// Turns the write `poll` into an error, if it has been pending for the write timeout. The
+    /// deadline is set once a write becomes pending and cleared once one completes. Otherwise
+    /// `poll` is returned as is.
+    fn check_stalled<R>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<R>>,
+    ) -> Poll<io::Result<R>> {
+        let Some(stalled) = &mut self.stalled else {
+            return poll;
+        };
+        if poll.is_ready() {
+            stalled.pending = false;
+            return poll;
+        }
+        if !stalled.pending {
+            stalled.pending = true;
+            let deadline = Instant::now() + stalled.timeout;
+            stalled.deadline.as_mut().reset(deadline);
+        }
+        ready!(stalled.deadline.as_mut().poll(cx));
+        Poll::Ready(Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "The peer has not accepted any data within the write timeout",
+        )))
+    }
+
+    /// Turns `poll` into an error, if it is pending past the deadline. Otherwise `poll` is
+    /// returned as is.
+    fn check_idle<R>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<R>>,
+    ) -> Poll<io::Result<R>> {
+        if poll.is_ready() {
+            return poll;
+        }
+        let Some(idle) = &mut self.idle else {
+            return poll;
+        };
+        ready!(idle.deadline.as_mut().poll(cx));
+        Poll::Ready(Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Nothing has been sent to the peer within the idle timeout",
+        )))
+    }
+}
+
+impl<T> AsyncRead for IdleTimeout<T>
+where
+    T: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.io).poll_read(cx, buf);
+        self.check_idle(cx, poll)
+    }
+}
+
+impl<T> AsyncWrite for IdleTimeout<T>
+where
+    T: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.io).poll_write(cx, buf);
+        if matches!(poll, Poll::Ready(Ok(written)) if written > 0) {
+            self.sent();
+        }
+        let poll = self.check_stalled(cx, poll);
+        self.check_idle(cx, poll)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.io).poll_write_vectored(cx, bufs);
+        if matches!(poll, Poll::Ready(Ok(written)) if written > 0) {
+            self.sent();
+        }
+        let poll = self.check_stalled(cx, poll);
+        self.check_idle(cx, poll)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.io).poll_flush(cx);
+        let poll = self.check_stalled(cx, poll);
+        self.check_idle(cx, poll)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.io).poll_shutdown(cx);
+        self.check_idle(cx, poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        future::pending,
+        io,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use axum::{
+        Router,
+        response::{Sse, sse::Event as SseEvent},
+        routing::get,
+        serve::Listener,
+    };
+    use futures_util::stream;
+    use tokio::{
+        io::{AsyncReadExt as _, AsyncWriteExt as _, DuplexStream, duplex},
+        sync::oneshot,
+        time::{sleep, timeout},
+    };
+
+    use super::IdleTimeoutListener;
+
+    #[tokio::test]
+    async fn stream_of_stalled_reader_is_reclaimed() {
+        // Given a server with an idle timeout and an endless event stream, which tells us once it
+        // is dropped
+        let (stream_dropped, on_stream_dropped) = oneshot::channel::<()>();
+        let stream_dropped = Arc::new(Mutex::new(Some(stream_dropped)));
+        let router = Router::new().route(
+            "/events",
+            get(move || {
+                let guard = stream_dropped.lock().unwrap().take();
+                let events = stream::repeat_with(move || {
+                    let _guard = &guard;
+                    Ok::<_, Infallible>(SseEvent::default().data("Hi"))
+                });
+                async move { Sse::new(events) }
+            }),
+        );
+        let (server_side, mut client) = duplex(1024);
+        let listener = IdleTimeoutListener::new(
+            OneConnection(Some(server_side)),
+            Some(Duration::from_millis(100)),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        // When a client requests the event stream, but never reads from it
+        client
+            .write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        // Then the event stream is dropped
+        let result = timeout(Duration::from_secs(5), on_stream_dropped).await;
+        assert!(result.is_ok(), "Event stream must be reclaimed");
+    }
+
+    #[tokio::test]
+    async fn stream_of_stalled_reader_is_reclaimed_by_write_timeout() {
+        // Given a server without an idle timeout, but with a write timeout, and an endless event
+        // stream, which tells us once it is dropped
+        let (stream_dropped, on_stream_dropped) = oneshot::channel::<()>();
+        let stream_dropped = Arc::new(Mutex::new(Some(stream_dropped)));
+        let router = Router::new().route(
+            "/events",
+            get(move || {
+                let guard = stream_dropped.lock().unwrap().take();
+                let events = stream::repeat_with(move || {
+                    let _guard = &guard;
+                    Ok::<_, Infallible>(SseEvent::default().data("Hi"))
+                });
+                async move { Sse::new(events) }
+            }),
+        );
+        let (server_side, mut client) = duplex(1024);
+        let listener = IdleTimeoutListener::new(OneConnection(Some(server_side)), None)
+            .with_write_timeout(Some(Duration::from_millis(100)));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        // When a client requests the event stream, but never reads from it
+        client
+            .write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        // Then the event stream is dropped
+        let result = timeout(Duration::from_secs(5), on_stream_dropped).await;
+        assert!(result.is_ok(), "Event stream must be reclaimed");
+    }
+
+    #[tokio::test]
+    async fn idle_connection_is_kept_despite_write_timeout() {
+        // Given a server without an idle timeout, but with a write timeout
+        let (server_side, mut client) = duplex(1024);
+        let listener = IdleTimeoutListener::new(OneConnection(Some(server_side)), None)
+            .with_write_timeout(Some(Duration::from_millis(100)));
+        tokio::spawn(async move { axum::serve(listener, Router::new()).await });
+
+        // When a client connects, but does not send a request for several times the write timeout
+        let mut buf = [0; 1024];
+        let still_open = timeout(Duration::from_millis(500), client.read(&mut buf)).await;
+
+        // Then the connection is still open, since there is nothing to write
+        assert!(still_open.is_err());
+    }
+
+    #[tokio::test]
+    async fn idle_connection_is_closed() {
+        // Given a server with an idle timeout
+        let (server_side, mut client) = duplex(1024);
+        let listener = IdleTimeoutListener::new(
+            OneConnection(Some(server_side)),
+            Some(Duration::from_millis(100)),
+        );
+        tokio::spawn(async move { axum::serve(listener, Router::new()).await });
+
+        // When a client connects, but never sends a request
+        let mut received = Vec::new();
+        let closed = timeout(Duration::from_secs(5), client.read_to_end(&mut received)).await;
+
+        // Then the connection is closed, without anything sent
+        assert!(closed.is_ok(), "Idle connection must be closed");
+        assert!(received.is_empty());
+    }
+
+    #[tokio::test]
+    async fn connection_sending_regularly_is_kept() {
+        // Given a server with an idle timeout and an event stream sending more often than that
+        let router = Router::new().route(
+            "/events",
+            get(|| async {
+                let events = stream::unfold((), |()| async {
+                    sleep(Duration::from_millis(20)).await;
+                    Some((Ok::<_, Infallible>(SseEvent::default().data("Hi")), ()))
+                });
+                Sse::new(events)
+            }),
+        );
+        let (server_side, mut client) = duplex(1024);
+        let listener = IdleTimeoutListener::new(
+            OneConnection(Some(server_side)),
+            Some(Duration::from_millis(100)),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        client
+            .write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        // When the client keeps reading for several times the idle timeout
+        let mut buf = [0; 1024];
+        let reading = async {
+            loop {
+                let read = client.read(&mut buf).await.unwrap();
+                assert_ne!(0, read, "Connection must not be closed");
+            }
+        };
+        let still_open = timeout(Duration::from_millis(500), reading).await;
+
+        // Then the connection is still open
+        assert!(still_open.is_err());
+    }
+
+    /// Accepts a single in-memory connection and then never again.
+    struct OneConnection(Option<DuplexStream>);
+
+    impl Listener for OneConnection {
+        type Io = DuplexStream;
+        type Addr = ();
+
+        async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+            match self.0.take() {
+                Some(io) => (io, ()),
+                None => pending().await,
+            }
+        }
+
+        fn local_addr(&self) -> io::Result<Self::Addr> {
+            Ok(())
+        }
+    }
+}
@@ -1,9 +1,23 @@
 //! Module for statically hosting the UI assets
 
+use std::path::Path;
+
 use axum::Router;
-use static_serve::embed_assets;
+use static_serve::{embed_asset, embed_assets};
+use tower_http::services::{ServeDir, ServeFile};
 
-pub fn ui_router() -> Router {
+/// Serves the UI assets embedded into the binary at build time. If `ui_dir` is set, assets are
+/// served from this directory instead. This allows frontend developers to see their changes without
+/// recompiling klatsch. Paths which match no asset are answered with `index.html`, so deep links to
+/// client-side routes, like `/room/abc`, load the UI.
+pub fn ui_router(ui_dir: Option<&Path>) -> Router {
+    if let Some(ui_dir) = ui_dir {
+        // Match `/login` to `/login.html`, like the embedded assets do
+        let assets = ServeDir::new(ui_dir)
+            .html_as_default_extension(true)
+            .fallback(ServeFile::new(ui_dir.join("index.html")));
+        return Router::new().fallback_service(assets);
+    }
     embed_assets!(
         // Populated by `build.rs`, which stages `ui/` into `target/ui/` and runs npm there so the
         // build output stays inside cargo's `target/` instead of polluting the source tree.
@@ -13,12 +27,17 @@ pub fn ui_router() -> Router {
         compress = true,
         cache_busted_paths = ["_app/immutable"]
     );
-    static_router()
+    let index = embed_asset!("./target/ui/build/index.html", compress = true);
+    static_router().fallback_service(index)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
     use axum::{body::Body, http::Request};
+    use http_body_util::BodyExt as _;
+    use tempfile::tempdir;
     use tower::ServiceExt;
 
     use super::ui_router;
@@ -26,7 +45,7 @@ mod tests {
     #[tokio::test]
     async fn static_ui_serves_index_page() {
         // Given a running server
-        let app = ui_router();
+        let app = ui_router(None);
 
         // When requesting the root path
         let response = app
@@ -43,4 +62,54 @@ mod tests {
                 .contains("text/html")
         );
     }
+
+    #[tokio::test]
+    async fn client_side_routes_are_answered_with_index_page() {
+        // Given a running server
+        let app = ui_router(None);
+
+        // When following a deep link to a client-side route
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/room/abc")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then the index page is served, so the UI can take over routing
+        assert_eq!(response.status(), 200);
+        assert!(
+            response.headers()["content-type"]
+                .to_str()
+                .unwrap()
+                .contains("text/html")
+        );
+    }
+
+    #[tokio::test]
+    async fn ui_served_from_directory_if_configured() {
+        // Given a directory containing a UI asset
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("app.js"), "console.log('edited')").unwrap();
+        let app = ui_router(Some(dir.path()));
+
+        // When requesting the asset
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/app.js")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        // Then it is served from disk, rather than from the embedded assets
+        assert_eq!(response.status(), 200);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(&body[..], b"console.log('edited')");
+    }
 }
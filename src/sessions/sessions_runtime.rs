@@ -10,7 +10,7 @@ use tokio::{
     time::{Instant, Sleep, sleep_until},
 };
 
-use crate::user::UserId;
+use crate::{task::spawn_named, user::UserId};
 
 use super::{SessionId, SessionStore};
 
@@ -34,7 +34,7 @@ impl SessionsRuntime {
     pub(super) fn with_session_store(store: impl SessionStore + Send + 'static) -> Self {
         let (sender, receiver) = mpsc::channel(16);
         let actor = SessionActor::new(store, receiver);
-        let handle = tokio::spawn(async move { actor.run().await });
+        let handle = spawn_named("sessions", async move { actor.run().await });
         Self { sender, handle }
     }
 
@@ -0,0 +1,29 @@
+//! Spawning of long running tasks.
+
+use tokio::task::JoinHandle;
+
+#[cfg(all(feature = "tokio-console", not(tokio_unstable)))]
+compile_error!(
+    "The `tokio-console` feature requires building with `RUSTFLAGS=\"--cfg tokio_unstable\"`."
+);
+
+/// Spawns a task onto the tokio runtime. With the `tokio-console` feature enabled, the task carries
+/// `name`, so it can be told apart from other tasks in `tokio-console`.
+pub fn spawn_named<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(feature = "tokio-console")]
+    {
+        tokio::task::Builder::new()
+            .name(name)
+            .spawn(future)
+            .expect("Spawning a task must not fail")
+    }
+    #[cfg(not(feature = "tokio-console"))]
+    {
+        let _ = name;
+        tokio::spawn(future)
+    }
+}
@@ -2,27 +2,57 @@ mod format;
 
 use std::io::stderr;
 
-use tracing::level_filters::LevelFilter;
-use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use tracing::{Subscriber, level_filters::LevelFilter};
+use tracing_subscriber::{
+    EnvFilter, Layer, field::MakeExt as _, fmt, fmt::MakeWriter, layer::SubscriberExt as _,
+    registry, registry::LookupSpan,
+};
 
 use self::format::OperatorFormat;
 
-pub fn init_tracing() {
-    let subscriber = FmtSubscriber::builder()
-        .with_writer(stderr)
-        .event_format(OperatorFormat)
-        .with_env_filter(
-            EnvFilter::builder()
-                .with_env_var("LOG_LEVEL")
-                .with_default_directive(LevelFilter::INFO.into())
-                .from_env_lossy()
-                .add_directive("memory_serve=off".parse().unwrap()),
-        )
-        .finish();
+/// Fields which carry the content of a message or identify its sender. Withheld from the logs if
+/// content is redacted.
+const SENSITIVE_FIELDS: [&str; 2] = ["content", "sender"];
+
+/// Installs the global subscriber. If `redact_content` is `true`, no message content or sender
+/// is ever written to the logs. Message related logging must use the fields in
+/// [`SENSITIVE_FIELDS`] for this to hold.
+pub fn init_tracing(redact_content: bool) {
+    // The filter only applies to the logs, so `tokio-console` still receives all runtime events.
+    let logs = log_layer(stderr, redact_content).with_filter(
+        EnvFilter::builder()
+            .with_env_var("LOG_LEVEL")
+            .with_default_directive(LevelFilter::INFO.into())
+            .from_env_lossy()
+            .add_directive("memory_serve=off".parse().unwrap()),
+    );
+    let subscriber = registry().with(logs);
+    #[cfg(feature = "tokio-console")]
+    let subscriber = subscriber.with(console_subscriber::spawn());
     tracing::subscriber::set_global_default(subscriber)
         .expect("Setting global default provider must not fail.");
 }
 
+/// Formats logs for operators and writes them to `writer`.
+pub fn log_layer<S, W>(writer: W, redact_content: bool) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let fields = fmt::format::debug_fn(move |writer, field, value| match field.name() {
+        "message" => write!(writer, "{value:?}"),
+        name if redact_content && SENSITIVE_FIELDS.contains(&name) => {
+            write!(writer, "{name}=<redacted>")
+        }
+        name => write!(writer, "{name}={value:?}"),
+    })
+    .delimited(" ");
+    fmt::layer()
+        .with_writer(writer)
+        .event_format(OperatorFormat)
+        .fmt_fields(fields)
+}
+
 /// Maps the module string to an operator friendly target.
 ///
 /// The rust tracinig ecosystem uses the module path as the default target for log messages. This
@@ -35,3 +65,33 @@ fn operator_target(target: &'static str) -> &'static str {
         other => other,
     }
 }
+
+/// Collects the logs written by the current thread at debug level and above.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl CapturedLogs {
+    pub fn capture(&self, redact_content: bool) -> tracing::subscriber::DefaultGuard {
+        let logs = self.clone();
+        let layer = log_layer(move || logs.clone(), redact_content).with_filter(LevelFilter::DEBUG);
+        tracing::subscriber::set_default(registry().with(layer))
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[cfg(test)]
+impl std::io::Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 mod password_hash;
 mod user_http;
 mod user_id;
+mod user_name;
 mod user_persistence;
 
 use serde::{Deserialize, Serialize};
@@ -8,6 +9,7 @@ use serde::{Deserialize, Serialize};
 pub use self::{
     user_http::user_routes,
     user_id::UserId,
+    user_name::NameNormalization,
     user_persistence::{UserPersistence, migrate_users_persistence},
 };
 
@@ -21,11 +23,21 @@ pub struct User {
 #[derive(Clone)]
 pub struct UserStore<P> {
     persistence: P,
+    /// Applied to names before signup and login, so both agree on which user is meant.
+    name_normalization: NameNormalization,
 }
 
 impl<P> UserStore<P> {
     pub fn new(persistence: P) -> Self {
-        UserStore { persistence }
+        UserStore {
+            persistence,
+            name_normalization: NameNormalization::default(),
+        }
+    }
+
+    pub fn with_name_normalization(mut self, name_normalization: NameNormalization) -> Self {
+        self.name_normalization = name_normalization;
+        self
     }
 }
 
@@ -51,6 +63,7 @@ where
     P: UserPersistence + Send,
 {
     async fn signup(&mut self, name: String, password: String) -> Result<UserId, UsersError> {
+        let name = self.name_normalization.apply(name)?;
         let new_id = UserId::new();
         let password_hash = (!password.is_empty()).then(|| password_hash::generate(&password));
         let outcome = self
@@ -77,6 +90,7 @@ where
     }
 
     async fn login(&mut self, name: String, password: String) -> Result<UserId, UsersError> {
+        let name = self.name_normalization.apply(name)?;
         let maybe_user = self
             .persistence
             .id_and_hash_by_name(&name)
@@ -111,6 +125,8 @@ pub enum UsersError {
     UnknownUser,
     /// Either name or password is incorrect.
     Unauthenticated,
+    /// The name has been rejected by the configured [`NameNormalization`].
+    InvalidName,
 }
 
 #[cfg(test)]
@@ -118,8 +134,11 @@ mod tests {
     use std::assert_matches;
 
     use anyhow::bail;
+    use double_trait::Dummy;
 
-    use crate::user::{UserCreateOutcome, UserId, UserPersistence, UserStore, Users, UsersError};
+    use crate::user::{
+        NameNormalization, UserCreateOutcome, UserId, UserPersistence, UserStore, Users, UsersError,
+    };
 
     use super::{User, password_hash};
 
@@ -146,6 +165,55 @@ mod tests {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn signup_and_login_normalize_name() {
+        // Given a user store normalizing names
+        struct AliceMock;
+        impl UserPersistence for AliceMock {
+            async fn create(
+                &self,
+                name: &str,
+                _new_id: UserId,
+                _password_hash: Option<&str>,
+            ) -> anyhow::Result<UserCreateOutcome> {
+                assert_eq!(name, "Alice");
+                Ok(UserCreateOutcome::Created)
+            }
+
+            async fn id_and_hash_by_name(
+                &self,
+                name: &str,
+            ) -> anyhow::Result<Option<(UserId, Option<String>)>> {
+                assert_eq!(name, "Alice");
+                Ok(Some((UserId::ALICE, None)))
+            }
+        }
+        let mut users =
+            UserStore::new(AliceMock).with_name_normalization(NameNormalization::Normalize);
+
+        // When signing up and logging in with trailing whitespace
+        let signup = users.signup("Alice ".to_owned(), "secret".to_owned()).await;
+        let login = users.login("Alice ".to_owned(), "secret".to_owned()).await;
+
+        // Then persistence only ever sees the trimmed name
+        assert!(signup.is_ok());
+        assert!(login.is_ok());
+    }
+
+    #[tokio::test]
+    async fn signup_rejects_confusable_name_if_strict() {
+        // Given a user store rejecting confusable names. Persistence must not be touched.
+        let mut users = UserStore::new(Dummy).with_name_normalization(NameNormalization::Strict);
+
+        // When signing up as "Аlice" with a cyrillic "А"
+        let result = users
+            .signup("\u{0410}lice".to_owned(), "secret".to_owned())
+            .await;
+
+        // Then
+        assert_matches!(result, Err(UsersError::InvalidName));
+    }
+
     #[tokio::test]
     async fn signup_generates_distinct_ids() {
         struct CreateStub;
@@ -63,6 +63,10 @@ impl From<UsersError> for HttpError {
                 status_code: StatusCode::UNAUTHORIZED,
                 message: "Either user name or password is incorrect".into(),
             },
+            UsersError::InvalidName => HttpError {
+                status_code: StatusCode::BAD_REQUEST,
+                message: "User name contains invisible characters or mixes scripts".into(),
+            },
         }
     }
 }
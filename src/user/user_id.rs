@@ -18,6 +18,14 @@ impl UserId {
         Self::from_uuid(Uuid::new_v4())
     }
 
+    /// Reserved for the probe message of the startup self check. Never assigned to a user, since
+    /// they are given random (v4) UUIDs.
+    pub const SELF_CHECK: UserId = UserId::from_uuid(Uuid::max());
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        self.0.as_bytes()
+    }
+
     #[cfg(test)]
     pub const fn nil() -> Self {
         Self::from_uuid(Uuid::nil())
@@ -0,0 +1,109 @@
+use unicode_normalization::UnicodeNormalization as _;
+use unicode_security::MixedScript as _;
+
+use super::UsersError;
+
+/// Characters which are not rendered, yet make two otherwise identical names distinct.
+const INVISIBLE_CHARACTERS: [char; 6] = [
+    '\u{00AD}', // Soft hyphen
+    '\u{200B}', // Zero width space
+    '\u{200C}', // Zero width non-joiner
+    '\u{200D}', // Zero width joiner
+    '\u{2060}', // Word joiner
+    '\u{FEFF}', // Zero width no-break space
+];
+
+/// How user names are processed before they are used to sign up or log in. User names are displayed
+/// as the sender of messages. Names which render alike, yet are distinct, allow users to impersonate
+/// each other.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NameNormalization {
+    /// Names are used exactly as submitted.
+    #[default]
+    Verbatim,
+    /// Leading and trailing whitespace is removed and the name is brought into Unicode
+    /// normalization form C. This way "Alice " and "Alice" refer to the same user.
+    Normalize,
+    /// Like [`Self::Normalize`], but additionally rejects names which contain invisible characters
+    /// or mix scripts. E.g. "Аlice" with a cyrillic "А".
+    Strict,
+}
+
+impl NameNormalization {
+    pub fn apply(self, name: String) -> Result<String, UsersError> {
+        if self == NameNormalization::Verbatim {
+            return Ok(name);
+        }
+        let name: String = name.trim().nfc().collect();
+        if self == NameNormalization::Strict
+            && (name.contains(INVISIBLE_CHARACTERS) || !name.is_single_script())
+        {
+            return Err(UsersError::InvalidName);
+        }
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::assert_matches;
+
+    use super::{NameNormalization, UsersError};
+
+    #[test]
+    fn verbatim_keeps_trailing_whitespace() {
+        let name = NameNormalization::Verbatim.apply("Alice ".to_owned());
+
+        assert_eq!("Alice ", name.unwrap());
+    }
+
+    #[test]
+    fn normalize_trims_whitespace() {
+        let name = NameNormalization::Normalize.apply(" Alice ".to_owned());
+
+        assert_eq!("Alice", name.unwrap());
+    }
+
+    #[test]
+    fn normalize_composes_characters() {
+        // "e" followed by a combining acute accent
+        let name = NameNormalization::Normalize.apply("Rene\u{0301}".to_owned());
+
+        assert_eq!("Ren\u{00E9}", name.unwrap());
+    }
+
+    #[test]
+    fn strict_trims_whitespace() {
+        let name = NameNormalization::Strict.apply("Alice ".to_owned());
+
+        assert_eq!("Alice", name.unwrap());
+    }
+
+    #[test]
+    fn only_strict_rejects_confusable_character() {
+        // "Аlice" with a cyrillic "А"
+        let confusable = "\u{0410}lice";
+
+        let verbatim = NameNormalization::Verbatim.apply(confusable.to_owned());
+        let normalize = NameNormalization::Normalize.apply(confusable.to_owned());
+        let strict = NameNormalization::Strict.apply(confusable.to_owned());
+
+        assert_eq!(confusable, verbatim.unwrap());
+        assert_eq!(confusable, normalize.unwrap());
+        assert_matches!(strict, Err(UsersError::InvalidName));
+    }
+
+    #[test]
+    fn strict_rejects_zero_width_characters() {
+        let name = NameNormalization::Strict.apply("Al\u{200B}ice".to_owned());
+
+        assert_matches!(name, Err(UsersError::InvalidName));
+    }
+
+    #[test]
+    fn strict_accepts_names_in_a_single_non_latin_script() {
+        let name = NameNormalization::Strict.apply("Алиса".to_owned());
+
+        assert_eq!("Алиса", name.unwrap());
+    }
+}
@@ -5,7 +5,7 @@ use std::{
 };
 
 use eventsource_stream::Eventsource as _;
-use futures_util::{Stream, StreamExt as _};
+use futures_util::{Stream, StreamExt as _, future::ready};
 use reqwest::Client;
 use serde_json::json;
 use tokio::{
@@ -129,12 +129,155 @@ async fn sent_messages_appear_in_event_stream() {
         .unwrap();
     let data_1: serde_json::Value = serde_json::from_str(&event_1.data).unwrap();
     let data_2: serde_json::Value = serde_json::from_str(&event_2.data).unwrap();
-    assert_eq!(data_1["sender_id"], alice_id.to_string());
+    assert_eq!(data_1[field("sender_id")], alice_id.to_string());
     assert_eq!(data_1["content"], "Hello");
-    assert_eq!(data_2["sender_id"], bob_id.to_string());
+    assert_eq!(data_2[field("sender_id")], bob_id.to_string());
     assert_eq!(data_2["content"], "Hi there");
 }
 
+#[tokio::test]
+async fn sending_same_message_twice_yields_same_event() {
+    // Given a server
+    let server = TestServer::new(None).await;
+    server.register_alice().await;
+    let alice_session = server.login_alice().await;
+    let msg = json!({ "id": "019c0ab6-9d11-75ef-ab02-60f070b1582a", "content": "Hello" });
+
+    // When sending the same message twice, e.g. due to a retry
+    let first = server.post_message(msg.clone(), &alice_session).await;
+    let second = server.post_message(msg, &alice_session).await;
+
+    // Then both answers carry the same authoritative event
+    assert_eq!(first.status(), 200);
+    assert_eq!(second.status(), 200);
+    assert_eq!(first.headers()["x-klatsch-outcome"], "new");
+    assert_eq!(second.headers()["x-klatsch-outcome"], "duplicate");
+    let first: serde_json::Value = first.json().await.unwrap();
+    let second: serde_json::Value = second.json().await.unwrap();
+    assert_eq!(first[field("event_id")], 1);
+    assert_eq!(first, second);
+}
+
+#[tokio::test]
+async fn pinned_messages_are_listed_and_announced() {
+    // Given a server with one message and a client following the event stream
+    let server = TestServer::new(None).await;
+    server.register_alice().await;
+    let alice_session = server.login_alice().await;
+    let message_id = "019c0ab6-9d11-75ef-ab02-60f070b1582a";
+    let msg = json!({ "id": message_id, "content": "Meeting at noon" });
+    server.send_message(msg, &alice_session).await;
+    let mut sse = server.events(&alice_session).await;
+    timeout(Duration::from_secs(1), sse.next())
+        .await
+        .expect("timed out waiting for message")
+        .unwrap();
+
+    // When pinning the message
+    let response = server.pin_message(message_id, true, &alice_session).await;
+
+    // Then it is listed as pinned and the client is told about it
+    assert_eq!(response.status(), 200);
+    let pinned = server.pinned(&alice_session).await;
+    assert_eq!(pinned.as_array().unwrap().len(), 1);
+    assert_eq!(pinned[0]["id"], message_id);
+    assert_eq!(pinned[0]["pinned"], true);
+    let pin = timeout(Duration::from_secs(1), sse.next())
+        .await
+        .expect("timed out waiting for pin event")
+        .unwrap();
+    assert_eq!(pin.event, "pin");
+    let pin: serde_json::Value = serde_json::from_str(&pin.data).unwrap();
+    assert_eq!(
+        pin,
+        api_json(json!({ "message_id": message_id, "event_id": 1, "pinned": true }))
+    );
+
+    // When unpinning it again
+    let response = server.pin_message(message_id, false, &alice_session).await;
+
+    // Then it is no longer listed and the client is told about it as well
+    assert_eq!(response.status(), 200);
+    let pinned = server.pinned(&alice_session).await;
+    assert_eq!(pinned, json!([]));
+    let unpin = timeout(Duration::from_secs(1), sse.next())
+        .await
+        .expect("timed out waiting for pin event")
+        .unwrap();
+    let unpin: serde_json::Value = serde_json::from_str(&unpin.data).unwrap();
+    assert_eq!(unpin["pinned"], false);
+}
+
+#[tokio::test]
+async fn tombstoned_messages_can_not_be_pinned() {
+    // Given a server with a message of Alice, whose messages have been deleted by her as an admin,
+    // and a client following the event stream
+    let server = TestServer::with_env(None, &[("ADMINS", "Alice")]).await;
+    let alice_id = server.register_alice().await;
+    let alice_session = server.login_alice().await;
+    let message_id = "019c0ab6-9d11-75ef-ab02-60f070b1582a";
+    let msg = json!({ "id": message_id, "content": "Meeting at noon" });
+    server.send_message(msg, &alice_session).await;
+    let mut sse = server.events(&alice_session).await;
+    timeout(Duration::from_secs(1), sse.next())
+        .await
+        .expect("timed out waiting for message")
+        .unwrap();
+    let response = server.delete_sender(alice_id, &alice_session).await;
+    assert_eq!(response.status(), 200);
+    let tombstone = timeout(Duration::from_secs(1), sse.next())
+        .await
+        .expect("timed out waiting for tombstone event")
+        .unwrap();
+    assert_eq!(tombstone.event, "tombstone");
+
+    // When pinning the deleted message
+    let response = server.pin_message(message_id, true, &alice_session).await;
+
+    // Then it is rejected like an unknown message
+    assert_eq!(response.status(), 404);
+    assert_eq!(server.pinned(&alice_session).await, json!([]));
+    // And no pin event is broadcast. The next event is the one of a later message.
+    let msg = json!({ "id": "019c0ab6-9d11-7a5b-abde-cb349e5fd995", "content": "Hello" });
+    server.send_message(msg, &alice_session).await;
+    let next = timeout(Duration::from_secs(1), sse.next())
+        .await
+        .expect("timed out waiting for message")
+        .unwrap();
+    assert_ne!(next.event, "pin");
+    let next: serde_json::Value = serde_json::from_str(&next.data).unwrap();
+    assert_eq!(next["content"], "Hello");
+}
+
+#[tokio::test]
+async fn deleted_messages_are_not_announced_as_gap() {
+    // Given a server with a message of Alice followed by one of Bob, whose messages have been
+    // deleted by Alice as an admin
+    let server = TestServer::with_env(None, &[("ADMINS", "Alice")]).await;
+    let alice_id = server.register_alice().await;
+    server.register_bob().await;
+    let alice_session = server.login_alice().await;
+    let bob_session = server.login_bob().await;
+    let msg = json!({ "id": "019c0ab6-9d11-75ef-ab02-60f070b1582a", "content": "Oops" });
+    server.send_message(msg, &alice_session).await;
+    let msg = json!({ "id": "019c0ab6-9d11-7a5b-abde-cb349e5fd995", "content": "Hi there" });
+    server.send_message(msg, &bob_session).await;
+    let response = server.delete_sender(alice_id, &alice_session).await;
+    assert_eq!(response.status(), 200);
+
+    // When requesting the events stream
+    let mut sse = server.events(&bob_session).await;
+
+    // Then the message of Bob is the first event, without announcing the deleted one as gap
+    let event = timeout(Duration::from_secs(1), sse.next())
+        .await
+        .expect("timed out waiting for event")
+        .unwrap();
+    assert_ne!(event.event, "gap");
+    let data: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+    assert_eq!(data["content"], "Hi there");
+}
+
 #[cfg(not(windows))]
 #[tokio::test]
 async fn persistence() {
@@ -171,10 +314,10 @@ async fn persistence() {
         .expect("timed out waiting for second event")
         .unwrap();
     let data_1: serde_json::Value = serde_json::from_str(&event_1.data).unwrap();
-    assert_eq!(data_1["sender_id"], alice_id.to_string());
+    assert_eq!(data_1[field("sender_id")], alice_id.to_string());
     assert_eq!(data_1["content"], "Hello");
     let data_2: serde_json::Value = serde_json::from_str(&event_2.data).unwrap();
-    assert_eq!(data_2["sender_id"], bob_id.to_string());
+    assert_eq!(data_2[field("sender_id")], bob_id.to_string());
     assert_eq!(data_2["content"], "Hi there");
 }
 
@@ -227,11 +370,11 @@ async fn load_v1_persistence() {
         .expect("timed out waiting for second event")
         .unwrap();
     let data_1: serde_json::Value = serde_json::from_str(&event_1.data).unwrap();
-    let sender_1: Uuid = serde_json::from_value(data_1["sender_id"].clone()).unwrap();
+    let sender_1: Uuid = serde_json::from_value(data_1[field("sender_id")].clone()).unwrap();
     assert_eq!(server.user(sender_1, &alice_session).await["name"], "Bob");
     assert_eq!(data_1["content"], "Hi Alice");
     let data_2: serde_json::Value = serde_json::from_str(&event_2.data).unwrap();
-    let sender_2: Uuid = serde_json::from_value(data_2["sender_id"].clone()).unwrap();
+    let sender_2: Uuid = serde_json::from_value(data_2[field("sender_id")].clone()).unwrap();
     assert_eq!(server.user(sender_2, &alice_session).await["name"], "Alice");
     assert_eq!(data_2["content"], "Hi Bob");
 }
@@ -270,6 +413,52 @@ async fn shutdown_within_1_sec_with_active_events_stream_client() {
     );
 }
 
+// Draining is triggered by SIGUSR1, which does not exist on Windows.
+#[cfg(unix)]
+#[tokio::test]
+async fn draining_rejects_messages_but_keeps_streaming_events() {
+    // Given a running server with one message
+    let mut server = TestServer::new(None).await;
+    server.register_alice().await;
+    let alice_session = server.login_alice().await;
+    let msg = json!({ "id": "019c0ab6-9d11-75ef-ab02-60f070b1582a", "content": "Hello" });
+    server.send_message(msg, &alice_session).await;
+
+    // When sending SIGUSR1 to the server process
+    server.send_sigusr1();
+
+    // Then new messages are rejected once the signal has been handled
+    let msg = json!({ "id": "019c0ab6-9d11-7a5b-abde-cb349e5fd995", "content": "Too late" });
+    let status = timeout(Duration::from_secs(2), async {
+        loop {
+            let response = server.post_message(msg.clone(), &alice_session).await;
+            if response.status() == 503 {
+                break response.status();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("Server must reject messages with 503 while draining");
+    assert_eq!(status, 503);
+    // And events are still streamed
+    let mut sse = server.events(&alice_session).await;
+    let event = timeout(Duration::from_secs(1), sse.next())
+        .await
+        .expect("timed out waiting for event")
+        .unwrap();
+    let data: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+    assert_eq!(data["content"], "Hello");
+    drop(sse);
+    // And SIGTERM still shuts down the server gracefully
+    server.send_sigterm();
+    let exit_status = server
+        .wait_for_termination(Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert!(exit_status.success());
+}
+
 /// Allows to interact with a Klatsch Server Running in its own process.
 struct TestServer {
     // Process member is currently unused in windows. This might change if we can have test helpers
@@ -285,8 +474,14 @@ struct TestServer {
 
 impl TestServer {
     async fn new(db_path: Option<&Path>) -> Self {
+        Self::with_env(db_path, &[]).await
+    }
+
+    /// Like [`Self::new`], but with additional environment variables configuring the server.
+    async fn with_env(db_path: Option<&Path>, env: &[(&str, &str)]) -> Self {
         let working_dir = tempfile::tempdir().unwrap();
         let mut cmd = server_command(db_path, working_dir.path());
+        cmd.envs(env.iter().copied());
         let mut child = cmd.spawn().unwrap();
         let stderr = child.stderr.take().unwrap();
         let process = ServerProcess::new(child);
@@ -322,12 +517,14 @@ impl TestServer {
             .expect("Failed to connect to events stream")
     }
 
+    /// Events of the event stream, except for the leading `server_time` event.
     async fn events(&self, session: &str) -> impl Stream<Item = eventsource_stream::Event> {
         self.request_event_stream(session)
             .await
             .bytes_stream()
             .eventsource()
             .map(|r| r.expect("SSE event must be parseable"))
+            .filter(|event| ready(event.event != "server_time"))
     }
 
     async fn user(&self, id: Uuid, session: &str) -> serde_json::Value {
@@ -394,6 +591,13 @@ impl TestServer {
     }
 
     async fn send_message(&self, message: serde_json::Value, session: &str) {
+        self.post_message(message, session)
+            .await
+            .error_for_status()
+            .expect("Server rejected message");
+    }
+
+    async fn post_message(&self, message: serde_json::Value, session: &str) -> reqwest::Response {
         self.client
             .post(format!("http://localhost:{}/api/v0/add_message", self.port))
             .header("cookie", format!("session={session}"))
@@ -401,8 +605,50 @@ impl TestServer {
             .send()
             .await
             .expect("Failed to send message")
+    }
+
+    async fn pin_message(
+        &self,
+        message_id: &str,
+        pinned: bool,
+        session: &str,
+    ) -> reqwest::Response {
+        self.client
+            .post(format!(
+                "http://localhost:{}/api/v0/messages/{message_id}/pin",
+                self.port
+            ))
+            .header("cookie", format!("session={session}"))
+            .json(&json!({ "pinned": pinned }))
+            .send()
+            .await
+            .expect("Failed to pin message")
+    }
+
+    async fn pinned(&self, session: &str) -> serde_json::Value {
+        self.client
+            .get(format!("http://localhost:{}/api/v0/pinned", self.port))
+            .header("cookie", format!("session={session}"))
+            .send()
+            .await
+            .expect("Failed to fetch pinned messages")
             .error_for_status()
-            .expect("Server rejected message");
+            .expect("Server returned error for pinned messages")
+            .json()
+            .await
+            .expect("Failed to parse pinned messages")
+    }
+
+    async fn delete_sender(&self, sender: Uuid, session: &str) -> reqwest::Response {
+        self.client
+            .delete(format!(
+                "http://localhost:{}/api/v0/admin/senders/{sender}",
+                self.port
+            ))
+            .header("cookie", format!("session={session}"))
+            .send()
+            .await
+            .expect("Failed to delete messages of sender")
     }
 
     #[cfg(unix)]
@@ -410,6 +656,11 @@ impl TestServer {
         self.process.send_sigterm();
     }
 
+    #[cfg(unix)]
+    fn send_sigusr1(&mut self) {
+        self.process.send_sigusr1();
+    }
+
     #[cfg(unix)]
     async fn wait_for_termination(
         &mut self,
@@ -506,6 +757,12 @@ impl ServerProcess {
         signal::kill(pid, Signal::SIGTERM).unwrap();
     }
 
+    #[cfg(unix)]
+    fn send_sigusr1(&mut self) {
+        let pid = Pid::from_raw(self.child.id().expect("Test process must be running") as i32);
+        signal::kill(pid, Signal::SIGUSR1).unwrap();
+    }
+
     #[cfg(unix)]
     async fn wait_for_termination(
         &mut self,
@@ -515,6 +772,36 @@ impl ServerProcess {
     }
 }
 
+/// Name of a field of the JSON payloads of the API, given in snake_case. In camelCase if built with
+/// the `camel-case-json` feature, like the payloads themselves.
+fn field(snake_case: &str) -> String {
+    if !cfg!(feature = "camel-case-json") {
+        return snake_case.to_owned();
+    }
+    let mut words = snake_case.split('_');
+    let mut camel_case = words.next().unwrap_or_default().to_owned();
+    for word in words {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            camel_case.extend(first.to_uppercase());
+            camel_case.push_str(chars.as_str());
+        }
+    }
+    camel_case
+}
+
+/// `payload` with the fields of its objects named by [`field`].
+fn api_json(payload: serde_json::Value) -> serde_json::Value {
+    match payload {
+        serde_json::Value::Object(fields) => fields
+            .into_iter()
+            .map(|(name, value)| (field(&name), api_json(value)))
+            .collect(),
+        serde_json::Value::Array(items) => items.into_iter().map(api_json).collect(),
+        other => other,
+    }
+}
+
 /// Extracts the port number from a log line like `... Listening port=3000`.
 fn parse_port(line: &str) -> Option<u16> {
     let suffix = line.split("port=").nth(1)?;